@@ -0,0 +1,299 @@
+//! Conversions from the models of this crate to the types of alloy, for use
+//! with tooling built on alloy rather than on ethers.
+//!
+//! The models keep their ethereum-types fields, as they are decoded straight
+//! from the db. The helpers here convert those fields to their alloy
+//! counterparts.
+use alloy_consensus::{
+    Eip658Value, Header, Receipt, Signed, TxEip1559, TxEip2930, TxEip4844, TxEip4844Variant,
+    TxEip7702, TxEnvelope, TxLegacy,
+};
+use alloy_eips::{
+    eip2930::{AccessList as AlloyAccessList, AccessListItem},
+    eip7702::{Authorization as AlloyAuthorization, SignedAuthorization},
+};
+use alloy_primitives::{Bloom, Log, LogData, Sealed, Signature, TxKind, B256, B64};
+use ethereum_types::{Address, H256, U256};
+
+use crate::erigon::models::{
+    transaction::{AccessTuple, Authorization, TxAction},
+    Account, BlockHeader, CborLog, CborReceipt, Transaction,
+};
+
+/// Converts an ethereum-types address to an alloy address.
+pub fn address(adr: Address) -> alloy_primitives::Address {
+    alloy_primitives::Address::from(adr.0)
+}
+
+/// Converts an ethereum-types hash to an alloy hash.
+pub fn b256(hash: H256) -> B256 {
+    B256::from(hash.0)
+}
+
+/// Converts an ethereum-types U256 to an alloy U256. Both store the value as
+/// little-endian u64 limbs.
+pub fn u256(val: U256) -> alloy_primitives::U256 {
+    alloy_primitives::U256::from_limbs(val.0)
+}
+
+// Numbers are U256 in the db, but fit in the narrower alloy fields on any real
+// chain. These return None rather than truncate a value that does not fit.
+fn u64(val: U256) -> Option<u64> {
+    val.try_into().ok()
+}
+
+fn u128(val: U256) -> Option<u128> {
+    val.try_into().ok()
+}
+
+// The fields of the header, leaving out an AuRa seal, which has no alloy
+// counterpart.
+fn header(h: &BlockHeader) -> Option<Header> {
+    let base_fee_per_gas = match h.base_fee {
+        Some(fee) => Some(u64(fee)?),
+        None => None,
+    };
+    Some(Header {
+        parent_hash: b256(h.parent_hash),
+        ommers_hash: b256(h.uncle_hash),
+        beneficiary: address(h.coinbase),
+        state_root: b256(h.root),
+        transactions_root: b256(h.tx_hash),
+        receipts_root: b256(h.receipts_hash),
+        logs_bloom: Bloom::from(h.bloom.0),
+        difficulty: u256(h.difficulty),
+        number: u64(h.number)?,
+        gas_limit: h.gas_limit,
+        gas_used: h.gas_used,
+        timestamp: h.time,
+        extra_data: h.extra.clone().into(),
+        mix_hash: b256(h.mix_digest),
+        nonce: B64::from(h.nonce.0),
+        base_fee_per_gas,
+        withdrawals_root: h.withdrawals_root.map(b256),
+        blob_gas_used: h.blob_gas_used,
+        excess_blob_gas: h.excess_blob_gas,
+        parent_beacon_block_root: h.parent_beacon_block_root.map(b256),
+        requests_hash: h.requests_hash.map(b256),
+        ..Default::default()
+    })
+}
+
+/// Fails, giving back the header, for AuRa headers, whose seal has no alloy
+/// field to go in, and for headers with a number or base fee past u64. AuRa
+/// headers convert to a [`Sealed<Header>`] instead.
+impl TryFrom<BlockHeader> for Header {
+    type Error = BlockHeader;
+    fn try_from(h: BlockHeader) -> Result<Self, BlockHeader> {
+        if h.seal.is_some() {
+            return Err(h);
+        }
+        header(&h).ok_or(h)
+    }
+}
+
+/// Seals the header with its block hash, which covers the AuRa seal on chains
+/// such as Gnosis, so the sealed hash is the block hash even though the step
+/// and signature are not carried in the alloy fields (see
+/// [`BlockHeader::aura_seal`]). Fails, giving back the header, for headers with
+/// a number or base fee past u64.
+impl TryFrom<BlockHeader> for Sealed<Header> {
+    type Error = BlockHeader;
+    fn try_from(h: BlockHeader) -> Result<Self, BlockHeader> {
+        match header(&h) {
+            Some(header) => Ok(Sealed::new_unchecked(header, b256(h.hash()))),
+            None => Err(h),
+        }
+    }
+}
+
+impl From<TxAction> for TxKind {
+    fn from(to: TxAction) -> Self {
+        match to {
+            TxAction::Call(adr) => Self::Call(address(adr)),
+            TxAction::Create => Self::Create,
+        }
+    }
+}
+
+impl From<AccessTuple> for AccessListItem {
+    fn from(item: AccessTuple) -> Self {
+        Self {
+            address: address(item.address),
+            storage_keys: item.slots.into_iter().map(b256).collect(),
+        }
+    }
+}
+
+fn access_list(list: &[AccessTuple]) -> AlloyAccessList {
+    AlloyAccessList(list.iter().cloned().map(From::from).collect())
+}
+
+/// Fails, giving back the authorization, if its chain id or y parity do not
+/// fit in their alloy fields.
+impl TryFrom<Authorization> for SignedAuthorization {
+    type Error = Authorization;
+    fn try_from(auth: Authorization) -> Result<Self, Authorization> {
+        let y_parity = match u8::try_from(auth.v) {
+            Ok(y_parity) => y_parity,
+            Err(_) => return Err(auth),
+        };
+        let inner = AlloyAuthorization {
+            chain_id: u256(auth.chain_id),
+            address: address(auth.address),
+            nonce: auth.nonce,
+        };
+        Ok(Self::new_unchecked(
+            inner,
+            y_parity,
+            u256(auth.r),
+            u256(auth.s),
+        ))
+    }
+}
+
+/// Fails, giving back the tx, for OP stack deposits, which have no alloy
+/// envelope, for legacy txs whose v is invalid, and for txs with a chain id or
+/// fee that does not fit in its alloy field.
+impl TryFrom<Transaction> for TxEnvelope {
+    type Error = Transaction;
+    fn try_from(tx: Transaction) -> Result<Self, Transaction> {
+        envelope(&tx).ok_or(tx)
+    }
+}
+
+fn envelope(tx: &Transaction) -> Option<TxEnvelope> {
+    let hash = b256(tx.tx_hash());
+    // typed txs store the y parity as v, and legacy txs pack it with the
+    // Eip155 chain id
+    let parity = match tx {
+        Transaction::Legacy(legacy) => legacy.v.y_parity().ok()?,
+        _ => !tx.v().is_zero(),
+    };
+    let sig = Signature::new(u256(tx.r()), u256(tx.s()), parity);
+    Some(match tx {
+        Transaction::Legacy(tx) => {
+            let chain_id = match tx.v.derive_chain_id().ok()? {
+                Some(id) => Some(u64(id)?),
+                None => None,
+            };
+            let tx = TxLegacy {
+                chain_id,
+                nonce: tx.nonce,
+                gas_price: u128(tx.gas_price)?,
+                gas_limit: tx.gas,
+                to: tx.to.into(),
+                value: u256(tx.value),
+                input: tx.data.clone().into(),
+            };
+            Signed::new_unchecked(tx, sig, hash).into()
+        }
+        Transaction::AccessList(tx) => {
+            let tx = TxEip2930 {
+                chain_id: u64(tx.chain_id)?,
+                nonce: tx.nonce,
+                gas_price: u128(tx.gas_price)?,
+                gas_limit: tx.gas,
+                to: tx.to.into(),
+                value: u256(tx.value),
+                access_list: access_list(&tx.access_list),
+                input: tx.data.clone().into(),
+            };
+            Signed::new_unchecked(tx, sig, hash).into()
+        }
+        Transaction::DynamicFee(tx) => {
+            let tx = TxEip1559 {
+                chain_id: u64(tx.chain_id)?,
+                nonce: tx.nonce,
+                gas_limit: tx.gas,
+                max_fee_per_gas: u128(tx.fee_cap)?,
+                max_priority_fee_per_gas: u128(tx.tip)?,
+                to: tx.to.into(),
+                value: u256(tx.value),
+                access_list: access_list(&tx.access_list),
+                input: tx.data.clone().into(),
+            };
+            Signed::new_unchecked(tx, sig, hash).into()
+        }
+        Transaction::Blob(tx) => {
+            let tx = TxEip4844 {
+                chain_id: u64(tx.chain_id)?,
+                nonce: tx.nonce,
+                gas_limit: tx.gas,
+                max_fee_per_gas: u128(tx.fee_cap)?,
+                max_priority_fee_per_gas: u128(tx.tip)?,
+                to: address(tx.to),
+                value: u256(tx.value),
+                access_list: access_list(&tx.access_list),
+                blob_versioned_hashes: tx.blob_versioned_hashes.iter().copied().map(b256).collect(),
+                max_fee_per_blob_gas: u128(tx.max_fee_per_blob_gas)?,
+                input: tx.data.clone().into(),
+            };
+            Signed::new_unchecked(TxEip4844Variant::TxEip4844(tx), sig, hash).into()
+        }
+        Transaction::SetCode(tx) => {
+            let authorization_list = tx
+                .authorizations
+                .iter()
+                .cloned()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()
+                .ok()?;
+            let tx = TxEip7702 {
+                chain_id: u64(tx.chain_id)?,
+                nonce: tx.nonce,
+                gas_limit: tx.gas,
+                max_fee_per_gas: u128(tx.fee_cap)?,
+                max_priority_fee_per_gas: u128(tx.tip)?,
+                to: address(tx.to),
+                value: u256(tx.value),
+                access_list: access_list(&tx.access_list),
+                authorization_list,
+                input: tx.data.clone().into(),
+            };
+            Signed::new_unchecked(tx, sig, hash).into()
+        }
+        #[cfg(feature = "op")]
+        Transaction::Deposit(_) => return None,
+    })
+}
+
+impl From<CborLog> for Log {
+    fn from(log: CborLog) -> Self {
+        let topics = log.topics.into_iter().map(b256).collect();
+        Self {
+            address: address(log.address),
+            data: LogData::new_unchecked(topics, log.data.into()),
+        }
+    }
+}
+
+impl CborReceipt {
+    /// Converts the receipt to its alloy form, with the logs of its tx, which
+    /// are stored apart from the receipt (see [`Erigon::read_logs`](crate::Erigon::read_logs)).
+    pub fn into_alloy(self, logs: Vec<CborLog>) -> Receipt<Log> {
+        let status = match self.post_state {
+            Some(root) => Eip658Value::PostState(b256(root)),
+            None => Eip658Value::Eip658(self.status == 1),
+        };
+        Receipt {
+            status,
+            cumulative_gas_used: self.cumulative_gas_used,
+            logs: logs.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl Account {
+    /// Converts the account to its alloy (trie) form. Erigon does not store
+    /// the storage root with the account, so it is passed in, e.g. from
+    /// [`Erigon::storage_root`](crate::Erigon::storage_root).
+    pub fn into_alloy(self, storage_root: H256) -> alloy_consensus::Account {
+        alloy_consensus::Account {
+            nonce: self.nonce,
+            balance: u256(self.balance),
+            storage_root: b256(storage_root),
+            code_hash: b256(self.codehash),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use clap::{Parser, Subcommand};
+use erigon_db::{env_open_schema, kv::MdbxEnv, schema::Schema, Erigon, ENV_FLAGS};
+use ethereum_types::{Address, H256, U256};
+use eyre::{eyre, Result};
+use mdbx::RO;
+use std::path::{Path, PathBuf};
+
+/// Inspect an Erigon chaindata directory.
+#[derive(Debug, Parser)]
+#[command(name = "erigon-db", version)]
+struct Cli {
+    /// The chaindata directory, e.g. <datadir>/chaindata
+    #[arg(long, env = "ERIGON_CHAINDATA")]
+    chaindata: PathBuf,
+    /// Open the db without touching its lock file, e.g. on a read-only
+    /// filesystem. Fails if another process has the db open.
+    #[arg(long)]
+    no_lock: bool,
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// Print an account, at the head block or after the given block
+    Account {
+        address: Address,
+        #[arg(long)]
+        block: Option<u64>,
+    },
+    /// Print the value of a storage slot, at the head block or after the given block
+    Storage {
+        address: Address,
+        /// The slot, as a decimal or 0x-prefixed hex number
+        #[arg(value_parser = parse_slot)]
+        slot: H256,
+        #[arg(long)]
+        block: Option<u64>,
+    },
+    /// Print the canonical header at the given height
+    Header { number: u64 },
+    /// Print the canonical block at the given height, with its transactions
+    Block { number: u64 },
+    /// Print the size of each table, largest first
+    Stats,
+    /// List the tables of the db's schema, with their entry counts
+    Tables,
+    /// Check the tables for consistency with each other
+    Check,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    // the schema decides how many tables to allow and where state is kept, so
+    // it is read first and the env reopened for it
+    let env = open_env(&cli.chaindata, cli.no_lock, Schema::V2)?;
+    let schema = Erigon::begin(&env)?.schema()?;
+    drop(env);
+    let env = open_env(&cli.chaindata, cli.no_lock, schema)?;
+    let db = Erigon::begin(&env)?;
+
+    match cli.cmd {
+        Cmd::Account { address, block } => {
+            let acct = match (schema, block) {
+                (Schema::V2, Some(num)) => db.state_at(num).account(address)?,
+                (Schema::V2, None) => db.read_account(address)?,
+                (Schema::V3, Some(num)) => db.state_at_v3(num)?.account(address)?,
+                (Schema::V3, None) => db.read_account_v3(address)?,
+            };
+            match acct {
+                Some(acct) => println!("{:#?}", acct),
+                None => println!("account not found"),
+            }
+        }
+        Cmd::Storage {
+            address,
+            slot,
+            block,
+        } => {
+            let val = match (schema, block) {
+                (Schema::V2, Some(num)) => db.state_at(num).storage(address, slot)?,
+                (Schema::V2, None) => match db.read_account(address)? {
+                    Some(acct) => db
+                        .read_storage(address, acct.incarnation, slot)?
+                        .unwrap_or_default(),
+                    None => U256::zero(),
+                },
+                (Schema::V3, Some(num)) => db.state_at_v3(num)?.storage(address, slot)?,
+                (Schema::V3, None) => db.read_storage_v3(address, slot)?.unwrap_or_default(),
+            };
+            println!("{:#x}", val);
+        }
+        Cmd::Header { number } => {
+            let key = db
+                .read_header_key(number)?
+                .ok_or_else(|| eyre!("no canonical block {}", number))?;
+            match db.read_header(key)? {
+                Some(header) => println!("{:#?}", header),
+                None => println!("header not found"),
+            }
+        }
+        Cmd::Block { number } => match db.read_block(number)? {
+            Some(block) => println!("{:#?}", block),
+            None => println!("block not found"),
+        },
+        Cmd::Stats => {
+            let report = db.db_report()?;
+            let total: u64 = report.iter().map(|(_, stat)| stat.size_bytes()).sum();
+            println!("{:<32} {:>14} {:>14}", "table", "entries", "size (MiB)");
+            for (name, stat) in report {
+                println!(
+                    "{:<32} {:>14} {:>14.1}",
+                    name,
+                    stat.entries,
+                    mib(stat.size_bytes())
+                );
+            }
+            println!("{:<32} {:>14} {:>14.1}", "total", "", mib(total));
+        }
+        Cmd::Tables => {
+            let schema = db.schema()?;
+            println!("schema: {:?}", schema);
+            for name in schema.tables() {
+                match db.0.table_stat_by_name(name)? {
+                    Some(stat) => println!("{:<32} {:>14}", name, stat.entries),
+                    None => println!("{:<32} {:>14}", name, "missing"),
+                }
+            }
+            for name in db.unknown_tables()? {
+                eprintln!("warning: unknown table {}", name);
+            }
+        }
+        Cmd::Check => {
+            let report = db.check()?;
+            for check in &report.checks {
+                let status = if check.passed() { "ok" } else { "FAILED" };
+                println!(
+                    "{:<20} {:>6} {:>14} checked {:>10} failed",
+                    check.name, status, check.checked, check.failed
+                );
+                for key in &check.samples {
+                    println!("    {}", key);
+                }
+            }
+            if !report.passed() {
+                return Err(eyre!("consistency check failed"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_env(path: &Path, no_lock: bool, schema: Schema) -> Result<MdbxEnv<RO>> {
+    Ok(if no_lock {
+        MdbxEnv::open_readonly_no_lock(path, schema.num_tables(), ENV_FLAGS)?
+    } else {
+        env_open_schema(path, schema)?
+    })
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn parse_slot(s: &str) -> Result<H256, String> {
+    let num = match s.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| e.to_string())?,
+        None => U256::from_dec_str(s).map_err(|e| e.to_string())?,
+    };
+    let mut slot = H256::zero();
+    num.to_big_endian(slot.as_bytes_mut());
+    Ok(slot)
+}
@@ -0,0 +1,194 @@
+use mdbx::{WriteFlags, RW};
+
+use crate::{
+    erigon::Erigon,
+    kv::{
+        traits::{DefaultFlags, Table, TableEncode},
+        MdbxEnv,
+    },
+    Result,
+};
+
+/// The number of writes after which a [`BatchWriter`] commits by default.
+pub const DEFAULT_MAX_OPS: usize = 1_000_000;
+/// The number of bytes written after which a [`BatchWriter`] commits by
+/// default.
+pub const DEFAULT_MAX_BYTES: usize = 512 << 20;
+
+/// The writes made by a [`BatchWriter`] so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchProgress {
+    /// The number of transactions committed.
+    pub commits: usize,
+    /// The number of writes and deletes made, committed or not.
+    pub ops: usize,
+    /// The number of key and value bytes written, committed or not.
+    pub bytes: usize,
+}
+
+/// Writes to the db in a series of transactions, committing and beginning a
+/// new one every `max_ops` writes or `max_bytes` bytes written.
+///
+/// A single transaction holds every page it dirties until it commits, so a
+/// long import run in one transaction can exhaust the dirty page limit. The
+/// writes are not atomic as a whole: if the job fails, the batches committed
+/// before the failure stay in the db. Call [`finish`](Self::finish) to commit
+/// the last batch.
+pub struct BatchWriter<'env> {
+    env: &'env MdbxEnv<RW>,
+    // None after a failed commit, until the next transaction is begun
+    db: Option<Erigon<'env, RW>>,
+    max_ops: usize,
+    max_bytes: usize,
+    progress: BatchProgress,
+    batch_ops: usize,
+    batch_bytes: usize,
+    on_commit: Option<Box<dyn FnMut(&BatchProgress) + 'env>>,
+}
+
+impl std::fmt::Debug for BatchWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchWriter")
+            .field("max_ops", &self.max_ops)
+            .field("max_bytes", &self.max_bytes)
+            .field("progress", &self.progress)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'env> BatchWriter<'env> {
+    /// Begins the first transaction on `env`.
+    pub fn new(env: &'env MdbxEnv<RW>) -> Result<Self> {
+        Ok(Self {
+            env,
+            db: Some(Erigon::begin_rw(env)?),
+            max_ops: DEFAULT_MAX_OPS,
+            max_bytes: DEFAULT_MAX_BYTES,
+            progress: Default::default(),
+            batch_ops: 0,
+            batch_bytes: 0,
+            on_commit: None,
+        })
+    }
+
+    /// Sets the number of writes after which to commit.
+    pub fn max_ops(mut self, max_ops: usize) -> Self {
+        self.max_ops = max_ops;
+        self
+    }
+
+    /// Sets the number of bytes written after which to commit.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets a callback to be called with the progress so far after each
+    /// commit.
+    pub fn on_commit(mut self, f: impl FnMut(&BatchProgress) + 'env) -> Self {
+        self.on_commit = Some(Box::new(f));
+        self
+    }
+
+    pub fn progress(&self) -> BatchProgress {
+        self.progress
+    }
+
+    /// Returns the current transaction, e.g. to read what has been written,
+    /// beginning a new one if the last commit failed. Writes made through it
+    /// directly are not counted, see [`Self::with`].
+    pub fn db(&mut self) -> Result<&Erigon<'env, RW>> {
+        if self.db.is_none() {
+            self.db = Some(Erigon::begin_rw(self.env)?);
+        }
+        Ok(self.db.as_ref().unwrap())
+    }
+
+    /// Writes an entry to the table, committing if the batch is full.
+    pub fn write<'tx, T>(&mut self, key: T::Key, val: T::Value) -> Result<()>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        let (key, val) = (key.encode(), val.encode());
+        let tx = &self.db()?.0;
+        let db = tx.open_db::<T, T::Flags>()?;
+        tx.inner.put(db.as_ref(), &key, &val, WriteFlags::UPSERT)?;
+        self.record(1, key.as_ref().len() + val.as_ref().len())
+    }
+
+    /// Deletes the entry at `key`, or only the duplicate `val` if one is
+    /// given, committing if the batch is full. Returns false if there was
+    /// nothing to delete.
+    pub fn delete<'tx, T>(&mut self, key: T::Key, val: Option<T::Value>) -> Result<bool>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        let (key, val) = (key.encode(), val.map(TableEncode::encode));
+        let tx = &self.db()?.0;
+        let db = tx.open_db::<T, T::Flags>()?;
+        let deleted = tx
+            .inner
+            .del(db.as_ref(), &key, val.as_ref().map(AsRef::as_ref))?;
+        self.record(1, key.as_ref().len())?;
+        Ok(deleted)
+    }
+
+    /// Calls `f` with the current transaction, e.g. to use the write methods
+    /// of [`Erigon`], counting it as `ops` writes of `bytes` bytes in total.
+    pub fn with<R>(
+        &mut self,
+        ops: usize,
+        bytes: usize,
+        f: impl FnOnce(&Erigon<'env, RW>) -> Result<R>,
+    ) -> Result<R> {
+        let res = f(self.db()?)?;
+        self.record(ops, bytes)?;
+        Ok(res)
+    }
+
+    // Counts the writes, committing if the batch is full.
+    fn record(&mut self, ops: usize, bytes: usize) -> Result<()> {
+        self.progress.ops += ops;
+        self.progress.bytes += bytes;
+        self.batch_ops += ops;
+        self.batch_bytes += bytes;
+        if self.batch_ops >= self.max_ops || self.batch_bytes >= self.max_bytes {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the current transaction and begins a new one. If the commit
+    /// fails, the writes since the last commit are lost, and the next one is
+    /// begun on the next write.
+    pub fn commit(&mut self) -> Result<()> {
+        self.commit_batch()?;
+        self.db = Some(Erigon::begin_rw(self.env)?);
+        Ok(())
+    }
+
+    /// Commits the writes made since the last commit, returning the progress
+    /// of the whole job. If none were made, nothing is committed and the
+    /// callback is not called.
+    pub fn finish(mut self) -> Result<BatchProgress> {
+        if self.batch_ops > 0 {
+            self.commit_batch()?;
+        }
+        Ok(self.progress)
+    }
+
+    // Commits the current transaction, if one is open, leaving none open
+    // whether or not the commit succeeds.
+    fn commit_batch(&mut self) -> Result<()> {
+        self.batch_ops = 0;
+        self.batch_bytes = 0;
+        if let Some(db) = self.db.take() {
+            db.0.commit()?;
+        }
+        self.progress.commits += 1;
+        if let Some(f) = &mut self.on_commit {
+            f(&self.progress);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Tables and reads for the chaindata of Erigon nodes following a Bor chain,
+//! i.e. Polygon PoS.
+//!
+//! Every sprint, Bor commits the state sync events bridged from the root chain
+//! in a system tx that is not part of the block body. Erigon stores the events
+//! and the receipt of that tx in tables of their own, and gives the tx a hash
+//! derived from the block, see [`bor_tx_hash`].
+use bytes::Bytes;
+use ethereum_types::{H256, U256};
+
+use crate::{
+    erigon::{models::*, utils, Erigon},
+    kv::traits::Mode,
+    Result,
+};
+
+pub mod models;
+pub mod tables;
+
+use self::{models::*, tables::*};
+
+/// Returns the hash Erigon gives the state sync tx of a block, which is
+/// `keccak256("matic-bor-receipt-" || blocknum || blockhash)`.
+pub fn bor_tx_hash(num: impl Into<BlockNumber>, hash: H256) -> H256 {
+    let mut b = b"matic-bor-receipt-".to_vec();
+    b.extend_from_slice(&num.into().0.to_be_bytes());
+    b.extend_from_slice(hash.as_bytes());
+    H256(utils::keccak256(b))
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the receipt of the state sync tx of the canonical block, or
+    /// None if no events were committed in the block. Its logs are stored in
+    /// the TransactionLog table, at the tx index after the block's last tx.
+    pub fn read_bor_receipt(&self, num: impl Into<BlockNumber>) -> Result<Option<CborReceipt>> {
+        Ok(self.read::<BorReceipt>(num.into())?.and_then(|r| r.0))
+    }
+
+    /// Returns the number of the block containing the state sync tx with the
+    /// given hash.
+    pub fn read_bor_tx_block_number(&self, hash: H256) -> Result<Option<U256>> {
+        self.read::<BlockBorTransactionLookup>(hash)
+    }
+
+    /// Returns the rlp-encoded state sync events committed in block `num`,
+    /// in the order they were committed.
+    pub fn read_state_sync_events(&self, num: impl Into<BlockNumber>) -> Result<Vec<Bytes>> {
+        let mut cur = self.cursor::<BorEventNums>()?;
+        let start = match cur.seek_exact(num.into())? {
+            Some(id) => id,
+            None => return Ok(Vec::new()),
+        };
+        // the events of a block end where those of the next block with events
+        // begin
+        let end = cur.next()?.map(|(_, id)| id);
+        self.cursor::<BorEvents>()?
+            .walk(start)?
+            .take_while(|res| {
+                res.as_ref()
+                    .map_or(true, |(id, _)| end.map_or(true, |end| *id < end))
+            })
+            .map(|res| res.map(|(_, event)| event))
+            .collect()
+    }
+
+    /// Returns the span with the given id, as fetched from Heimdall.
+    pub fn read_span(&self, id: impl Into<SpanId>) -> Result<Option<Span>> {
+        self.read::<BorSpans>(id.into())
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    erigon::{macros::*, models::CborReceipt},
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+// The id of a state sync event, counting every event bridged from the root
+// chain.
+u64_wrapper!(EventId);
+// The id of a span, a range of blocks produced by one set of validators.
+u64_wrapper!(SpanId);
+
+// The receipt of a block's state sync tx. Unlike the Receipt table, which
+// stores a list, the BorReceipt table stores a single receipt per block.
+cbor_wrapper!(CborBorReceipt(Option<CborReceipt>));
+
+/// A span as fetched from Heimdall and stored as json in the BorSpans table.
+///
+/// The validator set and producers are kept as json, as they are only needed
+/// to check the signers of a block.
+// Go embeds the span's id and block range in the struct, so they are not nested.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    #[serde(rename = "span_id")]
+    pub id: SpanId,
+    pub start_block: u64,
+    pub end_block: u64,
+    #[serde(default)]
+    pub validator_set: serde_json::Value,
+    #[serde(default)]
+    pub selected_producers: serde_json::Value,
+    #[serde(rename = "bor_chain_id", default)]
+    pub chain_id: String,
+}
+
+impl TableEncode for Span {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        // serializing plain fields and json values to json cannot fail
+        serde_json::to_vec(&self).unwrap()
+    }
+}
+
+impl TableDecode for Span {
+    fn decode(b: &[u8]) -> Result<Self> {
+        serde_json::from_slice(b).map_err(|e| DecodeError::from(e).into())
+    }
+}
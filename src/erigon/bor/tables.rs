@@ -0,0 +1,33 @@
+use crate::{
+    erigon::{bor::models::*, models::*},
+    table_set,
+};
+use bytes::Bytes;
+use ethereum_types::{H256, U256};
+
+// --- Bor (Polygon) tables ---
+
+// || indicates concatenation
+
+// Tables written only by Erigon nodes following a Bor chain. Bor snapshots
+// (the validator set at a checkpoint) are kept in a separate consensus db
+// rather than in chaindata, so they are not listed here.
+
+table_set! {
+    /// The names of the Bor tables, as they are stored in the db.
+    pub const ALL_TABLES;
+    /// The tables in [`ALL_TABLES`] that are dupsort.
+    pub const DUPSORT_TABLES;
+
+    // Table name                   => Key          => Value
+    // key: blocknum. val: cbor(receipt) of the block's state sync tx. erigon: BorReceipts
+    table!(BorReceipt               => BlockNumber  => CborBorReceipt);
+    // key: bor_tx_hash. val: blocknum containing the state sync tx. erigon: BorTxLookup
+    table!(BlockBorTransactionLookup => H256        => U256);
+    // key: event_id. val: rlp(state_sync_event)
+    table!(BorEvents                => EventId      => Bytes);
+    // key: blocknum. val: id of the first event committed in the block
+    table!(BorEventNums             => BlockNumber  => EventId);
+    // key: span_id. val: json(span)
+    table!(BorSpans                 => SpanId       => Span);
+}
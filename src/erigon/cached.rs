@@ -0,0 +1,152 @@
+use ethereum_types::{Address, H256};
+use lru::LruCache;
+use mdbx::RO;
+use std::{
+    cell::{Cell, RefCell},
+    hash::Hash,
+    num::NonZeroUsize,
+    ops::Deref,
+};
+
+use crate::{
+    erigon::{models::*, Erigon},
+    kv::{traits::Mode, MdbxEnv},
+    Result,
+};
+
+/// The number of entries kept by each cache of a [`CachedErigon`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheSizes {
+    /// Bytecode, by codehash.
+    pub code: NonZeroUsize,
+    /// Headers, by block number and hash.
+    pub headers: NonZeroUsize,
+    /// Accounts, by address, including those that don't exist.
+    pub accounts: NonZeroUsize,
+}
+
+impl Default for CacheSizes {
+    fn default() -> Self {
+        Self {
+            code: NonZeroUsize::new(1024).unwrap(),
+            headers: NonZeroUsize::new(1024).unwrap(),
+            accounts: NonZeroUsize::new(16384).unwrap(),
+        }
+    }
+}
+
+/// The number of cached reads of a [`CachedErigon`] that were answered from
+/// its caches and that went to the db.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// A read-only transaction that keeps the code, headers and accounts it reads
+/// in LRU caches, for workloads such as tracing that read the same few
+/// entries over and over. Every other read goes straight to the db through
+/// the inner [`Erigon`], which it derefs to.
+///
+/// The cached entries are those of the transaction's snapshot of the db, so
+/// the caches are cleared when the transaction is replaced by
+/// [`refresh`](Self::refresh).
+pub struct CachedErigon<'env, M> {
+    env: &'env MdbxEnv<M>,
+    db: Erigon<'env, RO>,
+    code: RefCell<LruCache<H256, Option<Bytecode>>>,
+    headers: RefCell<LruCache<(BlockNumber, H256), Option<BlockHeader>>>,
+    accounts: RefCell<LruCache<Address, Option<Account>>>,
+    stats: Cell<CacheStats>,
+}
+
+impl<'env, M: Mode> CachedErigon<'env, M> {
+    /// Begins a read-only transaction on `env`, with empty caches of the
+    /// given sizes.
+    pub fn new(env: &'env MdbxEnv<M>, sizes: CacheSizes) -> Result<Self> {
+        Ok(Self {
+            env,
+            db: Erigon::new(env.begin_ro()?),
+            code: RefCell::new(LruCache::new(sizes.code)),
+            headers: RefCell::new(LruCache::new(sizes.headers)),
+            accounts: RefCell::new(LruCache::new(sizes.accounts)),
+            stats: Default::default(),
+        })
+    }
+
+    /// Replaces the transaction with a new one, to see the changes committed
+    /// since it began, and clears the caches and their stats.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.db = Erigon::new(self.env.begin_ro()?);
+        self.code.get_mut().clear();
+        self.headers.get_mut().clear();
+        self.accounts.get_mut().clear();
+        self.stats.take();
+        Ok(())
+    }
+
+    /// Returns the hits and misses of the caches since the transaction began.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    /// Returns the bytecode with the given codehash. See [`Erigon::read_code`].
+    pub fn read_code(&self, codehash: H256) -> Result<Option<Bytecode>> {
+        self.cached(&self.code, codehash, || self.db.read_code(codehash))
+    }
+
+    /// Returns the bytecode of the contract at `adr`, through the account and
+    /// code caches. See [`Erigon::read_code_by_address`].
+    pub fn read_code_by_address(&self, adr: Address) -> Result<Option<Bytecode>> {
+        match self.read_account(adr)? {
+            Some(acct) if acct.codehash != H256::zero() => self.read_code(acct.codehash),
+            // the codehash must be looked up by incarnation
+            Some(_) => self.db.read_code_by_address(adr),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the header with the given key. See [`Erigon::read_header`].
+    pub fn read_header(&self, key: impl Into<HeaderKey>) -> Result<Option<BlockHeader>> {
+        let HeaderKey(num, hash) = key.into();
+        self.cached(&self.headers, (num, hash), || {
+            self.db.read_header((num, hash))
+        })
+    }
+
+    /// Returns the account at `adr`. See [`Erigon::read_account`].
+    pub fn read_account(&self, adr: Address) -> Result<Option<Account>> {
+        self.cached(&self.accounts, adr, || self.db.read_account(adr))
+    }
+
+    // Returns the value at `key` in the cache, reading and caching it on a miss.
+    fn cached<K, V>(
+        &self,
+        cache: &RefCell<LruCache<K, V>>,
+        key: K,
+        read: impl FnOnce() -> Result<V>,
+    ) -> Result<V>
+    where
+        K: Hash + Eq,
+        V: Clone,
+    {
+        let mut stats = self.stats.get();
+        if let Some(val) = cache.borrow_mut().get(&key) {
+            stats.hits += 1;
+            self.stats.set(stats);
+            return Ok(val.clone());
+        }
+        stats.misses += 1;
+        self.stats.set(stats);
+        let val = read()?;
+        cache.borrow_mut().put(key, val.clone());
+        Ok(val)
+    }
+}
+
+impl<'env, M> Deref for CachedErigon<'env, M> {
+    type Target = Erigon<'env, RO>;
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
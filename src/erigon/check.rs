@@ -0,0 +1,184 @@
+//! Consistency checks across the tables of an Erigon 2 db, for finding the
+//! corruption left by a failed disk or an interrupted write. Each check walks
+//! one table and looks up the entries it references in the others.
+use std::fmt::Debug;
+
+use ethereum_types::{Address, H256};
+
+use crate::{
+    erigon::{models::*, seek_storage_change, tables::*, Erigon},
+    kv::traits::Mode,
+    Error, Result,
+};
+
+/// The max number of offending keys kept by a [`CheckResult`].
+pub const MAX_SAMPLES: usize = 10;
+
+/// The outcome of a single check.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// The number of entries checked.
+    pub checked: u64,
+    /// The number of entries that failed the check.
+    pub failed: u64,
+    /// The first [`MAX_SAMPLES`] offending keys, formatted with `Debug`.
+    pub samples: Vec<String>,
+}
+
+impl CheckResult {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failed == 0
+    }
+
+    fn record(&mut self, ok: bool, key: impl Debug) {
+        self.checked += 1;
+        if !ok {
+            self.failed += 1;
+            if self.samples.len() < MAX_SAMPLES {
+                self.samples.push(format!("{:?}", key));
+            }
+        }
+    }
+}
+
+/// The outcome of every check run by [`Erigon::check`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(CheckResult::passed)
+    }
+
+    /// Returns the checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|check| !check.passed())
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Runs every check. A check fails on a missing or undecodable entry in
+    /// the tables it looks up; errors reading the table it walks are returned.
+    pub fn check(&self) -> Result<CheckReport> {
+        Ok(CheckReport {
+            checks: vec![
+                self.check_canonical_blocks()?,
+                self.check_senders()?,
+                self.check_tx_lookup()?,
+                self.check_account_history()?,
+                self.check_storage_history()?,
+                self.check_incarnations()?,
+            ],
+        })
+    }
+
+    /// Checks that every hash in CanonicalHeader has a Header and a BlockBody.
+    pub fn check_canonical_blocks(&self) -> Result<CheckResult> {
+        let mut res = CheckResult::new("canonical_blocks");
+        for read in self.cursor::<CanonicalHeader>()?.walk(BlockNumber(0))? {
+            let key = HeaderKey::from(read?);
+            let ok = self.read_raw::<Header>(key)?.is_some()
+                && self.read_raw::<BlockBody>(key)?.is_some();
+            res.record(ok, key);
+        }
+        Ok(res)
+    }
+
+    /// Checks that each list of senders in TxSender has one sender for each
+    /// transaction of the block's body, not counting system transactions.
+    pub fn check_senders(&self) -> Result<CheckResult> {
+        let mut res = CheckResult::new("senders");
+        for read in self.cursor::<TxSender>()?.walk(Default::default())? {
+            let (key, senders) = read?;
+            let ok = lookup(self.read_body_for_storage(key))?
+                .map_or(false, |body| body.tx_amount as usize == senders.len());
+            res.record(ok, key);
+        }
+        Ok(res)
+    }
+
+    /// Checks that the block each transaction hash in BlockTransactionLookup
+    /// points to is canonical and contains the transaction.
+    pub fn check_tx_lookup(&self) -> Result<CheckResult> {
+        let mut res = CheckResult::new("tx_lookup");
+        for read in self
+            .cursor::<BlockTransactionLookup>()?
+            .walk(H256::zero())?
+        {
+            let (hash, _) = read?;
+            let ok = lookup(self.read_transaction_by_hash(hash))?.flatten();
+            res.record(ok.is_some(), hash);
+        }
+        Ok(res)
+    }
+
+    /// Checks that AccountChangeSet has an entry for the account at each
+    /// block of its AccountHistory bitmaps.
+    pub fn check_account_history(&self) -> Result<CheckResult> {
+        let mut res = CheckResult::new("account_history");
+        let mut cs_cur = self.cursor::<AccountChangeSet>()?;
+        let start = AccountHistKey(Address::zero(), BlockNumber(0));
+        for read in self.cursor::<AccountHistory>()?.walk(start)? {
+            let (AccountHistKey(adr, _), bitmap) = read?;
+            for block in bitmap.into_iter().map(BlockNumber) {
+                let ok = lookup(cs_cur.seek_exact_dup(block, adr))?.flatten();
+                res.record(ok.is_some(), (block, adr));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Checks that StorageChangeSet has an entry for the slot, at any
+    /// incarnation of its account, at each block of its StorageHistory
+    /// bitmaps.
+    pub fn check_storage_history(&self) -> Result<CheckResult> {
+        let mut res = CheckResult::new("storage_history");
+        let mut cs_cur = self.cursor::<StorageChangeSet>()?;
+        let start = StorageHistKey(Address::zero(), H256::zero(), BlockNumber(0));
+        for read in self.cursor::<StorageHistory>()?.walk(start)? {
+            let (StorageHistKey(adr, slot, _), bitmap) = read?;
+            for block in bitmap.into_iter().map(BlockNumber) {
+                let ok = lookup(seek_storage_change(&mut cs_cur, block, adr, slot))?.flatten();
+                res.record(ok.is_some(), (block, adr, slot));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Checks that no contract in PlainState has an incarnation at or below
+    /// the one IncarnationMap records for its last deletion, since a contract
+    /// recreated at the same address is given the next incarnation.
+    pub fn check_incarnations(&self) -> Result<CheckResult> {
+        let mut res = CheckResult::new("incarnations");
+        for read in self.cursor::<IncarnationMap>()?.walk(Address::zero())? {
+            let (adr, deleted) = read?;
+            let ok = match lookup(self.read_account(adr))? {
+                Some(Some(acct)) => *acct.incarnation == 0 || acct.incarnation > deleted,
+                Some(None) => true,
+                None => false,
+            };
+            res.record(ok, adr);
+        }
+        Ok(res)
+    }
+}
+
+// Turns the result of a lookup that found a missing or undecodable entry into
+// `None`, so that it fails the check rather than aborting it.
+fn lookup<T>(res: Result<T>) -> Result<Option<T>> {
+    match res {
+        Ok(val) => Ok(Some(val)),
+        Err(Error::NotFound(_) | Error::Decode(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
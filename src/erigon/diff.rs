@@ -0,0 +1,181 @@
+use ethereum_types::{Address, H256, U256};
+use roaring::RoaringTreemap;
+use std::{
+    collections::BTreeMap,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    erigon::{models::*, seek_storage_change, tables::*, Erigon},
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+
+/// A value changed by a block, with its values before and after the block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    pub block: BlockNumber,
+    pub before: T,
+    pub after: T,
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the changes made to account `adr` by the blocks after `from`, up
+    /// to and including `to`, in block order. An account that did not exist is
+    /// `None`.
+    ///
+    /// The blocks that changed the account are read from its AccountHistory
+    /// shards, so only those blocks are looked up in AccountChangeSet.
+    pub fn account_diff(
+        &self,
+        adr: Address,
+        from: impl Into<BlockNumber>,
+        to: impl Into<BlockNumber>,
+    ) -> Result<Vec<Change<Option<Account>>>> {
+        let (from, to) = (from.into(), to.into());
+        let mut cs_cur = self.cursor::<AccountChangeSet>()?;
+        let mut befores = Vec::new();
+        // the first shard that may hold a block after `from`
+        let start = AccountHistKey(adr, BlockNumber(from.saturating_add(1)));
+        for read in self.cursor::<AccountHistory>()?.iter(start) {
+            let (AccountHistKey(k, shard_max), bitmap) = read?;
+            if k != adr {
+                break;
+            }
+            for block in blocks_between(bitmap, from, to) {
+                let acct = cs_cur
+                    .seek_exact_dup(block, adr)?
+                    .ok_or(Error::NotFound(AccountChangeSet::NAME))?;
+                befores.push((block, self.account_from_changeset(adr, acct)?));
+            }
+            if shard_max >= to {
+                break;
+            }
+        }
+        let last = self.state_at(to).account(adr)?;
+        Ok(chain_changes(befores, last))
+    }
+
+    /// Returns the changes made to the storage of account `adr` by the blocks
+    /// after `from`, up to and including `to`, for each slot in `slots` that
+    /// changed. The changes to each slot are in block order. Changes are
+    /// listed across incarnations, so the slots of a contract that was
+    /// destroyed and created again are treated as the same slots.
+    ///
+    /// The slots and blocks are read from the StorageHistory shards of the
+    /// account, so only the blocks that changed a slot in the range are
+    /// looked up in StorageChangeSet.
+    pub fn storage_diff(
+        &self,
+        adr: Address,
+        slots: impl RangeBounds<H256>,
+        from: impl Into<BlockNumber>,
+        to: impl Into<BlockNumber>,
+    ) -> Result<BTreeMap<H256, Vec<Change<U256>>>> {
+        let (from, to) = (from.into(), to.into());
+        let first_slot = match slots.start_bound() {
+            Bound::Included(slot) | Bound::Excluded(slot) => *slot,
+            Bound::Unbounded => H256::zero(),
+        };
+        let past_end = |slot: &H256| match slots.end_bound() {
+            Bound::Included(end) => slot > end,
+            Bound::Excluded(end) => slot >= end,
+            Bound::Unbounded => false,
+        };
+        let mut cs_cur = self.cursor::<StorageChangeSet>()?;
+        let mut befores = BTreeMap::<H256, Vec<_>>::new();
+        let start = StorageHistKey(adr, first_slot, BlockNumber(0));
+        for read in self.cursor::<StorageHistory>()?.iter(start) {
+            let (StorageHistKey(k, slot, shard_max), bitmap) = read?;
+            if k != adr || past_end(&slot) {
+                break;
+            }
+            // skip the shards that end before the range
+            if !slots.contains(&slot) || shard_max <= from {
+                continue;
+            }
+            for block in blocks_between(bitmap, from, to) {
+                let val = seek_storage_change(&mut cs_cur, block, adr, slot)?
+                    .ok_or(Error::NotFound(StorageChangeSet::NAME))?;
+                befores.entry(slot).or_default().push((block, val));
+            }
+        }
+
+        let state = self.state_at(to);
+        befores
+            .into_iter()
+            .map(|(slot, befores)| {
+                let last = state.storage(adr, slot)?;
+                Ok((slot, chain_changes(befores, last)))
+            })
+            .collect()
+    }
+
+    /// Returns every account changed in `block`, in address order, with its
+    /// value from before the block. An account created in the block is
+    /// `None`.
+    pub fn changed_accounts(
+        &self,
+        block: impl Into<BlockNumber>,
+    ) -> Result<Vec<(Address, Option<Account>)>> {
+        self.cursor::<AccountChangeSet>()?
+            .walk_dup_from(block.into(), Address::zero())?
+            .map(|val| {
+                let (adr, acct) = val?;
+                Ok((adr, self.account_from_changeset(adr, acct)?))
+            })
+            .collect()
+    }
+
+    /// Returns every storage slot changed in `block`, ordered by address and
+    /// slot, with its value from before the block.
+    pub fn changed_storage(
+        &self,
+        block: impl Into<BlockNumber>,
+    ) -> Result<Vec<(Address, H256, U256)>> {
+        let block = block.into();
+        let mut changes = Vec::new();
+        let mut cur = self.cursor::<StorageChangeSet>()?;
+        for read in cur.iter(StorageCSKey(block, Default::default())) {
+            let (StorageCSKey(b, StorageKey(adr, _)), StorageCSVal(slot, val)) = read?;
+            if b != block {
+                break;
+            }
+            changes.push((adr, slot, val));
+        }
+        Ok(changes)
+    }
+}
+
+// Returns the blocks of a history index shard after `from`, up to and
+// including `to`.
+fn blocks_between(
+    bitmap: RoaringTreemap,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> impl Iterator<Item = BlockNumber> {
+    bitmap
+        .into_iter()
+        .skip_while(move |&block| block <= *from)
+        .take_while(move |&block| block <= *to)
+        .map(BlockNumber)
+}
+
+// Pairs each value from before a block with the value from before the next
+// change, or with `last` for the final change.
+fn chain_changes<T: Clone>(befores: Vec<(BlockNumber, T)>, last: T) -> Vec<Change<T>> {
+    let afters = befores
+        .iter()
+        .skip(1)
+        .map(|(_, before)| before.clone())
+        .chain(Some(last));
+    befores
+        .iter()
+        .zip(afters)
+        .map(|((block, before), after)| Change {
+            block: *block,
+            before: before.clone(),
+            after,
+        })
+        .collect()
+}
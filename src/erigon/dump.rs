@@ -0,0 +1,160 @@
+use mdbx::{WriteFlags, RW};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    erigon::Erigon,
+    error::DecodeError,
+    kv::traits::{DefaultFlags, Mode, Table, TableDecode, TableEncode},
+    Result,
+};
+
+/// The text format of a table dump written by [`Erigon::dump_table`].
+///
+/// Either way, each entry is written on its own line, with the raw key as
+/// 0x-prefixed hex and the value as JSON, through the serde impls of the
+/// table's value type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Newline-delimited JSON, one `{"key":"0x..","value":..}` object per
+    /// line.
+    Json,
+    /// CSV with a `key,value` header, the value column holding quoted JSON.
+    Csv,
+}
+
+// The header row of a CSV dump, which tells the formats apart on load.
+const CSV_HEADER: &str = "key,value";
+
+#[derive(Serialize, Deserialize)]
+struct Entry<V> {
+    key: String,
+    value: V,
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Writes every entry of table `T`, in key order and including each
+    /// duplicate of a dupsorted table, to `w`. Returns the number of entries
+    /// written.
+    ///
+    /// Every entry must decode as a `T::Value`, so a table sharing its db
+    /// with another, such as PlainState holding Storage, cannot be dumped
+    /// once both have been written.
+    pub fn dump_table<'tx, T>(&'tx self, mut w: impl Write, format: DumpFormat) -> Result<usize>
+    where
+        T: Table<'tx> + DefaultFlags,
+        T::Value: Serialize,
+    {
+        if format == DumpFormat::Csv {
+            writeln!(w, "{CSV_HEADER}")?;
+        }
+        let mut cur = self.cursor::<T>()?;
+        let mut next: Option<(Cow<'_, [u8]>, Cow<'_, [u8]>)> = cur.inner.first()?;
+        let mut written = 0;
+        while let Some((k, v)) = next {
+            let entry = Entry {
+                key: to_hex(&k),
+                value: T::Value::decode(&v)?,
+            };
+            match format {
+                DumpFormat::Json => {
+                    serde_json::to_writer(&mut w, &entry).map_err(std::io::Error::from)?;
+                    writeln!(w)?;
+                }
+                DumpFormat::Csv => {
+                    let value =
+                        serde_json::to_string(&entry.value).map_err(std::io::Error::from)?;
+                    writeln!(w, "{},\"{}\"", entry.key, value.replace('"', "\"\""))?;
+                }
+            }
+            written += 1;
+            next = cur.inner.next()?;
+        }
+        w.flush()?;
+        Ok(written)
+    }
+}
+
+impl<'env> Erigon<'env, RW> {
+    /// Writes the entries of a dump made by [`Self::dump_table`] to table
+    /// `T`, overwriting the values at keys already in the table, or adding
+    /// to them if it is dupsorted. The format of the dump is detected from
+    /// its first line. Returns the number of entries written.
+    pub fn load_table<'tx, T>(&'tx self, r: impl BufRead) -> Result<usize>
+    where
+        T: Table<'tx> + DefaultFlags,
+        T::Value: DeserializeOwned,
+    {
+        let db = self.0.open_db::<T, T::Flags>()?;
+        let mut csv = false;
+        let mut written = 0;
+        for (i, line) in r.lines().enumerate() {
+            let line = line?;
+            if i == 0 && line == CSV_HEADER {
+                csv = true;
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let parse = || -> Result<_, DecodeError> {
+                let entry = if csv {
+                    parse_csv_row::<T::Value>(&line)?
+                } else {
+                    serde_json::from_str::<Entry<T::Value>>(&line)?
+                };
+                Ok((from_hex(&entry.key)?, entry.value.encode()))
+            };
+            let (key, val) =
+                parse().map_err(|e| DecodeError::custom(format!("line {}: {e}", i + 1)))?;
+            self.0
+                .inner
+                .put(db.as_ref(), &key, &val, WriteFlags::UPSERT)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+fn parse_csv_row<V: DeserializeOwned>(line: &str) -> Result<Entry<V>, DecodeError> {
+    let (key, value) = line
+        .split_once(',')
+        .ok_or_else(|| DecodeError::custom("expected a key and a value"))?;
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| DecodeError::custom("expected a quoted value"))?;
+    Ok(Entry {
+        key: key.to_string(),
+        value: serde_json::from_str(&value.replace("\"\"", "\""))?,
+    })
+}
+
+fn to_hex(b: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + 2 * b.len());
+    s.push_str("0x");
+    for byte in b {
+        write!(s, "{byte:02x}").unwrap();
+    }
+    s
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, DecodeError> {
+    let digits = s.strip_prefix("0x").unwrap_or(s).as_bytes();
+    if digits.len() % 2 != 0 {
+        return Err(DecodeError::custom("odd number of hex digits"));
+    }
+    let nibble = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .ok_or_else(|| DecodeError::custom(format!("invalid hex digit {:?}", c as char)))
+    };
+    digits
+        .chunks(2)
+        .map(|pair| Ok(((nibble(pair[0])? << 4) | nibble(pair[1])?) as u8))
+        .collect()
+}
@@ -0,0 +1,162 @@
+//! Tables and read paths for the Erigon 3 state schema.
+//!
+//! Erigon 3 drops PlainState and the changeset/history tables of Erigon 2 in
+//! favour of three kinds of table per piece of state (accounts, storage, code):
+//!
+//! - a domain, holding the latest value of each key,
+//! - a history, holding the value of a key before each change to it,
+//! - an inverted index, mapping each key to the tx_nums at which it changed.
+//!
+//! History is keyed by tx_num rather than block number, and the MaxTxNum table
+//! maps each block to the tx_num of its last tx.
+//!
+//! Only the data still in the db is read here. Erigon 3 moves older steps into
+//! `.kv`, `.v` and `.ef` files, which are not read yet, so the history of a key
+//! may be incomplete on a node that has frozen steps.
+use ethereum_types::{Address, H256, U256};
+
+use crate::{
+    erigon::{models::*, Erigon},
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+
+pub mod models;
+pub mod tables;
+
+use self::{models::*, tables::*};
+
+/// A read-only view of the Erigon 3 state as of the end of a given block.
+#[derive(Debug)]
+pub struct StateAtV3<'tx, 'env, K: Mode> {
+    db: &'tx Erigon<'env, K>,
+    block: BlockNumber,
+    tx_num: TxNum,
+}
+
+impl<'tx, 'env, K: Mode> StateAtV3<'tx, 'env, K> {
+    pub fn block(&self) -> BlockNumber {
+        self.block
+    }
+
+    /// The first tx_num after the block, i.e. the tx_num whose reads see the
+    /// state this view was created for.
+    pub fn tx_num(&self) -> TxNum {
+        self.tx_num
+    }
+
+    /// Returns the account at `adr`, or None if the account did not exist.
+    pub fn account(&self, adr: Address) -> Result<Option<Account>> {
+        self.db.read_account_as_of(adr, self.tx_num)
+    }
+
+    /// Returns the value of the storage slot, which is zero if the slot did not exist.
+    pub fn storage(&self, adr: Address, slot: H256) -> Result<U256> {
+        Ok(self
+            .db
+            .read_storage_as_of(adr, slot, self.tx_num)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the code deployed at `adr`, which is empty if the account did
+    /// not exist or has no code.
+    pub fn code(&self, adr: Address) -> Result<Bytecode> {
+        Ok(self
+            .db
+            .read_code_as_of(adr, self.tx_num)?
+            .unwrap_or_default())
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the tx_num of the last tx in the block, which is the system tx
+    /// at the end of the block.
+    pub fn read_max_tx_num(&self, num: impl Into<BlockNumber>) -> Result<Option<TxNum>> {
+        self.read::<MaxTxNum>(num.into())
+    }
+
+    /// Returns a view of the Erigon 3 state after the given block was executed.
+    pub fn state_at_v3(&self, block: impl Into<BlockNumber>) -> Result<StateAtV3<'_, 'env, K>> {
+        let block = block.into();
+        let max = self
+            .read_max_tx_num(block)?
+            .ok_or(Error::NotFound(MaxTxNum::NAME))?;
+        Ok(StateAtV3 {
+            db: self,
+            block,
+            tx_num: TxNum(max.saturating_add(1)),
+        })
+    }
+
+    /// Returns the latest account at `adr` from the AccountsDomain table.
+    pub fn read_account_v3(&self, adr: Address) -> Result<Option<Account>> {
+        Ok(self
+            .read::<AccountsDomain>(adr)?
+            .and_then(|val| val.value)
+            .map(|acct| acct.0))
+    }
+
+    /// Returns the latest value of the storage slot from the StorageDomain table.
+    pub fn read_storage_v3(&self, adr: Address, slot: H256) -> Result<Option<U256>> {
+        Ok(self
+            .read::<StorageDomain>((adr, slot).into())?
+            .and_then(|val| val.value))
+    }
+
+    /// Returns the latest code deployed at `adr` from the CodeDomain table.
+    pub fn read_code_v3(&self, adr: Address) -> Result<Option<Bytecode>> {
+        match self.cursor::<CodeDomain>()?.seek(adr)? {
+            Some((CodeDomainKey(k, _), code)) if k == adr && !code.is_empty() => Ok(Some(code)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the account as seen by the tx with the given tx_num, i.e.
+    /// before that tx was executed.
+    ///
+    /// The first change to the account at or after `tx_num` is found in the
+    /// AccountIdx table, and the history stores the value from before that
+    /// change. If there is no such change, the account is read from the domain.
+    pub fn read_account_as_of(&self, adr: Address, tx_num: TxNum) -> Result<Option<Account>> {
+        let change = match self.cursor::<AccountIdx>()?.seek_dup(adr, tx_num)? {
+            Some(change) => change,
+            None => return self.read_account_v3(adr),
+        };
+        let acct = self
+            .cursor::<AccountHistoryVals>()?
+            .seek_exact_dup(adr, change)?
+            .ok_or(Error::NotFound(AccountHistoryVals::NAME))?;
+        Ok(acct.map(|acct| acct.0))
+    }
+
+    /// Returns the value of the storage slot as seen by the tx with the given tx_num.
+    pub fn read_storage_as_of(
+        &self,
+        adr: Address,
+        slot: H256,
+        tx_num: TxNum,
+    ) -> Result<Option<U256>> {
+        let key = StorageDomainKey(adr, slot);
+        let change = match self.cursor::<StorageIdx>()?.seek_dup(key, tx_num)? {
+            Some(change) => change,
+            None => return self.read_storage_v3(adr, slot),
+        };
+        self.cursor::<StorageHistoryVals>()?
+            .seek_exact_dup(key, change)?
+            .ok_or(Error::NotFound(StorageHistoryVals::NAME))
+    }
+
+    /// Returns the code deployed at `adr` as seen by the tx with the given tx_num.
+    pub fn read_code_as_of(&self, adr: Address, tx_num: TxNum) -> Result<Option<Bytecode>> {
+        let change = match self.cursor::<CodeIdx>()?.seek_dup(adr, tx_num)? {
+            Some(change) => change,
+            None => return self.read_code_v3(adr),
+        };
+        // code is large, so its history is not dupsorted
+        match self.read::<CodeHistoryVals>(CodeHistKey(adr, change))? {
+            Some(code) if code.is_empty() => Ok(None),
+            Some(code) => Ok(Some(code)),
+            None => Err(Error::NotFound(CodeHistoryVals::NAME)),
+        }
+    }
+}
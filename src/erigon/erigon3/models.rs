@@ -0,0 +1,166 @@
+use bytes::BufMut;
+use derive_more::{Deref, DerefMut, From};
+use ethereum_types::{Address, H256, U256};
+
+use crate::{
+    erigon::{
+        macros::*,
+        models::Account,
+        utils::{consts::*, *},
+    },
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+// The number of a transaction across the whole chain, counting the system txs
+// at either end of each block.
+u64_wrapper!(TxNum);
+
+// Erigon 3 groups txs into steps (of 1562500 txs by default) when freezing
+// them into files. The domain tables store the bitwise negation of the step,
+// so the most recent step sorts first.
+u64_wrapper!(InvertedStep);
+
+impl InvertedStep {
+    pub fn from_step(step: u64) -> Self {
+        Self(!step)
+    }
+    pub fn step(&self) -> u64 {
+        !self.0
+    }
+}
+
+// address||slot
+tuple_key!(StorageDomainKey(Address, H256));
+// address||^step
+tuple_key!(CodeDomainKey(Address, InvertedStep));
+// address||tx_num
+tuple_key!(CodeHistKey(Address, TxNum));
+
+/// An account in the Erigon 3 encoding, which is the same in the domain,
+/// history and snapshot files.
+///
+/// Each of the nonce, balance, codehash and incarnation is written as a
+/// one-byte length followed by the big-endian value, with leading zeros
+/// trimmed. A zero length means the field is zero.
+// erigon-lib: accounts.SerialiseV3
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deref, DerefMut, From)]
+pub struct AccountV3(pub Account);
+
+impl TableEncode for AccountV3 {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let mut out = Vec::with_capacity(4 + 2 * U64_LENGTH + 2 * KECCAK_LENGTH);
+        let nonce = self.nonce.to_be_bytes();
+        put_trimmed(&mut out, &nonce);
+        let mut balance = [0; KECCAK_LENGTH];
+        self.balance.to_big_endian(&mut balance);
+        put_trimmed(&mut out, &balance);
+        if self.codehash.is_zero() || self.codehash == EMPTY_HASH {
+            out.put_u8(0);
+        } else {
+            out.put_u8(KECCAK_LENGTH as u8);
+            out.put_slice(self.codehash.as_bytes());
+        }
+        let incarnation = self.incarnation.to_be_bytes();
+        put_trimmed(&mut out, &incarnation);
+        out
+    }
+}
+
+impl TableDecode for AccountV3 {
+    fn decode(mut buf: &[u8]) -> Result<Self> {
+        let mut acct = Account::default();
+        if buf.is_empty() {
+            return Ok(Self(acct));
+        }
+        acct.nonce = bytes_to_u64(take_field(&mut buf, U64_LENGTH)?);
+        acct.balance = U256::from_big_endian(take_field(&mut buf, KECCAK_LENGTH)?);
+        let codehash = take_field(&mut buf, KECCAK_LENGTH)?;
+        match codehash.len() {
+            0 => (),
+            KECCAK_LENGTH => acct.codehash = H256::from_slice(codehash),
+            other => {
+                return Err(DecodeError::InvalidLength {
+                    expected: KECCAK_LENGTH,
+                    got: other,
+                }
+                .into())
+            }
+        }
+        acct.incarnation = bytes_to_u64(take_field(&mut buf, U64_LENGTH)?).into();
+        Ok(Self(acct))
+    }
+}
+
+/// A value in a dupsorted domain table, stored as `^step||value`. An empty
+/// value means the key was deleted in that step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DomainValue<T> {
+    pub step: InvertedStep,
+    pub value: Option<T>,
+}
+
+impl<T: TableEncode> TableEncode for DomainValue<T> {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let mut out = self.step.encode().to_vec();
+        if let Some(value) = self.value {
+            out.extend_from_slice(value.encode().as_ref());
+        }
+        out
+    }
+}
+
+impl<T: TableDecode> TableDecode for DomainValue<T> {
+    fn decode(b: &[u8]) -> Result<Self> {
+        let (step, value) = split_u64_prefix(b)?;
+        Ok(Self {
+            step: step.into(),
+            value: Option::decode(value)?,
+        })
+    }
+}
+
+/// A value in a dupsorted history table, stored as `tx_num||value`. The
+/// value is the one held by the key *before* it was changed in `tx_num`. An
+/// empty value means the key did not exist yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryValue<T> {
+    pub tx_num: TxNum,
+    pub value: Option<T>,
+}
+
+impl<T: TableEncode> TableEncode for HistoryValue<T> {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let mut out = self.tx_num.encode().to_vec();
+        if let Some(value) = self.value {
+            out.extend_from_slice(value.encode().as_ref());
+        }
+        out
+    }
+}
+
+impl<T: TableDecode> TableDecode for HistoryValue<T> {
+    fn decode(b: &[u8]) -> Result<Self> {
+        let (tx_num, value) = split_u64_prefix(b)?;
+        Ok(Self {
+            tx_num: tx_num.into(),
+            value: Option::decode(value)?,
+        })
+    }
+}
+
+fn split_u64_prefix(b: &[u8]) -> Result<(u64, &[u8])> {
+    if b.len() < U64_LENGTH {
+        return Err(DecodeError::TooShort {
+            minimum: U64_LENGTH,
+            got: b.len(),
+        }
+        .into());
+    }
+    let (prefix, rest) = b.split_at(U64_LENGTH);
+    Ok((bytes_to_u64(prefix), rest))
+}
@@ -0,0 +1,64 @@
+use crate::{
+    erigon::{erigon3::models::*, models::*},
+    table_set,
+};
+use ethereum_types::{Address, U256};
+
+// --- Erigon 3 state tables ---
+
+// || indicates concatenation, ^ bitwise negation
+
+// Erigon 3 keeps the latest state in domains and the state before each change
+// in histories, with an inverted index from each key to the tx_nums at which it
+// changed. Only the most recent steps are kept in these tables; older steps are
+// frozen into .kv (domain), .v (history) and .ef (inverted index) files.
+
+table_set! {
+    /// The names of the Erigon 3 state tables, as they are stored in the db.
+    pub const ALL_TABLES;
+    /// The tables in [`ALL_TABLES`] that are dupsort.
+    pub const DUPSORT_TABLES;
+
+    // Table name                   => Key          => Value
+    // key: address. val: ^step||encode_v3(account) (dupsorted). erigon: AccountVals
+    dupsort_table!(
+        AccountsDomain => Address => DomainValue<AccountV3>,
+        subkey = InvertedStep,
+        subvalue = Option<AccountV3>,
+        rename = AccountVals
+    );
+    // key: address||slot. val: ^step||slot_value (dupsorted). erigon: StorageVals
+    dupsort_table!(
+        StorageDomain => StorageDomainKey => DomainValue<U256>,
+        subkey = InvertedStep,
+        subvalue = Option<U256>,
+        rename = StorageVals
+    );
+    // key: address||^step. val: contract code. erigon: CodeVals
+    table!(CodeDomain               => CodeDomainKey => Bytecode, seek_key = Address, rename = CodeVals);
+
+    // key: address. val: tx_num||encode_v3(account) (dupsorted)
+    dupsort_table!(
+        AccountHistoryVals => Address => HistoryValue<AccountV3>,
+        subkey = TxNum,
+        subvalue = Option<AccountV3>
+    );
+    // key: address||slot. val: tx_num||slot_value (dupsorted)
+    dupsort_table!(
+        StorageHistoryVals => StorageDomainKey => HistoryValue<U256>,
+        subkey = TxNum,
+        subvalue = Option<U256>
+    );
+    // key: address||tx_num. val: contract code
+    table!(CodeHistoryVals          => CodeHistKey  => Bytecode, seek_key = Address);
+
+    // key: address. val: tx_num of each change (dupsorted)
+    dupsort_table!(AccountIdx       => Address      => TxNum, subkey = TxNum, subvalue = ());
+    // key: address||slot. val: tx_num of each change (dupsorted)
+    dupsort_table!(StorageIdx       => StorageDomainKey => TxNum, subkey = TxNum, subvalue = ());
+    // key: address. val: tx_num of each change (dupsorted)
+    dupsort_table!(CodeIdx          => Address      => TxNum, subkey = TxNum, subvalue = ());
+
+    // key: blocknum. val: tx_num of the last (system) tx in the block
+    table!(MaxTxNum                 => BlockNumber  => TxNum);
+}
@@ -0,0 +1,341 @@
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use revm::{
+    AccountInfo, BlockEnv, Bytecode, CreateScheme, Database, DatabaseRef, Env, Return, SpecId,
+    TransactOut, TransactTo, TxEnv, EVM, KECCAK_EMPTY,
+};
+use std::cell::RefCell;
+
+use crate::{
+    erigon::{
+        models::{transaction::TxAction, *},
+        state::StateAt,
+        tables::{Code, HeadersTotalDifficulty},
+        utils::consts as C,
+        Erigon,
+    },
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+
+// Serves revm's state reads from a historical view of the db.
+impl<'tx, 'env, K: Mode> DatabaseRef for StateAt<'tx, 'env, K> {
+    type Error = Error;
+
+    fn basic(&self, address: Address) -> Result<Option<AccountInfo>> {
+        Ok(self.account(address)?.map(|acct| AccountInfo {
+            balance: acct.balance,
+            nonce: acct.nonce,
+            // Erigon stores no codehash for accounts without code
+            code_hash: if acct.codehash.is_zero() || acct.codehash == C::EMPTY_HASH {
+                KECCAK_EMPTY
+            } else {
+                acct.codehash
+            },
+            code: None,
+        }))
+    }
+
+    fn code_by_hash(&self, code_hash: H256) -> Result<Bytecode> {
+        if code_hash.is_zero() || code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::new());
+        }
+        let code = StateAt::code_by_hash(self, code_hash)?.ok_or(Error::NotFound(Code::NAME))?;
+        Ok(Bytecode::new_raw(code.0))
+    }
+
+    fn storage(&self, address: Address, index: U256) -> Result<U256> {
+        let mut slot = H256::zero();
+        index.to_big_endian(slot.as_bytes_mut());
+        StateAt::storage(self, address, slot)
+    }
+
+    fn block_hash(&self, number: U256) -> Result<H256> {
+        if number > U256::from(u64::MAX) {
+            return Ok(H256::zero());
+        }
+        Ok(StateAt::block_hash(self, number.as_u64())?.unwrap_or_default())
+    }
+}
+
+impl<'tx, 'env, K: Mode> Database for StateAt<'tx, 'env, K> {
+    type Error = Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>> {
+        DatabaseRef::basic(self, address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode> {
+        DatabaseRef::code_by_hash(self, code_hash)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256> {
+        DatabaseRef::storage(self, address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<H256> {
+        DatabaseRef::block_hash(self, number)
+    }
+}
+
+/// Wraps a revm database to keep the first error it returns. revm stops
+/// executing on a db error, but reports it only as
+/// `Return::FatalExternalError` and drops the error itself, so it is taken
+/// back with [`take_error`](Self::take_error) once execution is done.
+#[derive(Debug)]
+pub struct KeepError<D> {
+    pub db: D,
+    error: RefCell<Option<Error>>,
+}
+
+impl<D> KeepError<D> {
+    pub fn new(db: D) -> Self {
+        Self {
+            db,
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Returns the error kept from the first failed read, if any.
+    pub fn take_error(&self) -> Option<Error> {
+        self.error.take()
+    }
+
+    fn keep<T>(&self, res: Result<T>) -> std::result::Result<T, ()> {
+        res.map_err(|e| {
+            self.error.borrow_mut().get_or_insert(e);
+        })
+    }
+}
+
+impl<D: DatabaseRef<Error = Error>> DatabaseRef for KeepError<D> {
+    type Error = ();
+
+    fn basic(&self, address: Address) -> std::result::Result<Option<AccountInfo>, ()> {
+        self.keep(self.db.basic(address))
+    }
+
+    fn code_by_hash(&self, code_hash: H256) -> std::result::Result<Bytecode, ()> {
+        self.keep(self.db.code_by_hash(code_hash))
+    }
+
+    fn storage(&self, address: Address, index: U256) -> std::result::Result<U256, ()> {
+        self.keep(self.db.storage(address, index))
+    }
+
+    fn block_hash(&self, number: U256) -> std::result::Result<H256, ()> {
+        self.keep(self.db.block_hash(number))
+    }
+}
+
+impl<D: DatabaseRef<Error = Error>> Database for KeepError<D> {
+    type Error = ();
+
+    fn basic(&mut self, address: Address) -> std::result::Result<Option<AccountInfo>, ()> {
+        DatabaseRef::basic(self, address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: H256) -> std::result::Result<Bytecode, ()> {
+        DatabaseRef::code_by_hash(self, code_hash)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> std::result::Result<U256, ()> {
+        DatabaseRef::storage(self, address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> std::result::Result<H256, ()> {
+        DatabaseRef::block_hash(self, number)
+    }
+}
+
+/// The parameters of a message call, equivalent to the call object accepted
+/// by `eth_call`. Unset fields take the same defaults as in geth.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CallRequest {
+    pub from: Option<Address>,
+    /// The callee. A missing `to` executes `data` as contract creation code.
+    pub to: Option<Address>,
+    /// Defaults to the block gas limit.
+    pub gas: Option<u64>,
+    pub gas_price: Option<U256>,
+    pub value: Option<U256>,
+    pub data: Bytes,
+}
+
+/// The outcome of executing a [`CallRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallResult {
+    pub exit_reason: Return,
+    /// The return data, or the revert data if the call reverted.
+    pub output: Bytes,
+    pub gas_used: u64,
+}
+
+impl CallResult {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.exit_reason,
+            Return::Continue | Return::Stop | Return::Return | Return::SelfDestruct
+        )
+    }
+
+    /// Returns the revert data, if the call reverted.
+    pub fn revert_data(&self) -> Option<&Bytes> {
+        match self.exit_reason {
+            Return::Revert => Some(&self.output),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the revm transaction environment for executing a signed transaction.
+pub fn tx_env(tx: &TransactionWithSigner) -> TxEnv {
+    let msg = &tx.msg;
+    TxEnv {
+        caller: tx.signer,
+        gas_limit: msg.gas(),
+        gas_price: msg
+            .gas_price()
+            .or_else(|| msg.fee_cap())
+            .unwrap_or_default(),
+        gas_priority_fee: msg.tip(),
+        transact_to: match msg.to() {
+            TxAction::Call(to) => TransactTo::Call(to),
+            TxAction::Create => TransactTo::Create(CreateScheme::Create),
+        },
+        value: msg.value(),
+        data: msg.data().clone(),
+        chain_id: msg.chain_id().map(|id| id.low_u64()),
+        access_list: msg
+            .access_list()
+            .map(|list| {
+                list.iter()
+                    .map(|item| {
+                        let slots = item
+                            .slots
+                            .iter()
+                            .map(|slot| U256::from_big_endian(slot.as_bytes()))
+                            .collect();
+                        (item.address, slots)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+/// Returns the revm spec that applies to block `num`, whose parent has the
+/// total difficulty `parent_td`.
+pub fn spec_id(config: &ChainConfig, num: u64, parent_td: U256) -> SpecId {
+    if config.is_paris(parent_td) {
+        SpecId::MERGE
+    } else if config.gray_glacier_block.map_or(false, |n| n <= num) {
+        SpecId::GRAY_GLACIER
+    } else if config.arrow_glacier_block.map_or(false, |n| n <= num) {
+        SpecId::ARROW_GLACIER
+    } else if config.is_london(num) {
+        SpecId::LONDON
+    } else if config.is_berlin(num) {
+        SpecId::BERLIN
+    } else if config.muir_glacier_block.map_or(false, |n| n <= num) {
+        SpecId::MUIR_GLACIER
+    } else if config.is_istanbul(num) {
+        SpecId::ISTANBUL
+    } else if config.is_petersburg(num) {
+        SpecId::PETERSBURG
+    } else if config.is_constantinople(num) {
+        SpecId::CONSTANTINOPLE
+    } else if config.is_byzantium(num) {
+        SpecId::BYZANTIUM
+    } else if config.is_eip158(num) {
+        SpecId::SPURIOUS_DRAGON
+    } else if config.is_eip150(num) {
+        SpecId::TANGERINE
+    } else if config.is_homestead(num) {
+        SpecId::HOMESTEAD
+    } else {
+        SpecId::FRONTIER
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the revm environment for executing transactions in the given block.
+    pub fn block_env(&self, id: impl Into<BlockId>) -> Result<Option<Env>> {
+        let key = match self.read_header_key(id)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let header = match self.read_header(key)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let config = self.read_chain_config()?.unwrap_or_default();
+        let td = self
+            .read_total_difficulty(key)?
+            .ok_or(Error::NotFound(HeadersTotalDifficulty::NAME))?;
+
+        let mut env = Env::default();
+        env.cfg.chain_id = config.chain_id.into();
+        env.cfg.spec_id = spec_id(&config, *key.0, td.saturating_sub(header.difficulty));
+        env.block = BlockEnv {
+            number: (*key.0).into(),
+            coinbase: header.coinbase,
+            timestamp: header.time.into(),
+            difficulty: header.difficulty,
+            basefee: header.base_fee.unwrap_or_default(),
+            gas_limit: header.gas_limit.into(),
+            ..Default::default()
+        };
+        Ok(Some(env))
+    }
+
+    /// Executes the call on top of the state at the end of the given block,
+    /// without committing any changes. Returns `None` if the block is unknown.
+    pub fn call(&self, block: impl Into<BlockId>, req: CallRequest) -> Result<Option<CallResult>> {
+        let block = block.into();
+        let mut env = match self.block_env(block)? {
+            Some(env) => env,
+            None => return Ok(None),
+        };
+        let num = match env.block.number.try_into() {
+            Ok(num) => BlockNumber(num),
+            Err(_) => return Ok(None),
+        };
+
+        // like geth, a call with no gas price is not charged the base fee
+        if req.gas_price.is_none() {
+            env.block.basefee = U256::zero();
+        }
+        env.tx = TxEnv {
+            caller: req.from.unwrap_or_default(),
+            gas_limit: req.gas.unwrap_or_else(|| env.block.gas_limit.low_u64()),
+            gas_price: req.gas_price.unwrap_or_default(),
+            transact_to: match req.to {
+                Some(to) => TransactTo::Call(to),
+                None => TransactTo::Create(CreateScheme::Create),
+            },
+            value: req.value.unwrap_or_default(),
+            data: req.data,
+            ..Default::default()
+        };
+
+        let mut evm = EVM::new();
+        evm.env = env;
+        evm.database(KeepError::new(self.state_at(num)));
+        let (res, _) = evm.transact_ref();
+        if let Some(e) = evm.db.as_ref().and_then(|db| db.take_error()) {
+            return Err(e);
+        }
+        let output = match res.out {
+            TransactOut::None => Bytes::new(),
+            TransactOut::Call(out) => out,
+            TransactOut::Create(out, _) => out,
+        };
+        Ok(Some(CallResult {
+            exit_reason: res.exit_reason,
+            output,
+            gas_used: res.gas_used,
+        }))
+    }
+}
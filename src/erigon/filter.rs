@@ -0,0 +1,208 @@
+use ethereum_types::{Address, Bloom, BloomInput, H256};
+use roaring::RoaringBitmap;
+use std::ops::RangeBounds;
+
+use crate::{
+    erigon::{models::*, tables::*, utils, Erigon},
+    kv::traits::Mode,
+    Result,
+};
+
+/// A filter over the logs in a range of canonical blocks, equivalent to the
+/// filter object accepted by `eth_getLogs`.
+///
+/// A log matches if it was emitted by any of `addresses` (or `addresses` is
+/// empty), and if, for each position `i`, its `i`th topic is one of `topics[i]`
+/// (or `topics[i]` is empty).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    pub from_block: u64,
+    /// The last block to search, inclusive. Defaults to the head block.
+    pub to_block: Option<u64>,
+    pub addresses: Vec<Address>,
+    pub topics: Vec<Vec<H256>>,
+}
+
+/// Membership checks on the logs bloom of a header or receipt. A bloom can
+/// report an address or topic that no log has, but never misses one that a
+/// log does have.
+pub trait BloomExt {
+    fn contains_address(&self, adr: &Address) -> bool;
+    fn contains_topic(&self, topic: &H256) -> bool;
+}
+
+impl BloomExt for Bloom {
+    fn contains_address(&self, adr: &Address) -> bool {
+        self.contains_input(BloomInput::Raw(adr.as_bytes()))
+    }
+    fn contains_topic(&self, topic: &H256) -> bool {
+        self.contains_input(BloomInput::Raw(topic.as_bytes()))
+    }
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn from_block(mut self, num: u64) -> Self {
+        self.from_block = num;
+        self
+    }
+    pub fn to_block(mut self, num: u64) -> Self {
+        self.to_block = Some(num);
+        self
+    }
+    pub fn address(mut self, adr: Address) -> Self {
+        self.addresses.push(adr);
+        self
+    }
+    /// Adds `topic` to the set of topics accepted at position `pos`.
+    pub fn topic(mut self, pos: usize, topic: H256) -> Self {
+        if self.topics.len() <= pos {
+            self.topics.resize(pos + 1, Vec::new());
+        }
+        self.topics[pos].push(topic);
+        self
+    }
+
+    /// Returns true if the log satisfies the address and topic constraints.
+    pub fn matches(&self, log: &CborLog) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+        self.topics.iter().enumerate().all(|(i, wanted)| {
+            wanted.is_empty() || log.topics.get(i).map_or(false, |t| wanted.contains(t))
+        })
+    }
+
+    /// Returns false if no log in a block with the given bloom can match the
+    /// filter. The bloom doesn't record topic positions, so each topic is
+    /// only checked for presence.
+    pub fn may_match(&self, bloom: &Bloom) -> bool {
+        let address_ok = self.addresses.is_empty()
+            || self.addresses.iter().any(|adr| bloom.contains_address(adr));
+        let topics_ok = self
+            .topics
+            .iter()
+            .all(|wanted| wanted.is_empty() || wanted.iter().any(|t| bloom.contains_topic(t)));
+        address_ok && topics_ok
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the logs matching the filter as `(block_number, tx_index, log_index, log)`.
+    ///
+    /// The LogAddressIndex and LogTopicIndex bitmaps are intersected first, so
+    /// only the blocks that may contain a matching log are read from the
+    /// TransactionLog table.
+    // https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/cmd/rpcdaemon/commands/eth_receipts.go#L69
+    pub fn get_logs(&self, filter: &LogFilter) -> Result<Vec<(BlockNumber, u32, u32, CborLog)>> {
+        let from = filter.from_block;
+        let to = match filter.to_block {
+            Some(to) => to,
+            None => match self.read_head_block_number()? {
+                Some(head) => *head,
+                None => return Ok(Vec::new()),
+            },
+        };
+        if from > to {
+            return Ok(Vec::new());
+        }
+
+        // None means every block in the range may match.
+        let mut blocks: Option<RoaringBitmap> = None;
+        for wanted in filter.topics.iter().filter(|t| !t.is_empty()) {
+            let mut union = RoaringBitmap::new();
+            for topic in wanted {
+                union |= self.read_log_topic_bitmap(*topic, from..=to)?;
+            }
+            intersect(&mut blocks, union);
+        }
+        if !filter.addresses.is_empty() {
+            let mut union = RoaringBitmap::new();
+            for adr in &filter.addresses {
+                union |= self.read_log_address_bitmap(*adr, from..=to)?;
+            }
+            intersect(&mut blocks, union);
+        }
+
+        let mut out = Vec::new();
+        let mut scan = |num: u64| -> Result<()> {
+            for (tx_index, log_index, log) in self.read_logs(num)? {
+                if filter.matches(&log) {
+                    out.push((BlockNumber(num), tx_index, log_index, log));
+                }
+            }
+            Ok(())
+        };
+        match blocks {
+            Some(blocks) => {
+                for num in blocks.iter().map(u64::from) {
+                    if (from..=to).contains(&num) {
+                        scan(num)?;
+                    }
+                }
+            }
+            None => {
+                for num in from..=to {
+                    scan(num)?;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns the canonical blocks in the range whose header bloom may match
+    /// the filter, ignoring the filter's own block range. This only reads
+    /// headers, so it can narrow down the blocks to search where the log
+    /// index tables have been pruned.
+    pub fn blocks_maybe_matching(
+        &self,
+        filter: &LogFilter,
+        range: impl RangeBounds<u64>,
+    ) -> Result<Vec<BlockNumber>> {
+        let mut out = Vec::new();
+        for read in self.walk_canonical_headers(range)? {
+            let (num, header) = read?;
+            if filter.may_match(&header.bloom) {
+                out.push(num);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns the bitmap of blocks in the range whose logs include the given topic.
+    pub fn read_log_topic_bitmap(
+        &self,
+        topic: H256,
+        range: impl RangeBounds<u64>,
+    ) -> Result<RoaringBitmap> {
+        let (start, end) = utils::block_range(range);
+        let walker = self
+            .cursor::<LogTopicIndex>()?
+            .walk(LogTopicIndexKey(topic, utils::shard_start(start)))?
+            .map(|res| res.map(|(k, v)| ((k.0, k.1), v)));
+        utils::union_shards(walker, topic, start, end)
+    }
+
+    /// Returns the bitmap of blocks in the range containing logs emitted by the given address.
+    pub fn read_log_address_bitmap(
+        &self,
+        adr: Address,
+        range: impl RangeBounds<u64>,
+    ) -> Result<RoaringBitmap> {
+        let (start, end) = utils::block_range(range);
+        let walker = self
+            .cursor::<LogAddressIndex>()?
+            .walk(LogAddressIndexKey(adr, utils::shard_start(start)))?
+            .map(|res| res.map(|(k, v)| ((k.0, k.1), v)));
+        utils::union_shards(walker, adr, start, end)
+    }
+}
+
+fn intersect(acc: &mut Option<RoaringBitmap>, other: RoaringBitmap) {
+    match acc {
+        Some(acc) => *acc &= other,
+        None => *acc = Some(other),
+    }
+}
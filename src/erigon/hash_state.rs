@@ -0,0 +1,98 @@
+use ethereum_types::{Address, H256, U256};
+use mdbx::RW;
+use std::collections::BTreeSet;
+
+use crate::{
+    erigon::{models::*, tables::*, utils::keccak256, Erigon},
+    kv::{
+        tables::RawTable,
+        traits::{DbName, TableDecode},
+    },
+    Error, Result,
+};
+
+impl<'env> Erigon<'env, RW> {
+    /// Rebuilds HashedAccount, HashedStorage and HashedCodeHash from PlainState
+    /// and PlainCodeHash, as Erigon's HashState stage does on its first run.
+    /// The HashState stage is then marked as having reached the Execution
+    /// stage, whose state PlainState holds, and that block is returned.
+    pub fn promote_hashed_state(&self) -> Result<BlockNumber> {
+        let to = self.execution_progress()?;
+        self.clear::<HashedAccount>()?;
+        self.clear::<HashedStorage>()?;
+        self.clear::<HashedCodeHash>()?;
+
+        // PlainState holds both accounts, keyed by address, and storage,
+        // keyed by address||incarnation, so it is walked untyped
+        let plain = self.0.open_raw(PlainState::NAME)?;
+        for read in self.0.cursor::<RawTable, _>(plain)?.walk(vec![])? {
+            let (key, val) = read?;
+            if key.len() == Address::len_bytes() {
+                let hashed_adr = H256(keccak256(&key));
+                self.write::<HashedAccount>(hashed_adr, Account::decode(&val)?)?;
+            } else {
+                let StorageKey(adr, inc) = StorageKey::decode(&key)?;
+                let (slot, val) = <(H256, U256)>::decode(&val)?;
+                let key = HashStorageKey::make(adr, inc);
+                self.write::<HashedStorage>(key, (H256(keccak256(slot)), val))?;
+            }
+        }
+        for read in self.cursor::<PlainCodeHash>()?.walk(Default::default())? {
+            let (PlainCodeKey(adr, inc), codehash) = read?;
+            self.write::<HashedCodeHash>(ContractCodeKey::make(adr, inc), codehash)?;
+        }
+
+        self.write::<SyncStage>(StageId::HashState, to)?;
+        Ok(to)
+    }
+
+    /// Updates the hashed state with the accounts and storage changed in the
+    /// blocks after `block`, as listed in the changesets, bringing it up to
+    /// the Execution stage. `block` is normally the progress of the HashState
+    /// stage. Returns the block reached.
+    pub fn promote_hashed_state_since(&self, block: impl Into<BlockNumber>) -> Result<BlockNumber> {
+        let to = self.execution_progress()?;
+        let from = BlockNumber(*block.into() + 1);
+
+        let mut accounts = BTreeSet::new();
+        for read in self.cursor::<AccountChangeSet>()?.iter(from) {
+            let (_, AccountCSVal(adr, _)) = read?;
+            accounts.insert(adr);
+        }
+        for adr in accounts {
+            let hashed_adr = H256(keccak256(adr));
+            let acct = match self.read_account(adr)? {
+                Some(acct) => acct,
+                None => {
+                    self.delete::<HashedAccount>(hashed_adr, None)?;
+                    continue;
+                }
+            };
+            self.write::<HashedAccount>(hashed_adr, acct)?;
+            if let Some(codehash) = self.read_codehash(adr, acct.incarnation)? {
+                let key = ContractCodeKey(hashed_adr, acct.incarnation);
+                self.write::<HashedCodeHash>(key, codehash)?;
+            }
+        }
+
+        let mut slots = BTreeSet::new();
+        let start = StorageCSKey(from, Default::default());
+        for read in self.cursor::<StorageChangeSet>()?.iter(start) {
+            let (StorageCSKey(_, StorageKey(adr, inc)), StorageCSVal(slot, _)) = read?;
+            slots.insert((adr, inc, slot));
+        }
+        for (adr, inc, slot) in slots {
+            let val = self.read_storage(adr, inc, slot)?.unwrap_or_default();
+            let hashed_slot = H256(keccak256(slot));
+            self.write_hashed_storage(H256(keccak256(adr)), inc, hashed_slot, val)?;
+        }
+
+        self.write::<SyncStage>(StageId::HashState, to)?;
+        Ok(to)
+    }
+
+    fn execution_progress(&self) -> Result<BlockNumber> {
+        self.read_stage_progress(StageId::Execution)?
+            .ok_or(Error::NotFound(SyncStage::NAME))
+    }
+}
@@ -0,0 +1,106 @@
+use ethereum_types::{Address, H256, U256};
+use std::collections::HashMap;
+
+use crate::{
+    erigon::{models::*, seek_change, tables::*, Erigon},
+    kv::{
+        traits::{DbName, Mode},
+        MdbxCursor,
+    },
+    Error, Result,
+};
+
+/// Reads the state as it was before a given block was executed, i.e. the
+/// state that the block's transactions ran against, for many lookups at the
+/// same block, e.g. while tracing it.
+///
+/// Unlike [`Erigon::read_account_hist`] and [`Erigon::read_storage_hist`],
+/// which open new cursors on every call, the reader opens its cursors once
+/// and moves them from one lookup to the next. It also keeps each account and
+/// slot it resolves, so a repeated lookup, including the account read behind
+/// every storage read, costs no history seek. Reads fall back to PlainState
+/// if the history shows no change since the block.
+pub struct HistoryReader<'tx, 'env, K: Mode> {
+    db: &'tx Erigon<'env, K>,
+    block: BlockNumber,
+    account_hist: MdbxCursor<'tx, K, AccountHistory>,
+    storage_hist: MdbxCursor<'tx, K, StorageHistory>,
+    account_cs: MdbxCursor<'tx, K, AccountChangeSet>,
+    storage_cs: MdbxCursor<'tx, K, StorageChangeSet>,
+    plain: MdbxCursor<'tx, K, PlainState>,
+    storage: MdbxCursor<'tx, K, Storage>,
+    accounts: HashMap<Address, Option<Account>>,
+    slots: HashMap<(Address, H256), U256>,
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns a reader of the state before the given block was executed.
+    pub fn history_reader(
+        &self,
+        block: impl Into<BlockNumber>,
+    ) -> Result<HistoryReader<'_, 'env, K>> {
+        Ok(HistoryReader {
+            db: self,
+            block: block.into(),
+            account_hist: self.cursor()?,
+            storage_hist: self.cursor()?,
+            account_cs: self.cursor()?,
+            storage_cs: self.cursor()?,
+            plain: self.cursor()?,
+            storage: self.cursor()?,
+            accounts: HashMap::new(),
+            slots: HashMap::new(),
+        })
+    }
+}
+
+impl<'tx, 'env, K: Mode> HistoryReader<'tx, 'env, K> {
+    pub fn block(&self) -> BlockNumber {
+        self.block
+    }
+
+    /// Returns the account at `adr`, or None if the account did not exist.
+    pub fn account(&mut self, adr: Address) -> Result<Option<Account>> {
+        if let Some(acct) = self.accounts.get(&adr) {
+            return Ok(*acct);
+        }
+        let key = AccountHistKey(adr, self.block);
+        let change = seek_change(&mut self.account_hist, key, self.block, |k| k.0 == adr)?;
+        let acct = match change {
+            Some(cs_block) => {
+                let acct = self
+                    .account_cs
+                    .seek_exact_dup(cs_block, adr)?
+                    .ok_or(Error::NotFound(AccountChangeSet::NAME))?;
+                self.db.account_from_changeset(adr, acct)?
+            }
+            None => self.plain.seek_exact(adr)?,
+        };
+        self.accounts.insert(adr, acct);
+        Ok(acct)
+    }
+
+    /// Returns the value of the storage slot, which is zero if the account or
+    /// slot did not exist.
+    pub fn storage(&mut self, adr: Address, slot: H256) -> Result<U256> {
+        if let Some(val) = self.slots.get(&(adr, slot)) {
+            return Ok(*val);
+        }
+        let inc = match self.account(adr)? {
+            Some(acct) => acct.incarnation,
+            None => return Ok(U256::zero()),
+        };
+        let key = StorageHistKey(adr, slot, self.block);
+        let is_key = |k: &StorageHistKey| k.0 == adr && k.1 == slot;
+        let change = seek_change(&mut self.storage_hist, key, self.block, is_key)?;
+        let val = match change {
+            Some(cs_block) => self
+                .storage_cs
+                .seek_exact_dup((cs_block, adr, inc).into(), slot)?,
+            None => self.storage.seek_exact_dup(StorageKey(adr, inc), slot)?,
+        };
+        let val = val.unwrap_or_default();
+        self.slots.insert((adr, slot), val);
+        Ok(val)
+    }
+}
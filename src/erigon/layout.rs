@@ -0,0 +1,69 @@
+//! The storage slots of solidity state variables, as laid out by the
+//! compiler. See
+//! <https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html>.
+//!
+//! Keys and slots are 32-byte words. A key of a value type is left-padded to
+//! a word, so an address key is `H256::from(adr)` and an integer key is
+//! `H256::from_low_u64_be(n)`. A nested mapping is one mapping inside
+//! another, so the slot of `allowances[owner][spender]` is
+//! `mapping_slot(mapping_slot(base, owner.into()), spender.into())`.
+use ethereum_types::{Address, H256, U256};
+
+use crate::{
+    erigon::{models::BlockNumber, utils::keccak256, Erigon},
+    kv::traits::Mode,
+    Result,
+};
+
+/// The slot of the balances mapping of an ERC-20 token laid out as
+/// OpenZeppelin's, in which `_balances` is the first state variable.
+pub const ERC20_BALANCES_SLOT: H256 = H256([0; 32]);
+
+/// Returns the slot of the value at `key` in the mapping at `base_slot`,
+/// `keccak256(key || base_slot)`.
+pub fn mapping_slot(base_slot: H256, key: H256) -> H256 {
+    let mut buf = [0; 64];
+    buf[..32].copy_from_slice(key.as_bytes());
+    buf[32..].copy_from_slice(base_slot.as_bytes());
+    H256(keccak256(buf))
+}
+
+/// Returns the slot of element `index` of the dynamic array at `base_slot`,
+/// `keccak256(base_slot) + index`, for elements that take a whole slot each.
+/// The array's length is stored at `base_slot` itself.
+pub fn array_slot(base_slot: H256, index: U256) -> H256 {
+    let slot = U256::from_big_endian(&keccak256(base_slot))
+        .overflowing_add(index)
+        .0;
+    let mut out = [0; 32];
+    slot.to_big_endian(&mut out);
+    H256(out)
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the value at `key` in the mapping at `base_slot` of the
+    /// contract at `adr`, after the given block was executed.
+    pub fn read_mapping(
+        &self,
+        adr: Address,
+        base_slot: H256,
+        key: H256,
+        block: impl Into<BlockNumber>,
+    ) -> Result<U256> {
+        self.state_at(block)
+            .storage(adr, mapping_slot(base_slot, key))
+    }
+
+    /// Returns the balance of `holder` in the ERC-20 `token` after the given
+    /// block was executed, for a token that keeps its balances at
+    /// [`ERC20_BALANCES_SLOT`]. The balances of a token with another layout
+    /// can be read with [`Self::read_mapping`].
+    pub fn read_erc20_balance(
+        &self,
+        token: Address,
+        holder: Address,
+        block: impl Into<BlockNumber>,
+    ) -> Result<U256> {
+        self.read_mapping(token, ERC20_BALANCES_SLOT, holder.into(), block)
+    }
+}
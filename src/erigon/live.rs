@@ -0,0 +1,88 @@
+use mdbx::RO;
+use std::path::PathBuf;
+
+use crate::{
+    erigon::{env_builder, Erigon},
+    kv::{MdbxEnv, MdbxEnvBuilder},
+    Error, Result,
+};
+
+// The number of times a read is retried after the env is reopened.
+const DEFAULT_MAX_RETRIES: usize = 3;
+
+/// Reads from the db of a running Erigon node, retrying reads that fail
+/// because the node changed the db under them.
+///
+/// When Erigon grows the db file past the size mapped by this process, reads
+/// fail with `MDBX_UNABLE_EXTEND_MAPSIZE` (formerly `MDBX_MAP_RESIZED`), and a
+/// reader slot or transaction may be left stale. On those errors the env is
+/// closed and opened again, which maps the db at its new size, and the read is
+/// run again in a new transaction.
+///
+/// mdbx does not allow a process to open the same env twice, so the reader
+/// owns its env, and reads take `&mut self` so that no transaction is open
+/// while it is reopened.
+#[derive(Debug)]
+pub struct LiveReader {
+    path: PathBuf,
+    builder: MdbxEnvBuilder,
+    env: Option<MdbxEnv<RO>>,
+    max_retries: usize,
+}
+
+impl LiveReader {
+    /// Opens the Erigon db at `path` read-only.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        Self::with_builder(path, env_builder())
+    }
+
+    /// Opens the db at `path` read-only with the given env options, which are
+    /// also used each time the env is reopened.
+    pub fn with_builder(path: impl Into<PathBuf>, builder: MdbxEnvBuilder) -> Result<Self> {
+        let path = path.into();
+        let env = builder.open(&path)?;
+        Ok(Self {
+            path,
+            builder,
+            env: Some(env),
+            max_retries: DEFAULT_MAX_RETRIES,
+        })
+    }
+
+    /// Sets the number of times a failed read is retried before its error is
+    /// returned.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Runs `f` in a new read-only transaction. If the read fails because the
+    /// db was resized, the env is reopened and `f` is run again.
+    pub fn read<R>(&mut self, mut f: impl FnMut(&Erigon<'_, RO>) -> Result<R>) -> Result<R> {
+        let mut retries = 0;
+        loop {
+            let env = match self.env.take() {
+                Some(env) => env,
+                None => self.builder.open(&self.path)?,
+            };
+            let env = self.env.insert(env);
+            let res = Erigon::begin(env).and_then(|db| f(&db));
+            match res {
+                Err(e) if is_stale(&e) && retries < self.max_retries => {
+                    retries += 1;
+                    // dropped first, as an env cannot be opened twice
+                    self.env = None;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+// Whether the error means the env or transaction no longer matches the db.
+fn is_stale(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::Mdbx(mdbx::Error::UnableExtendMapsize | mdbx::Error::BadRslot | mdbx::Error::BadTxn)
+    )
+}
@@ -10,8 +10,6 @@ macro_rules! declare_tuple {
             ::derive_more::From,
             ::serde::Serialize,
             ::serde::Deserialize,
-            ::fastrlp::RlpEncodable,
-            ::fastrlp::RlpDecodable,
         )]
         pub struct $name($(pub $t),+);
     }
@@ -73,14 +71,17 @@ pub(crate) use impl_encode_tuple;
 macro_rules! impl_decode_tuple {
     ($name:ident($($t:ty),+), $n:literal) => {
         impl $crate::kv::traits::TableDecode for $name {
-            fn decode(b: &[u8]) -> ::eyre::Result<Self> {
+            fn decode(b: &[u8]) -> $crate::Result<Self> {
                 if b.len() > Self::SIZE {
-                    return Err(
-                        $crate::kv::tables::TooLong::<{ Self::SIZE }> { got: b.len() }.into(),
-                    );
+                    return Err($crate::error::DecodeError::TooLong {
+                        maximum: Self::SIZE,
+                        got: b.len(),
+                    }
+                    .into());
                 }
                 if b.len() < Self::MIN_SIZE {
-                    return Err($crate::kv::tables::TooShort::<{ Self::MIN_SIZE }> {
+                    return Err($crate::error::DecodeError::TooShort {
+                        minimum: Self::MIN_SIZE,
                         got: b.len(),
                     }
                     .into());
@@ -158,13 +159,13 @@ macro_rules! rlp_table_value {
             type Encoded = ::bytes::Bytes;
             fn encode(self) -> Self::Encoded {
                 let mut buf = ::bytes::BytesMut::new();
-                ::fastrlp::Encodable::encode(&self, &mut buf);
+                $crate::rlp::Encodable::encode(&self, &mut buf);
                 buf.into()
             }
         }
         impl $crate::kv::traits::TableDecode for $t {
-            fn decode(mut b: &[u8]) -> ::eyre::Result<Self> {
-                ::fastrlp::Decodable::decode(&mut b).map_err(From::from)
+            fn decode(mut b: &[u8]) -> $crate::Result<Self> {
+                $crate::rlp::Decodable::decode(&mut b).map_err(From::from)
             }
         }
     };
@@ -211,7 +212,7 @@ macro_rules! bytes_wrapper {
         }
 
         impl $crate::kv::traits::TableDecode for $name {
-            fn decode(b: &[u8]) -> ::eyre::Result<Self> {
+            fn decode(b: &[u8]) -> $crate::Result<Self> {
                 $crate::kv::traits::TableDecode::decode(b).map(Self)
             }
         }
@@ -219,6 +220,31 @@ macro_rules! bytes_wrapper {
 }
 pub(crate) use bytes_wrapper;
 
+/// bytes_ref! declares a view of a bytes_wrapper! type that borrows its bytes
+/// from the db, to be read with
+/// [`Erigon::read_borrowed`](crate::Erigon::read_borrowed) when copying the
+/// value would be costly.
+macro_rules! bytes_ref {
+    ($name:ident($owned:ident)) => {
+        #[derive(Debug, Clone, PartialEq, Eq, ::derive_more::Deref)]
+        pub struct $name<'tx>(pub ::std::borrow::Cow<'tx, [u8]>);
+
+        impl $name<'_> {
+            /// Copies the bytes out of the db, if they are borrowed.
+            pub fn into_owned(self) -> $owned {
+                $owned(::bytes::Bytes::from(self.0.into_owned()))
+            }
+        }
+
+        impl<'tx> $crate::kv::traits::TableDecodeBorrowed<'tx> for $name<'tx> {
+            fn decode_borrowed(b: ::std::borrow::Cow<'tx, [u8]>) -> $crate::Result<Self> {
+                Ok(Self(b))
+            }
+        }
+    };
+}
+pub(crate) use bytes_ref;
+
 macro_rules! decl_u64_wrapper {
     ($ty:ident) => {
         #[derive(
@@ -238,13 +264,11 @@ macro_rules! decl_u64_wrapper {
             ::derive_more::FromStr,
             ::serde::Serialize,
             ::serde::Deserialize,
-            ::fastrlp::RlpEncodableWrapper,
-            ::fastrlp::RlpDecodableWrapper,
-            ::fastrlp::RlpMaxEncodedLen,
         )]
         #[serde(transparent)]
         #[repr(transparent)]
         pub struct $ty(pub u64);
+        $crate::rlp::rlp_wrapper!($ty);
 
         $crate::erigon::macros::impl_from!($ty, u64);
         $crate::erigon::macros::impl_from!($ty, usize);
@@ -263,10 +287,14 @@ macro_rules! u64_table_key {
         }
 
         impl $crate::kv::traits::TableDecode for $ty {
-            fn decode(b: &[u8]) -> ::eyre::Result<Self> {
+            fn decode(b: &[u8]) -> $crate::Result<Self> {
                 match b.len() {
                     8 => Ok(u64::from_be_bytes(*::arrayref::array_ref!(&*b, 0, 8)).into()),
-                    other => Err($crate::kv::tables::InvalidLength::<8> { got: other }.into()),
+                    other => Err($crate::error::DecodeError::InvalidLength {
+                        expected: 8,
+                        got: other,
+                    }
+                    .into()),
                 }
             }
         }
@@ -301,12 +329,11 @@ macro_rules! decl_u256_wrapper {
             ::derive_more::FromStr,
             ::serde::Serialize,
             ::serde::Deserialize,
-            ::fastrlp::RlpEncodable,
-            ::fastrlp::RlpDecodableWrapper,
         )]
         #[serde(transparent)]
         #[repr(transparent)]
         pub struct $ty(pub U256);
+        $crate::rlp::rlp_wrapper!($ty);
 
         $crate::erigon::macros::impl_from!($ty, U256);
     };
@@ -340,7 +367,7 @@ macro_rules! cbor_wrapper {
         }
 
         impl $crate::kv::traits::TableDecode for $name {
-            fn decode(b: &[u8]) -> ::eyre::Result<Self> {
+            fn decode(b: &[u8]) -> $crate::Result<Self> {
                 ::serde_cbor::from_slice(b).map_err(From::from).map(Self)
             }
         }
@@ -1,22 +1,65 @@
-use crate::kv::{
-    traits::{DefaultFlags, Mode, Table},
-    EnvFlags, MdbxCursor, MdbxEnv, MdbxTx,
+use crate::{
+    error::DecodeError,
+    kv::{
+        self,
+        tables::DupSortFlags,
+        traits::{
+            DbName, DefaultFlags, Mode, Table, TableDecode, TableDecodeBorrowed, TableEncode,
+        },
+        EnvFlags, MdbxCursor, MdbxEnv, MdbxEnvBuilder, MdbxTx, TableStat,
+    },
+    Error, Result,
 };
+use bytes::Bytes;
 use ethereum_types::{Address, H256, U256};
-use eyre::{eyre, Result};
 use mdbx::{TransactionKind, RO, RW};
+use roaring::RoaringTreemap;
+use std::{borrow::Cow, collections::BTreeMap, ops::RangeBounds};
 
+pub mod batch;
+#[cfg(feature = "polygon")]
+pub mod bor;
+#[cfg(feature = "lru")]
+pub mod cached;
+pub mod check;
+pub mod diff;
+pub mod dump;
+pub mod erigon3;
+#[cfg(feature = "revm")]
+pub mod evm;
+pub mod filter;
+mod hash_state;
+pub mod history;
+pub mod layout;
+pub mod live;
 mod macros;
 pub mod models;
+pub mod owned;
+#[cfg(feature = "rayon")]
+pub mod par;
+pub mod reader;
+pub mod schema;
+pub mod state;
+pub mod stats;
+pub mod supply;
 pub mod tables;
-mod utils;
+#[cfg(feature = "revm")]
+pub mod trace;
+pub mod trie;
+mod unwind;
+pub(crate) mod utils;
+pub mod watch;
 
 use utils::consts as C;
 
 use models::*;
+use schema::Schema;
 use tables::*;
 
-pub const NUM_TABLES: usize = 50;
+/// The max number of named dbs to allow when opening an env: every table of
+/// [`ALL_TABLES`](tables::ALL_TABLES), with room for the Bor tables and for
+/// tables opened by name with [`MdbxTx::open_raw`].
+pub const NUM_TABLES: usize = tables::ALL_TABLES.len() + 16;
 // https://github.com/ledgerwatch/erigon-lib/blob/625c9f5385d209dc2abfadedf6e4b3914a26ed3e/kv/mdbx/kv_mdbx.go#L154
 pub const ENV_FLAGS: EnvFlags = EnvFlags {
     no_rdahead: true,
@@ -33,7 +76,40 @@ pub fn env_open<M: Mode>(path: &std::path::Path) -> Result<MdbxEnv<M>> {
     MdbxEnv::<M>::open(path, NUM_TABLES, ENV_FLAGS)
 }
 
+/// Open an mdbx env read-only with Erigon-specific configuration, without
+/// creating or writing the LCK-file, for chaindata on a read-only filesystem.
+/// See [`MdbxEnv::open_readonly_no_lock`].
+pub fn env_open_readonly_no_lock(path: &std::path::Path) -> Result<MdbxEnv<RO>> {
+    MdbxEnv::open_readonly_no_lock(path, NUM_TABLES, ENV_FLAGS)
+}
+
+/// Open an mdbx env with Erigon-specific configuration, allowing as many named
+/// dbs as the schema needs.
+pub fn env_open_schema<M: Mode>(path: &std::path::Path, schema: Schema) -> Result<MdbxEnv<M>> {
+    MdbxEnv::<M>::open(path, schema.num_tables(), ENV_FLAGS)
+}
+
+/// Open an mdbx env with Erigon-specific configuration, allowing as many named
+/// dbs as any supported schema needs, after checking with
+/// [`Erigon::check_compat`] that the db can be read by this crate. Returns
+/// [`Error::Incompatible`] with the report if it cannot.
+pub fn env_open_checked<M: Mode>(path: &std::path::Path) -> Result<MdbxEnv<M>> {
+    let env = MdbxEnv::<M>::open(path, schema::NUM_TABLES_V3, ENV_FLAGS)?;
+    let report = Erigon::new(env.begin_ro()?).check_compat()?;
+    if !report.is_compatible() {
+        return Err(Error::Incompatible(Box::new(report)));
+    }
+    Ok(env)
+}
+
+/// Returns a builder for an mdbx env with Erigon-specific configuration, for
+/// tuning the sync mode, geometry and reader limit before opening it.
+pub fn env_builder() -> MdbxEnvBuilder {
+    MdbxEnvBuilder::new(NUM_TABLES, ENV_FLAGS)
+}
+
 /// Erigon wraps an `MdbxTx` and provides Erigon-specific access methods.
+#[derive(Debug)]
 pub struct Erigon<'env, K: TransactionKind>(pub MdbxTx<'env, K>);
 
 impl<'env> Erigon<'env, RO> {
@@ -60,6 +136,34 @@ impl<'env, K: Mode> Erigon<'env, K> {
     {
         self.0.get::<T, T::Flags>(self.0.open_db()?, key)
     }
+    /// Reads the raw value at `key` without decoding it. The value borrows
+    /// from the db rather than being copied, see [`TableDecodeBorrowed`].
+    pub fn read_raw<'tx, T>(&'tx self, key: T::Key) -> Result<Option<Cow<'tx, [u8]>>>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        self.0.get_raw::<T, T::Flags>(self.0.open_db()?, key)
+    }
+    /// Reads the value at `key` into a type that borrows from the db, e.g.
+    /// [`BytecodeRef`], rather than the table's owned value type.
+    pub fn read_borrowed<'tx, T, V>(&'tx self, key: T::Key) -> Result<Option<V>>
+    where
+        T: Table<'tx> + DefaultFlags,
+        V: TableDecodeBorrowed<'tx>,
+    {
+        self.read_raw::<T>(key)?.map(V::decode_borrowed).transpose()
+    }
+    /// Reads the values for many keys from the same table using a single cursor.
+    /// The returned values are in the same order as the input keys.
+    pub fn read_many<'tx, T>(
+        &'tx self,
+        keys: impl IntoIterator<Item = T::Key>,
+    ) -> Result<Vec<Option<T::Value>>>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        self.cursor::<T>()?.seek_many(keys)
+    }
     /// Opens a table with the table's default flags and creates a cursor into
     /// the opened table.
     pub fn cursor<'tx, T>(&'tx self) -> Result<MdbxCursor<'tx, K, T>>
@@ -69,6 +173,28 @@ impl<'env, K: Mode> Erigon<'env, K> {
         self.0.cursor::<T, T::Flags>(self.0.open_db()?)
     }
 
+    /// Returns the entry count and page usage of the table.
+    pub fn table_stat<'tx, T>(&'tx self) -> Result<TableStat>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        self.0.table_stat::<T, T::Flags>()
+    }
+
+    /// Returns the stats of every known table of the db's schema present in the
+    /// environment, sorted by approximate on-disk size, largest first.
+    pub fn db_report(&self) -> Result<Vec<(&'static str, TableStat)>> {
+        let tables = self.schema()?.tables();
+        let mut report = Vec::with_capacity(tables.len());
+        for name in tables {
+            if let Some(stat) = self.0.table_stat_by_name(name)? {
+                report.push((name, stat));
+            }
+        }
+        report.sort_by_key(|(_, stat)| std::cmp::Reverse(stat.size_bytes()));
+        Ok(report)
+    }
+
     /// Returns the hash of the current canonical head header.
     pub fn read_head_header_hash(&self) -> Result<Option<H256>> {
         self.read::<LastHeader>(LastHeaderKey)
@@ -89,16 +215,181 @@ impl<'env, K: Mode> Erigon<'env, K> {
         self.read::<PlainState>(adr)
     }
 
+    /// Returns an iterator over every account in the PlainState table, beginning
+    /// at the smallest address >= start.
+    ///
+    /// PlainState also holds the dupsorted storage entries, keyed by
+    /// address||incarnation. These keys are longer than an address, so we
+    /// jump over each of them (and all of their duplicates) with next_nodup.
+    pub fn walk_accounts(
+        &self,
+        start: Option<Address>,
+    ) -> Result<impl Iterator<Item = Result<(Address, Account)>> + '_> {
+        let mut cur = self.cursor::<PlainState>()?;
+        let start = start.unwrap_or_default().encode();
+        let mut started = false;
+        Ok(std::iter::from_fn(move || loop {
+            let next: mdbx::Result<Option<(Cow<'_, [u8]>, Cow<'_, [u8]>)>> = if started {
+                cur.inner.next_nodup()
+            } else {
+                started = true;
+                cur.inner.set_range(start.as_ref())
+            };
+            match next {
+                Err(e) => return Some(Err(e.into())),
+                Ok(None) => return None,
+                Ok(Some((k, v))) if k.len() == C::ADDRESS_LENGTH => {
+                    return Some(kv::decode::<PlainState>((k, v)))
+                }
+                Ok(Some(_)) => continue,
+            }
+        }))
+    }
+
     /// Returns the number of the block containing the specified transaction.
     pub fn read_transaction_block_number(&self, hash: H256) -> Result<Option<U256>> {
         self.read::<BlockTransactionLookup>(hash)
     }
 
+    /// Returns the receipts for each transaction in the canonical block.
+    pub fn read_receipts(&self, num: impl Into<BlockNumber>) -> Result<Option<Vec<CborReceipt>>> {
+        Ok(self
+            .read::<Receipt>(num.into())?
+            .map(|receipts| receipts.0.unwrap_or_default()))
+    }
+
+    /// Returns the logs emitted in the canonical block as
+    /// `(tx_index, log_index, log)`, where `log_index` is the index of the log
+    /// within the block.
+    pub fn read_logs(&self, num: impl Into<BlockNumber>) -> Result<Vec<(u32, u32, CborLog)>> {
+        let num = *num.into();
+        self.walk_logs(num..=num)?
+            .map(|res| res.map(|(_, tx_index, log_index, log)| (tx_index, log_index, log)))
+            .collect()
+    }
+
+    /// Returns an iterator over the logs emitted in a range of canonical blocks
+    /// as `(block_number, tx_index, log_index, log)`, where `log_index` is the
+    /// index of the log within its block.
+    pub fn walk_logs(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> Result<impl Iterator<Item = Result<(BlockNumber, u32, u32, CborLog)>> + '_> {
+        let (start, end) = utils::block_range(range);
+        let mut cur_block = None;
+        let mut log_index = 0;
+        Ok(self
+            .cursor::<TransactionLog>()?
+            .walk(LogsKey(start.into(), 0))?
+            .take_while(move |res| res.as_ref().map_or(true, |(k, _)| *k.0 < end))
+            .flat_map(move |res| match res {
+                Ok((LogsKey(num, tx_index), logs)) => {
+                    if cur_block != Some(num) {
+                        cur_block = Some(num);
+                        log_index = 0;
+                    }
+                    logs.0
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|log| {
+                            log_index += 1;
+                            Ok((num, tx_index, log_index - 1, log))
+                        })
+                        .collect::<Vec<_>>()
+                }
+                Err(e) => vec![Err(e)],
+            }))
+    }
+
+    /// Returns an iterator over the addresses that sent or received a call in
+    /// each block of the range.
+    pub fn walk_call_traces(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> Result<impl Iterator<Item = Result<(BlockNumber, CallTrace)>> + '_> {
+        let (start, end) = utils::block_range(range);
+        Ok(self
+            .cursor::<CallTraceSet>()?
+            .walk(BlockNumber(start))?
+            .take_while(move |res| res.as_ref().map_or(true, |(k, _)| **k < end)))
+    }
+
+    /// Returns every block in which the address was the sender (or receiver) of a call.
+    pub fn blocks_touching(&self, adr: Address, dir: CallDirection) -> Result<Vec<BlockNumber>> {
+        let bitmap = match dir {
+            CallDirection::From => {
+                let walker = self
+                    .cursor::<CallFromIndex>()?
+                    .walk(CallIndexKey(adr, 0))?
+                    .map(|res| res.map(|(k, v)| ((k.0, k.1), v)));
+                utils::union_shards(walker, adr, 0, u64::MAX)?
+            }
+            CallDirection::To => {
+                let walker = self
+                    .cursor::<CallToIndex>()?
+                    .walk(CallIndexKey(adr, 0))?
+                    .map(|res| res.map(|(k, v)| ((k.0, k.1), v)));
+                utils::union_shards(walker, adr, 0, u64::MAX)?
+            }
+        };
+        Ok(bitmap.iter().map(|n| BlockNumber(n.into())).collect())
+    }
+
+    /// Returns the highest block processed by the given stage of the staged sync.
+    pub fn read_stage_progress(&self, stage: StageId) -> Result<Option<BlockNumber>> {
+        self.read::<SyncStage>(stage)
+    }
+
+    /// Returns the progress of every stage that has an entry in the SyncStage table.
+    pub fn read_all_stages(&self) -> Result<Vec<(StageId, BlockNumber)>> {
+        let mut out = Vec::with_capacity(StageId::ALL.len());
+        for stage in StageId::ALL {
+            if let Some(num) = self.read_stage_progress(*stage)? {
+                out.push((*stage, num));
+            }
+        }
+        Ok(out)
+    }
+
+    /// Returns the hash of the genesis block.
+    pub fn read_genesis_hash(&self) -> Result<Option<H256>> {
+        self.read_canonical_hash(0)
+    }
+
+    /// Returns the header of the genesis block.
+    pub fn read_genesis_header(&self) -> Result<Option<BlockHeader>> {
+        match self.read_genesis_hash()? {
+            Some(hash) => self.read_header((BlockNumber(0), hash)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the chain config, which is stored under the genesis hash.
+    pub fn read_chain_config(&self) -> Result<Option<ChainConfig>> {
+        let hash = self
+            .read_genesis_hash()?
+            .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
+        self.read::<Config>(hash)
+    }
+
     /// Returns the block header identified by the (block number, block hash) key
     pub fn read_header(&self, key: impl Into<HeaderKey>) -> Result<Option<BlockHeader>> {
         self.read::<Header>(key.into())
     }
 
+    /// Returns the proof of the validator set transition signalled in the
+    /// given block, for AuRa and Clique chains, whose validators change at
+    /// epoch boundaries.
+    pub fn read_epoch_transition(&self, key: impl Into<HeaderKey>) -> Result<Option<Bytes>> {
+        self.read::<DevEpoch>(key.into())
+    }
+
+    /// Returns the proof of a validator set transition signalled in the given
+    /// block that has not yet been finalized.
+    pub fn read_pending_epoch(&self, key: impl Into<HeaderKey>) -> Result<Option<Bytes>> {
+        self.read::<DevPendingEpoch>(key.into())
+    }
+
     /// Returns header total difficulty
     pub fn read_total_difficulty(
         &self,
@@ -120,11 +411,10 @@ impl<'env, K: Mode> Erigon<'env, K> {
                 // https://github.com/ledgerwatch/erigon-lib/blob/625c9f5385d209dc2abfadedf6e4b3914a26ed3e/kv/tables.go#L28
                 body.base_tx_id += 1;
                 body.tx_amount = body.tx_amount.checked_sub(2).ok_or_else(|| {
-                    eyre!(
+                    DecodeError::custom(format!(
                         "Block body has too few txs: {}. HeaderKey: {:?}",
-                        body.tx_amount,
-                        key,
-                    )
+                        body.tx_amount, key,
+                    ))
                 })?;
                 Ok(body)
             })
@@ -138,10 +428,29 @@ impl<'env, K: Mode> Erigon<'env, K> {
 
     /// Returns the number of the current canonical block header.
     pub fn read_head_block_number(&self) -> Result<Option<BlockNumber>> {
-        let hash = self.read_head_header_hash()?.ok_or(eyre!("No value"))?;
+        let hash = self
+            .read_head_header_hash()?
+            .ok_or(Error::NotFound(LastHeader::NAME))?;
         self.read_header_number(hash)
     }
 
+    /// Returns true if the CanonicalHeader and HeaderNumber tables agree on
+    /// the hash of canonical block `num`, and the hash of the stored header
+    /// matches.
+    pub fn verify_canonical(&self, num: impl Into<BlockNumber>) -> Result<bool> {
+        let num = num.into();
+        let hash = self
+            .read_canonical_hash(num)?
+            .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
+        if self.read_header_number(hash)? != Some(num) {
+            return Ok(false);
+        }
+        let header = self
+            .read_header((num, hash))?
+            .ok_or(Error::NotFound(Header::NAME))?;
+        Ok(header.number == U256::from(*num) && header.hash() == hash)
+    }
+
     /// Returns the signers of each transaction in the block.
     pub fn read_senders(&self, key: impl Into<HeaderKey>) -> Result<Option<Vec<Address>>> {
         self.read::<TxSender>(key.into())
@@ -152,10 +461,103 @@ impl<'env, K: Mode> Erigon<'env, K> {
         self.read::<CanonicalHeader>(num.into())
     }
 
+    /// Returns the header of the canonical block `num`.
+    pub fn read_canonical_header(
+        &self,
+        num: impl Into<BlockNumber>,
+    ) -> Result<Option<BlockHeader>> {
+        match self.read_header_key(num.into())? {
+            Some(key) => self.read_header(key),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the body of the canonical block `num`, as stored in the
+    /// BlockBody table. See [`read_body_for_storage`](Self::read_body_for_storage).
+    pub fn read_canonical_body(
+        &self,
+        num: impl Into<BlockNumber>,
+    ) -> Result<Option<BodyForStorage>> {
+        match self.read_header_key(num.into())? {
+            Some(key) => self.read_body_for_storage(key),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the signers of each transaction in the canonical block `num`.
+    pub fn read_canonical_senders(
+        &self,
+        num: impl Into<BlockNumber>,
+    ) -> Result<Option<Vec<Address>>> {
+        match self.read_header_key(num.into())? {
+            Some(key) => self.read_senders(key),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the canonical block closest to the unix timestamp `time`, on the
+    /// side given by `hint`, by binary search over the canonical headers.
+    /// Returns None if there is no block on that side.
+    pub fn block_by_timestamp(&self, time: u64, hint: Hint) -> Result<Option<BlockNumber>> {
+        let head = match self.cursor::<CanonicalHeader>()?.last()? {
+            Some((num, _)) => *num,
+            None => return Ok(None),
+        };
+        let time_at = |num: u64| -> Result<u64> {
+            let header = self
+                .read_canonical_header(num)?
+                .ok_or(Error::NotFound(Header::NAME))?;
+            Ok(header.time)
+        };
+
+        // find the first block past the blocks on the `Before` side of `time`
+        let (mut lo, mut hi) = (0, head + 1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let before = match hint {
+                Hint::Before => time_at(mid)? <= time,
+                Hint::After => time_at(mid)? < time,
+            };
+            if before {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(match hint {
+            Hint::Before => lo.checked_sub(1).map(BlockNumber),
+            Hint::After => (lo <= head).then_some(BlockNumber(lo)),
+        })
+    }
+
+    /// Returns an iterator over the headers of the canonical blocks in the
+    /// range, in block order.
+    pub fn walk_canonical_headers(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> Result<impl Iterator<Item = Result<(BlockNumber, BlockHeader)>> + '_> {
+        let (start, end) = utils::block_range(range);
+        Ok(self
+            .cursor::<CanonicalHeader>()?
+            .walk(BlockNumber(start))?
+            .take_while(move |res| res.as_ref().map_or(true, |(k, _)| **k < end))
+            .map(move |res| {
+                let (num, hash) = res?;
+                let header = self
+                    .read_header((num, hash))?
+                    .ok_or(Error::NotFound(Header::NAME))?;
+                Ok((num, header))
+            }))
+    }
+
     /// Determines whether a header with the given hash is on the canonical chain.
     pub fn is_canonical_hash(&self, hash: H256) -> Result<bool> {
-        let num = self.read_header_number(hash)?.ok_or(eyre!("No value"))?;
-        let canon = self.read_canonical_hash(num)?.ok_or(eyre!("No value"))?;
+        let num = self
+            .read_header_number(hash)?
+            .ok_or(Error::NotFound(HeaderNumber::NAME))?;
+        let canon = self
+            .read_canonical_hash(num)?
+            .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
         Ok(canon != Default::default() && canon == hash)
     }
 
@@ -167,14 +569,34 @@ impl<'env, K: Mode> Erigon<'env, K> {
         slot: H256,
     ) -> Result<Option<U256>> {
         let bucket = StorageKey(adr, inc.into());
-        let mut cur = self.cursor::<Storage>()?;
-        cur.seek_dup(bucket, slot)
-            .map(|kv| kv.and_then(|(k, v)| if k == slot { Some(v) } else { None }))
+        self.cursor::<Storage>()?.seek_exact_dup(bucket, slot)
+    }
+
+    /// Returns the values of the given storage slots for the address and
+    /// account incarnation, read with a single cursor. Slots without a value
+    /// are left out of the map.
+    pub fn read_storage_many(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        slots: &[H256],
+    ) -> Result<BTreeMap<H256, U256>> {
+        let bucket = StorageKey(adr, inc.into());
+        let vals = self
+            .cursor::<Storage>()?
+            .seek_exact_dup_many(bucket, slots.iter().copied())?;
+        Ok(slots
+            .iter()
+            .zip(vals)
+            .filter_map(|(&slot, val)| Some((slot, val?)))
+            .collect())
     }
 
     /// Returns an iterator over all of the storage (key, value) pairs for the
     /// given address and account incarnation. If a start_slot is provided, the
-    /// iterator will begin at the smallest slot >= start_slot.
+    /// iterator will begin at the smallest slot >= start_slot, so a walk over
+    /// the storage of a large contract can be done in chunks, each resuming
+    /// just past the last slot of the one before.
     pub fn walk_storage(
         &self,
         adr: Address,
@@ -182,7 +604,77 @@ impl<'env, K: Mode> Erigon<'env, K> {
         start_slot: Option<H256>,
     ) -> Result<impl Iterator<Item = Result<(H256, U256)>>> {
         let key = StorageKey(adr, inc.into());
-        self.cursor::<Storage>()?.walk_dup(key, start_slot.unwrap_or_default())
+        self.cursor::<Storage>()?.walk_dup_from(key, start_slot.unwrap_or_default())
+    }
+
+    /// Returns the value of the storage slot like [`Self::read_storage`], at
+    /// the incarnation of the account currently at `adr`, or of the last
+    /// contract destroyed there if there is none.
+    pub fn read_storage_auto(&self, adr: Address, slot: H256) -> Result<Option<U256>> {
+        self.read_storage(adr, self.storage_incarnation(adr)?, slot)
+    }
+
+    /// Returns an iterator over the storage of `adr` like
+    /// [`Self::walk_storage`], at the incarnation found as with
+    /// [`Self::read_storage_auto`].
+    pub fn walk_storage_auto(
+        &self,
+        adr: Address,
+    ) -> Result<impl Iterator<Item = Result<(H256, U256)>>> {
+        self.walk_storage(adr, self.storage_incarnation(adr)?, None)
+    }
+
+    /// Returns an iterator over the storage of the account with hash
+    /// `hashed_adr` and incarnation `inc` in the HashedStorage table, as
+    /// (hashed slot, value) pairs in order of hashed slot.
+    pub fn walk_hashed_storage(
+        &self,
+        hashed_adr: H256,
+        inc: impl Into<Incarnation>,
+    ) -> Result<impl Iterator<Item = Result<(H256, U256)>>> {
+        let key = HashStorageKey(hashed_adr, inc.into());
+        self.cursor::<HashedStorage>()?
+            .walk_dup_from(key, H256::zero())
+    }
+
+    // Contracts begin at incarnation 1, so an account that never held a
+    // contract has no storage at the incarnation of 0 returned for it.
+    fn storage_incarnation(&self, adr: Address) -> Result<Incarnation> {
+        Ok(match self.read_account(adr)? {
+            Some(acct) => acct.incarnation,
+            None => self.read_incarnation(adr)?.unwrap_or_default(),
+        })
+    }
+
+    /// Returns an iterator over the nodes of the account trie in the
+    /// TrieAccount table whose paths begin with `prefix`, in order of path.
+    pub fn walk_trie_accounts(
+        &self,
+        prefix: Nibbles,
+    ) -> Result<impl Iterator<Item = Result<(Nibbles, TrieNode)>> + '_> {
+        Ok(self
+            .cursor::<TrieAccount>()?
+            .walk(prefix.clone())?
+            .take_while(move |res| res.as_ref().map_or(true, |(k, _)| k.starts_with(&prefix))))
+    }
+
+    /// Returns an iterator over the nodes of the storage trie of the account
+    /// with the given hashed address and incarnation, in order of path.
+    pub fn walk_trie_storage(
+        &self,
+        hashed_adr: H256,
+        inc: impl Into<Incarnation>,
+    ) -> Result<impl Iterator<Item = Result<(Nibbles, TrieNode)>> + '_> {
+        let inc = inc.into();
+        let start = TrieStorageKey(hashed_adr, inc, Nibbles::default());
+        Ok(self
+            .cursor::<TrieStorage>()?
+            .walk(start)?
+            .take_while(move |res| {
+                res.as_ref()
+                    .map_or(true, |(k, _)| k.0 == hashed_adr && k.1 == inc)
+            })
+            .map(|res| res.map(|(TrieStorageKey(_, _, path), node)| (path, node))))
     }
 
     /// Returns the code associated with the given codehash.
@@ -193,12 +685,312 @@ impl<'env, K: Mode> Erigon<'env, K> {
         self.read::<Code>(codehash)
     }
 
+    /// Returns the bytecode with the given codehash like [`Self::read_code`],
+    /// but borrowed from the db rather than copied out of it.
+    pub fn read_code_ref(&self, codehash: H256) -> Result<Option<BytecodeRef<'_>>> {
+        if codehash == C::EMPTY_HASH {
+            return Ok(Default::default());
+        }
+        self.read_borrowed::<Code, _>(codehash)
+    }
+
     /// Returns the codehash at the `adr` with incarnation `inc`
     pub fn read_codehash(&self, adr: Address, inc: impl Into<Incarnation>) -> Result<Option<H256>> {
         let key = PlainCodeKey(adr, inc.into());
         self.read::<PlainCodeHash>(key)
     }
 
+    /// Returns the code currently deployed at `adr`, or None if there is no
+    /// account at `adr` or it has no code. A destroyed contract has no account
+    /// in PlainState, only its last incarnation in IncarnationMap, so has no
+    /// code either.
+    pub fn read_code_by_address(&self, adr: Address) -> Result<Option<Bytecode>> {
+        match self.account_codehash(adr, self.read_account(adr)?)? {
+            Some(codehash) => self.read_code(codehash),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the length of the code currently deployed at `adr`, as with
+    /// [`Self::read_code_by_address`], without copying the code out of the db.
+    pub fn read_code_size(&self, adr: Address) -> Result<Option<usize>> {
+        match self.account_codehash(adr, self.read_account(adr)?)? {
+            Some(codehash) => Ok(self.read_raw::<Code>(codehash)?.map(|code| code.len())),
+            None => Ok(None),
+        }
+    }
+
+    // Returns the codehash of `acct`, the account at `adr`, or None if it has
+    // no code. The codehash of a contract is looked up by its incarnation if
+    // the account was stored without one.
+    fn account_codehash(&self, adr: Address, acct: Option<Account>) -> Result<Option<H256>> {
+        let acct = match acct {
+            Some(acct) => acct,
+            None => return Ok(None),
+        };
+        let codehash = if acct.codehash.is_zero() && *acct.incarnation > 0 {
+            self.read_codehash(adr, acct.incarnation)?
+        } else {
+            Some(acct.codehash)
+        };
+        Ok(codehash.filter(|hash| !hash.is_zero() && *hash != C::EMPTY_HASH))
+    }
+
+    /// Resolves a block number or hash to the (block number, block hash) key
+    /// used by the header and body tables. Block numbers resolve to the
+    /// canonical block at that height.
+    pub fn read_header_key(&self, id: impl Into<BlockId>) -> Result<Option<HeaderKey>> {
+        Ok(match id.into() {
+            BlockId::Number(num) => self
+                .read_canonical_hash(num)?
+                .map(|hash| HeaderKey(num, hash)),
+            BlockId::Hash(hash) => self
+                .read_header_number(hash)?
+                .map(|num| HeaderKey(num, hash)),
+        })
+    }
+
+    /// Returns the hashes of all headers stored at height `num`, canonical or
+    /// not, in hash order.
+    pub fn read_header_hashes(&self, num: impl Into<BlockNumber>) -> Result<Vec<H256>> {
+        let num = num.into();
+        let mut hashes = Vec::new();
+        for read in self.cursor::<Header>()?.iter(HeaderKey(num, H256::zero())) {
+            let (HeaderKey(k, hash), _) = read?;
+            if k != num {
+                break;
+            }
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
+    /// Returns the headers stored at height `num` that are not canonical, such
+    /// as those of blocks reorged out of the chain, with their hashes.
+    pub fn read_non_canonical_headers(
+        &self,
+        num: impl Into<BlockNumber>,
+    ) -> Result<Vec<(H256, BlockHeader)>> {
+        let num = num.into();
+        let canonical = self.read_canonical_hash(num)?;
+        let mut headers = Vec::new();
+        for read in self.cursor::<Header>()?.iter(HeaderKey(num, H256::zero())) {
+            let (HeaderKey(k, hash), header) = read?;
+            if k != num {
+                break;
+            }
+            if Some(hash) != canonical {
+                headers.push((hash, header));
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Returns the number and hash of the most recent block that is an
+    /// ancestor of both blocks, following parent hashes through the Header
+    /// table. A block counts as its own ancestor. Returns None if either hash
+    /// is unknown, or if the two chains share no block.
+    pub fn find_common_ancestor(&self, a: H256, b: H256) -> Result<Option<HeaderKey>> {
+        let (mut a, mut b) = match (self.read_header_key(a)?, self.read_header_key(b)?) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Ok(None),
+        };
+        while a != b {
+            // step back along the higher chain, or both chains at equal height
+            let (step_a, step_b) = (a.0 >= b.0, b.0 >= a.0);
+            if *a.0 == 0 && *b.0 == 0 {
+                return Ok(None);
+            }
+            if step_a {
+                a = self.read_parent_key(a)?;
+            }
+            if step_b {
+                b = self.read_parent_key(b)?;
+            }
+        }
+        Ok(Some(a))
+    }
+
+    // Returns the key of the parent of the block with the given key.
+    fn read_parent_key(&self, key: HeaderKey) -> Result<HeaderKey> {
+        let header = self
+            .read_header(key)?
+            .ok_or(Error::NotFound(Header::NAME))?;
+        Ok(HeaderKey(BlockNumber(*key.0 - 1), header.parent_hash))
+    }
+
+    /// Returns the transactions contained in the block, excluding the system
+    /// transactions Erigon stores at either end of each block's range. Canonical
+    /// transactions are read from BlockTransaction, others from NonCanonicalTransaction.
+    pub fn read_block_transactions(
+        &self,
+        key: impl Into<HeaderKey>,
+    ) -> Result<Option<Vec<Transaction>>> {
+        let key = key.into();
+        let body = match self.read_body_for_storage(key)? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let canonical = self.read_canonical_hash(key.0)? == Some(key.1);
+        if canonical {
+            self.read_tx_range::<BlockTransaction>(body.base_tx_id, body.tx_amount)
+        } else {
+            self.read_tx_range::<NonCanonicalTransaction>(body.base_tx_id, body.tx_amount)
+        }
+        .map(Some)
+    }
+
+    /// Reads `amount` consecutive transactions beginning at `base_tx_id`. Fails
+    /// if any id in the range is missing, rather than reading past the gap.
+    fn read_tx_range<'tx, T>(&'tx self, base_tx_id: u64, amount: u32) -> Result<Vec<Transaction>>
+    where
+        T: Table<'tx, Key = TxIndex, Value = Transaction> + DefaultFlags,
+    {
+        let mut txs = Vec::with_capacity(amount as usize);
+        let walk = self.cursor::<T>()?.walk(TxIndex(base_tx_id))?;
+        for (res, id) in walk.take(amount as usize).zip(base_tx_id..) {
+            let (TxIndex(got), tx) = res?;
+            if got != id {
+                break;
+            }
+            txs.push(tx);
+        }
+        if txs.len() != amount as usize {
+            return Err(Error::NotFound(T::NAME));
+        }
+        Ok(txs)
+    }
+
+    /// Returns the block's header, uncles, withdrawals, and transactions along with their
+    /// signers, or `None` if the block is unknown.
+    pub fn read_block(&self, id: impl Into<BlockId>) -> Result<Option<Block>> {
+        let id = id.into();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("read_block", ?id).entered();
+        let key = match self.read_header_key(id)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let header = match self.read_header(key)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let body = self
+            .read_body_for_storage(key)?
+            .ok_or(Error::NotFound(BlockBody::NAME))?;
+        let txs = self
+            .read_block_transactions(key)?
+            .ok_or(Error::NotFound(BlockBody::NAME))?;
+        let senders = if txs.is_empty() {
+            Vec::new()
+        } else {
+            self.read_senders(key)?
+                .ok_or(Error::NotFound(TxSender::NAME))?
+        };
+        if senders.len() != txs.len() {
+            return Err(DecodeError::custom(format!(
+                "Block has {} txs but {} senders. HeaderKey: {:?}",
+                txs.len(),
+                senders.len(),
+                key,
+            ))
+            .into());
+        }
+
+        let transactions = txs
+            .into_iter()
+            .zip(senders)
+            .map(|(msg, signer)| TransactionWithSigner { msg, signer })
+            .collect();
+        Ok(Some(Block {
+            header,
+            transactions,
+            uncles: body.uncles,
+            withdrawals: body.withdrawals,
+        }))
+    }
+
+    /// Returns the canonical transaction with the given hash, along with the
+    /// number of the block containing it and its index within that block.
+    pub fn read_transaction_by_hash(
+        &self,
+        hash: H256,
+    ) -> Result<Option<(Transaction, BlockNumber, usize)>> {
+        let num = match self.read_transaction_block_number(hash)? {
+            Some(num) if num.bits() <= 64 => BlockNumber(num.low_u64()),
+            Some(num) => {
+                return Err(
+                    DecodeError::custom(format!("Block number overflows u64: {}", num)).into(),
+                )
+            }
+            None => return Ok(None),
+        };
+        let key = self
+            .read_header_key(num)?
+            .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
+        let txs = self
+            .read_block_transactions(key)?
+            .ok_or(Error::NotFound(BlockBody::NAME))?;
+        Ok(txs
+            .into_iter()
+            .enumerate()
+            .find(|(_, tx)| tx.tx_hash() == hash)
+            .map(|(i, tx)| (tx, num, i)))
+    }
+
+    /// Returns the receipt of the canonical transaction with the given hash,
+    /// assembled from its block's header, body, senders, receipts and logs.
+    pub fn read_transaction_receipt(&self, hash: H256) -> Result<Option<TransactionReceipt>> {
+        let (msg, num, index) = match self.read_transaction_by_hash(hash)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let key = self
+            .read_header_key(num)?
+            .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
+        let header = self
+            .read_header(key)?
+            .ok_or(Error::NotFound(Header::NAME))?;
+        let from = self
+            .read_senders(key)?
+            .and_then(|senders| senders.get(index).copied())
+            .ok_or(Error::NotFound(TxSender::NAME))?;
+        let receipts = self
+            .read_receipts(num)?
+            .ok_or(Error::NotFound(Receipt::NAME))?;
+        let receipt = receipts.get(index).ok_or(Error::NotFound(Receipt::NAME))?;
+        let prev_gas = match index.checked_sub(1) {
+            Some(prev) => receipts[prev].cumulative_gas_used,
+            None => 0,
+        };
+        let logs = self
+            .read_logs(num)?
+            .into_iter()
+            .filter(|(tx_index, ..)| *tx_index as usize == index)
+            .map(|(_, log_index, log)| (log_index, log))
+            .collect::<Vec<_>>();
+        let to = msg.to().into();
+        Ok(Some(TransactionReceipt {
+            transaction_hash: hash,
+            transaction_index: index as u64,
+            block_hash: key.1,
+            block_number: num,
+            from,
+            to,
+            tx_type: receipt.tx_type,
+            cumulative_gas_used: receipt.cumulative_gas_used,
+            gas_used: receipt.cumulative_gas_used.saturating_sub(prev_gas),
+            effective_gas_price: msg.effective_gas_price(header.base_fee),
+            contract_address: to
+                .is_none()
+                .then(|| utils::create_address(from, msg.nonce())),
+            logs_bloom: logs_bloom(logs.iter().map(|(_, log)| log)),
+            logs,
+            root: receipt.post_state,
+            status: receipt.post_state.is_none().then_some(receipt.status),
+        }))
+    }
+
     pub fn walk_txs_canonical(
         &self,
         start_key: Option<TxIndex>,
@@ -243,64 +1035,340 @@ impl<'env, K: Mode> Erigon<'env, K> {
     // - `AccountHistory` and `StorageHistory` are written [here](https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/core/state/db_state_writer.go#L179).
     // - `GetAsOf()` Erigon implementation [here](https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/core/state/history.go#L19).
     //
-    /// Returns the state of account `adr` at the given block number.
+    /// Returns the state of account `adr` at the given block number. Returns `None` if the state
+    /// is not found in history (e.g., if it's in the PlainState table instead).
+    /// See [`Erigon::state_at`] for reads that fall back to PlainState.
     pub fn read_account_hist(
         &self,
         adr: Address,
         block: impl Into<BlockNumber>,
     ) -> Result<Option<Account>> {
         let block = block.into();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("read_account_hist", ?adr, block = *block).entered();
+        match self.account_change_block(adr, block)? {
+            Some(cs_block) => self.read_account_changeset(cs_block, adr),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the state of each account at the given block number, in input
+    /// order, like [`Self::read_account_hist`] for each address. The history
+    /// and changeset cursors are opened once, and the addresses are visited
+    /// in sorted order so the history cursor only ever moves forward.
+    pub fn read_accounts_hist(
+        &self,
+        addrs: &[Address],
+        block: impl Into<BlockNumber>,
+    ) -> Result<Vec<Option<Account>>> {
+        let block = block.into();
+        let mut order = (0..addrs.len()).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|&i| addrs[i]);
+
         let mut hist_cur = self.cursor::<AccountHistory>()?;
-        let (_, bitmap) = hist_cur
-            .seek((adr, block).into())?
-            .ok_or(eyre!("No value"))?;
-        let cs_block = match utils::find_gte(bitmap, *block) {
-            Some(changeset) => BlockNumber(changeset),
-            _ => return Ok(None),
-        };
         let mut cs_cur = self.cursor::<AccountChangeSet>()?;
-        if let Some(AccountCSVal(k, mut acct)) = cs_cur.seek_dup(cs_block, adr)? {
-            if k == adr {
-                // recover the codehash
-                if *acct.incarnation > 0 && acct.codehash == Default::default() {
-                    acct.codehash = self
-                        .read_codehash(adr, acct.incarnation)?
-                        .ok_or(eyre!("No value"))?
-                }
-                return Ok(Some(acct));
+        let mut out = vec![None; addrs.len()];
+        for i in order {
+            let adr = addrs[i];
+            let key = AccountHistKey(adr, block);
+            if let Some(cs_block) = seek_change(&mut hist_cur, key, block, |k| k.0 == adr)? {
+                let acct = cs_cur
+                    .seek_exact_dup(cs_block, adr)?
+                    .ok_or(Error::NotFound(AccountChangeSet::NAME))?;
+                out[i] = self.account_from_changeset(adr, acct)?;
             }
         }
-        Ok(None)
+        Ok(out)
     }
 
-
     /// Returns the value of an address's storage at the given block number. Returns `None` if the state
     /// is not found in history (e.g., if it's in the PlainState table instead).
+    /// See [`Self::read_storage_at`] for reads at the account's incarnation at
+    /// the time that fall back to PlainState.
     pub fn read_storage_hist(
         &self,
         adr: Address,
         inc: impl Into<Incarnation>,
         slot: H256,
         block: impl Into<BlockNumber>,
+    ) -> Result<Option<U256>> {
+        match self.storage_change_block(adr, slot, block.into())? {
+            Some(cs_block) => self.read_storage_changeset(cs_block, adr, inc.into(), slot),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the value of the storage `slot` of account `adr` before the
+    /// given block was executed. Unlike [`Self::read_storage_hist`], the
+    /// incarnation is that of the account at the time, and the value is read
+    /// from PlainState if the slot has not changed since. Returns `None` if
+    /// there was no account at `adr` or the slot had no value.
+    pub fn read_storage_at(
+        &self,
+        adr: Address,
+        slot: H256,
+        block: impl Into<BlockNumber>,
     ) -> Result<Option<U256>> {
         let block = block.into();
-        let mut hist_cur = self.cursor::<StorageHistory>()?;
-        let (_, bitmap) = hist_cur
-            .seek((adr, slot, block).into())?
-            .ok_or(eyre!("No value"))?;
-        let cs_block = match utils::find_gte(bitmap, *block) {
-            Some(changeset) => BlockNumber(changeset),
-            _ => return Ok(None),
+        let inc = match self.account_before(adr, block)? {
+            Some(acct) => acct.incarnation,
+            None => return Ok(None),
         };
-        let cs_key = (cs_block, adr, inc.into()).into();
-        let mut cs_cur = self.cursor::<StorageChangeSet>()?;
-        if let Some(StorageCSVal(k, v)) = cs_cur.seek_dup(cs_key, slot)? {
-            if k == slot {
-                return Ok(Some(v));
+        match self.storage_change_block(adr, slot, block)? {
+            Some(cs_block) => self.read_storage_changeset(cs_block, adr, inc, slot),
+            None => self.read_storage(adr, inc, slot),
+        }
+    }
+
+    /// Returns the code deployed at `adr` before the given block was executed,
+    /// i.e. the code its transactions ran against, or None if there was no
+    /// account at `adr` or it had no code. This is the code after block
+    /// `block - 1`, as read by [`Self::state_at`]. Unlike
+    /// [`Self::read_account_hist`], this falls back to PlainState if the
+    /// account has not changed since, so a contract that was destroyed or
+    /// redeployed later resolves to the code of its incarnation at the time.
+    pub fn read_code_hist(
+        &self,
+        adr: Address,
+        block: impl Into<BlockNumber>,
+    ) -> Result<Option<Bytecode>> {
+        let acct = self.account_before(adr, block.into())?;
+        match self.account_codehash(adr, acct)? {
+            Some(codehash) => self.read_code(codehash),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns every block at which account `adr` changed, from the union of
+    /// its history index shards.
+    pub fn read_account_history_index(&self, adr: Address) -> Result<RoaringTreemap> {
+        let mut blocks = RoaringTreemap::new();
+        for read in self
+            .cursor::<AccountHistory>()?
+            .iter(AccountHistKey(adr, BlockNumber(0)))
+        {
+            let (AccountHistKey(k, _), bitmap) = read?;
+            if k != adr {
+                break;
+            }
+            blocks |= bitmap;
+        }
+        Ok(blocks)
+    }
+
+    /// Returns every block at which the storage `slot` of account `adr`
+    /// changed, from the union of its history index shards.
+    pub fn read_storage_history_index(&self, adr: Address, slot: H256) -> Result<RoaringTreemap> {
+        let mut blocks = RoaringTreemap::new();
+        for read in self
+            .cursor::<StorageHistory>()?
+            .iter(StorageHistKey(adr, slot, BlockNumber(0)))
+        {
+            let (StorageHistKey(k, s, _), bitmap) = read?;
+            if k != adr || s != slot {
+                break;
             }
+            blocks |= bitmap;
+        }
+        Ok(blocks)
+    }
+
+    /// Returns an iterator over each block at which account `adr` changed,
+    /// in order, along with the account as it was after that block. An
+    /// account that was deleted is `None`.
+    pub fn account_history_iter(
+        &self,
+        adr: Address,
+    ) -> Result<impl Iterator<Item = Result<(BlockNumber, Option<Account>)>> + '_> {
+        let mut blocks = self
+            .read_account_history_index(adr)?
+            .into_iter()
+            .map(BlockNumber)
+            .peekable();
+        Ok(std::iter::from_fn(move || {
+            let block = blocks.next()?;
+            // the value after this change is the one from before the next
+            let after = match blocks.peek() {
+                Some(&next) => self.read_account_changeset(next, adr),
+                None => self.read_account(adr),
+            };
+            Some(after.map(|acct| (block, acct)))
+        }))
+    }
+
+    /// Returns an iterator over each block at which the storage `slot` of
+    /// account `adr` changed, in order, along with the value of the slot
+    /// after that block.
+    pub fn storage_history_iter(
+        &self,
+        adr: Address,
+        slot: H256,
+    ) -> Result<impl Iterator<Item = Result<(BlockNumber, U256)>> + '_> {
+        let mut blocks = self
+            .read_storage_history_index(adr, slot)?
+            .into_iter()
+            .map(BlockNumber)
+            .peekable();
+        Ok(std::iter::from_fn(move || {
+            let block = blocks.next()?;
+            let after = match blocks.peek() {
+                Some(&next) => self.read_storage_change_any_inc(next, adr, slot),
+                None => self.read_account(adr).and_then(|acct| match acct {
+                    Some(acct) => self.read_storage(adr, acct.incarnation, slot),
+                    None => Ok(None),
+                }),
+            };
+            Some(after.map(|val| (block, val.unwrap_or_default())))
+        }))
+    }
+
+    /// Returns the first block >= `block` at which the account changed.
+    fn account_change_block(
+        &self,
+        adr: Address,
+        block: BlockNumber,
+    ) -> Result<Option<BlockNumber>> {
+        let mut hist_cur = self.cursor::<AccountHistory>()?;
+        let key = AccountHistKey(adr, block);
+        seek_change(&mut hist_cur, key, block, |k| k.0 == adr)
+    }
+
+    /// Returns the first block >= `block` at which the storage slot changed.
+    fn storage_change_block(
+        &self,
+        adr: Address,
+        slot: H256,
+        block: BlockNumber,
+    ) -> Result<Option<BlockNumber>> {
+        let mut hist_cur = self.cursor::<StorageHistory>()?;
+        let key = StorageHistKey(adr, slot, block);
+        seek_change(&mut hist_cur, key, block, |k| k.0 == adr && k.1 == slot)
+    }
+
+    /// Returns the account as it was before `block` was executed, from the
+    /// changesets of its next change or from PlainState if there is none.
+    fn account_before(&self, adr: Address, block: BlockNumber) -> Result<Option<Account>> {
+        match self.account_change_block(adr, block)? {
+            Some(cs_block) => self.read_account_changeset(cs_block, adr),
+            None => self.read_account(adr),
+        }
+    }
+
+    /// Returns the account as it was before the changes made in `cs_block`.
+    /// The history index says the account changed in `cs_block`, so a missing
+    /// changeset entry is an error rather than an account that did not exist.
+    fn read_account_changeset(
+        &self,
+        cs_block: BlockNumber,
+        adr: Address,
+    ) -> Result<Option<Account>> {
+        let acct = self
+            .cursor::<AccountChangeSet>()?
+            .seek_exact_dup(cs_block, adr)?
+            .ok_or(Error::NotFound(AccountChangeSet::NAME))?;
+        self.account_from_changeset(adr, acct)
+    }
+
+    /// Returns the storage value as it was before the changes made in
+    /// `cs_block`, under whichever incarnation of the account it was changed.
+    fn read_storage_change_any_inc(
+        &self,
+        cs_block: BlockNumber,
+        adr: Address,
+        slot: H256,
+    ) -> Result<Option<U256>> {
+        seek_storage_change(&mut self.cursor::<StorageChangeSet>()?, cs_block, adr, slot)
+    }
+
+    /// Restores an account as stored in a changeset, where an empty value
+    /// means the account did not exist and the codehash is left out.
+    fn account_from_changeset(
+        &self,
+        adr: Address,
+        acct: Option<Account>,
+    ) -> Result<Option<Account>> {
+        let mut acct = match acct {
+            Some(acct) => acct,
+            None => return Ok(None),
+        };
+        // recover the codehash
+        if *acct.incarnation > 0 && acct.codehash == Default::default() {
+            acct.codehash = self
+                .read_codehash(adr, acct.incarnation)?
+                .ok_or(Error::NotFound(PlainCodeHash::NAME))?
         }
-        Ok(None)
+        Ok(Some(acct))
     }
+
+    /// Returns the storage value as it was before the changes made in `cs_block`.
+    fn read_storage_changeset(
+        &self,
+        cs_block: BlockNumber,
+        adr: Address,
+        inc: Incarnation,
+        slot: H256,
+    ) -> Result<Option<U256>> {
+        let cs_key = (cs_block, adr, inc).into();
+        self.cursor::<StorageChangeSet>()?
+            .seek_exact_dup(cs_key, slot)
+    }
+}
+
+/// Returns the first block >= `block` in a history index, beginning at the
+/// shard found by seeking `key`, the account or storage slot and `block`.
+/// Shards are checked to belong to the account or slot with `is_key`.
+///
+/// The index is sharded across keys, each keyed by the last block of its shard,
+/// except the last, which is keyed by `u64::MAX`. Seeking `key` finds the
+/// first shard that ends at or after the block, but if it holds no block
+/// after ours, e.g. in a db whose shards were keyed otherwise, the shards that
+/// follow are searched as well.
+fn seek_change<'tx, K, T>(
+    cur: &mut MdbxCursor<'tx, K, T>,
+    key: T::SeekKey,
+    block: BlockNumber,
+    is_key: impl Fn(&T::Key) -> bool,
+) -> Result<Option<BlockNumber>>
+where
+    K: Mode,
+    T: Table<'tx, Value = RoaringTreemap>,
+    T::Key: TableDecode,
+{
+    let mut next = cur.seek(key)?;
+    while let Some((k, bitmap)) = next {
+        if !is_key(&k) {
+            break;
+        }
+        if let Some(found) = utils::find_gte(bitmap, *block) {
+            return Ok(Some(BlockNumber(found)));
+        }
+        next = cur.next()?;
+    }
+    Ok(None)
+}
+
+// Returns the value of the storage `slot` of account `adr` from before the
+// changes made in `cs_block`, under whichever incarnation of the account it
+// was changed, or None if StorageChangeSet has no change to it in the block.
+fn seek_storage_change<K: Mode>(
+    cs_cur: &mut MdbxCursor<'_, K, StorageChangeSet>,
+    cs_block: BlockNumber,
+    adr: Address,
+    slot: H256,
+) -> Result<Option<U256>> {
+    let mut inc = Incarnation(0);
+    // seek to each incarnation of the account changed in the block
+    while let Some((key, _)) = cs_cur.seek((cs_block, adr, inc).into())? {
+        let StorageCSKey(block, StorageKey(k, key_inc)) = key;
+        if block != cs_block || k != adr {
+            break;
+        }
+        match cs_cur.seek_exact_dup(key, slot)? {
+            Some(v) => return Ok(Some(v)),
+            None => inc = Incarnation(*key_inc + 1),
+        }
+    }
+    Ok(None)
 }
 
 impl<'env> Erigon<'env, mdbx::RW> {
@@ -312,6 +1380,240 @@ impl<'env> Erigon<'env, mdbx::RW> {
         self.0.put::<T, T::Flags>(self.0.open_db()?, key, val)
     }
 
+    /// Opens the table with its default flags and deletes the entry at `key`,
+    /// or only the duplicate `val` if one is given. Returns false if there was
+    /// nothing to delete.
+    pub fn delete<'tx, T>(&'tx self, key: T::Key, val: Option<T::Value>) -> Result<bool>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        self.0.del::<T, T::Flags>(self.0.open_db()?, key, val)
+    }
+
+    /// Opens the table with its default flags and deletes all of its entries.
+    pub fn clear<'tx, T>(&'tx self) -> Result<()>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        self.0.clear::<T, T::Flags>(self.0.open_db()?)
+    }
+
+    /// Sets the storage `slot` of account `adr` with incarnation `inc`. Since
+    /// the storage table is dupsorted by slot, any existing value is deleted
+    /// first, and a zero value leaves the slot empty.
+    pub fn write_storage(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        slot: H256,
+        val: U256,
+    ) -> Result<()> {
+        let inc = inc.into();
+        self.delete_storage(adr, inc, slot)?;
+        if !val.is_zero() {
+            self.write::<Storage>(StorageKey(adr, inc), (slot, val))?;
+        }
+        Ok(())
+    }
+
+    /// Clears the storage `slot` of account `adr` with incarnation `inc`.
+    /// Returns false if the slot was already empty.
+    pub fn delete_storage(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        slot: H256,
+    ) -> Result<bool> {
+        let inc = inc.into();
+        match self.read_storage(adr, inc, slot)? {
+            Some(old) => self.delete::<Storage>(StorageKey(adr, inc), Some((slot, old))),
+            None => Ok(false),
+        }
+    }
+
+    /// Sets the slot with hash `hashed_slot` in the hashed storage of the
+    /// account with hash `hashed_adr`, in the same way as
+    /// [`Self::write_storage`].
+    pub fn write_hashed_storage(
+        &self,
+        hashed_adr: H256,
+        inc: impl Into<Incarnation>,
+        hashed_slot: H256,
+        val: U256,
+    ) -> Result<()> {
+        let key = HashStorageKey(hashed_adr, inc.into());
+        let old = self
+            .cursor::<HashedStorage>()?
+            .seek_exact_dup(key, hashed_slot)?;
+        if let Some(old) = old {
+            self.delete::<HashedStorage>(key, Some((hashed_slot, old)))?;
+        }
+        if !val.is_zero() {
+            self.write::<HashedStorage>(key, (hashed_slot, val))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the genesis block and state into an empty db, returning the
+    /// genesis hash. Every stage is marked as having processed block 0, and
+    /// contracts are given incarnation 1, as in Erigon. The hashed state is
+    /// left for Erigon's HashState stage to build from PlainState.
+    pub fn init_genesis(&self, genesis: &Genesis) -> Result<H256> {
+        if let Some(hash) = self.read_canonical_hash(0)? {
+            return Err(Error::AlreadyInitialized(hash));
+        }
+        let header = genesis.header();
+        let block = Block {
+            withdrawals: header.withdrawals_root.map(|_| vec![]),
+            header,
+            transactions: vec![],
+            uncles: vec![],
+        };
+        let hash = self.write_block(block)?;
+        self.write_head_header_hash(hash)?;
+        self.write_head_block_hash(hash)?;
+        self.write::<Config>(hash, genesis.config.clone())?;
+
+        for (&adr, alloc) in &genesis.alloc {
+            let is_contract = !alloc.code.is_empty() || !alloc.storage.is_empty();
+            let inc = Incarnation(u64::from(is_contract));
+            let mut acct = Account::new()
+                .nonce(alloc.nonce.as_u64())
+                .balance(alloc.balance)
+                .incarnation(inc);
+            if !alloc.code.is_empty() {
+                acct.codehash = utils::keccak256(&alloc.code).into();
+                self.write::<Code>(acct.codehash, Bytecode(alloc.code.clone()))?;
+                self.write::<PlainCodeHash>(PlainCodeKey(adr, inc), acct.codehash)?;
+            }
+            self.write_account(adr, acct)?;
+            for (slot, val) in &alloc.storage {
+                self.write_storage(adr, inc, *slot, U256::from_big_endian(val.as_bytes()))?;
+            }
+        }
+
+        for &stage in StageId::ALL {
+            self.write::<SyncStage>(stage, BlockNumber(0))?;
+        }
+        Ok(hash)
+    }
+
+    /// Writes a block and makes it canonical: its header, total difficulty,
+    /// body, transactions and senders, along with its canonical hash and
+    /// header number. As in Erigon, the block's tx ids are allocated from the
+    /// Sequence table, with an id reserved for the system tx at either end of
+    /// the block. The head pointers are left as they are. Returns the block
+    /// hash.
+    pub fn write_block(&self, block: Block) -> Result<H256> {
+        let Block {
+            header,
+            transactions,
+            uncles,
+            withdrawals,
+        } = block;
+        let num = BlockNumber(header.number.low_u64());
+        let hash = header.hash();
+        let key = HeaderKey(num, hash);
+
+        let parent_td = match num.checked_sub(1) {
+            Some(parent) => *self
+                .read_total_difficulty((BlockNumber(parent), header.parent_hash))?
+                .ok_or(Error::NotFound(HeadersTotalDifficulty::NAME))?,
+            None => U256::zero(),
+        };
+        let tx_amount = transactions.len() as u32 + 2;
+        let base_tx_id = self.read::<TxSequence>(TxSequenceKey)?.map_or(0, |id| *id);
+        self.write::<TxSequence>(TxSequenceKey, TxIndex(base_tx_id + u64::from(tx_amount)))?;
+
+        let senders = transactions.iter().map(|tx| tx.signer).collect();
+        for (id, tx) in (base_tx_id + 1..).zip(transactions) {
+            self.write::<BlockTransaction>(TxIndex(id), tx.msg)?;
+        }
+        let body = BodyForStorage {
+            base_tx_id,
+            tx_amount,
+            uncles,
+            withdrawals,
+        };
+        self.write::<HeadersTotalDifficulty>(key, (parent_td + header.difficulty).into())?;
+        self.write_header(key, header)?;
+        self.write_body_for_storage(key, body)?;
+        self.write::<TxSender>(key, senders)?;
+        self.write::<CanonicalHeader>(num, hash)?;
+        self.write_header_number(hash, num)?;
+        Ok(hash)
+    }
+
+    /// Records the state of account `adr` before the changes made in `block`,
+    /// or `None` if the account did not exist yet. As in Erigon, the codehash
+    /// is not stored, since it can be recovered from the PlainCodeHash table.
+    /// If a change is already recorded for the account in `block`, it is kept,
+    /// as it holds the earlier state.
+    pub fn write_account_change(
+        &self,
+        block: impl Into<BlockNumber>,
+        adr: Address,
+        prev: Option<Account>,
+    ) -> Result<()> {
+        let block = block.into();
+        let mut cur = self.cursor::<AccountChangeSet>()?;
+        if cur.seek_exact_dup(block, adr)?.is_some() {
+            return Ok(());
+        }
+        let prev = prev.map(|acct| acct.codehash(H256::zero()));
+        self.write::<AccountChangeSet>(block, AccountCSVal(adr, prev))
+    }
+
+    /// Records the value of the storage `slot` of account `adr` with
+    /// incarnation `inc` before the changes made in `block`. If a change is
+    /// already recorded for the slot in `block`, it is kept, as it holds the
+    /// earlier value.
+    pub fn write_storage_change(
+        &self,
+        block: impl Into<BlockNumber>,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        slot: H256,
+        prev: U256,
+    ) -> Result<()> {
+        let key: StorageCSKey = (block, adr, inc.into()).into();
+        let mut cur = self.cursor::<StorageChangeSet>()?;
+        if cur.seek_exact_dup(key, slot)?.is_some() {
+            return Ok(());
+        }
+        self.write::<StorageChangeSet>(key, StorageCSVal(slot, prev))
+    }
+
+    /// Adds the given blocks to the history index of account `adr`. The blocks
+    /// are merged into the account's last shard, which is then split into
+    /// shards of at most 1950 serialized bytes, as in Erigon.
+    pub fn write_account_history(&self, adr: Address, blocks: RoaringTreemap) -> Result<()> {
+        let last = AccountHistKey(adr, BlockNumber(u64::MAX));
+        let mut bitmap = self.read::<AccountHistory>(last)?.unwrap_or_default();
+        bitmap |= blocks;
+        for (shard, chunk) in utils::shard_bitmap(bitmap, C::CHUNK_LIMIT) {
+            self.write::<AccountHistory>(AccountHistKey(adr, BlockNumber(shard)), chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the given blocks to the history index of the storage `slot` of
+    /// account `adr`, sharded in the same way as [`Self::write_account_history`].
+    pub fn write_storage_history(
+        &self,
+        adr: Address,
+        slot: H256,
+        blocks: RoaringTreemap,
+    ) -> Result<()> {
+        let last = StorageHistKey(adr, slot, BlockNumber(u64::MAX));
+        let mut bitmap = self.read::<StorageHistory>(last)?.unwrap_or_default();
+        bitmap |= blocks;
+        for (shard, chunk) in utils::shard_bitmap(bitmap, C::CHUNK_LIMIT) {
+            self.write::<StorageHistory>(StorageHistKey(adr, slot, BlockNumber(shard)), chunk)?;
+        }
+        Ok(())
+    }
+
     pub fn write_head_header_hash(&self, v: H256) -> Result<()> {
         self.write::<LastHeader>(LastHeaderKey, v)
     }
@@ -321,8 +1623,19 @@ impl<'env> Erigon<'env, mdbx::RW> {
     pub fn write_incarnation(&self, k: Address, v: Incarnation) -> Result<()> {
         self.write::<IncarnationMap>(k, v)
     }
+    /// Sets the account at `k`. PlainState is dupsorted, since it also holds
+    /// storage, so any existing account is deleted first rather than left as
+    /// a duplicate.
     pub fn write_account(&self, k: Address, v: Account) -> Result<()> {
-        self.write::<PlainState>(k, v)
+        self.delete_account(k)?;
+        self.0
+            .put::<PlainState, DupSortFlags>(self.0.open_db()?, k, v)
+    }
+    /// Deletes the account at `k`, leaving its storage in place. Returns false
+    /// if there was no account.
+    pub fn delete_account(&self, k: Address) -> Result<bool> {
+        self.0
+            .del::<PlainState, DupSortFlags>(self.0.open_db()?, k, None)
     }
     pub fn write_transaction_block_number(&self, k: H256, v: U256) -> Result<()> {
         self.write::<BlockTransactionLookup>(k, v)
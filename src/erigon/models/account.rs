@@ -1,7 +1,5 @@
-use bytes::Buf;
+use bytes::{Buf, BufMut};
 use ethereum_types::{H256, U256};
-use eyre::Result;
-use fastrlp::{RlpDecodable, RlpEncodable};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -9,17 +7,20 @@ use crate::{
         utils::{consts::*, *},
         Incarnation,
     },
+    error::DecodeError,
     kv::traits::{TableDecode, TableEncode},
+    rlp::rlp_struct,
+    Result,
 };
 
-#[derive(
-    Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize, RlpEncodable, RlpDecodable,
-)]
-pub struct Account {
-    pub nonce: u64,
-    pub incarnation: Incarnation,
-    pub balance: U256,
-    pub codehash: H256, // hash of the bytecode
+rlp_struct! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+    pub struct Account {
+        pub nonce: u64,
+        pub incarnation: Incarnation,
+        pub balance: U256,
+        pub codehash: H256, // hash of the bytecode
+    }
 }
 
 impl TableDecode for Account {
@@ -34,42 +35,69 @@ impl TableDecode for Account {
 
         // has nonce
         if fieldset & 1 > 0 {
-            acct.nonce = take_u64_rlp(&mut buf)?;
+            acct.nonce = bytes_to_u64(take_field(&mut buf, U64_LENGTH)?);
         }
 
         // has balance
         if fieldset & 2 > 0 {
-            let bal_len = buf.get_u8();
-            acct.balance = buf[..bal_len.into()].into();
-            buf.advance(bal_len.into());
+            acct.balance = U256::from_big_endian(take_field(&mut buf, KECCAK_LENGTH)?);
         }
 
         // has incarnation
         if fieldset & 4 > 0 {
-            acct.incarnation = take_u64_rlp(&mut buf)?.into();
+            acct.incarnation = bytes_to_u64(take_field(&mut buf, U64_LENGTH)?).into();
         }
 
         // has codehash
         if fieldset & 8 > 0 {
-            let len: usize = buf.get_u8().into();
-            if len != KECCAK_LENGTH {
-                eyre::bail!(
-                    "codehash should be {} bytes long. Got {} instead",
-                    KECCAK_LENGTH,
-                    len
-                );
+            let codehash = take_field(&mut buf, KECCAK_LENGTH)?;
+            if codehash.len() != KECCAK_LENGTH {
+                return Err(DecodeError::InvalidLength {
+                    expected: KECCAK_LENGTH,
+                    got: codehash.len(),
+                }
+                .into());
             }
-            acct.codehash = H256::from_slice(&buf[..KECCAK_LENGTH]);
-            buf.advance(KECCAK_LENGTH)
+            acct.codehash = H256::from_slice(codehash);
         }
         Ok(acct)
     }
 }
-//TODO: dummy impl as we only need to decode for now, but need the trait bound
+// erigon: accounts.EncodeForStorage. Each field is written as a one-byte
+// length followed by its big-endian bytes with leading zeroes trimmed, and
+// is omitted entirely if it holds the default value.
 impl TableEncode for Account {
     type Encoded = Vec<u8>;
     fn encode(self) -> Self::Encoded {
-        unreachable!("Can't encode Account")
+        let mut fieldset = 0;
+        let mut out = Vec::with_capacity(1 + 2 * (1 + U64_LENGTH) + 2 * (1 + KECCAK_LENGTH));
+        out.put_u8(0);
+
+        if self.nonce > 0 {
+            fieldset |= 1;
+            put_trimmed(&mut out, &self.nonce.to_be_bytes());
+        }
+
+        if !self.balance.is_zero() {
+            fieldset |= 2;
+            let mut balance = [0; KECCAK_LENGTH];
+            self.balance.to_big_endian(&mut balance);
+            put_trimmed(&mut out, &balance);
+        }
+
+        if *self.incarnation > 0 {
+            fieldset |= 4;
+            put_trimmed(&mut out, &self.incarnation.to_be_bytes());
+        }
+
+        if !self.codehash.is_zero() && self.codehash != EMPTY_HASH {
+            fieldset |= 8;
+            out.put_u8(KECCAK_LENGTH as u8);
+            out.put_slice(self.codehash.as_bytes());
+        }
+
+        out[0] = fieldset;
+        out
     }
 }
 
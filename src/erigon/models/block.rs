@@ -1,19 +1,153 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use ethereum_types::{Address, Bloom, H256, H64, U256};
-use eyre::Result;
-use fastrlp::{BufMut, Decodable, DecodeError, Encodable, RlpDecodable, RlpEncodable};
 use serde::{Deserialize, Serialize};
 
-use crate::erigon::{macros::*, utils::consts::*, Rlp};
+use crate::{
+    erigon::{
+        macros::*,
+        models::{BlockNumber, TransactionWithSigner},
+        utils::{consts::*, keccak256},
+        Rlp,
+    },
+    rlp::{self, rlp_struct, BufMut, Decodable, DecodeError, Encodable},
+};
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, RlpEncodable, RlpDecodable)]
+/// Identifies a block either by its number or by its hash. A block number
+/// always refers to the canonical block at that height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockId {
+    Number(BlockNumber),
+    Hash(H256),
+}
+impl From<BlockNumber> for BlockId {
+    fn from(num: BlockNumber) -> Self {
+        Self::Number(num)
+    }
+}
+impl From<u64> for BlockId {
+    fn from(num: u64) -> Self {
+        Self::Number(num.into())
+    }
+}
+impl From<H256> for BlockId {
+    fn from(hash: H256) -> Self {
+        Self::Hash(hash)
+    }
+}
+
+/// Which side of a timestamp to look for a block on, in
+/// [`Erigon::block_by_timestamp`](crate::Erigon::block_by_timestamp).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hint {
+    /// The last block with a timestamp at or before the given time.
+    Before,
+    /// The first block with a timestamp at or after the given time.
+    After,
+}
+
+/// A fully assembled block, as returned by [`Erigon::read_block`](crate::Erigon::read_block).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<TransactionWithSigner>,
+    pub uncles: Vec<BlockHeader>,
+    /// Only present for post-Shanghai blocks.
+    pub withdrawals: Option<Vec<Withdrawal>>,
+}
+
+impl Block {
+    /// Returns the length of the block's RLP encoding, `[header, txs, uncles]`
+    /// followed by the withdrawals post-Shanghai, as reported in the `size`
+    /// field of `eth_getBlockByNumber`.
+    pub fn size(&self) -> usize {
+        let txs = self
+            .transactions
+            .iter()
+            .map(|tx| tx.msg.length())
+            .sum::<usize>();
+        let mut payload =
+            self.header.length() + rlp::length_of_length(txs) + txs + self.uncles.length();
+        if let Some(withdrawals) = &self.withdrawals {
+            payload += withdrawals.length();
+        }
+        rlp::length_of_length(payload) + payload
+    }
+}
+
+/// A validator withdrawal from the beacon chain, introduced by Eip4895.
+// https://github.com/ledgerwatch/erigon/blob/a1cdbb7be4d5ac3d0fc5a6aa4c1c3bbd4c1ee62c/core/types/withdrawal.go#L33
+rlp_struct! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+    pub struct Withdrawal {
+        pub index: u64,
+        pub validator: u64,
+        pub address: Address,
+        /// The amount withdrawn, in gwei.
+        pub amount: u64,
+    }
+}
+
+/// The block body as stored in the BlockBody table. Post-Shanghai bodies also
+/// carry the block's withdrawals, which Erigon stores inline rather than in a
+/// table of their own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BodyForStorage {
     pub base_tx_id: u64,
     pub tx_amount: u32,
     pub uncles: Vec<BlockHeader>,
+    pub withdrawals: Option<Vec<Withdrawal>>,
 }
 rlp_table_value!(BodyForStorage);
 
+impl BodyForStorage {
+    fn rlp_header(&self) -> rlp::Header {
+        let mut payload_length =
+            self.base_tx_id.length() + self.tx_amount.length() + self.uncles.length();
+        if let Some(withdrawals) = &self.withdrawals {
+            payload_length += withdrawals.length();
+        }
+        rlp::Header {
+            list: true,
+            payload_length,
+        }
+    }
+}
+
+impl Encodable for BodyForStorage {
+    fn encode(&self, out: &mut dyn BufMut) {
+        self.rlp_header().encode(out);
+        Encodable::encode(&self.base_tx_id, out);
+        Encodable::encode(&self.tx_amount, out);
+        Encodable::encode(&self.uncles, out);
+        if let Some(withdrawals) = &self.withdrawals {
+            Encodable::encode(withdrawals, out);
+        }
+    }
+    fn length(&self) -> usize {
+        let rlp_head = self.rlp_header();
+        rlp::length_of_length(rlp_head.payload_length) + rlp_head.payload_length
+    }
+}
+
+// https://github.com/ledgerwatch/erigon/blob/a1cdbb7be4d5ac3d0fc5a6aa4c1c3bbd4c1ee62c/core/types/block.go#L582
+impl Decodable for BodyForStorage {
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+        let rlp_head = rlp::Header::decode(buf)?;
+        if !rlp_head.list {
+            return Err(DecodeError::UnexpectedString);
+        }
+        let rest = buf.len() - rlp_head.payload_length;
+        let body = Self {
+            base_tx_id: Decodable::decode(buf)?,
+            tx_amount: Decodable::decode(buf)?,
+            uncles: Decodable::decode(buf)?,
+            withdrawals: decode_optional(buf, rest)?,
+        };
+        check_consumed(buf, rest, rlp_head.payload_length)?;
+        Ok(body)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct BlockHeader {
     pub parent_hash: H256,
@@ -32,13 +166,54 @@ pub struct BlockHeader {
     pub mix_digest: H256,
     pub nonce: H64,
     pub base_fee: Option<U256>,
+    /// Eip4895 (Shanghai)
+    pub withdrawals_root: Option<H256>,
+    /// Eip4844 (Cancun)
+    pub blob_gas_used: Option<u64>,
+    /// Eip4844 (Cancun)
+    pub excess_blob_gas: Option<u64>,
+    /// Eip4788 (Cancun)
+    pub parent_beacon_block_root: Option<H256>,
+    /// Eip7685 (Prague)
+    pub requests_hash: Option<H256>,
+    /// The raw rlp of the seal fields of an AuRa chain (e.g. Gnosis Chain),
+    /// `rlp(step) || rlp(signature)`, which take the place of `mix_digest` and
+    /// `nonce` in the encoding.
     pub seal: Option<Rlp>,
 }
 rlp_table_value!(BlockHeader);
 
 impl BlockHeader {
-    fn rlp_header(&self) -> fastrlp::Header {
-        let mut rlp_head = fastrlp::Header {
+    /// Computes the block hash, the keccak256 of the rlp-encoded header.
+    ///
+    /// The hash covers the seal fields of the header's own chain, so it is the
+    /// block hash on AuRa chains as well as on ethash and Clique chains.
+    pub fn hash(&self) -> H256 {
+        let mut buf = BytesMut::new();
+        Encodable::encode(self, &mut buf);
+        keccak256(buf).into()
+    }
+
+    /// Returns the step and signature sealing an AuRa header, or None if the
+    /// header has no seal or its seal cannot be decoded.
+    pub fn aura_seal(&self) -> Option<(u64, Bytes)> {
+        let mut buf = &self.seal.as_ref()?[..];
+        let step = Decodable::decode(&mut buf).ok()?;
+        let signature = Decodable::decode(&mut buf).ok()?;
+        buf.is_empty().then_some((step, signature))
+    }
+
+    /// Seals the header with an AuRa step and signature, replacing
+    /// `mix_digest` and `nonce` in its encoding.
+    pub fn set_aura_seal(&mut self, step: u64, signature: Bytes) {
+        let mut buf = BytesMut::new();
+        Encodable::encode(&step, &mut buf);
+        Encodable::encode(&signature, &mut buf);
+        self.seal = Some(Rlp(buf.freeze()));
+    }
+
+    fn rlp_header(&self) -> rlp::Header {
+        let mut rlp_head = rlp::Header {
             list: true,
             payload_length: 0,
         };
@@ -49,7 +224,7 @@ impl BlockHeader {
         rlp_head.payload_length += KECCAK_LENGTH + 1; // state_root
         rlp_head.payload_length += KECCAK_LENGTH + 1; // transactions_root
         rlp_head.payload_length += KECCAK_LENGTH + 1; // receipts_root
-        rlp_head.payload_length += BLOOM_BYTE_LENGTH + fastrlp::length_of_length(BLOOM_BYTE_LENGTH); // logs_bloom
+        rlp_head.payload_length += BLOOM_BYTE_LENGTH + rlp::length_of_length(BLOOM_BYTE_LENGTH); // logs_bloom
         rlp_head.payload_length += self.difficulty.length(); // difficulty
         rlp_head.payload_length += self.number.length(); // block height
         rlp_head.payload_length += self.gas_limit.length(); // gas_limit
@@ -57,12 +232,31 @@ impl BlockHeader {
         rlp_head.payload_length += self.time.length(); // timestamp
         rlp_head.payload_length += self.extra.length(); // extra_data
 
-        rlp_head.payload_length += KECCAK_LENGTH + 1; // mix_hash
-        rlp_head.payload_length += 8 + 1; // nonce
+        if let Some(seal) = &self.seal {
+            rlp_head.payload_length += seal.len(); // seal fields
+        } else {
+            rlp_head.payload_length += KECCAK_LENGTH + 1; // mix_hash
+            rlp_head.payload_length += 8 + 1; // nonce
+        }
 
         if let Some(base_fee) = self.base_fee {
             rlp_head.payload_length += base_fee.length();
         }
+        if self.withdrawals_root.is_some() {
+            rlp_head.payload_length += KECCAK_LENGTH + 1; // withdrawals_root
+        }
+        if let Some(blob_gas_used) = self.blob_gas_used {
+            rlp_head.payload_length += blob_gas_used.length();
+        }
+        if let Some(excess_blob_gas) = self.excess_blob_gas {
+            rlp_head.payload_length += excess_blob_gas.length();
+        }
+        if self.parent_beacon_block_root.is_some() {
+            rlp_head.payload_length += KECCAK_LENGTH + 1; // parent_beacon_block_root
+        }
+        if self.requests_hash.is_some() {
+            rlp_head.payload_length += KECCAK_LENGTH + 1; // requests_hash
+        }
 
         rlp_head
     }
@@ -84,22 +278,41 @@ impl Encodable for BlockHeader {
         Encodable::encode(&self.gas_used, out);
         Encodable::encode(&self.time, out);
         Encodable::encode(&self.extra, out);
-        Encodable::encode(&self.mix_digest, out);
-        Encodable::encode(&self.nonce, out);
+        if let Some(seal) = &self.seal {
+            out.put_slice(seal);
+        } else {
+            Encodable::encode(&self.mix_digest, out);
+            Encodable::encode(&self.nonce, out);
+        }
         if let Some(base_fee) = self.base_fee {
             Encodable::encode(&base_fee, out);
         }
+        if let Some(withdrawals_root) = self.withdrawals_root {
+            Encodable::encode(&withdrawals_root, out);
+        }
+        if let Some(blob_gas_used) = self.blob_gas_used {
+            Encodable::encode(&blob_gas_used, out);
+        }
+        if let Some(excess_blob_gas) = self.excess_blob_gas {
+            Encodable::encode(&excess_blob_gas, out);
+        }
+        if let Some(root) = self.parent_beacon_block_root {
+            Encodable::encode(&root, out);
+        }
+        if let Some(requests_hash) = self.requests_hash {
+            Encodable::encode(&requests_hash, out);
+        }
     }
     fn length(&self) -> usize {
         let rlp_head = self.rlp_header();
-        fastrlp::length_of_length(rlp_head.payload_length) + rlp_head.payload_length
+        rlp::length_of_length(rlp_head.payload_length) + rlp_head.payload_length
     }
 }
 
 // https://github.com/ledgerwatch/erigon/blob/156da607e7495d709c141aec40f66a2556d35dc0/core/types/block.go#L430
 impl Decodable for BlockHeader {
     fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
-        let rlp_head = fastrlp::Header::decode(buf)?;
+        let rlp_head = rlp::Header::decode(buf)?;
         if !rlp_head.list {
             return Err(DecodeError::UnexpectedString);
         }
@@ -118,15 +331,28 @@ impl Decodable for BlockHeader {
         let time = Decodable::decode(buf)?;
         let extra = Decodable::decode(buf)?;
 
-        // TODO: seal fields
-        let seal = None;
-        let mix_digest = Decodable::decode(buf)?;
-        let nonce = Decodable::decode(buf)?;
-        let base_fee = if buf.len() > rest {
-            Some(Decodable::decode(buf)?)
+        // AuRa headers have a step and a signature in place of mix_digest
+        // and nonce, told apart by the length of the first field.
+        let (mix_digest, nonce, seal) = if is_mix_digest(buf)? {
+            (Decodable::decode(buf)?, Decodable::decode(buf)?, None)
         } else {
-            None
+            let start = *buf;
+            <u64 as Decodable>::decode(buf)?; // step
+            <Bytes as Decodable>::decode(buf)?; // signature
+            let seal = Bytes::copy_from_slice(&start[..start.len() - buf.len()]);
+            (Default::default(), Default::default(), Some(Rlp(seal)))
         };
+        // Each fork appends its fields to the end of the header, so the
+        // optional fields are present in order until the payload runs out.
+        let base_fee = decode_optional(buf, rest)?;
+        let withdrawals_root = decode_optional(buf, rest)?;
+        let blob_gas_used = decode_optional(buf, rest)?;
+        let excess_blob_gas = decode_optional(buf, rest)?;
+        let parent_beacon_block_root = decode_optional(buf, rest)?;
+        let requests_hash = decode_optional(buf, rest)?;
+        // a field from a later fork would otherwise be dropped, and the header
+        // re-encoded to the wrong hash
+        check_consumed(buf, rest, rlp_head.payload_length)?;
 
         Ok(Self {
             parent_hash,
@@ -145,7 +371,40 @@ impl Decodable for BlockHeader {
             mix_digest,
             nonce,
             base_fee,
+            withdrawals_root,
+            blob_gas_used,
+            excess_blob_gas,
+            parent_beacon_block_root,
+            requests_hash,
             seal,
         })
     }
 }
+
+// Whether the next field is a 32-byte string, as mix_digest is and an AuRa
+// step never is.
+fn is_mix_digest(buf: &[u8]) -> Result<bool, DecodeError> {
+    let head = rlp::Header::decode(&mut &buf[..])?;
+    Ok(!head.list && head.payload_length == KECCAK_LENGTH)
+}
+
+// Fails unless the fields decoded so far took up exactly the list's payload,
+// which ends where `rest` bytes of the buffer are left.
+fn check_consumed(buf: &[u8], rest: usize, payload_length: usize) -> Result<(), DecodeError> {
+    if buf.len() != rest {
+        return Err(DecodeError::ListLengthMismatch {
+            expected: payload_length,
+            got: payload_length + rest - buf.len(),
+        });
+    }
+    Ok(())
+}
+
+// Decodes the next field if the header's payload has not been exhausted.
+fn decode_optional<T: Decodable>(buf: &mut &[u8], rest: usize) -> Result<Option<T>, DecodeError> {
+    if buf.len() > rest {
+        Decodable::decode(buf).map(Some)
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,96 @@
+use ethereum_types::{Address, H256, H520};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+
+use crate::{
+    erigon::{
+        models::BlockHeader,
+        utils::{consts::ADDRESS_LENGTH, keccak256},
+    },
+    error::DecodeError,
+    Result,
+};
+
+// The extra data of a Clique header begins with 32 bytes of vanity and ends
+// with the 65-byte signature of the sealer.
+const VANITY_LENGTH: usize = 32;
+const SIGNATURE_LENGTH: usize = 65;
+
+/// The extra data of a Clique header, `vanity || signers || signature`.
+// https://eips.ethereum.org/EIPS/eip-225
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CliqueExtra {
+    /// Free for the sealer to fill, e.g. with its client version.
+    pub vanity: H256,
+    /// The authorized signers, in ascending order. Only listed at the start of
+    /// each epoch (checkpoint blocks), and empty otherwise.
+    pub signers: Vec<Address>,
+    /// The sealer's signature over [`BlockHeader::clique_seal_hash`], as
+    /// `r || s || recovery_id`.
+    pub signature: H520,
+}
+
+impl BlockHeader {
+    /// Splits the extra data of a Clique header into its vanity, signers and
+    /// signature.
+    pub fn clique_extra(&self) -> Result<CliqueExtra> {
+        let extra = &self.extra[..];
+        let minimum = VANITY_LENGTH + SIGNATURE_LENGTH;
+        if extra.len() < minimum {
+            return Err(DecodeError::TooShort {
+                minimum,
+                got: extra.len(),
+            }
+            .into());
+        }
+        let (vanity, rest) = extra.split_at(VANITY_LENGTH);
+        let (signers, signature) = rest.split_at(rest.len() - SIGNATURE_LENGTH);
+        if signers.len() % ADDRESS_LENGTH != 0 {
+            return Err(DecodeError::custom(format!(
+                "clique signers of {} bytes are not a list of addresses",
+                signers.len()
+            ))
+            .into());
+        }
+        Ok(CliqueExtra {
+            vanity: H256::from_slice(vanity),
+            signers: signers
+                .chunks(ADDRESS_LENGTH)
+                .map(Address::from_slice)
+                .collect(),
+            signature: H520::from_slice(signature),
+        })
+    }
+
+    /// Computes the hash signed by the sealer of a Clique header, which is the
+    /// hash of the header without the signature at the end of its extra data.
+    pub fn clique_seal_hash(&self) -> Result<H256> {
+        let len = self.extra.len();
+        if len < SIGNATURE_LENGTH {
+            return Err(DecodeError::TooShort {
+                minimum: SIGNATURE_LENGTH,
+                got: len,
+            }
+            .into());
+        }
+        let mut header = self.clone();
+        header.extra = self.extra.slice(..len - SIGNATURE_LENGTH);
+        Ok(header.hash())
+    }
+
+    /// Recovers the address of the signer that sealed a Clique header.
+    pub fn clique_signer(&self) -> Result<Address> {
+        let sig = self.clique_extra()?.signature;
+        let hash = self.clique_seal_hash()?;
+        let invalid = |e: String| DecodeError::custom(format!("invalid clique signature: {}", e));
+        let recid = RecoveryId::from_byte(sig[64])
+            .ok_or_else(|| invalid(format!("recovery id {}", sig[64])))?;
+        let sig = Signature::from_slice(&sig[..64]).map_err(|e| invalid(e.to_string()))?;
+        let key = VerifyingKey::recover_from_prehash(hash.as_bytes(), &sig, recid)
+            .map_err(|e| invalid(e.to_string()))?;
+        // the address is the last 20 bytes of the hash of the uncompressed
+        // public key, without its 0x04 prefix
+        let pubkey = key.to_encoded_point(false);
+        let hash = keccak256(&pubkey.as_bytes()[1..]);
+        Ok(Address::from_slice(&hash[32 - ADDRESS_LENGTH..]))
+    }
+}
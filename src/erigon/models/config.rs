@@ -0,0 +1,137 @@
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+/// The chain config, stored as json in the Config table under the genesis hash.
+///
+/// Fork blocks that are unset (or null) have not been scheduled. Post-merge
+/// forks are activated by timestamp rather than by block number.
+// https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/params/config.go#L235
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_name: Option<String>,
+    pub chain_id: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consensus: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homestead_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dao_fork_block: Option<u64>,
+    #[serde(default)]
+    pub dao_fork_support: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eip150_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eip155_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eip158_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byzantium_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constantinople_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub petersburg_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub istanbul_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muir_glacier_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub berlin_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub london_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrow_glacier_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gray_glacier_block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_netsplit_block: Option<u64>,
+    /// The total difficulty at which the chain switched to proof of stake.
+    /// Stored as a json number, which can exceed a u64.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_total_difficulty: Option<u128>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shanghai_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancun_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prague_time: Option<u64>,
+}
+
+fn is_forked(fork: Option<u64>, at: u64) -> bool {
+    fork.map_or(false, |fork| fork <= at)
+}
+
+impl ChainConfig {
+    pub fn is_homestead(&self, num: u64) -> bool {
+        is_forked(self.homestead_block, num)
+    }
+    pub fn is_eip150(&self, num: u64) -> bool {
+        is_forked(self.eip150_block, num)
+    }
+    pub fn is_eip155(&self, num: u64) -> bool {
+        is_forked(self.eip155_block, num)
+    }
+    pub fn is_eip158(&self, num: u64) -> bool {
+        is_forked(self.eip158_block, num)
+    }
+    pub fn is_byzantium(&self, num: u64) -> bool {
+        is_forked(self.byzantium_block, num)
+    }
+    pub fn is_constantinople(&self, num: u64) -> bool {
+        is_forked(self.constantinople_block, num)
+    }
+    /// Petersburg is considered active alongside Constantinople if it was not scheduled separately.
+    pub fn is_petersburg(&self, num: u64) -> bool {
+        match self.petersburg_block {
+            Some(_) => is_forked(self.petersburg_block, num),
+            None => self.is_constantinople(num),
+        }
+    }
+    pub fn is_istanbul(&self, num: u64) -> bool {
+        is_forked(self.istanbul_block, num)
+    }
+    pub fn is_berlin(&self, num: u64) -> bool {
+        is_forked(self.berlin_block, num)
+    }
+    pub fn is_london(&self, num: u64) -> bool {
+        is_forked(self.london_block, num)
+    }
+    /// Paris (the merge) is activated by total difficulty: a block is past the
+    /// merge once its parent has reached the terminal total difficulty.
+    pub fn is_paris(&self, parent_td: U256) -> bool {
+        self.terminal_total_difficulty
+            .map_or(false, |ttd| parent_td >= U256::from(ttd))
+    }
+    pub fn is_shanghai(&self, time: u64) -> bool {
+        is_forked(self.shanghai_time, time)
+    }
+    pub fn is_cancun(&self, time: u64) -> bool {
+        is_forked(self.cancun_time, time)
+    }
+    pub fn is_prague(&self, time: u64) -> bool {
+        is_forked(self.prague_time, time)
+    }
+}
+
+impl TableEncode for ChainConfig {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        // serializing a struct of plain fields to json cannot fail
+        serde_json::to_vec(&self).unwrap()
+    }
+}
+
+impl TableDecode for ChainConfig {
+    fn decode(b: &[u8]) -> Result<Self> {
+        serde_json::from_slice(b).map_err(|e| DecodeError::from(e).into())
+    }
+}
@@ -0,0 +1,142 @@
+use bytes::Bytes;
+use ethereum_types::{Address, Bloom, H256, H64, U256, U64};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+use crate::erigon::{
+    models::{Account, BlockHeader, ChainConfig},
+    trie::{account_rlp, storage_rlp, HashBuilder},
+    utils::{consts::*, keccak256},
+};
+
+// https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/params/protocol_params.go#L173
+const INITIAL_BASE_FEE: u64 = 1_000_000_000;
+
+/// A genesis spec, in the genesis.json format read by geth and Erigon.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Genesis {
+    pub config: ChainConfig,
+    #[serde(default)]
+    pub nonce: U64,
+    #[serde(default)]
+    pub timestamp: U64,
+    #[serde(default, deserialize_with = "hex_bytes")]
+    pub extra_data: Bytes,
+    pub gas_limit: U64,
+    pub difficulty: U256,
+    #[serde(default)]
+    pub mix_hash: H256,
+    #[serde(default)]
+    pub coinbase: Address,
+    #[serde(default)]
+    pub alloc: BTreeMap<Address, GenesisAccount>,
+    /// Defaults to the initial base fee if London is active at genesis.
+    #[serde(default)]
+    pub base_fee_per_gas: Option<U256>,
+}
+
+/// An account allocated in the genesis state.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct GenesisAccount {
+    #[serde(default)]
+    pub balance: U256,
+    #[serde(default)]
+    pub nonce: U64,
+    #[serde(default, deserialize_with = "hex_bytes")]
+    pub code: Bytes,
+    #[serde(default)]
+    pub storage: BTreeMap<H256, H256>,
+}
+
+impl Genesis {
+    /// Builds the genesis block header.
+    pub fn header(&self) -> BlockHeader {
+        let time = self.timestamp.as_u64();
+        let london = self.config.is_london(0);
+        let cancun = self.config.is_cancun(time);
+        BlockHeader {
+            parent_hash: H256::zero(),
+            uncle_hash: EMPTY_LIST_HASH,
+            coinbase: self.coinbase,
+            root: self.state_root(),
+            tx_hash: EMPTY_ROOT,
+            receipts_hash: EMPTY_ROOT,
+            bloom: Bloom::zero(),
+            difficulty: self.difficulty,
+            number: U256::zero(),
+            gas_limit: self.gas_limit.as_u64(),
+            gas_used: 0,
+            time,
+            extra: self.extra_data.clone(),
+            mix_digest: self.mix_hash,
+            nonce: H64::from_low_u64_be(self.nonce.as_u64()),
+            base_fee: match self.base_fee_per_gas {
+                Some(fee) => Some(fee),
+                None if london => Some(INITIAL_BASE_FEE.into()),
+                None => None,
+            },
+            withdrawals_root: self.config.is_shanghai(time).then_some(EMPTY_ROOT),
+            blob_gas_used: cancun.then_some(0),
+            excess_blob_gas: cancun.then_some(0),
+            parent_beacon_block_root: cancun.then_some(H256::zero()),
+            requests_hash: self.config.is_prague(time).then_some(EMPTY_REQUESTS_HASH),
+            seal: None,
+        }
+    }
+
+    /// Computes the root of the state trie holding `alloc`.
+    pub fn state_root(&self) -> H256 {
+        let mut accounts = self
+            .alloc
+            .iter()
+            .map(|(adr, alloc)| (H256(keccak256(adr)), alloc))
+            .collect::<Vec<_>>();
+        accounts.sort_by_key(|(hashed_adr, _)| *hashed_adr);
+
+        let mut builder = HashBuilder::new();
+        for (hashed_adr, alloc) in accounts {
+            let acct = Account::new()
+                .nonce(alloc.nonce.as_u64())
+                .balance(alloc.balance)
+                .codehash(keccak256(&alloc.code).into());
+            builder.add_leaf(hashed_adr, account_rlp(&acct, alloc.storage_root()));
+        }
+        builder.root()
+    }
+}
+
+impl GenesisAccount {
+    /// Computes the root of the account's storage trie. Zero values are left
+    /// out, as they are never stored.
+    pub fn storage_root(&self) -> H256 {
+        let mut slots = self
+            .storage
+            .iter()
+            .filter(|(_, val)| !val.is_zero())
+            .map(|(slot, val)| (H256(keccak256(slot)), U256::from_big_endian(val.as_bytes())))
+            .collect::<Vec<_>>();
+        slots.sort_by_key(|(hashed_slot, _)| *hashed_slot);
+
+        let mut builder = HashBuilder::new();
+        for (hashed_slot, val) in slots {
+            builder.add_leaf(hashed_slot, storage_rlp(val));
+        }
+        builder.root()
+    }
+}
+
+// Deserializes 0x-prefixed hex into bytes.
+fn hex_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    let digits = s.strip_prefix("0x").unwrap_or(&s);
+    if digits.len() % 2 != 0 {
+        return Err(serde::de::Error::custom("odd number of hex digits"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Bytes::from)
+        .map_err(serde::de::Error::custom)
+}
@@ -3,32 +3,160 @@ use crate::erigon::{
     models::BlockNumber,
 };
 use bytes::Bytes;
-use ethereum_types::{Address, H256};
-use serde::{Deserialize, Serialize};
+use ethereum_types::{Address, Bloom, BloomInput, H256, U256};
+use serde::{ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
 
 cbor_wrapper!(CborReceipts(Option<Vec<CborReceipt>>));
 
-// blocknum||log_index_in_tx
+// blocknum||tx_index
 tuple_key!(LogsKey(BlockNumber, u32));
+// topic||shard_max_block
+tuple_key!(LogTopicIndexKey(H256, u32));
+// address||shard_max_block
+tuple_key!(LogAddressIndexKey(Address, u32));
 cbor_wrapper!(CborLogs(Option<Vec<CborLog>>));
 
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+/// A log emitted by a transaction, as stored in the TransactionLog table.
+///
+/// Like receipts, each log is encoded as a cbor array `[address, topics, data]`.
+/// The derived fields (block number, tx hash, etc.) are not stored.
+// https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/core/types/log.go#L31
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
 pub struct CborLog {
-    address: Address,
-    topics: Vec<H256>,
-    data: Bytes,
-    // block_number: u64,
-    // tx_hash: H256,
-    // tx_index: usize,
-    // block_hash: H256,
-    // index: usize,
-    // removed: bool,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(deserialize_with = "deserialize_address")]
+    pub address: Address,
+    #[serde(deserialize_with = "deserialize_topics")]
+    pub topics: Vec<H256>,
+    pub data: Bytes,
+}
+
+impl Serialize for CborLog {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let topics = self
+            .topics
+            .iter()
+            .map(|t| Bytes::copy_from_slice(t.as_bytes()))
+            .collect::<Vec<_>>();
+        let mut tup = serializer.serialize_tuple(3)?;
+        tup.serialize_element(&Bytes::copy_from_slice(self.address.as_bytes()))?;
+        tup.serialize_element(&topics)?;
+        tup.serialize_element(&self.data)?;
+        tup.end()
+    }
+}
+
+// The serde impls from ethereum-types expect hex strings, but cbor stores
+// addresses and hashes as raw byte strings.
+fn deserialize_address<'de, D: Deserializer<'de>>(d: D) -> Result<Address, D::Error> {
+    let b = Bytes::deserialize(d)?;
+    if b.len() != Address::len_bytes() {
+        return Err(serde::de::Error::invalid_length(b.len(), &"20 bytes"));
+    }
+    Ok(Address::from_slice(&b))
+}
+
+fn deserialize_topics<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<H256>, D::Error> {
+    Vec::<Bytes>::deserialize(d)?
+        .into_iter()
+        .map(|b| {
+            if b.len() != H256::len_bytes() {
+                return Err(serde::de::Error::invalid_length(b.len(), &"32 bytes"));
+            }
+            Ok(H256::from_slice(&b))
+        })
+        .collect()
+}
+
+/// The consensus fields of a transaction receipt, as stored in the Receipt table.
+///
+/// Erigon encodes receipts with `codecgen -ta=true`, meaning each receipt is
+/// a cbor array `[tx_type, post_state, status, cumulative_gas_used]`. Logs
+/// and the bloom are not stored with the receipt.
+// https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/core/types/receipt.go#L51
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
 pub struct CborReceipt {
-    tx_type: u8, //omitempty
-    post_state: Option<H256>,
-    status: u64,
-    cumulative_gas_used: u64,
+    pub tx_type: u8,
+    /// The intermediate state root. Only set for pre-Byzantium receipts.
+    #[serde(deserialize_with = "deserialize_post_state")]
+    pub post_state: Option<H256>,
+    /// 1 for success, 0 for failure. Unused pre-Byzantium.
+    pub status: u64,
+    pub cumulative_gas_used: u64,
+}
+
+impl Serialize for CborReceipt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tup = serializer.serialize_tuple(4)?;
+        tup.serialize_element(&self.tx_type)?;
+        tup.serialize_element(
+            &self
+                .post_state
+                .map(|root| Bytes::copy_from_slice(root.as_bytes())),
+        )?;
+        tup.serialize_element(&self.status)?;
+        tup.serialize_element(&self.cumulative_gas_used)?;
+        tup.end()
+    }
+}
+
+// Erigon stores a nil post state as either cbor null or an empty byte string.
+fn deserialize_post_state<'de, D: Deserializer<'de>>(d: D) -> Result<Option<H256>, D::Error> {
+    match Option::<Bytes>::deserialize(d)? {
+        None => Ok(None),
+        Some(b) if b.is_empty() => Ok(None),
+        Some(b) if b.len() == H256::len_bytes() => Ok(Some(H256::from_slice(&b))),
+        Some(b) => Err(serde::de::Error::invalid_length(b.len(), &"32 bytes")),
+    }
+}
+
+/// Returns the gas used by each transaction in a block, computed from the
+/// cumulative gas used in the block's receipts.
+pub fn gas_used_per_tx(receipts: &[CborReceipt]) -> Vec<u64> {
+    let mut prev = 0;
+    receipts
+        .iter()
+        .map(|r| {
+            let used = r.cumulative_gas_used.saturating_sub(prev);
+            prev = r.cumulative_gas_used;
+            used
+        })
+        .collect()
+}
+
+/// Returns the bloom filter of the given logs, over each log's address and
+/// topics.
+pub fn logs_bloom<'a>(logs: impl IntoIterator<Item = &'a CborLog>) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for log in logs {
+        bloom.accrue(BloomInput::Raw(log.address.as_bytes()));
+        for topic in &log.topics {
+            bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+        }
+    }
+    bloom
+}
+
+/// A transaction receipt with the fields derived from its block and the
+/// block's other receipts, as returned by `eth_getTransactionReceipt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionReceipt {
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    pub block_hash: H256,
+    pub block_number: BlockNumber,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub tx_type: u8,
+    pub cumulative_gas_used: u64,
+    pub gas_used: u64,
+    pub effective_gas_price: U256,
+    /// The address of the contract deployed by the tx, if it was a creation.
+    pub contract_address: Option<Address>,
+    /// The logs emitted by the tx, each with its index within the block.
+    pub logs: Vec<(u32, CborLog)>,
+    pub logs_bloom: Bloom,
+    /// The intermediate state root. Only set for pre-Byzantium receipts.
+    pub root: Option<H256>,
+    /// 1 for success, 0 for failure. Only set for post-Byzantium receipts.
+    pub status: Option<u64>,
 }
@@ -1,23 +1,38 @@
 use bytes::Bytes;
 use ethereum_types::{Address, H256, U256};
-use eyre::Result;
 
 use crate::{
     erigon::{macros::*, utils::*},
+    error::DecodeError,
     kv::{
         tables::VariableVec,
         traits::{TableDecode, TableEncode},
     },
+    Result,
 };
 
 pub mod transaction;
-pub use transaction::Transaction;
+pub use transaction::{Transaction, TransactionWithSigner};
 pub mod block;
 pub use block::*;
+pub mod clique;
+pub use clique::CliqueExtra;
 pub mod account;
 pub use account::*;
 pub mod log;
 pub use log::*;
+pub mod trace;
+pub use trace::*;
+pub mod stage;
+pub use stage::StageId;
+pub mod config;
+pub use config::ChainConfig;
+pub mod schema;
+pub use schema::{DbSchemaVersionKey, SchemaVersion};
+pub mod genesis;
+pub use genesis::{Genesis, GenesisAccount};
+pub mod trie;
+pub use trie::{Nibbles, TrieNode, TrieStorageKey};
 
 use crate::erigon::utils::consts::*;
 
@@ -25,6 +40,8 @@ use crate::erigon::utils::consts::*;
 constant_key!(LastHeaderKey, LastHeader);
 // the LastBlock table stores only one key, bytes("LastBlock")
 constant_key!(LastBlockKey, LastBlock);
+// the Sequence table is keyed by the name of the table whose ids it allocates
+constant_key!(TxSequenceKey, BlockTransaction);
 
 // u64 newtype aliases
 u64_wrapper!(BlockNumber);
@@ -50,8 +67,22 @@ where
     }
 }
 
-// values for the AccountChangeSet table. address||encode(account)
-tuple_key!(AccountCSVal(Address, Account));
+// values for the AccountChangeSet table. address||encode(account), where an
+// account that did not exist yet is stored as the bare address and decodes to
+// None. An empty account that did exist is encoded as usual, i.e. as [0].
+declare_tuple!(AccountCSVal(Address, Option<Account>));
+size_tuple!(AccountCSVal(Address, Option<Account>));
+impl_decode_tuple!(AccountCSVal(Address, Option<Account>), 1);
+impl TableEncode for AccountCSVal {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let mut out = self.0.encode().to_vec();
+        if let Some(acct) = self.1 {
+            out.extend_from_slice(&acct.encode());
+        }
+        out
+    }
+}
 
 // address||storage_slot||block_number
 tuple_key!(StorageHistKey(Address, H256, BlockNumber));
@@ -66,11 +97,11 @@ impl ContractCodeKey {
     }
 }
 
-// keccak(address)||incarnation||keccak(storage_key)
-tuple_key!(HashStorageKey(H256, Incarnation, H256));
+// keccak(address)||incarnation
+tuple_key!(HashStorageKey(H256, Incarnation));
 impl HashStorageKey {
-    pub fn make(who: Address, inc: impl Into<Incarnation>, key: H256) -> Self {
-        Self(keccak256(who).into(), inc.into(), keccak256(key).into())
+    pub fn make(who: Address, inc: impl Into<Incarnation>) -> Self {
+        Self(keccak256(who).into(), inc.into())
     }
 }
 
@@ -91,6 +122,8 @@ impl TableEncode for BurntKey {
 
 bytes_wrapper!(Rlp(Bytes));
 bytes_wrapper!(Bytecode(Bytes));
+bytes_ref!(RlpRef(Rlp));
+bytes_ref!(BytecodeRef(Bytecode));
 
 decl_u256_wrapper!(TotalDifficulty);
 rlp_table_value!(TotalDifficulty);
@@ -111,7 +144,11 @@ impl TableEncode for Vec<Address> {
 impl TableDecode for Vec<Address> {
     fn decode(b: &[u8]) -> Result<Self> {
         if b.len() % ADDRESS_LENGTH != 0 {
-            eyre::bail!("Slice len should be divisible by {}", ADDRESS_LENGTH);
+            return Err(DecodeError::custom(format!(
+                "Slice len should be divisible by {}",
+                ADDRESS_LENGTH
+            ))
+            .into());
         }
 
         let mut v = Vec::with_capacity(b.len() / ADDRESS_LENGTH);
@@ -0,0 +1,63 @@
+use crate::{
+    erigon::macros::constant_key,
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+// the DbInfo table stores the schema version under bytes("dbVersion")
+constant_key!(DbSchemaVersionKey, dbVersion);
+
+/// The version of the db schema, as written by Erigon when it creates or
+/// migrates a database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl SchemaVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// major||minor||patch, each a big-endian u32
+impl TableEncode for SchemaVersion {
+    type Encoded = [u8; 12];
+    fn encode(self) -> Self::Encoded {
+        let mut out = [0; 12];
+        out[..4].copy_from_slice(&self.major.to_be_bytes());
+        out[4..8].copy_from_slice(&self.minor.to_be_bytes());
+        out[8..].copy_from_slice(&self.patch.to_be_bytes());
+        out
+    }
+}
+
+impl TableDecode for SchemaVersion {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != 12 {
+            return Err(DecodeError::InvalidLength {
+                expected: 12,
+                got: b.len(),
+            }
+            .into());
+        }
+        Ok(Self {
+            major: u32::decode(&b[..4])?,
+            minor: u32::decode(&b[4..8])?,
+            patch: u32::decode(&b[8..])?,
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+macro_rules! stage_ids {
+    ($($id:ident => $name:literal),* $(,)?) => {
+        /// The stages of Erigon's staged sync, keyed in the SyncStage table by name.
+        // https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/eth/stagedsync/stages/stages.go#L30
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum StageId {
+            $($id,)*
+        }
+
+        impl StageId {
+            /// Every stage, in the order they are run during sync.
+            pub const ALL: &'static [StageId] = &[$(StageId::$id,)*];
+
+            /// The name of the stage, as stored in the db.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(StageId::$id => $name,)*
+                }
+            }
+
+            pub fn from_name(name: &[u8]) -> Option<Self> {
+                match name {
+                    $(n if n == $name.as_bytes() => Some(StageId::$id),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+stage_ids!(
+    Snapshots => "Snapshots",
+    Headers => "Headers",
+    BlockHashes => "BlockHashes",
+    Bodies => "Bodies",
+    Senders => "Senders",
+    Execution => "Execution",
+    Translation => "Translation",
+    HashState => "HashState",
+    IntermediateHashes => "IntermediateHashes",
+    AccountHistoryIndex => "AccountHistoryIndex",
+    StorageHistoryIndex => "StorageHistoryIndex",
+    LogIndex => "LogIndex",
+    CallTraces => "CallTraces",
+    TxLookup => "TxLookup",
+    // kept under its old name in the db
+    Issuance => "WatchTheBurn",
+    Finish => "Finish",
+);
+
+impl std::fmt::Display for StageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TableEncode for StageId {
+    type Encoded = &'static [u8];
+    fn encode(self) -> Self::Encoded {
+        self.as_str().as_bytes()
+    }
+}
+
+impl TableDecode for StageId {
+    fn decode(b: &[u8]) -> Result<Self> {
+        Self::from_name(b).ok_or_else(|| {
+            DecodeError::custom(format!("unknown stage {}", String::from_utf8_lossy(b))).into()
+        })
+    }
+}
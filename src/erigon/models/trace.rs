@@ -0,0 +1,66 @@
+use ethereum_types::Address;
+
+use crate::{
+    erigon::{macros::tuple_key, utils::consts::ADDRESS_LENGTH},
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+// address||shard_max_block
+tuple_key!(CallIndexKey(Address, u32));
+
+/// Whether an address appeared as the sender or the receiver of a call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallDirection {
+    From,
+    To,
+}
+
+/// An entry in the CallTraceSet table, recording that `address` was the sender
+/// and/or receiver of at least one call in the block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallTrace {
+    pub address: Address,
+    pub from: bool,
+    pub to: bool,
+}
+
+impl CallTrace {
+    const FLAG_FROM: u8 = 1;
+    const FLAG_TO: u8 = 2;
+}
+
+// address||flags
+impl TableEncode for CallTrace {
+    type Encoded = [u8; ADDRESS_LENGTH + 1];
+    fn encode(self) -> Self::Encoded {
+        let mut out = [0; ADDRESS_LENGTH + 1];
+        out[..ADDRESS_LENGTH].copy_from_slice(self.address.as_bytes());
+        if self.from {
+            out[ADDRESS_LENGTH] |= Self::FLAG_FROM;
+        }
+        if self.to {
+            out[ADDRESS_LENGTH] |= Self::FLAG_TO;
+        }
+        out
+    }
+}
+
+impl TableDecode for CallTrace {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() != ADDRESS_LENGTH + 1 {
+            return Err(DecodeError::InvalidLength {
+                expected: ADDRESS_LENGTH + 1,
+                got: b.len(),
+            }
+            .into());
+        }
+        let flags = b[ADDRESS_LENGTH];
+        Ok(Self {
+            address: Address::decode(&b[..ADDRESS_LENGTH])?,
+            from: flags & Self::FLAG_FROM != 0,
+            to: flags & Self::FLAG_TO != 0,
+        })
+    }
+}
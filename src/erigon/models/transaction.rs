@@ -1,12 +1,14 @@
 use bytes::{Buf, Bytes, BytesMut};
 use ethereum_types::{Address, H256, U256};
-use fastrlp::{BufMut, Decodable, DecodeError, Encodable, RlpDecodable, RlpEncodable};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
-use crate::erigon::{
-    macros::decl_u256_wrapper,
-    utils::{consts as C, keccak256},
+use crate::{
+    erigon::{
+        macros::decl_u256_wrapper,
+        utils::{consts as C, keccak256},
+    },
+    rlp::{self, rlp_struct, BufMut, Decodable, DecodeError, Encodable},
 };
 
 // https://github.com/akula-bft/akula/blob/e5af0ab9cea24c7ff4713b1e61c60a918abc6fef/src/models/transaction.rs#L41
@@ -47,7 +49,7 @@ impl Encodable for TxAction {
     fn encode(&self, out: &mut dyn BufMut) {
         match self {
             Self::Call(adr) => {
-                fastrlp::Header {
+                rlp::Header {
                     list: false,
                     payload_length: C::ADDRESS_LENGTH,
                 }
@@ -55,7 +57,7 @@ impl Encodable for TxAction {
                 out.put_slice(adr.as_bytes());
             }
             Self::Create => {
-                out.put_u8(fastrlp::EMPTY_STRING_CODE);
+                out.put_u8(rlp::EMPTY_STRING_CODE);
             }
         }
     }
@@ -66,10 +68,10 @@ impl Decodable for TxAction {
         if buf.is_empty() {
             return Err(DecodeError::InputTooShort);
         }
-        const RLP_ADDRESS_CODE: u8 = fastrlp::EMPTY_STRING_CODE + C::ADDRESS_LENGTH as u8;
+        const RLP_ADDRESS_CODE: u8 = rlp::EMPTY_STRING_CODE + C::ADDRESS_LENGTH as u8;
 
         Ok(match buf.get_u8() {
-            fastrlp::EMPTY_STRING_CODE => Self::Create,
+            rlp::EMPTY_STRING_CODE => Self::Create,
             RLP_ADDRESS_CODE => {
                 let slice = buf
                     .get(..C::ADDRESS_LENGTH)
@@ -82,88 +84,182 @@ impl Decodable for TxAction {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, RlpDecodable, RlpEncodable)]
-pub struct AccessTuple {
-    pub address: Address,
-    pub slots: Vec<H256>,
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct AccessTuple {
+        pub address: Address,
+        pub slots: Vec<H256>,
+    }
 }
 pub type AccessList = Vec<AccessTuple>;
 
+// Eip7702 authorization, signed by the account delegating its code to `address`
+// rlp([chain_id, address, nonce, y_parity, r, s])
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Authorization {
+        pub chain_id: U256,
+        pub address: Address,
+        pub nonce: u64,
+        pub v: U256,
+        pub r: U256,
+        pub s: U256,
+    }
+}
+pub type AuthorizationList = Vec<Authorization>;
+
 // For legacy transactions, v is packed with the Eip155 chain id
 decl_u256_wrapper!(VPackChainId);
 
 impl VPackChainId {
-    // Eip155 defines v as either {0,1} + 27 (no chain id) OR {0,1} + chain_id * 2 + 35
-    pub fn derive_chain_id(&self) -> Option<U256> {
+    /// Returns the Eip155 chain id packed into v, which is then
+    /// `y_parity + chain_id * 2 + 35`, or None for a tx signed without one,
+    /// whose v is `y_parity + 27`. Errors for any other v below 35, which no
+    /// valid signature has.
+    pub fn derive_chain_id(&self) -> Result<Option<U256>, DecodeError> {
         if self.0 == U256::from(27) || self.0 == U256::from(28) {
-            None
-        } else {
-            Some(
-                (self
-                    .0
-                    .checked_sub(35.into())
-                    .expect("invalid eip155 chainid"))
-                    / 2,
-            )
-        }
-    }
-    //TODO
-    pub fn derive_v(&self) -> U256 {
-        if let Some(chain_id) = self.derive_chain_id() {
-            self.0 - (chain_id * 2 + 35)
+            Ok(None)
+        } else if self.0 >= U256::from(35) {
+            Ok(Some((self.0 - U256::from(35)) / 2))
         } else {
-            self.0
+            Err(DecodeError::Custom("invalid legacy tx v"))
         }
     }
+    /// Returns the y parity of the signature.
+    pub fn y_parity(&self) -> Result<bool, DecodeError> {
+        Ok(match self.derive_chain_id()? {
+            Some(_) => (self.0 - U256::from(35)).bit(0),
+            None => self.0 == U256::from(28),
+        })
+    }
+    /// Returns v with the Eip155 chain id removed: the y parity for a tx
+    /// signed with a chain id, or v as it is, 27 or 28, for one without.
+    pub fn derive_v(&self) -> Result<U256, DecodeError> {
+        Ok(match self.derive_chain_id()? {
+            Some(_) => U256::from(u8::from(self.y_parity()?)),
+            None => self.0,
+        })
+    }
 }
 
 // rlp([nonce, gas_price, gas_limit, to, value, data, v, r, s])
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, RlpDecodable, RlpEncodable)]
-pub struct LegacyTx {
-    pub nonce: u64,
-    pub gas_price: U256,
-    pub gas: u64,
-    pub to: TxAction,
-    pub value: U256,
-    pub data: Bytes,
-    pub v: VPackChainId,
-    pub r: U256,
-    pub s: U256,
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct LegacyTx {
+        pub nonce: u64,
+        pub gas_price: U256,
+        pub gas: u64,
+        pub to: TxAction,
+        pub value: U256,
+        pub data: Bytes,
+        pub v: VPackChainId,
+        pub r: U256,
+        pub s: U256,
+    }
 }
 
 // Eip2930 transaction
 // 0x01 || rlp([chain_id, nonce, gas_price, gas_limit, to, value, data, access_list, sig_y_parity, sig_r, sig_s])
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, RlpDecodable, RlpEncodable)]
-pub struct AccessListTx {
-    pub chain_id: U256,
-    pub nonce: u64,
-    pub gas_price: U256,
-    pub gas: u64,
-    pub to: TxAction,
-    pub value: U256,
-    pub data: Bytes,
-    pub access_list: AccessList,
-    pub v: U256,
-    pub r: U256,
-    pub s: U256,
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct AccessListTx {
+        pub chain_id: U256,
+        pub nonce: u64,
+        pub gas_price: U256,
+        pub gas: u64,
+        pub to: TxAction,
+        pub value: U256,
+        pub data: Bytes,
+        pub access_list: AccessList,
+        pub v: U256,
+        pub r: U256,
+        pub s: U256,
+    }
 }
 
 // Eip1559 transaction
 // 0x02 || rlp([chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas_limit, destination, amount, data, access_list, sig_y_parity, sig_r, sig_s])
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, RlpDecodable, RlpEncodable)]
-pub struct DynamicFeeTx {
-    pub chain_id: U256,
-    pub nonce: u64,
-    pub tip: U256,
-    pub fee_cap: U256,
-    pub gas: u64,
-    pub to: TxAction,
-    pub value: U256,
-    pub data: Bytes,
-    pub access_list: AccessList,
-    pub v: U256,
-    pub r: U256,
-    pub s: U256,
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct DynamicFeeTx {
+        pub chain_id: U256,
+        pub nonce: u64,
+        pub tip: U256,
+        pub fee_cap: U256,
+        pub gas: u64,
+        pub to: TxAction,
+        pub value: U256,
+        pub data: Bytes,
+        pub access_list: AccessList,
+        pub v: U256,
+        pub r: U256,
+        pub s: U256,
+    }
+}
+
+// Eip4844 transaction
+// 0x03 || rlp([chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas_limit, to, value, data, access_list, max_fee_per_blob_gas, blob_versioned_hashes, y_parity, r, s])
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct BlobTx {
+        pub chain_id: U256,
+        pub nonce: u64,
+        pub tip: U256,
+        pub fee_cap: U256,
+        pub gas: u64,
+        // blob transactions cannot create contracts
+        pub to: Address,
+        pub value: U256,
+        pub data: Bytes,
+        pub access_list: AccessList,
+        pub max_fee_per_blob_gas: U256,
+        pub blob_versioned_hashes: Vec<H256>,
+        pub v: U256,
+        pub r: U256,
+        pub s: U256,
+    }
+}
+
+// Eip7702 transaction
+// 0x04 || rlp([chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas_limit, destination, value, data, access_list, authorization_list, sig_y_parity, sig_r, sig_s])
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct SetCodeTx {
+        pub chain_id: U256,
+        pub nonce: u64,
+        pub tip: U256,
+        pub fee_cap: U256,
+        pub gas: u64,
+        // set code transactions cannot create contracts
+        pub to: Address,
+        pub value: U256,
+        pub data: Bytes,
+        pub access_list: AccessList,
+        pub authorizations: AuthorizationList,
+        pub v: U256,
+        pub r: U256,
+        pub s: U256,
+    }
+}
+
+// Optimism deposit transaction, minted on L2 for a deposit made on L1. It is
+// neither signed nor charged for gas, and the tx carries its sender.
+// 0x7e || rlp([source_hash, from, to, mint, value, gas, is_system_tx, data])
+#[cfg(feature = "op")]
+rlp_struct! {
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct DepositTx {
+        // uniquely identifies the L1 deposit, see the OP stack deposit spec
+        pub source_hash: H256,
+        pub from: Address,
+        pub to: TxAction,
+        // the ETH minted on L2 for the deposit, zero if none
+        pub mint: U256,
+        pub value: U256,
+        pub gas: u64,
+        pub is_system_tx: bool,
+        pub data: Bytes,
+    }
 }
 
 crate::erigon::macros::rlp_table_value!(Transaction);
@@ -172,6 +268,10 @@ pub enum Transaction {
     Legacy(LegacyTx),
     AccessList(AccessListTx),
     DynamicFee(DynamicFeeTx),
+    Blob(BlobTx),
+    SetCode(SetCodeTx),
+    #[cfg(feature = "op")]
+    Deposit(DepositTx),
 }
 
 impl DynamicFeeTx {
@@ -180,32 +280,74 @@ impl DynamicFeeTx {
 impl AccessListTx {
     pub const TYPE: u8 = 0x01;
 }
+impl BlobTx {
+    pub const TYPE: u8 = 0x03;
+}
+impl SetCodeTx {
+    pub const TYPE: u8 = 0x04;
+}
+#[cfg(feature = "op")]
+impl DepositTx {
+    pub const TYPE: u8 = 0x7e;
+}
 
 impl Decodable for Transaction {
-    fn decode(buf: &mut &[u8]) -> Result<Self, fastrlp::DecodeError> {
+    fn decode(buf: &mut &[u8]) -> Result<Self, rlp::DecodeError> {
         // if input is rlp encoded as a list, interpret as a legacy transaction
         // rlp([nonce, gas_price, gas_limit, to, value, data, v, r, s])
-        if buf[0] >= 0xc0 {
+        let first = *buf.first().ok_or(DecodeError::InputTooShort)?;
+        if first >= 0xc0 {
             return Decodable::decode(buf).map(Self::Legacy);
         }
         // strip string length and length of length
-        fastrlp::Header::decode(buf)?;
+        let before = buf.len();
+        let head = rlp::Header::decode(buf)?;
+        if head.payload_length == 0 {
+            return Err(DecodeError::InputTooShort);
+        }
+        // a bare envelope, as sent over RPC, begins with its type byte, which
+        // is its own header
+        if buf.len() == before {
+            return Self::decode_enveloped(buf);
+        }
+        // the envelope must fill the string that holds it
+        let (mut payload, rest) = buf.split_at(head.payload_length);
+        let tx = Self::decode_enveloped(&mut payload)?;
+        if !payload.is_empty() {
+            return Err(DecodeError::UnexpectedLength);
+        }
+        *buf = rest;
+        Ok(tx)
+    }
+}
 
-        // Eip2718 Typed Transaction. TransactionType || TransactionPayload
+impl Transaction {
+    // Decodes an Eip2718 typed tx, TransactionType || TransactionPayload.
+    fn decode_enveloped(buf: &mut &[u8]) -> Result<Self, rlp::DecodeError> {
         match buf.get_u8() {
             AccessListTx::TYPE => Decodable::decode(buf).map(Self::AccessList),
             DynamicFeeTx::TYPE => Decodable::decode(buf).map(Self::DynamicFee),
+            BlobTx::TYPE => Decodable::decode(buf).map(Self::Blob),
+            SetCodeTx::TYPE => Decodable::decode(buf).map(Self::SetCode),
+            #[cfg(feature = "op")]
+            DepositTx::TYPE => Decodable::decode(buf).map(Self::Deposit),
             _ => Err(DecodeError::Custom("Unknown transaction type")),
         }
     }
 }
 
+// As in Erigon, a tx is stored in the db in the form it takes in a block body:
+// a legacy tx as its rlp list, and a typed tx as an rlp string holding its
+// Eip2718 envelope.
 impl Encodable for Transaction {
     fn encode(&self, out: &mut dyn BufMut) {
-        match self {
-            Self::Legacy(tx) => tx.encode(out),
-            Self::AccessList(tx) => tx.encode(out),
-            Self::DynamicFee(tx) => tx.encode(out),
+        self.encode_for_block_body(out)
+    }
+    fn length(&self) -> usize {
+        let len = self.enveloped_length();
+        match self.tx_type() {
+            Some(_) => rlp::length_of_length(len) + len,
+            None => len,
         }
     }
 }
@@ -215,21 +357,93 @@ impl Transaction {
         match self {
             Self::AccessList(_) => Some(AccessListTx::TYPE),
             Self::DynamicFee(_) => Some(DynamicFeeTx::TYPE),
+            Self::Blob(_) => Some(BlobTx::TYPE),
+            Self::SetCode(_) => Some(SetCodeTx::TYPE),
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => Some(DepositTx::TYPE),
             Self::Legacy(_) => None,
         }
     }
-    pub fn hash(&self) -> H256 {
+    /// Encodes the tx as its Eip2718 envelope, `type || rlp(payload)`, or as
+    /// its rlp list for a legacy tx. This is the form of a raw tx over RPC.
+    pub fn encode_enveloped(&self, out: &mut dyn BufMut) {
+        if let Some(ty) = self.tx_type() {
+            out.put_u8(ty);
+        }
         match self {
-            Self::Legacy(tx) => tx.hash(),
-            Self::AccessList(tx) => tx.hash(),
-            Self::DynamicFee(tx) => tx.hash(),
+            Self::Legacy(tx) => tx.encode(out),
+            Self::AccessList(tx) => tx.encode(out),
+            Self::DynamicFee(tx) => tx.encode(out),
+            Self::Blob(tx) => tx.encode(out),
+            Self::SetCode(tx) => tx.encode(out),
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => tx.encode(out),
+        }
+    }
+    /// The length of the output of [`Self::encode_enveloped`].
+    pub fn enveloped_length(&self) -> usize {
+        let payload = match self {
+            Self::Legacy(tx) => tx.length(),
+            Self::AccessList(tx) => tx.length(),
+            Self::DynamicFee(tx) => tx.length(),
+            Self::Blob(tx) => tx.length(),
+            Self::SetCode(tx) => tx.length(),
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => tx.length(),
+        };
+        usize::from(self.tx_type().is_some()) + payload
+    }
+    /// Encodes the tx as it appears in a block body, in devp2p messages and
+    /// in the db: the rlp list of a legacy tx, or an rlp string holding the
+    /// Eip2718 envelope of a typed tx. This is the same as its [`Encodable`]
+    /// impl.
+    pub fn encode_for_block_body(&self, out: &mut dyn BufMut) {
+        if self.tx_type().is_some() {
+            rlp::Header {
+                list: false,
+                payload_length: self.enveloped_length(),
+            }
+            .encode(out);
+        }
+        self.encode_enveloped(out);
+    }
+    /// Computes the hash of the signed transaction, i.e. the keccak256 of its
+    /// Eip2718 envelope. This is the hash that identifies the transaction on the
+    /// network and in the BlockTransactionLookup table.
+    pub fn tx_hash(&self) -> H256 {
+        let mut buf = BytesMut::new();
+        self.encode_enveloped(&mut buf);
+        keccak256(buf).into()
+    }
+    /// Computes the signing hash of the transaction, the hash of its fields
+    /// without the signature that the sender signs, from which the sender is
+    /// recovered. Unlike [`Self::tx_hash`], this does not identify the
+    /// transaction, except for a deposit, see [`DepositTx::signing_hash`].
+    pub fn signing_hash(&self) -> H256 {
+        match self {
+            Self::Legacy(tx) => tx.signing_hash(),
+            Self::AccessList(tx) => tx.signing_hash(),
+            Self::DynamicFee(tx) => tx.signing_hash(),
+            Self::Blob(tx) => tx.signing_hash(),
+            Self::SetCode(tx) => tx.signing_hash(),
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => tx.signing_hash(),
         }
     }
+    #[deprecated(note = "renamed to `signing_hash`; `tx_hash` is the hash that identifies the tx")]
+    pub fn hash(&self) -> H256 {
+        self.signing_hash()
+    }
     pub fn nonce(&self) -> u64 {
         match self {
             Self::Legacy(tx) => tx.nonce,
             Self::AccessList(tx) => tx.nonce,
             Self::DynamicFee(tx) => tx.nonce,
+            Self::Blob(tx) => tx.nonce,
+            Self::SetCode(tx) => tx.nonce,
+            // the nonce of a deposit is only recorded in its receipt
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => 0,
         }
     }
     pub fn to(&self) -> TxAction {
@@ -237,6 +451,10 @@ impl Transaction {
             Self::Legacy(tx) => tx.to,
             Self::AccessList(tx) => tx.to,
             Self::DynamicFee(tx) => tx.to,
+            Self::Blob(tx) => TxAction::Call(tx.to),
+            Self::SetCode(tx) => TxAction::Call(tx.to),
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => tx.to,
         }
     }
     pub fn value(&self) -> U256 {
@@ -244,39 +462,71 @@ impl Transaction {
             Self::Legacy(tx) => tx.value,
             Self::AccessList(tx) => tx.value,
             Self::DynamicFee(tx) => tx.value,
+            Self::Blob(tx) => tx.value,
+            Self::SetCode(tx) => tx.value,
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => tx.value,
         }
     }
     pub fn gas_price(&self) -> Option<U256> {
         match self {
             Self::Legacy(tx) => Some(tx.gas_price),
             Self::AccessList(tx) => Some(tx.gas_price),
-            Self::DynamicFee(_) => None,
+            Self::DynamicFee(_) | Self::Blob(_) | Self::SetCode(_) => None,
+            // deposits buy their gas on L1
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => Some(U256::zero()),
         }
     }
     pub fn chain_id(&self) -> Option<U256> {
         match self {
-            Self::Legacy(tx) => tx.v.derive_chain_id(),
+            // a legacy tx with an invalid v has no chain id to give
+            Self::Legacy(tx) => tx.v.derive_chain_id().ok().flatten(),
             Self::AccessList(tx) => Some(tx.chain_id),
             Self::DynamicFee(tx) => Some(tx.chain_id),
+            Self::Blob(tx) => Some(tx.chain_id),
+            Self::SetCode(tx) => Some(tx.chain_id),
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => None,
         }
     }
     pub fn tip(&self) -> Option<U256> {
         match self {
             Self::DynamicFee(tx) => Some(tx.tip),
+            Self::Blob(tx) => Some(tx.tip),
+            Self::SetCode(tx) => Some(tx.tip),
             _ => None,
         }
     }
     pub fn fee_cap(&self) -> Option<U256> {
         match self {
             Self::DynamicFee(tx) => Some(tx.fee_cap),
+            Self::Blob(tx) => Some(tx.fee_cap),
+            Self::SetCode(tx) => Some(tx.fee_cap),
             _ => None,
         }
     }
+    /// Returns the price per gas paid by the tx in a block with the given base
+    /// fee, or its fee cap if the base fee is unknown.
+    pub fn effective_gas_price(&self, base_fee: Option<U256>) -> U256 {
+        if let Some(price) = self.gas_price() {
+            return price;
+        }
+        let fee_cap = self.fee_cap().unwrap_or_default();
+        match base_fee {
+            Some(base_fee) => fee_cap.min(base_fee.saturating_add(self.tip().unwrap_or_default())),
+            None => fee_cap,
+        }
+    }
     pub fn gas(&self) -> u64 {
         match self {
             Self::Legacy(tx) => tx.gas,
             Self::AccessList(tx) => tx.gas,
             Self::DynamicFee(tx) => tx.gas,
+            Self::Blob(tx) => tx.gas,
+            Self::SetCode(tx) => tx.gas,
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => tx.gas,
         }
     }
     pub fn data(&self) -> &Bytes {
@@ -284,6 +534,10 @@ impl Transaction {
             Self::Legacy(tx) => &tx.data,
             Self::AccessList(tx) => &tx.data,
             Self::DynamicFee(tx) => &tx.data,
+            Self::Blob(tx) => &tx.data,
+            Self::SetCode(tx) => &tx.data,
+            #[cfg(feature = "op")]
+            Self::Deposit(tx) => &tx.data,
         }
     }
     pub fn r(&self) -> U256 {
@@ -291,6 +545,11 @@ impl Transaction {
             Self::Legacy(tx) => tx.r,
             Self::AccessList(tx) => tx.r,
             Self::DynamicFee(tx) => tx.r,
+            Self::Blob(tx) => tx.r,
+            Self::SetCode(tx) => tx.r,
+            // deposits are not signed
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => U256::zero(),
         }
     }
     pub fn s(&self) -> U256 {
@@ -298,14 +557,35 @@ impl Transaction {
             Self::Legacy(tx) => tx.s,
             Self::AccessList(tx) => tx.s,
             Self::DynamicFee(tx) => tx.s,
+            Self::Blob(tx) => tx.s,
+            Self::SetCode(tx) => tx.s,
+            // deposits are not signed
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => U256::zero(),
         }
     }
-    //TODO
+    /// Returns the v of the signature without any Eip155 chain id: the y
+    /// parity for a typed tx or a legacy tx signed with a chain id, and 27 or
+    /// 28 for a legacy tx signed without one. An invalid legacy v is returned
+    /// as it is. See [`Self::raw_v`] for v as it was signed.
     pub fn v(&self) -> U256 {
         match self {
-            Self::Legacy(tx) => tx.v.derive_v(),
+            Self::Legacy(tx) => tx.v.derive_v().unwrap_or(*tx.v),
             Self::AccessList(tx) => tx.v,
             Self::DynamicFee(tx) => tx.v,
+            Self::Blob(tx) => tx.v,
+            Self::SetCode(tx) => tx.v,
+            // deposits are not signed
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => U256::zero(),
+        }
+    }
+    /// Returns the v of the signature as it is stored in the tx, with the
+    /// chain id of an Eip155 legacy tx packed in, as reported over JSON-RPC.
+    pub fn raw_v(&self) -> U256 {
+        match self {
+            Self::Legacy(tx) => *tx.v,
+            _ => self.v(),
         }
     }
 
@@ -313,39 +593,79 @@ impl Transaction {
         match self {
             Self::AccessList(tx) => Some(Cow::Borrowed(&tx.access_list)),
             Self::DynamicFee(tx) => Some(Cow::Borrowed(&tx.access_list)),
+            Self::Blob(tx) => Some(Cow::Borrowed(&tx.access_list)),
+            Self::SetCode(tx) => Some(Cow::Borrowed(&tx.access_list)),
             Self::Legacy(_) => None,
+            #[cfg(feature = "op")]
+            Self::Deposit(_) => None,
+        }
+    }
+
+    /// Returns the sender of a deposit, which is not recovered from a
+    /// signature but stored in the tx.
+    #[cfg(feature = "op")]
+    pub fn deposit_from(&self) -> Option<Address> {
+        match self {
+            Self::Deposit(tx) => Some(tx.from),
+            _ => None,
+        }
+    }
+
+    /// Returns the versioned hashes of the blobs carried by an Eip4844 transaction.
+    pub fn blob_hashes(&self) -> Option<&[H256]> {
+        match self {
+            Self::Blob(tx) => Some(&tx.blob_versioned_hashes),
+            _ => None,
+        }
+    }
+    pub fn max_fee_per_blob_gas(&self) -> Option<U256> {
+        match self {
+            Self::Blob(tx) => Some(tx.max_fee_per_blob_gas),
+            _ => None,
+        }
+    }
+
+    /// Returns the authorization list of an Eip7702 transaction.
+    pub fn authorizations(&self) -> Option<&[Authorization]> {
+        match self {
+            Self::SetCode(tx) => Some(&tx.authorizations),
+            _ => None,
         }
     }
 }
 
 impl LegacyTx {
-    /// Computes the (signing) hash of the transaction
-    pub fn hash(&self) -> H256 {
-        #[derive(RlpEncodable)]
-        struct AsHash<'a> {
-            nonce: u64,
-            gas_price: &'a U256,
-            gas: u64,
-            to: &'a TxAction,
-            value: &'a U256,
-            data: &'a Bytes,
-        }
-
-        #[derive(RlpEncodable)]
-        struct AsHashWithChainId<'a> {
-            nonce: u64,
-            gas_price: &'a U256,
-            gas: u64,
-            to: &'a TxAction,
-            value: &'a U256,
-            data: &'a Bytes,
-            chain_id: U256,
-            _pad1: u8,
-            _pad2: u8,
+    /// Computes the signing hash of the transaction, the hash of its fields
+    /// without the signature that the sender signs.
+    pub fn signing_hash(&self) -> H256 {
+        rlp_struct! {
+            struct AsHash<'a> {
+                nonce: u64,
+                gas_price: &'a U256,
+                gas: u64,
+                to: &'a TxAction,
+                value: &'a U256,
+                data: &'a Bytes,
+            }
+        }
+
+        rlp_struct! {
+            struct AsHashWithChainId<'a> {
+                nonce: u64,
+                gas_price: &'a U256,
+                gas: u64,
+                to: &'a TxAction,
+                value: &'a U256,
+                data: &'a Bytes,
+                chain_id: U256,
+                _pad1: u8,
+                _pad2: u8,
+            }
         }
 
+        // a tx with an invalid v is hashed as if it had no chain id
         let mut buf = BytesMut::new();
-        if let Some(chain_id) = self.v.derive_chain_id() {
+        if let Ok(Some(chain_id)) = self.v.derive_chain_id() {
             AsHashWithChainId {
                 nonce: self.nonce,
                 gas_price: &self.gas_price,
@@ -371,21 +691,28 @@ impl LegacyTx {
         }
         keccak256(buf).into()
     }
+
+    #[deprecated(note = "renamed to `signing_hash`")]
+    pub fn hash(&self) -> H256 {
+        self.signing_hash()
+    }
 }
 
 impl AccessListTx {
-    /// Computes the (signing) hash of the transaction
-    pub fn hash(&self) -> H256 {
-        #[derive(RlpEncodable)]
-        struct AsHash<'a> {
-            chain_id: U256,
-            nonce: u64,
-            gas_price: &'a U256,
-            gas: u64,
-            to: &'a TxAction,
-            value: &'a U256,
-            data: &'a Bytes,
-            access_list: &'a AccessList,
+    /// Computes the signing hash of the transaction, the hash of its fields
+    /// without the signature that the sender signs.
+    pub fn signing_hash(&self) -> H256 {
+        rlp_struct! {
+            struct AsHash<'a> {
+                chain_id: U256,
+                nonce: u64,
+                gas_price: &'a U256,
+                gas: u64,
+                to: &'a TxAction,
+                value: &'a U256,
+                data: &'a Bytes,
+                access_list: &'a AccessList,
+            }
         }
 
         let mut buf = BytesMut::new();
@@ -405,22 +732,120 @@ impl AccessListTx {
 
         keccak256(buf).into()
     }
+
+    #[deprecated(note = "renamed to `signing_hash`")]
+    pub fn hash(&self) -> H256 {
+        self.signing_hash()
+    }
 }
 
 impl DynamicFeeTx {
-    /// Computes the (signing) hash of the transaction
+    /// Computes the signing hash of the transaction, the hash of its fields
+    /// without the signature that the sender signs.
+    pub fn signing_hash(&self) -> H256 {
+        rlp_struct! {
+            struct AsHash<'a> {
+                chain_id: U256,
+                nonce: u64,
+                tip: &'a U256,
+                fee_cap: &'a U256,
+                gas: u64,
+                to: &'a TxAction,
+                value: &'a U256,
+                data: &'a Bytes,
+                access_list: &'a AccessList,
+            }
+        }
+
+        let mut buf = BytesMut::new();
+        buf.put_u8(Self::TYPE);
+
+        AsHash {
+            chain_id: self.chain_id,
+            nonce: self.nonce,
+            tip: &self.tip,
+            fee_cap: &self.fee_cap,
+            gas: self.gas,
+            to: &self.to,
+            value: &self.value,
+            data: &self.data,
+            access_list: &self.access_list,
+        }
+        .encode(&mut buf);
+
+        keccak256(buf).into()
+    }
+
+    #[deprecated(note = "renamed to `signing_hash`")]
+    pub fn hash(&self) -> H256 {
+        self.signing_hash()
+    }
+}
+
+impl BlobTx {
+    /// Computes the signing hash of the transaction, the hash of its fields
+    /// without the signature that the sender signs.
+    pub fn signing_hash(&self) -> H256 {
+        rlp_struct! {
+            struct AsHash<'a> {
+                chain_id: U256,
+                nonce: u64,
+                tip: &'a U256,
+                fee_cap: &'a U256,
+                gas: u64,
+                to: &'a Address,
+                value: &'a U256,
+                data: &'a Bytes,
+                access_list: &'a AccessList,
+                max_fee_per_blob_gas: &'a U256,
+                blob_versioned_hashes: &'a Vec<H256>,
+            }
+        }
+
+        let mut buf = BytesMut::new();
+        buf.put_u8(Self::TYPE);
+
+        AsHash {
+            chain_id: self.chain_id,
+            nonce: self.nonce,
+            tip: &self.tip,
+            fee_cap: &self.fee_cap,
+            gas: self.gas,
+            to: &self.to,
+            value: &self.value,
+            data: &self.data,
+            access_list: &self.access_list,
+            max_fee_per_blob_gas: &self.max_fee_per_blob_gas,
+            blob_versioned_hashes: &self.blob_versioned_hashes,
+        }
+        .encode(&mut buf);
+
+        keccak256(buf).into()
+    }
+
+    #[deprecated(note = "renamed to `signing_hash`")]
     pub fn hash(&self) -> H256 {
-        #[derive(RlpEncodable)]
-        struct AsHash<'a> {
-            chain_id: U256,
-            nonce: u64,
-            tip: &'a U256,
-            fee_cap: &'a U256,
-            gas: u64,
-            to: &'a TxAction,
-            value: &'a U256,
-            data: &'a Bytes,
-            access_list: &'a AccessList,
+        self.signing_hash()
+    }
+}
+
+impl SetCodeTx {
+    /// Computes the signing hash of the transaction, the hash of its fields
+    /// without the signature that the sender signs.
+    pub fn signing_hash(&self) -> H256 {
+        rlp_struct! {
+            struct AsHash<'a> {
+                chain_id: U256,
+                nonce: u64,
+                tip: &'a U256,
+                fee_cap: &'a U256,
+                gas: u64,
+                to: &'a Address,
+                value: &'a U256,
+                data: &'a Bytes,
+                access_list: &'a AccessList,
+                authorizations: &'a AuthorizationList,
+            }
         }
 
         let mut buf = BytesMut::new();
@@ -436,13 +861,39 @@ impl DynamicFeeTx {
             value: &self.value,
             data: &self.data,
             access_list: &self.access_list,
+            authorizations: &self.authorizations,
         }
         .encode(&mut buf);
 
         keccak256(buf).into()
     }
+
+    #[deprecated(note = "renamed to `signing_hash`")]
+    pub fn hash(&self) -> H256 {
+        self.signing_hash()
+    }
+}
+
+#[cfg(feature = "op")]
+impl DepositTx {
+    /// Computes the hash of the transaction. A deposit is not signed and has
+    /// no signing hash, so this returns the hash of its Eip2718 envelope, the
+    /// same as [`Transaction::tx_hash`]. Its sender is the `from` field rather
+    /// than recovered from a signature.
+    pub fn signing_hash(&self) -> H256 {
+        let mut buf = BytesMut::new();
+        buf.put_u8(Self::TYPE);
+        self.encode(&mut buf);
+        keccak256(buf).into()
+    }
+
+    #[deprecated(note = "renamed to `signing_hash`")]
+    pub fn hash(&self) -> H256 {
+        self.signing_hash()
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransactionWithSigner {
     pub msg: Transaction,
     pub signer: Address,
@@ -452,7 +903,7 @@ pub struct TransactionWithSigner {
 impl From<TransactionWithSigner> for ethers::types::Transaction {
     fn from(tx: TransactionWithSigner) -> Self {
         Self {
-            hash: tx.msg.hash(),
+            hash: tx.msg.tx_hash(),
             nonce: tx.msg.nonce().into(),
             from: tx.signer,
             to: tx.msg.to().into(),
@@ -470,7 +921,7 @@ impl From<TransactionWithSigner> for ethers::types::Transaction {
             chain_id: tx.msg.chain_id(),
             max_fee_per_gas: tx.msg.fee_cap(),
             max_priority_fee_per_gas: tx.msg.tip(),
-            v: tx.msg.v().as_u64().into(),
+            v: tx.msg.raw_v().low_u64().into(),
             r: tx.msg.r(),
             s: tx.msg.s(),
             ..Default::default()
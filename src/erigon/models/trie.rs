@@ -0,0 +1,155 @@
+use bytes::Bytes;
+use ethereum_types::H256;
+
+use crate::{
+    erigon::{
+        macros::bytes_wrapper,
+        models::Incarnation,
+        utils::consts::{KECCAK_LENGTH, U64_LENGTH},
+    },
+    error::DecodeError,
+    kv::traits::{TableDecode, TableEncode},
+    Result,
+};
+
+// A path in the trie, stored as one nibble per byte.
+bytes_wrapper!(Nibbles(Bytes));
+
+impl Nibbles {
+    /// Splits each byte of `b` into two nibbles, high nibble first.
+    pub fn unpack(b: impl AsRef<[u8]>) -> Self {
+        let nibbles = b
+            .as_ref()
+            .iter()
+            .flat_map(|b| [b >> 4, b & 0x0f])
+            .collect::<Vec<_>>();
+        Self(nibbles.into())
+    }
+}
+
+/// A key in the TrieStorage table. keccak(address)||incarnation||nibbles
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct TrieStorageKey(pub H256, pub Incarnation, pub Nibbles);
+
+impl TableEncode for TrieStorageKey {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let mut out = Vec::with_capacity(KECCAK_LENGTH + U64_LENGTH + self.2.len());
+        out.extend_from_slice(self.0.as_bytes());
+        out.extend_from_slice(&self.1.encode());
+        out.extend_from_slice(&self.2);
+        out
+    }
+}
+
+impl TableDecode for TrieStorageKey {
+    fn decode(b: &[u8]) -> Result<Self> {
+        const PREFIX: usize = KECCAK_LENGTH + U64_LENGTH;
+        if b.len() < PREFIX {
+            return Err(DecodeError::TooShort {
+                minimum: PREFIX,
+                got: b.len(),
+            }
+            .into());
+        }
+        let (adr, rest) = b.split_at(KECCAK_LENGTH);
+        let (inc, path) = rest.split_at(U64_LENGTH);
+        Ok(Self(
+            H256::decode(adr)?,
+            Incarnation::decode(inc)?,
+            Nibbles::decode(path)?,
+        ))
+    }
+}
+
+/// A node of the account or storage trie, as stored in the TrieAccount and
+/// TrieStorage tables. Only branch nodes are stored, keyed by their path.
+///
+/// Bit `i` of each mask describes the child at nibble `i`:
+/// - `state_mask`: the child exists in the hashed state.
+/// - `tree_mask`: the child is itself stored in the table.
+/// - `hash_mask`: the child's hash is stored in `hashes`.
+///
+/// The root node of a trie also stores the root hash.
+// erigon: trie.MarshalTrieNode
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct TrieNode {
+    pub state_mask: u16,
+    pub tree_mask: u16,
+    pub hash_mask: u16,
+    /// The hashes of the children in `hash_mask`, in order of nibble.
+    pub hashes: Vec<H256>,
+    pub root_hash: Option<H256>,
+}
+
+impl TrieNode {
+    /// Returns the stored hash of the child at `nibble`, if any.
+    pub fn child_hash(&self, nibble: u8) -> Option<H256> {
+        let bit = 1u16.checked_shl(nibble.into())?;
+        if self.hash_mask & bit == 0 {
+            return None;
+        }
+        let index = (self.hash_mask & (bit - 1)).count_ones() as usize;
+        self.hashes.get(index).copied()
+    }
+}
+
+impl TableEncode for TrieNode {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let num_hashes = self.hashes.len() + usize::from(self.root_hash.is_some());
+        let mut out = Vec::with_capacity(6 + num_hashes * KECCAK_LENGTH);
+        out.extend_from_slice(&self.state_mask.to_be_bytes());
+        out.extend_from_slice(&self.tree_mask.to_be_bytes());
+        out.extend_from_slice(&self.hash_mask.to_be_bytes());
+        // the root hash comes before the hashes of the children
+        for hash in self.root_hash.iter().chain(&self.hashes) {
+            out.extend_from_slice(hash.as_bytes());
+        }
+        out
+    }
+}
+
+impl TableDecode for TrieNode {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.len() < 6 {
+            return Err(DecodeError::TooShort {
+                minimum: 6,
+                got: b.len(),
+            }
+            .into());
+        }
+        let mask = |i: usize| u16::from_be_bytes([b[i], b[i + 1]]);
+        let (state_mask, tree_mask, hash_mask) = (mask(0), mask(2), mask(4));
+        let hashes = &b[6..];
+        if hashes.len() % KECCAK_LENGTH != 0 {
+            return Err(DecodeError::custom(format!(
+                "trie node hashes of length {} not a multiple of {KECCAK_LENGTH}",
+                hashes.len()
+            ))
+            .into());
+        }
+        let mut hashes = hashes
+            .chunks(KECCAK_LENGTH)
+            .map(H256::from_slice)
+            .collect::<Vec<_>>();
+        let root_hash = match (hashes.len() as u32).checked_sub(hash_mask.count_ones()) {
+            Some(0) => None,
+            Some(1) => Some(hashes.remove(0)),
+            _ => {
+                return Err(DecodeError::custom(format!(
+                    "trie node has {} hashes for hash mask {hash_mask:#018b}",
+                    hashes.len()
+                ))
+                .into())
+            }
+        };
+        Ok(Self {
+            state_mask,
+            tree_mask,
+            hash_mask,
+            hashes,
+            root_hash,
+        })
+    }
+}
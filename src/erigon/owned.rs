@@ -0,0 +1,62 @@
+use mdbx::RO;
+use std::sync::Arc;
+
+use crate::{erigon::Erigon, kv::MdbxEnv, Result};
+
+/// A read-only transaction that holds its own handle to the env, so that it
+/// is `'static` and can be kept by long-lived services, e.g. as shared server
+/// state, without borrowing the env.
+///
+/// The transaction is reached through [`with`](Self::with), which lends it out
+/// for the length of a closure, e.g. `db.with(|db| db.read_account(adr))`.
+///
+/// Like any mdbx read transaction, it sees the db as it was when it began.
+/// Use [`renew`](Self::renew) to see later commits.
+// Safety invariant: `db` borrows the env inside the allocation of `env`,
+// which does not move when `Self` or the Arc does, and which `env` keeps
+// alive for as long as `db` exists. The `'static` lifetime of `db` is never
+// exposed: `db` is only reached through `with`, which lends it for no longer
+// than the borrow of self, and is dropped before `env`.
+#[derive(Debug)]
+pub struct ErigonOwned {
+    // declared before `env` so that it is dropped first
+    db: Erigon<'static, RO>,
+    env: Arc<MdbxEnv<RO>>,
+}
+
+// The tx is meant to be shared across threads, e.g. by a server, so a change
+// to the mdbx tx that makes it !Send or !Sync must fail the build here.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<ErigonOwned>();
+};
+
+impl ErigonOwned {
+    /// Begins a read-only transaction on `env`.
+    pub fn begin(env: Arc<MdbxEnv<RO>>) -> Result<Self> {
+        let db = Erigon::begin(&env)?;
+        // SAFETY: upholds the invariant on the struct: the tx borrows the env
+        // behind the Arc, which is moved into `env` alongside it.
+        let db = unsafe { std::mem::transmute::<Erigon<'_, RO>, Erigon<'static, RO>>(db) };
+        Ok(Self { db, env })
+    }
+
+    /// Begins a new transaction on the same env, which sees everything
+    /// committed up to now.
+    pub fn renew(&self) -> Result<Self> {
+        Self::begin(self.env.clone())
+    }
+
+    pub fn env(&self) -> &Arc<MdbxEnv<RO>> {
+        &self.env
+    }
+
+    /// Calls `f` with the transaction.
+    pub fn with<R>(&self, f: impl for<'a> FnOnce(&'a Erigon<'a, RO>) -> R) -> R {
+        // SAFETY: shortens the lifetime to the borrow of self, for which the
+        // env is alive. The closure cannot keep the tx past it, as it must
+        // accept any lifetime.
+        let db = unsafe { std::mem::transmute::<&Erigon<'static, RO>, &Erigon<'_, RO>>(&self.db) };
+        f(db)
+    }
+}
@@ -0,0 +1,71 @@
+use mdbx::RO;
+use rayon::prelude::*;
+use std::ops::{Range, RangeBounds};
+
+use crate::{
+    erigon::{utils, Erigon},
+    kv::{traits::Mode, MdbxEnv},
+    Result,
+};
+
+// The number of chunks given to each rayon thread by `par_blocks`, so that
+// threads given fast chunks can steal the rest of the work.
+const CHUNKS_PER_THREAD: u64 = 4;
+
+impl<'env> Erigon<'env, RO> {
+    /// Splits a range of blocks into chunks and calls `f` on each chunk from
+    /// the rayon thread pool, returning the results in block order.
+    ///
+    /// Each call gets its own read-only transaction, begun when the chunk
+    /// starts, so chunks may see different commits if the env is written to
+    /// meanwhile. An unbounded range ends at the head block.
+    pub fn par_blocks<M, R, F>(
+        env: &MdbxEnv<M>,
+        range: impl RangeBounds<u64>,
+        f: F,
+    ) -> Result<Vec<R>>
+    where
+        M: Mode + Sync,
+        R: Send,
+        F: Fn(&Erigon<'_, RO>, Range<u64>) -> Result<R> + Sync,
+    {
+        let (start, end) = block_range(env, range)?;
+        let threads = rayon::current_num_threads() as u64;
+        let chunk_size = (end.saturating_sub(start) / (threads * CHUNKS_PER_THREAD)).max(1);
+        Self::par_blocks_chunked(env, start..end, chunk_size, f)
+    }
+
+    /// Like [`Self::par_blocks`], with chunks of `chunk_size` blocks.
+    pub fn par_blocks_chunked<M, R, F>(
+        env: &MdbxEnv<M>,
+        range: impl RangeBounds<u64>,
+        chunk_size: u64,
+        f: F,
+    ) -> Result<Vec<R>>
+    where
+        M: Mode + Sync,
+        R: Send,
+        F: Fn(&Erigon<'_, RO>, Range<u64>) -> Result<R> + Sync,
+    {
+        let (start, end) = block_range(env, range)?;
+        let chunk_size = chunk_size.max(1);
+        let chunks = (start..end)
+            .step_by(chunk_size as usize)
+            .map(|from| from..from.saturating_add(chunk_size).min(end))
+            .collect::<Vec<_>>();
+        chunks
+            .into_par_iter()
+            .map(|chunk| f(&Erigon::new(env.begin_ro()?), chunk))
+            .collect()
+    }
+}
+
+// Resolves the range, ending an unbounded range at the head block.
+fn block_range<M: Mode>(env: &MdbxEnv<M>, range: impl RangeBounds<u64>) -> Result<(u64, u64)> {
+    let (start, end) = utils::block_range(range);
+    if end != u64::MAX {
+        return Ok((start, end));
+    }
+    let head = Erigon::new(env.begin_ro()?).read_head_block_number()?;
+    Ok((start, head.map_or(0, |head| *head + 1)))
+}
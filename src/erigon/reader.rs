@@ -0,0 +1,97 @@
+use ethereum_types::H256;
+
+use crate::{
+    erigon::{models::*, Erigon},
+    kv::traits::Mode,
+    snapshots::Snapshots,
+    Result,
+};
+
+/// Reads blocks from wherever they are stored: the db for recent blocks, and
+/// the frozen snapshot segments for older ones.
+// https://github.com/ledgerwatch/erigon/blob/a1cdbb7be4d5ac3d0fc5a6aa4c1c3bbd4c1ee62c/turbo/snapshotsync/block_reader.go#L214
+#[derive(Debug)]
+pub struct BlockReader<'a, 'env, K: Mode> {
+    db: &'a Erigon<'env, K>,
+    snapshots: &'a Snapshots,
+}
+
+impl<'a, 'env, K: Mode> BlockReader<'a, 'env, K> {
+    pub fn new(db: &'a Erigon<'env, K>, snapshots: &'a Snapshots) -> Self {
+        Self { db, snapshots }
+    }
+
+    /// Returns the hash of the canonical block `num`.
+    pub fn canonical_hash(&self, num: impl Into<BlockNumber>) -> Result<Option<H256>> {
+        let num = num.into();
+        if let Some(hash) = self.db.read_canonical_hash(num)? {
+            return Ok(Some(hash));
+        }
+        Ok(self.snapshots.read_header(*num)?.map(|h| h.hash()))
+    }
+
+    /// Returns the header of the canonical block `num`.
+    pub fn header(&self, num: impl Into<BlockNumber>) -> Result<Option<BlockHeader>> {
+        let num = num.into();
+        if let Some(hash) = self.db.read_canonical_hash(num)? {
+            if let Some(header) = self.db.read_header((num, hash))? {
+                return Ok(Some(header));
+            }
+        }
+        self.snapshots.read_header(*num)
+    }
+
+    /// Returns the header with the given hash. Only canonical headers are
+    /// stored in the snapshots, and the HeaderNumber table is needed to find
+    /// their block number.
+    pub fn header_by_hash(&self, hash: H256) -> Result<Option<BlockHeader>> {
+        let num = match self.db.read_header_number(hash)? {
+            Some(num) => num,
+            None => return Ok(None),
+        };
+        if let Some(header) = self.db.read_header((num, hash))? {
+            return Ok(Some(header));
+        }
+        Ok(self
+            .snapshots
+            .read_header(*num)?
+            .filter(|header| header.hash() == hash))
+    }
+
+    /// Returns the body of the canonical block `num`, excluding system txs.
+    pub fn body(&self, num: impl Into<BlockNumber>) -> Result<Option<BodyForStorage>> {
+        let num = num.into();
+        if let Some(hash) = self.db.read_canonical_hash(num)? {
+            if let Some(body) = self.db.read_body_for_storage((num, hash))? {
+                return Ok(Some(body));
+            }
+        }
+        self.snapshots.read_body_for_storage(*num)
+    }
+
+    /// Returns the canonical block `num`.
+    pub fn block(&self, num: impl Into<BlockNumber>) -> Result<Option<Block>> {
+        let num = num.into();
+        if let Some(block) = self.db.read_block(num)? {
+            return Ok(Some(block));
+        }
+        let header = match self.snapshots.read_header(*num)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let body = match self.snapshots.read_body_for_storage(*num)? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        let transactions = match self.snapshots.read_transactions(*num, &body)? {
+            Some(txs) => txs,
+            None => return Ok(None),
+        };
+        Ok(Some(Block {
+            header,
+            transactions,
+            uncles: body.uncles,
+            withdrawals: body.withdrawals,
+        }))
+    }
+}
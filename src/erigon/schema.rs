@@ -0,0 +1,242 @@
+use crate::{
+    erigon::{erigon3, models::*, tables, Erigon, NUM_TABLES},
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+use bytes::Bytes;
+use mdbx::DatabaseFlags;
+
+/// The max number of named dbs to allow when opening an Erigon 3 env. Erigon 3
+/// registers many more tables than the ones defined in this crate.
+pub const NUM_TABLES_V3: usize = 128;
+
+/// The DbInfo key under which Erigon 2 records whether it keeps its history in
+/// the Erigon 3 format, as a single byte 1 if so.
+pub const HISTORY_V3_KEY: &[u8] = b"history.v3";
+
+/// The layouts of the Erigon db supported by this crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Schema {
+    /// Schema 6, written by Erigon 2. State is kept in PlainState, with
+    /// changesets and history bitmaps keyed by block number.
+    V2,
+    /// Schema 7, written by Erigon 3. State is kept in domains, with history
+    /// and inverted indices keyed by tx_num.
+    V3,
+}
+
+impl Schema {
+    /// Returns the schema for a version read from the db, or
+    /// [`Error::UnsupportedSchema`] if its major version is unknown.
+    pub fn from_version(version: SchemaVersion) -> Result<Self> {
+        match version.major {
+            6 => Ok(Self::V2),
+            7 => Ok(Self::V3),
+            _ => Err(Error::UnsupportedSchema(version)),
+        }
+    }
+
+    /// The max number of named dbs needed to open an env with this schema.
+    pub fn num_tables(&self) -> usize {
+        match self {
+            Self::V2 => NUM_TABLES,
+            Self::V3 => NUM_TABLES_V3,
+        }
+    }
+
+    /// The names of the tables defined by this crate for the schema, as they
+    /// are stored in the db.
+    pub fn tables(&self) -> Vec<&'static str> {
+        match self {
+            Self::V2 => tables::ALL_TABLES.to_vec(),
+            Self::V3 => tables::ALL_TABLES
+                .iter()
+                .filter(|name| !tables::STATE_TABLES_V2.contains(name))
+                .chain(erigon3::tables::ALL_TABLES)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// The names of the tables in [`Self::tables`] that are dupsort.
+    pub fn dupsort_tables(&self) -> Vec<&'static str> {
+        match self {
+            Self::V2 => tables::DUPSORT_TABLES.to_vec(),
+            Self::V3 => tables::DUPSORT_TABLES
+                .iter()
+                .filter(|name| !tables::STATE_TABLES_V2.contains(name))
+                .chain(erigon3::tables::DUPSORT_TABLES)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// How the tables and schema version of a db differ from what this crate
+/// expects, as returned by [`Erigon::check_compat`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompatReport {
+    /// The schema version stored in the DbInfo table, if any.
+    pub version: Option<SchemaVersion>,
+    /// The schema the tables were checked against, or `None` if the version
+    /// is not supported, in which case no tables were checked.
+    pub schema: Option<Schema>,
+    /// Tables of the schema that are not in the db. Erigon creates every table
+    /// when it first opens a db, so these usually point to a db that was not
+    /// written by Erigon, or by one that predates the table.
+    pub missing_tables: Vec<&'static str>,
+    /// Tables whose dupsort flag in the db differs from their definition in
+    /// this crate.
+    pub flag_mismatches: Vec<FlagMismatch>,
+    /// Whether DbInfo marks the db as keeping Erigon 3 history next to its
+    /// PlainState (`--experimental.history.v3`). A [`Schema::V2`] db marked
+    /// so has no changesets or history bitmaps, so reads of past state would
+    /// silently return the latest state.
+    pub history_v3: bool,
+}
+
+/// A table whose dupsort flag in the db differs from its definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlagMismatch {
+    pub table: &'static str,
+    /// Whether the table is dupsort in this crate's definition.
+    pub expected_dupsort: bool,
+}
+
+impl CompatReport {
+    /// Whether the db can be read by this crate: its version is supported,
+    /// no table has the wrong flags and its history is in the format of its
+    /// schema. Missing tables are allowed, as the rest of the db can still be
+    /// read, but reading from one fails.
+    pub fn is_compatible(&self) -> bool {
+        let v3_history_in_v2 = self.schema == Some(Schema::V2) && self.history_v3;
+        self.schema.is_some() && self.flag_mismatches.is_empty() && !v3_history_in_v2
+    }
+}
+
+impl std::fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.version, self.schema) {
+            (Some(version), None) => return write!(f, "unsupported schema version {version}"),
+            (Some(version), Some(schema)) => write!(f, "schema version {version} ({schema:?})")?,
+            (None, _) => write!(f, "no schema version")?,
+        }
+        if !self.missing_tables.is_empty() {
+            write!(f, ", missing tables: {}", self.missing_tables.join(", "))?;
+        }
+        for m in &self.flag_mismatches {
+            let expected = if m.expected_dupsort {
+                "dupsort"
+            } else {
+                "not dupsort"
+            };
+            write!(f, ", table {} should be {expected}", m.table)?;
+        }
+        if self.history_v3 {
+            write!(f, ", history.v3 enabled")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the schema version stored in the DbInfo table.
+    pub fn schema_version(&self) -> Result<Option<SchemaVersion>> {
+        self.read::<tables::DbSchemaVersion>(DbSchemaVersionKey)
+    }
+
+    /// Detects the schema of the db from its version. Dbs written before
+    /// Erigon began recording a version are assumed to use [`Schema::V2`].
+    pub fn schema(&self) -> Result<Schema> {
+        match self.schema_version()? {
+            Some(version) => Schema::from_version(version),
+            None => Ok(Schema::V2),
+        }
+    }
+
+    /// Returns the names of the tables in the env that are not defined by this
+    /// crate for the db's schema, such as those added by a newer Erigon. They
+    /// can only be read by name, with
+    /// [`MdbxTx::open_raw`](crate::kv::MdbxTx::open_raw).
+    pub fn unknown_tables(&self) -> Result<Vec<String>> {
+        let known = self.schema()?.tables();
+        #[cfg(feature = "polygon")]
+        let known = [known, crate::erigon::bor::tables::ALL_TABLES.to_vec()].concat();
+        Ok(self
+            .0
+            .list_tables()?
+            .into_iter()
+            .filter(|name| !known.contains(&name.as_str()))
+            .collect())
+    }
+
+    /// Checks that the db can be read by this crate. The schema version in
+    /// DbInfo selects the tables to expect, each of which is checked for
+    /// presence and for the dupsort flag it was created with, and the history
+    /// format marker in DbInfo is read alongside the version. The check fails
+    /// only on errors reading the db; incompatibilities are returned in the
+    /// report. See [`env_open_checked`](crate::erigon::env_open_checked) to
+    /// run it when opening an env.
+    pub fn check_compat(&self) -> Result<CompatReport> {
+        let present = self.0.list_tables()?;
+        let has = |name: &str| present.iter().any(|p| p == name);
+        // a read-only tx can't open a table that doesn't exist
+        let version = if has(tables::DbSchemaVersion::NAME) {
+            self.schema_version()?
+        } else {
+            None
+        };
+        let schema = match version {
+            Some(version) => Schema::from_version(version).ok(),
+            None => Some(Schema::V2),
+        };
+        let history_v3 = if has(tables::DbInfo::NAME) {
+            let key = Bytes::from_static(HISTORY_V3_KEY);
+            self.read::<tables::DbInfo>(key)?
+                .is_some_and(|val| val[..] == [1])
+        } else {
+            false
+        };
+        let mut report = CompatReport {
+            version,
+            schema,
+            missing_tables: Vec::new(),
+            flag_mismatches: Vec::new(),
+            history_v3,
+        };
+        let schema = match schema {
+            Some(schema) => schema,
+            None => return Ok(report),
+        };
+
+        let dupsort = schema.dupsort_tables();
+        #[cfg(feature = "polygon")]
+        let dupsort = [dupsort, crate::erigon::bor::tables::DUPSORT_TABLES.to_vec()].concat();
+        // Bor tables only exist in the dbs of Polygon chains, so they are
+        // checked if present but not expected.
+        #[cfg(feature = "polygon")]
+        let optional = crate::erigon::bor::tables::ALL_TABLES;
+        #[cfg(not(feature = "polygon"))]
+        let optional: &[&str] = &[];
+
+        for table in schema.tables().into_iter().chain(optional.iter().copied()) {
+            let flags = match self.0.table_flags_by_name(table)? {
+                Some(flags) => flags,
+                None => {
+                    if !optional.contains(&table) {
+                        report.missing_tables.push(table);
+                    }
+                    continue;
+                }
+            };
+            let expected_dupsort = dupsort.contains(&table);
+            if flags.contains(DatabaseFlags::DUP_SORT) != expected_dupsort {
+                report.flag_mismatches.push(FlagMismatch {
+                    table,
+                    expected_dupsort,
+                });
+            }
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,88 @@
+use ethereum_types::{Address, H256, U256};
+
+use crate::{
+    erigon::{models::*, Erigon},
+    kv::traits::Mode,
+    Result,
+};
+
+/// A read-only view of the state as of the end of a given block.
+///
+/// Each read first consults the history tables. If they show no change to the
+/// value after the block, the value is read from the current PlainState.
+#[derive(Debug)]
+pub struct StateAt<'tx, 'env, K: Mode> {
+    db: &'tx Erigon<'env, K>,
+    block: BlockNumber,
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns a view of the state after the given block was executed.
+    pub fn state_at(&self, block: impl Into<BlockNumber>) -> StateAt<'_, 'env, K> {
+        StateAt {
+            db: self,
+            block: block.into(),
+        }
+    }
+}
+
+impl<'tx, 'env, K: Mode> StateAt<'tx, 'env, K> {
+    pub fn block(&self) -> BlockNumber {
+        self.block
+    }
+
+    // The changesets for block n hold the values from before block n was
+    // executed, so the state after our block is found in the changesets of
+    // the first change at or above block + 1.
+    fn timestamp(&self) -> BlockNumber {
+        BlockNumber(self.block.saturating_add(1))
+    }
+
+    /// Returns the account at `adr`, or None if the account did not exist.
+    pub fn account(&self, adr: Address) -> Result<Option<Account>> {
+        match self.db.account_change_block(adr, self.timestamp())? {
+            Some(cs_block) => self.db.read_account_changeset(cs_block, adr),
+            None => self.db.read_account(adr),
+        }
+    }
+
+    /// Returns the value of the storage slot, which is zero if the account or
+    /// slot did not exist.
+    pub fn storage(&self, adr: Address, slot: H256) -> Result<U256> {
+        let inc = match self.account(adr)? {
+            Some(acct) => acct.incarnation,
+            None => return Ok(U256::zero()),
+        };
+        let val = match self.db.storage_change_block(adr, slot, self.timestamp())? {
+            Some(cs_block) => self.db.read_storage_changeset(cs_block, adr, inc, slot)?,
+            None => self.db.read_storage(adr, inc, slot)?,
+        };
+        Ok(val.unwrap_or_default())
+    }
+
+    /// Returns the code deployed at `adr`, which is empty if the account did
+    /// not exist or has no code.
+    pub fn code(&self, adr: Address) -> Result<Bytecode> {
+        match self.account(adr)? {
+            Some(acct) if !acct.codehash.is_zero() => {
+                Ok(self.db.read_code(acct.codehash)?.unwrap_or_default())
+            }
+            _ => Ok(Default::default()),
+        }
+    }
+
+    /// Returns the code with the given hash.
+    pub fn code_by_hash(&self, codehash: H256) -> Result<Option<Bytecode>> {
+        self.db.read_code(codehash)
+    }
+
+    /// Returns the hash of the canonical block `num`, as long as it is not
+    /// after the block this view was created for.
+    pub fn block_hash(&self, num: impl Into<BlockNumber>) -> Result<Option<H256>> {
+        let num = num.into();
+        if num > self.block {
+            return Ok(None);
+        }
+        self.db.read_canonical_hash(num)
+    }
+}
@@ -0,0 +1,80 @@
+//! Per-block statistics of the canonical chain, joined from the header, body
+//! and transaction tables, for writing out as a time series.
+use ethereum_types::U256;
+use serde::Serialize;
+use std::ops::RangeBounds;
+
+use crate::{
+    erigon::{models::*, tables::*, utils, Erigon},
+    error::DecodeError,
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+
+/// The stats of a single block, one row of [`Erigon::chain_timeseries`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct BlockStats {
+    pub number: BlockNumber,
+    pub timestamp: u64,
+    pub tx_count: u64,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+    /// Only present for post-London blocks.
+    pub base_fee: Option<U256>,
+    /// The length of the block's RLP encoding. See [`Block::size`].
+    pub size: u64,
+    pub uncle_count: u64,
+    /// The number of withdrawals. Only present for post-Shanghai blocks.
+    pub withdrawal_count: Option<u64>,
+    /// The total amount withdrawn, in gwei. Only present for post-Shanghai
+    /// blocks.
+    pub withdrawal_amount: Option<u64>,
+}
+
+// Fails if the withdrawal amounts add up to more than a u64 of gwei, which
+// no real block comes close to.
+impl TryFrom<&Block> for BlockStats {
+    type Error = Error;
+    fn try_from(block: &Block) -> Result<Self> {
+        let withdrawals = block.withdrawals.as_deref();
+        let withdrawal_amount = withdrawals
+            .map(|ws| {
+                ws.iter()
+                    .try_fold(0u64, |sum, w| sum.checked_add(w.amount))
+                    .ok_or_else(|| DecodeError::custom("withdrawal amounts overflow u64"))
+            })
+            .transpose()?;
+        Ok(Self {
+            number: BlockNumber(block.header.number.low_u64()),
+            timestamp: block.header.time,
+            tx_count: block.transactions.len() as u64,
+            gas_used: block.header.gas_used,
+            gas_limit: block.header.gas_limit,
+            base_fee: block.header.base_fee,
+            size: block.size() as u64,
+            uncle_count: block.uncles.len() as u64,
+            withdrawal_count: withdrawals.map(|ws| ws.len() as u64),
+            withdrawal_amount,
+        })
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns an iterator over the stats of each canonical block in the
+    /// range, in block order.
+    pub fn chain_timeseries(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> Result<impl Iterator<Item = Result<BlockStats>> + '_> {
+        let (start, end) = utils::block_range(range);
+        Ok(self
+            .cursor::<CanonicalHeader>()?
+            .walk(BlockNumber(start))?
+            .take_while(move |res| res.as_ref().map_or(true, |(k, _)| **k < end))
+            .map(move |res| {
+                let (num, _) = res?;
+                let block = self.read_block(num)?.ok_or(Error::NotFound(Header::NAME))?;
+                BlockStats::try_from(&block)
+            }))
+    }
+}
@@ -0,0 +1,82 @@
+use ethereum_types::U256;
+use std::ops::RangeBounds;
+
+use crate::{
+    erigon::{models::*, tables::*, utils, Erigon},
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+
+/// The change in the ETH supply made by a block, as recorded by Erigon's
+/// Issuance stage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SupplyChange {
+    pub block: BlockNumber,
+    /// The ETH issued by the block, as block and uncle rewards.
+    pub issued: U256,
+    /// The ETH burnt by the block, as base fees.
+    pub burnt: U256,
+}
+
+impl SupplyChange {
+    /// Returns the net change in supply, `issued - burnt`, which is negative
+    /// when the block burnt more than it issued, or None if either amount
+    /// does not fit in an `i128`.
+    pub fn delta(&self) -> Option<i128> {
+        let to_i128 = |v: U256| u128::try_from(v).ok().and_then(|v| i128::try_from(v).ok());
+        to_i128(self.issued)?.checked_sub(to_i128(self.burnt)?)
+    }
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Returns the total ETH issued up to and including `block`.
+    pub fn read_issuance(&self, block: impl Into<BlockNumber>) -> Result<Option<U256>> {
+        self.read::<Issuance>(block.into())
+    }
+
+    /// Returns the total ETH burnt up to and including `block`.
+    pub fn read_burnt(&self, block: impl Into<BlockNumber>) -> Result<Option<U256>> {
+        self.read::<Burnt>(BurntKey(block.into()))
+    }
+
+    /// Returns an iterator over the change in supply made by each block in
+    /// the range, ending at the last block the Issuance stage has processed.
+    pub fn walk_supply(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> Result<impl Iterator<Item = Result<SupplyChange>> + '_> {
+        let (start, end) = utils::block_range(range);
+        // the totals before the first block of the range
+        let mut prev = match start.checked_sub(1) {
+            Some(block) => self.read_supply_totals(BlockNumber(block))?,
+            None => Some(Default::default()),
+        };
+        Ok((start..end).map(BlockNumber).map_while(move |block| {
+            let (issued, burnt) = match self.read_supply_totals(block) {
+                Ok(Some(totals)) => totals,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            let (prev_issued, prev_burnt) = match prev.replace((issued, burnt)) {
+                Some(totals) => totals,
+                None => return Some(Err(Error::NotFound(Issuance::NAME))),
+            };
+            Some(Ok(SupplyChange {
+                block,
+                issued: issued.saturating_sub(prev_issued),
+                burnt: burnt.saturating_sub(prev_burnt),
+            }))
+        }))
+    }
+
+    // Returns the totals issued and burnt up to and including `block`, if the
+    // Issuance stage has processed it.
+    fn read_supply_totals(&self, block: BlockNumber) -> Result<Option<(U256, U256)>> {
+        let issued = match self.read_issuance(block)? {
+            Some(issued) => issued,
+            None => return Ok(None),
+        };
+        let burnt = self.read_burnt(block)?.unwrap_or_default();
+        Ok(Some((issued, burnt)))
+    }
+}
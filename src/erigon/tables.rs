@@ -1,85 +1,152 @@
-use crate::{dupsort_table, erigon::models::*, table};
+use crate::{erigon::models::*, table_set};
 use bytes::Bytes;
 use ethereum_types::{Address, H256, U256};
-use roaring::RoaringTreemap;
+use roaring::{RoaringBitmap, RoaringTreemap};
 
 // --- Erigon db schema version 6.0.0 ---
 
 // || indicates concatenation
 
-// Table name                   => Key          => Value
-// key: bytes("LastHeader"). val: hash of current canonical head header. erigon: HeadHeaderKey
-table!(LastHeader               => LastHeaderKey => H256);
-// key: bytes("LastBlock"). val: hash of current canonical head block. erigon: HeadBlockKey
-table!(LastBlock                => LastBlockKey => H256);
-// key: address. val: incarnation of account when it was last deleted
-table!(IncarnationMap           => Address      => Incarnation);
-// key: tx_hash. val: blocknum containing the tx. erigon: TxLookup
-table!(BlockTransactionLookup   => H256         => U256);
-// key: header_hash. val: blocknum
-table!(HeaderNumber             => H256         => BlockNumber);
-// key: blocknum||blockhash. val: rlp(header). erigon: Headers
-table!(Header                   => HeaderKey    => BlockHeader, seek_key = BlockNumber);
-// key: blocknum||blockhash. val: encode(block_body)
-table!(BlockBody                => HeaderKey    => BodyForStorage, seek_key = BlockNumber);
-// key: address||incarnation. val: code_hash. erigon: PlainContractCode
-table!(PlainCodeHash            => PlainCodeKey => H256);
-// key: blocknum||blockhash. val: senders list. erigon: Senders
-table!(TxSender                 => HeaderKey    => Vec<Address>);
-// key: blocknum. val: blockhash. erigon: HeaderCanonical
-table!(CanonicalHeader          => BlockNumber  => H256);
-// key: index. val: rlp(tx). transaction. erigon: EthTx
-table!(BlockTransaction         => TxIndex      => Transaction);
-// key: index. val: rlp(tx). erigon: NonCanonicalTxs
-table!(NonCanonicalTransaction  => TxIndex      => Transaction);
-// key: address||shard_id_u64. val: bitmap of blocks w/ change. erigon: AccountsHistory
-table!(AccountHistory           => AccountHistKey => RoaringTreemap);
-// key: address||slot||shard_id_u64. val: bitmap of blocks w/ change.
-table!(StorageHistory           => StorageHistKey => RoaringTreemap);
-// key: blocknum. val: address||encode(account)
-dupsort_table!(AccountChangeSet => BlockNumber  => AccountCSVal, subkey = Address);
-// key: blocknum||address||incarnation. val: slot||slot_value
-dupsort_table!(StorageChangeSet => StorageCSKey => StorageCSVal, subkey = H256);
-// key: address. val: encode(account). PlainState table also contains Storage.
-table!(PlainState               => Address      => Account);
-// key: address||incarnation. val: slot||slot_value (dupsorted). erigon: PlainState
-dupsort_table!(
-    Storage => StorageKey => (H256, U256),
-    subkey = H256,
-    rename = PlainState
-);
+table_set! {
+    /// The names of tables in this set, as they are stored in the db.
+    /// Tables that share a db (e.g. Storage and PlainState) are listed once.
+    /// These are the tables of schema 6, used by Erigon 2.
+    pub const ALL_TABLES;
+    /// The tables in [`ALL_TABLES`] that are dupsort.
+    pub const DUPSORT_TABLES;
 
-// key: keccak(address). val: encode(account). erigon: HashedAcccounts
-table!(HashedAccount            => H256             => Account);
-//TODO: also dupsorted
-// key: keccak(address)||incarnation||keccak(slot). val: slot_value
-table!(HashedStorage            => HashStorageKey   => U256);
-// key: code_hash. val: contract code
-table!(Code                     => H256             => Bytecode);
-// key: keccak256(address)||incarnation. val: code_hash. erigon: ContractCode
-table!(HashedCodeHash           => ContractCodeKey  => H256);
-// key: bytestring. val: bytestring. erigon: DatabaseInfo
-table!(DbInfo                   => Bytes            => Bytes);
-// key: blocknum||blockhash. val: rlp(total_difficulty big.Int). erigon: HeaderTD
-table!(HeadersTotalDifficulty   => HeaderKey        => TotalDifficulty);
-// key: blocknum. val: total_issued
-table!(Issuance                 => BlockNumber      => U256);
-// key: bytes("burnt")||bloknum. val: total_burnt. erigon: Issuance
-table!(Burnt                    => BurntKey         => U256, rename = Issuance);
-// key: code_hash. value: contract_TEVM_code. erigon: ContractTEVMCode. Unused.
-table!(TEVMCode                 => H256             => Bytes);
-// receipts are only stored for canonical blocks
-// key: blocknum. val: cbor(receipt). erigon: Receipts
-table!(Receipt                  => BlockNumber      => CborReceipts);
-// key: blocknum||log_index_in_tx. val: cbor(log). erigon: Log
-table!(TransactionLog           => LogsKey          => CborLogs);
+    // Table name                   => Key          => Value
+    // key: bytes("LastHeader"). val: hash of current canonical head header. erigon: HeadHeaderKey
+    table!(LastHeader               => LastHeaderKey => H256);
+    // key: bytes("LastBlock"). val: hash of current canonical head block. erigon: HeadBlockKey
+    table!(LastBlock                => LastBlockKey => H256);
+    // key: address. val: incarnation of account when it was last deleted
+    table!(IncarnationMap           => Address      => Incarnation);
+    // key: tx_hash. val: blocknum containing the tx. erigon: TxLookup
+    table!(BlockTransactionLookup   => H256         => U256);
+    // key: header_hash. val: blocknum
+    table!(HeaderNumber             => H256         => BlockNumber);
+    // key: blocknum||blockhash. val: rlp(header). erigon: Headers
+    table!(Header                   => HeaderKey    => BlockHeader, seek_key = BlockNumber);
+    // key: blocknum||blockhash. val: encode(block_body)
+    table!(BlockBody                => HeaderKey    => BodyForStorage, seek_key = BlockNumber);
+    // key: address||incarnation. val: code_hash. erigon: PlainContractCode
+    table!(PlainCodeHash            => PlainCodeKey => H256);
+    // key: blocknum||blockhash. val: senders list. erigon: Senders
+    table!(TxSender                 => HeaderKey    => Vec<Address>);
+    // key: blocknum. val: blockhash. erigon: HeaderCanonical
+    table!(CanonicalHeader          => BlockNumber  => H256);
+    // key: index. val: rlp(tx). transaction. erigon: EthTx
+    table!(BlockTransaction         => TxIndex      => Transaction);
+    // key: index. val: rlp(tx). erigon: NonCanonicalTxs
+    table!(NonCanonicalTransaction  => TxIndex      => Transaction);
+    // key: bytes("BlockTransaction"). val: the next tx id to allocate. erigon: Sequence
+    table!(TxSequence               => TxSequenceKey => TxIndex, rename = Sequence);
+    // key: address||shard_id_u64. val: bitmap of blocks w/ change. erigon: AccountsHistory
+    table!(AccountHistory           => AccountHistKey => RoaringTreemap);
+    // key: address||slot||shard_id_u64. val: bitmap of blocks w/ change.
+    table!(StorageHistory           => StorageHistKey => RoaringTreemap);
+    // key: blocknum. val: address||encode(account)
+    dupsort_table!(
+        AccountChangeSet => BlockNumber => AccountCSVal,
+        subkey = Address,
+        subvalue = Option<Account>
+    );
+    // key: blocknum||address||incarnation. val: slot||slot_value
+    dupsort_table!(
+        StorageChangeSet => StorageCSKey => StorageCSVal,
+        subkey = H256,
+        subvalue = U256
+    );
+    // key: address. val: encode(account). PlainState table also contains Storage.
+    table!(PlainState               => Address      => Account);
+    // key: address||incarnation. val: slot||slot_value (dupsorted). erigon: PlainState
+    dupsort_table!(
+        Storage => StorageKey => (H256, U256),
+        subkey = H256,
+        subvalue = U256,
+        rename = PlainState
+    );
 
-type Todo = Bytes;
-// erigon: TrieOfAccounts
-table!(TrieAccount => Todo => Todo);
-// erigon: TrieOfStorage
-table!(TrieStorage => Todo => Todo);
-table!(LogTopicIndex => Todo => Todo);
-table!(LogAddressIndex => Todo => Todo);
-// key: blocknum||address.
-dupsort_table!(CallTraceSet => Todo => Todo, subkey = Todo);
+    // key: keccak(address). val: encode(account). erigon: HashedAcccounts
+    table!(HashedAccount            => H256             => Account);
+    // key: keccak(address)||incarnation. val: keccak(slot)||slot_value (dupsorted)
+    dupsort_table!(
+        HashedStorage => HashStorageKey => (H256, U256),
+        subkey = H256,
+        subvalue = U256
+    );
+    // key: code_hash. val: contract code
+    table!(Code                     => H256             => Bytecode);
+    // key: keccak256(address)||incarnation. val: code_hash. erigon: ContractCode
+    table!(HashedCodeHash           => ContractCodeKey  => H256);
+    // key: bytestring. val: bytestring. erigon: DatabaseInfo
+    table!(DbInfo                   => Bytes            => Bytes);
+    // key: bytes("dbVersion"). val: major_u32||minor_u32||patch_u32. erigon: DatabaseInfo
+    table!(DbSchemaVersion          => DbSchemaVersionKey => SchemaVersion, rename = DbInfo);
+    // key: blocknum||blockhash. val: rlp(total_difficulty big.Int). erigon: HeaderTD
+    table!(HeadersTotalDifficulty   => HeaderKey        => TotalDifficulty);
+    // key: blocknum. val: total_issued
+    table!(Issuance                 => BlockNumber      => U256);
+    // key: bytes("burnt")||bloknum. val: total_burnt. erigon: Issuance
+    table!(Burnt                    => BurntKey         => U256, rename = Issuance);
+    // key: code_hash. value: contract_TEVM_code. erigon: ContractTEVMCode. Unused.
+    table!(TEVMCode                 => H256             => Bytes);
+    // receipts are only stored for canonical blocks
+    // key: blocknum. val: cbor(receipt). erigon: Receipts
+    table!(Receipt                  => BlockNumber      => CborReceipts);
+    // key: blocknum||tx_index. val: cbor(logs). erigon: Log
+    table!(TransactionLog           => LogsKey          => CborLogs);
+    // key: topic||shard_max_block_u32. val: bitmap of blocks w/ a log containing the topic
+    table!(LogTopicIndex            => LogTopicIndexKey => RoaringBitmap);
+    // key: address||shard_max_block_u32. val: bitmap of blocks w/ a log emitted by the address
+    table!(LogAddressIndex          => LogAddressIndexKey => RoaringBitmap);
+
+    // key: blocknum. val: address||flags (dupsorted). flags: 1 if sender, 2 if receiver
+    dupsort_table!(CallTraceSet     => BlockNumber      => CallTrace, subkey = Address, subvalue = u8);
+    // key: address||shard_max_block_u32. val: bitmap of blocks w/ a call from the address
+    table!(CallFromIndex            => CallIndexKey     => RoaringBitmap);
+    // key: address||shard_max_block_u32. val: bitmap of blocks w/ a call to the address
+    table!(CallToIndex              => CallIndexKey     => RoaringBitmap);
+
+    // key: stage name. val: highest blocknum processed by the stage. erigon: SyncStage
+    table!(SyncStage                => StageId          => BlockNumber);
+    // key: stage name. val: stage-specific progress data. erigon: SyncStageProgress
+    table!(SyncStageProgress        => StageId          => Bytes);
+
+    // key: genesis_hash. val: json(chain_config). erigon: Config
+    table!(Config                   => H256             => ChainConfig);
+
+    // key: blocknum||blockhash. val: rlp(epoch_transition_proof). erigon: Epoch
+    table!(DevEpoch                 => HeaderKey        => Bytes, seek_key = BlockNumber);
+    // key: blocknum||blockhash. val: rlp(epoch_transition_proof). erigon: PendingEpoch
+    table!(DevPendingEpoch          => HeaderKey        => Bytes, seek_key = BlockNumber);
+
+    // key: nibbles. val: encode(trie_node). erigon: TrieOfAccounts
+    table!(TrieAccount              => Nibbles          => TrieNode);
+    // key: keccak(address)||incarnation||nibbles. val: encode(trie_node). erigon: TrieOfStorage
+    table!(TrieStorage              => TrieStorageKey   => TrieNode);
+}
+
+/// The tables in [`ALL_TABLES`] that hold state or state indices, which
+/// Erigon 3 replaces with the tables in
+/// [`erigon3::tables::ALL_TABLES`](crate::erigon::erigon3::tables::ALL_TABLES).
+pub const STATE_TABLES_V2: &[&str] = &[
+    "IncarnationMap",
+    "PlainCodeHash",
+    "AccountHistory",
+    "StorageHistory",
+    "AccountChangeSet",
+    "StorageChangeSet",
+    "PlainState",
+    "HashedAccount",
+    "HashedStorage",
+    "HashedCodeHash",
+    "TrieAccount",
+    "TrieStorage",
+    "LogTopicIndex",
+    "LogAddressIndex",
+    "CallTraceSet",
+    "CallFromIndex",
+    "CallToIndex",
+];
@@ -0,0 +1,78 @@
+use ethereum_types::H256;
+use revm::{db::CacheDB, ExecutionResult, Inspector, EVM};
+
+use crate::{
+    erigon::{
+        evm::{tx_env, KeepError},
+        models::*,
+        state::StateAt,
+        tables::*,
+        Erigon,
+    },
+    kv::traits::{DbName, Mode},
+    Error, Result,
+};
+
+/// The database that transactions are re-executed against: the state at the
+/// end of the parent block, plus the changes made by earlier txs in the block.
+pub type TraceDb<'tx, 'env, K> = CacheDB<KeepError<StateAt<'tx, 'env, K>>>;
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Re-executes the transaction with the given hash, running `inspector`
+    /// over its execution. Returns `None` if the transaction is not found.
+    pub fn trace_transaction<'tx, I>(
+        &'tx self,
+        hash: H256,
+        inspector: I,
+    ) -> Result<Option<ExecutionResult>>
+    where
+        I: Inspector<TraceDb<'tx, 'env, K>>,
+    {
+        match self.read_transaction_by_hash(hash)? {
+            Some((_, num, index)) => self.trace_block_transaction(num, index, inspector),
+            None => Ok(None),
+        }
+    }
+
+    /// Re-executes the transaction at `index` in the canonical block `num`,
+    /// running `inspector` over its execution. The preceding transactions in
+    /// the block are executed first, without the inspector. Returns `None` if
+    /// the block or the transaction is not found.
+    pub fn trace_block_transaction<'tx, I>(
+        &'tx self,
+        num: impl Into<BlockNumber>,
+        index: usize,
+        inspector: I,
+    ) -> Result<Option<ExecutionResult>>
+    where
+        I: Inspector<TraceDb<'tx, 'env, K>>,
+    {
+        let num = num.into();
+        let block = match self.read_block(num)? {
+            Some(block) if index < block.transactions.len() => block,
+            _ => return Ok(None),
+        };
+        let env = self.block_env(num)?.ok_or(Error::NotFound(Header::NAME))?;
+        // every block with transactions has a parent
+        let parent = BlockNumber(num.saturating_sub(1));
+
+        let mut evm = EVM::new();
+        evm.env = env;
+        evm.database(CacheDB::new(KeepError::new(self.state_at(parent))));
+        let db_error =
+            |evm: &EVM<TraceDb<'tx, 'env, K>>| evm.db.as_ref().and_then(|db| db.db.take_error());
+        for tx in &block.transactions[..index] {
+            evm.env.tx = tx_env(tx);
+            evm.transact_commit();
+            if let Some(e) = db_error(&evm) {
+                return Err(e);
+            }
+        }
+        evm.env.tx = tx_env(&block.transactions[index]);
+        let res = evm.inspect_commit(inspector);
+        match db_error(&evm) {
+            Some(e) => Err(e),
+            None => Ok(Some(res)),
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Merkle Patricia trie roots, computed from Erigon's hashed state tables.
+//!
+//! Roots and proofs are always computed from scratch by walking HashedAccount
+//! and HashedStorage in order. The intermediate hashes in TrieOfAccounts and
+//! TrieOfStorage are not used, so this reads the whole state and is slow on
+//! large dbs.
+use bytes::{Bytes, BytesMut};
+use ethereum_types::{Address, H256, U256};
+use std::collections::BTreeMap;
+
+use crate::{
+    erigon::{
+        models::{Account, BlockNumber, Incarnation, StageId},
+        tables::{HashedAccount, SyncStage},
+        utils::{consts::*, keccak256},
+        Erigon,
+    },
+    kv::traits::{DbName, Mode},
+    rlp::{self, rlp_struct, Encodable},
+    Error, Result,
+};
+
+// The rlp of an empty string, which stands in for missing children.
+const EMPTY_STRING: u8 = 0x80;
+
+/// Builds the root of a trie from leaves added in increasing key order.
+///
+/// Only the nodes along the path of the last added key are kept in memory,
+/// so the leaves can be streamed straight from a table. All keys are 32
+/// bytes, so no key is a prefix of another and branches never hold values.
+#[derive(Debug, Default)]
+pub struct HashBuilder {
+    // the last added leaf, not yet attached to a branch
+    prev: Option<([u8; 64], Vec<u8>)>,
+    // the open branches above `prev`, from the root down
+    stack: Vec<Branch>,
+    // the keys to retain proofs for, and the nodes on their paths
+    targets: Vec<[u8; 64]>,
+    proof_nodes: BTreeMap<Vec<u8>, Bytes>,
+}
+
+#[derive(Debug)]
+struct Branch {
+    // the index of the nibble that selects a child of the branch
+    depth: usize,
+    children: [Option<Vec<u8>>; 16],
+}
+
+// A finished subtree that contains the last added key.
+enum Subtree {
+    Leaf(Vec<u8>),
+    Branch(Branch),
+}
+
+impl HashBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retains the nodes needed to prove the given keys, which may or may not
+    /// be in the trie.
+    pub fn with_proofs(mut self, keys: impl IntoIterator<Item = H256>) -> Self {
+        self.targets = keys.into_iter().map(to_nibbles).collect();
+        self
+    }
+
+    /// Adds a leaf holding `value`, the rlp of the leaf's value. Keys must be
+    /// added in strictly increasing order.
+    pub fn add_leaf(&mut self, key: H256, value: Vec<u8>) {
+        let key = to_nibbles(key);
+        let (prev, prev_value) = match self.prev.replace((key, value)) {
+            Some(prev) => prev,
+            None => return,
+        };
+        assert!(prev < key, "trie keys must be added in increasing order");
+        let common = prev.iter().zip(&key).take_while(|(a, b)| a == b).count();
+
+        // close the branches below the point where the new key diverges
+        let mut sub = Subtree::Leaf(prev_value);
+        while let Some(top) = self.stack.last() {
+            if top.depth < common {
+                break;
+            }
+            let depth = top.depth;
+            let child = node_ref(self.encode(sub, &prev, depth + 1));
+            let top = self.stack.last_mut().unwrap();
+            top.children[prev[depth] as usize] = Some(child);
+            if depth == common {
+                return;
+            }
+            sub = Subtree::Branch(self.stack.pop().unwrap());
+        }
+        let mut branch = Branch {
+            depth: common,
+            children: Default::default(),
+        };
+        branch.children[prev[common] as usize] =
+            Some(node_ref(self.encode(sub, &prev, common + 1)));
+        self.stack.push(branch);
+    }
+
+    /// Returns the root of the trie, the hash of the empty trie if no leaves
+    /// were added.
+    pub fn root(mut self) -> H256 {
+        self.finish()
+    }
+
+    /// Returns the root of the trie along with a proof for each key passed to
+    /// [`with_proofs`](Self::with_proofs), in the same order. A proof lists
+    /// the rlp of the nodes on the key's path, from the root down, leaving
+    /// out nodes that are embedded in their parent.
+    pub fn root_with_proofs(mut self) -> (H256, Vec<Vec<Bytes>>) {
+        let root = self.finish();
+        let proofs = self
+            .targets
+            .iter()
+            .map(|target| {
+                self.proof_nodes
+                    .iter()
+                    .filter(|(path, _)| target.starts_with(path))
+                    .map(|(_, node)| node.clone())
+                    .collect()
+            })
+            .collect();
+        (root, proofs)
+    }
+
+    fn finish(&mut self) -> H256 {
+        let (prev, prev_value) = match self.prev.take() {
+            Some(prev) => prev,
+            None => return EMPTY_ROOT,
+        };
+        let mut sub = Subtree::Leaf(prev_value);
+        while let Some(mut top) = self.stack.pop() {
+            top.children[prev[top.depth] as usize] =
+                Some(node_ref(self.encode(sub, &prev, top.depth + 1)));
+            sub = Subtree::Branch(top);
+        }
+        keccak256(self.encode(sub, &prev, 0)).into()
+    }
+
+    // Encodes the subtree as a node whose path begins at nibble `start` of
+    // `key`, with an extension node above a branch that begins deeper.
+    fn encode(&mut self, sub: Subtree, key: &[u8; 64], start: usize) -> Vec<u8> {
+        let node = match sub {
+            Subtree::Leaf(value) => {
+                let path = hex_prefix(&key[start..], true);
+                encode_list(&[encode_bytes(&path), encode_bytes(&value)])
+            }
+            Subtree::Branch(branch) => {
+                let mut items = branch
+                    .children
+                    .iter()
+                    .map(|child| child.clone().unwrap_or_else(|| vec![EMPTY_STRING]))
+                    .collect::<Vec<_>>();
+                items.push(vec![EMPTY_STRING]);
+                let node = encode_list(&items);
+                if branch.depth == start {
+                    node
+                } else {
+                    self.retain(&key[..branch.depth], &node);
+                    let path = hex_prefix(&key[start..branch.depth], false);
+                    encode_list(&[encode_bytes(&path), node_ref(node)])
+                }
+            }
+        };
+        self.retain(&key[..start], &node);
+        node
+    }
+
+    // Keeps the node at `path` if it is on the path of a target key. Nodes
+    // embedded in their parent are skipped, as they are part of its rlp.
+    fn retain(&mut self, path: &[u8], node: &[u8]) {
+        let embedded = !path.is_empty() && node.len() < KECCAK_LENGTH;
+        if !embedded && self.targets.iter().any(|t| t.starts_with(path)) {
+            self.proof_nodes
+                .insert(path.to_vec(), Bytes::copy_from_slice(node));
+        }
+    }
+}
+
+fn to_nibbles(key: H256) -> [u8; 64] {
+    let mut out = [0; 64];
+    for (i, b) in key.as_bytes().iter().enumerate() {
+        out[2 * i] = b >> 4;
+        out[2 * i + 1] = b & 0x0f;
+    }
+    out
+}
+
+// The compact encoding of a path, with a flag for leaves and odd lengths.
+fn hex_prefix(nibbles: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 0x20 } else { 0 };
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        out.push(flag | 0x10 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag);
+        nibbles
+    };
+    out.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    out
+}
+
+// Nodes shorter than a hash are embedded in their parent, others are
+// referenced by hash.
+fn node_ref(node: Vec<u8>) -> Vec<u8> {
+    if node.len() < KECCAK_LENGTH {
+        node
+    } else {
+        encode_bytes(&keccak256(node))
+    }
+}
+
+fn encode_bytes(b: &[u8]) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    b.encode(&mut out);
+    out.to_vec()
+}
+
+// Wraps already encoded items in a list.
+fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload_length = items.iter().map(Vec::len).sum();
+    let mut out = BytesMut::new();
+    rlp::Header {
+        list: true,
+        payload_length,
+    }
+    .encode(&mut out);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out.to_vec()
+}
+
+// An account as stored in the leaves of the state trie.
+rlp_struct! {
+    struct AccountLeaf {
+        nonce: u64,
+        balance: U256,
+        storage_root: H256,
+        code_hash: H256,
+    }
+}
+
+/// Returns the rlp of an account as stored in the state trie.
+pub fn account_rlp(acct: &Account, storage_root: H256) -> Vec<u8> {
+    let leaf = AccountLeaf {
+        nonce: acct.nonce,
+        balance: acct.balance,
+        storage_root,
+        code_hash: code_hash(acct),
+    };
+    let mut out = BytesMut::new();
+    leaf.encode(&mut out);
+    out.to_vec()
+}
+
+// Erigon leaves the codehash of accounts without code zeroed.
+fn code_hash(acct: &Account) -> H256 {
+    if acct.codehash.is_zero() {
+        EMPTY_HASH
+    } else {
+        acct.codehash
+    }
+}
+
+/// Returns the rlp of a storage value as stored in a storage trie.
+pub fn storage_rlp(val: U256) -> Vec<u8> {
+    let mut out = BytesMut::new();
+    val.encode(&mut out);
+    out.to_vec()
+}
+
+/// An account with a proof of its value against the state root, as returned
+/// by `eth_getProof` (EIP-1186). An account missing from the state has zero
+/// fields, with the hashes of empty code and of an empty storage trie.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountProof {
+    pub address: Address,
+    pub balance: U256,
+    pub code_hash: H256,
+    pub nonce: u64,
+    pub storage_hash: H256,
+    pub account_proof: Vec<Bytes>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// A storage slot with a proof of its value against the account's storage
+/// root.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageProof {
+    pub key: H256,
+    pub value: U256,
+    pub proof: Vec<Bytes>,
+}
+
+impl<'env, K: Mode> Erigon<'env, K> {
+    /// Computes the storage root of the account with the given hashed
+    /// address and incarnation from the HashedStorage table.
+    pub fn storage_root(&self, hashed_adr: H256, inc: impl Into<Incarnation>) -> Result<H256> {
+        let builder = self.build_storage(hashed_adr, inc.into(), HashBuilder::new(), |_, _| ())?;
+        Ok(builder.root())
+    }
+
+    /// Computes the state root from the HashedAccount and HashedStorage
+    /// tables. The result matches the `root` of the header at the block
+    /// reached by the HashState stage.
+    pub fn state_root(&self) -> Result<H256> {
+        Ok(self.build_state(HashBuilder::new(), None)?.root())
+    }
+
+    /// Returns the account at `adr` and the given storage slots, with proofs
+    /// of their values against the state root at `block`. As the hashed state
+    /// only holds the latest state, `block` must be the block reached by the
+    /// HashState stage.
+    pub fn get_proof(
+        &self,
+        adr: Address,
+        slots: &[H256],
+        block: impl Into<BlockNumber>,
+    ) -> Result<AccountProof> {
+        let block = block.into();
+        let hashed_to = self
+            .read_stage_progress(StageId::HashState)?
+            .ok_or(Error::NotFound(SyncStage::NAME))?;
+        if block != hashed_to {
+            return Err(Error::StateUnavailable { block, hashed_to });
+        }
+
+        let hashed_adr = H256(keccak256(adr));
+        let acct = self.read::<HashedAccount>(hashed_adr)?;
+        let inc = acct.map(|acct| acct.incarnation).unwrap_or_default();
+        let hashed_slots = slots.iter().map(|slot| H256(keccak256(slot)));
+        let mut values = BTreeMap::new();
+        let builder = self.build_storage(
+            hashed_adr,
+            inc,
+            HashBuilder::new().with_proofs(hashed_slots.clone()),
+            |hashed_slot, val| {
+                values.insert(hashed_slot, val);
+            },
+        )?;
+        let (storage_hash, proofs) = builder.root_with_proofs();
+        let storage_proof = slots
+            .iter()
+            .zip(hashed_slots)
+            .zip(proofs)
+            .map(|((&key, hashed_slot), proof)| StorageProof {
+                key,
+                value: values.get(&hashed_slot).copied().unwrap_or_default(),
+                proof,
+            })
+            .collect();
+
+        let builder = HashBuilder::new().with_proofs([hashed_adr]);
+        let (_, mut proofs) = self
+            .build_state(builder, Some((hashed_adr, storage_hash)))?
+            .root_with_proofs();
+        let acct = acct.unwrap_or_default();
+        Ok(AccountProof {
+            address: adr,
+            balance: acct.balance,
+            code_hash: code_hash(&acct),
+            nonce: acct.nonce,
+            storage_hash,
+            account_proof: proofs.pop().unwrap_or_default(),
+            storage_proof,
+        })
+    }
+
+    // Adds every account in HashedAccount to the builder. The storage root of
+    // the account at `known.0`, if given, is taken to be `known.1`.
+    fn build_state(
+        &self,
+        mut builder: HashBuilder,
+        known: Option<(H256, H256)>,
+    ) -> Result<HashBuilder> {
+        for read in self.cursor::<HashedAccount>()?.walk(H256::zero())? {
+            let (hashed_adr, acct) = read?;
+            let storage_root = match known {
+                Some((adr, root)) if adr == hashed_adr => root,
+                _ if *acct.incarnation > 0 => self.storage_root(hashed_adr, acct.incarnation)?,
+                _ => EMPTY_ROOT,
+            };
+            builder.add_leaf(hashed_adr, account_rlp(&acct, storage_root));
+        }
+        Ok(builder)
+    }
+
+    // Adds the account's storage in HashedStorage to the builder, passing
+    // each hashed slot and value to `visit`.
+    fn build_storage(
+        &self,
+        hashed_adr: H256,
+        inc: Incarnation,
+        mut builder: HashBuilder,
+        mut visit: impl FnMut(H256, U256),
+    ) -> Result<HashBuilder> {
+        if *inc == 0 {
+            return Ok(builder);
+        }
+        for res in self.walk_hashed_storage(hashed_adr, inc)? {
+            let (slot, val) = res?;
+            visit(slot, val);
+            builder.add_leaf(slot, storage_rlp(val));
+        }
+        Ok(builder)
+    }
+}
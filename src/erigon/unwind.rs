@@ -0,0 +1,174 @@
+use ethereum_types::{Address, H256};
+use mdbx::RW;
+use roaring::RoaringTreemap;
+use std::collections::BTreeSet;
+
+use crate::{
+    erigon::{models::*, tables::*, Erigon},
+    Result,
+};
+
+impl<'env> Erigon<'env, RW> {
+    /// Reverts the db to the state it was in after `block`, mirroring the
+    /// unwind of Erigon's staged sync:
+    /// - PlainState is restored from the account and storage changesets of the
+    /// later blocks, and those changesets are deleted.
+    /// - Blocks after `block` are removed from the account and storage history
+    /// indices.
+    /// - The canonical headers, bodies, transactions and senders of the later
+    /// blocks are deleted, and the head pointers are moved back to `block`.
+    /// - The progress of any stage past `block` is set to `block`.
+    ///
+    /// Receipts, logs, call traces, tx lookups and their indices are left as
+    /// they are, as are the hashed state and trie tables.
+    pub fn unwind_to(&self, block: impl Into<BlockNumber>) -> Result<()> {
+        let block = block.into();
+        let from = *block + 1;
+        self.unwind_accounts(from)?;
+        self.unwind_storage(from)?;
+        self.unwind_blocks(from)?;
+
+        if let Some(hash) = self.read_canonical_hash(block)? {
+            self.write_head_header_hash(hash)?;
+            self.write_head_block_hash(hash)?;
+        }
+        for &stage in StageId::ALL {
+            match self.read::<SyncStage>(stage)? {
+                Some(progress) if progress > block => self.write::<SyncStage>(stage, block)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // Restores accounts changed in blocks >= `from`, newest block first, so
+    // that each account ends up with its value from before the earliest one.
+    fn unwind_accounts(&self, from: u64) -> Result<()> {
+        let changes = self
+            .cursor::<AccountChangeSet>()?
+            .iter(BlockNumber(from))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut touched = BTreeSet::new();
+        for (_, AccountCSVal(adr, acct)) in changes.iter().rev().copied() {
+            touched.insert(adr);
+            match self.account_from_changeset(adr, acct)? {
+                Some(acct) => self.write_account(adr, acct)?,
+                None => {
+                    self.delete_account(adr)?;
+                }
+            }
+        }
+
+        let blocks = changes.iter().map(|(num, _)| *num).collect::<BTreeSet<_>>();
+        for num in blocks {
+            self.delete::<AccountChangeSet>(num, None)?;
+        }
+        for adr in touched {
+            self.truncate_account_history(adr, from)?;
+        }
+        Ok(())
+    }
+
+    // Restores storage changed in blocks >= `from`, newest block first.
+    fn unwind_storage(&self, from: u64) -> Result<()> {
+        let start = StorageCSKey(BlockNumber(from), Default::default());
+        let changes = self
+            .cursor::<StorageChangeSet>()?
+            .iter(start)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut touched = BTreeSet::new();
+        for (StorageCSKey(_, StorageKey(adr, inc)), StorageCSVal(slot, val)) in
+            changes.iter().rev().copied()
+        {
+            touched.insert((adr, slot));
+            self.write_storage(adr, inc, slot, val)?;
+        }
+
+        let mut keys = changes.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        keys.dedup();
+        for key in keys {
+            self.delete::<StorageChangeSet>(key, None)?;
+        }
+        for (adr, slot) in touched {
+            self.truncate_storage_history(adr, slot, from)?;
+        }
+        Ok(())
+    }
+
+    // Deletes the canonical blocks >= `from`, along with their transactions.
+    fn unwind_blocks(&self, from: u64) -> Result<()> {
+        let canonical = self
+            .cursor::<CanonicalHeader>()?
+            .iter(BlockNumber(from))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (num, hash) in canonical {
+            let key = HeaderKey(num, hash);
+            // the raw body, whose tx range includes the system txs
+            if let Some(body) = self.read::<BlockBody>(key)? {
+                let end = body.base_tx_id + u64::from(body.tx_amount);
+                for id in body.base_tx_id..end {
+                    self.delete::<BlockTransaction>(TxIndex(id), None)?;
+                }
+                self.delete::<BlockBody>(key, None)?;
+            }
+            self.delete::<TxSender>(key, None)?;
+            self.delete::<HeadersTotalDifficulty>(key, None)?;
+            self.delete::<Header>(key, None)?;
+            self.delete::<HeaderNumber>(hash, None)?;
+            self.delete::<CanonicalHeader>(num, None)?;
+        }
+        Ok(())
+    }
+
+    // Removes blocks >= `from` from the account's history index. Every shard
+    // that may hold such blocks is deleted, and what remains of them is
+    // sharded again.
+    fn truncate_account_history(&self, adr: Address, from: u64) -> Result<()> {
+        let mut cur = self.cursor::<AccountHistory>()?;
+        let mut keys = Vec::new();
+        let mut rest = RoaringTreemap::new();
+        for read in cur.iter(AccountHistKey(adr, BlockNumber(from))) {
+            let (key, bitmap) = read?;
+            if key.0 != adr {
+                break;
+            }
+            keys.push(key);
+            rest |= bitmap;
+        }
+        rest.remove_range(from..);
+        for key in keys {
+            self.delete::<AccountHistory>(key, None)?;
+        }
+        if !rest.is_empty() {
+            self.write_account_history(adr, rest)?;
+        }
+        Ok(())
+    }
+
+    // Removes blocks >= `from` from the storage slot's history index, in the
+    // same way as `truncate_account_history`.
+    fn truncate_storage_history(&self, adr: Address, slot: H256, from: u64) -> Result<()> {
+        let mut cur = self.cursor::<StorageHistory>()?;
+        let mut keys = Vec::new();
+        let mut rest = RoaringTreemap::new();
+        for read in cur.iter(StorageHistKey(adr, slot, BlockNumber(from))) {
+            let (key, bitmap) = read?;
+            if key.0 != adr || key.1 != slot {
+                break;
+            }
+            keys.push(key);
+            rest |= bitmap;
+        }
+        rest.remove_range(from..);
+        for key in keys {
+            self.delete::<StorageHistory>(key, None)?;
+        }
+        if !rest.is_empty() {
+            self.write_storage_history(adr, slot, rest)?;
+        }
+        Ok(())
+    }
+}
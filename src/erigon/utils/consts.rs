@@ -9,3 +9,21 @@ pub const BLOOM_BYTE_LENGTH: usize = 256;
 pub const EMPTY_HASH: H256 = H256(hex_literal::hex!(
     "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
 ));
+
+// The serialized size limit of a history index shard. erigon: bitmapdb.ChunkLimit
+pub const CHUNK_LIMIT: usize = 1950;
+
+// keccak256(rlp("")), the root of an empty trie
+pub const EMPTY_ROOT: H256 = H256(hex_literal::hex!(
+    "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+));
+
+// keccak256(rlp([])), the uncle hash of a block without uncles
+pub const EMPTY_LIST_HASH: H256 = H256(hex_literal::hex!(
+    "1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"
+));
+
+// sha256(""), the Eip7685 requests hash of a block without requests
+pub const EMPTY_REQUESTS_HASH: H256 = H256(hex_literal::hex!(
+    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+));
@@ -1,17 +1,88 @@
-use bytes::Buf;
-use fastrlp::DecodeError;
-use roaring::RoaringTreemap;
+use bytes::{Buf, BytesMut};
+use ethereum_types::{Address, H256};
+use roaring::{RoaringBitmap, RoaringTreemap};
+use std::ops::{Bound, RangeBounds};
 use tiny_keccak::{Hasher, Keccak};
 
+use crate::{
+    error::DecodeError,
+    rlp::{self, Encodable},
+};
+
 pub mod consts;
-use consts as C;
 
 // https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/ethdb/bitmapdb/dbutils.go#L313
 pub fn find_gte(map: RoaringTreemap, n: u64) -> Option<u64> {
-    // rank() returns the number of integers in the map <= n, i.e. the index
-    // of n if it were in the bitmap.
-    let rank = map.rank(n.saturating_sub(1));
-    map.select(rank)
+    // rank() returns the number of integers in the map <= n - 1, i.e. the
+    // index of the first integer >= n. Every integer is >= 0.
+    match n.checked_sub(1) {
+        Some(prev) => map.select(map.rank(prev)),
+        None => map.min(),
+    }
+}
+
+// https://github.com/ledgerwatch/erigon/blob/f9d7cb5ca9e8a135a76ddcb6fa4ee526ea383554/ethdb/bitmapdb/dbutils.go#L274
+/// Removes and returns the longest prefix of `map` that serializes to at most
+/// `limit` bytes, or all of `map` if it fits. The prefix always holds at
+/// least one element.
+pub fn cut_left(map: &mut RoaringTreemap, limit: usize) -> RoaringTreemap {
+    if map.serialized_size() <= limit {
+        return std::mem::take(map);
+    }
+    let prefix = |n: u64| {
+        let mut out = map.clone();
+        if let Some(end) = map.select(n) {
+            out.remove_range(end..);
+        }
+        out
+    };
+    // binary search for the number of elements in the prefix
+    let (mut lo, mut hi) = (1, map.len());
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if prefix(mid).serialized_size() <= limit {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    let left = prefix(lo);
+    if let Some(end) = map.select(lo) {
+        map.remove_range(..end);
+    }
+    left
+}
+
+/// Splits a history index bitmap into shards of at most `limit` serialized
+/// bytes. Each shard is keyed by its largest element, except the last, which
+/// is keyed by `u64::MAX` so that it can be found and extended by later writes.
+pub fn shard_bitmap(mut map: RoaringTreemap, limit: usize) -> Vec<(u64, RoaringTreemap)> {
+    let mut out = Vec::new();
+    while !map.is_empty() {
+        let shard = cut_left(&mut map, limit);
+        let key = if map.is_empty() {
+            u64::MAX
+        } else {
+            shard.max().unwrap_or_default()
+        };
+        out.push((key, shard));
+    }
+    out
+}
+
+/// Converts a range of block numbers into a half-open `[start, end)` pair.
+pub fn block_range(range: impl RangeBounds<u64>) -> (u64, u64) {
+    let start = match range.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&n) => n.saturating_add(1),
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => u64::MAX,
+    };
+    (start, end)
 }
 
 // From ethers: https://github.com/gakonst/ethers-rs/blob/master/ethers-core/src/utils/hash.rs#L26
@@ -26,21 +97,65 @@ where
     output
 }
 
-/// advances buf past an rlp-encoded u64, returning the u64 left-padded with zeroes
-pub fn take_u64_rlp(buf: &mut &[u8]) -> Result<u64, DecodeError> {
-    if buf.is_empty() {
-        return Err(DecodeError::InputTooShort);
+/// Returns the address of the contract created by `sender` with the given
+/// nonce, `keccak256(rlp([sender, nonce]))[12..]`.
+pub fn create_address(sender: Address, nonce: u64) -> Address {
+    let mut buf = BytesMut::new();
+    rlp::Header {
+        list: true,
+        payload_length: sender.length() + nonce.length(),
     }
-    let len = buf.get_u8().into();
+    .encode(&mut buf);
+    sender.encode(&mut buf);
+    nonce.encode(&mut buf);
+    Address::from_slice(&keccak256(buf)[12..])
+}
 
-    if len > C::U64_LENGTH {
-        return Err(DecodeError::UnexpectedLength);
-    }
-    let val = bytes_to_u64(&buf[..len]);
-    buf.advance(len);
+/// Returns the address of the contract created by `sender` with CREATE2,
+/// `keccak256(0xff || sender || salt || keccak256(init_code))[12..]`.
+pub fn create2_address(sender: Address, salt: H256, init_code: &[u8]) -> Address {
+    let mut buf = Vec::with_capacity(85);
+    buf.push(0xff);
+    buf.extend_from_slice(sender.as_bytes());
+    buf.extend_from_slice(salt.as_bytes());
+    buf.extend_from_slice(&keccak256(init_code));
+    Address::from_slice(&keccak256(buf)[12..])
+}
+
+/// Writes the length of b without its leading zeroes as a single byte,
+/// followed by the remaining bytes.
+pub fn put_trimmed(out: &mut Vec<u8>, b: &[u8]) {
+    let start = b.iter().position(|&v| v != 0).unwrap_or(b.len());
+    out.push((b.len() - start) as u8);
+    out.extend_from_slice(&b[start..]);
+}
 
-    Ok(val)
+/// Advances buf past a one-byte length and that many bytes, returning the
+/// bytes. Errors if the length exceeds `max` or buf is too short.
+pub fn take_field<'a>(buf: &mut &'a [u8], max: usize) -> crate::Result<&'a [u8]> {
+    if buf.is_empty() {
+        return Err(DecodeError::TooShort { minimum: 1, got: 0 }.into());
+    }
+    let len = usize::from(buf.get_u8());
+    if len > max {
+        return Err(DecodeError::TooLong {
+            maximum: max,
+            got: len,
+        }
+        .into());
+    }
+    if buf.len() < len {
+        return Err(DecodeError::TooShort {
+            minimum: len,
+            got: buf.len(),
+        }
+        .into());
+    }
+    let (field, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(field)
 }
+
 // https://github.com/akula-bft/akula/blob/a9aed09b31bb41c89832149bcad7248f7fcd70ca/src/models/account.rs#L47
 pub fn bytes_to_u64(buf: &[u8]) -> u64 {
     let mut decoded = [0u8; 8];
@@ -49,3 +164,36 @@ pub fn bytes_to_u64(buf: &[u8]) -> u64 {
     }
     u64::from_le_bytes(decoded)
 }
+
+/// Clamps a block number to the u32 used to key 32-bit bitmap shards.
+pub fn shard_start(n: u64) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+// The 32-bit index bitmaps (logs, call traces) are sharded, with each shard keyed by the largest block
+// number it contains (and the last shard keyed by u32::MAX). We start at the
+// first shard that may contain `start` and union shards until we pass `end`.
+pub fn union_shards<P: PartialEq>(
+    shards: impl Iterator<Item = crate::Result<((P, u32), RoaringBitmap)>>,
+    prefix: P,
+    start: u64,
+    end: u64,
+) -> crate::Result<RoaringBitmap> {
+    let mut out = RoaringBitmap::new();
+    for res in shards {
+        let ((p, shard_max), bitmap) = res?;
+        if p != prefix {
+            break;
+        }
+        out |= bitmap;
+        if u64::from(shard_max) >= end {
+            break;
+        }
+    }
+    let (start, end) = (shard_start(start), shard_start(end));
+    out.remove_range(..start);
+    if end < u32::MAX {
+        out.remove_range(end..);
+    }
+    Ok(out)
+}
@@ -0,0 +1,111 @@
+use ethereum_types::H256;
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{
+    erigon::{models::BlockNumber, Erigon},
+    kv::{traits::Mode, MdbxEnv},
+    Result,
+};
+
+// The number of most recent canonical blocks remembered, and so the depth of
+// the deepest reorg that is followed exactly.
+const REORG_WINDOW: usize = 128;
+
+/// Watches the head of the chain, yielding each block as it becomes
+/// canonical. Returned by [`watch_head`].
+///
+/// A reorg is found by comparing the CanonicalHeader entries with the hashes
+/// of the blocks already yielded. The blocks from the fork point up to the new
+/// head are then yielded again with their new hashes, so a block number that
+/// is not above the last one yielded means the blocks from that number on
+/// were replaced. A reorg deeper than the last 128 blocks yielded is followed
+/// from the oldest of those.
+///
+/// The iterator never ends. Between polls that find no new blocks, it sleeps
+/// for the poll interval.
+#[derive(Debug)]
+pub struct HeadWatcher<'env, M> {
+    env: &'env MdbxEnv<M>,
+    poll_interval: Duration,
+    // the most recent blocks yielded that were canonical at the last poll
+    seen: VecDeque<(BlockNumber, H256)>,
+    pending: VecDeque<(BlockNumber, H256)>,
+    polled: bool,
+}
+
+/// Returns an iterator over the `(number, hash)` of each block that becomes
+/// canonical in the db, beginning with the current head, checking for new
+/// blocks every `poll_interval`.
+///
+/// Each poll reads in a new transaction, as a transaction only ever sees the
+/// db as it was when it began, which is why this takes the env rather than an
+/// [`Erigon`].
+pub fn watch_head<M: Mode>(env: &MdbxEnv<M>, poll_interval: Duration) -> HeadWatcher<'_, M> {
+    HeadWatcher {
+        env,
+        poll_interval,
+        seen: VecDeque::new(),
+        pending: VecDeque::new(),
+        polled: false,
+    }
+}
+
+impl<'env, M: Mode> HeadWatcher<'env, M> {
+    // Queues the blocks made canonical since the last poll.
+    fn poll(&mut self) -> Result<()> {
+        let db = Erigon(self.env.begin_ro()?);
+        let head = match db.read_head_header_hash()? {
+            Some(hash) => db.read_header_number(hash)?,
+            None => None,
+        };
+        let head = match head {
+            Some(head) => head,
+            None => return Ok(()),
+        };
+
+        // drop the blocks replaced by a reorg, and resume after the last block
+        // that is still canonical
+        let mut from = match self.seen.front() {
+            Some((oldest, _)) => *oldest,
+            None => head,
+        };
+        while let Some(&(num, hash)) = self.seen.back() {
+            if db.read_canonical_hash(num)? == Some(hash) {
+                from = BlockNumber(*num + 1);
+                break;
+            }
+            self.seen.pop_back();
+        }
+
+        for num in (*from..=*head).map(BlockNumber) {
+            let hash = match db.read_canonical_hash(num)? {
+                Some(hash) => hash,
+                None => break,
+            };
+            self.seen.push_back((num, hash));
+            if self.seen.len() > REORG_WINDOW {
+                self.seen.pop_front();
+            }
+            self.pending.push_back((num, hash));
+        }
+        Ok(())
+    }
+}
+
+impl<'env, M: Mode> Iterator for HeadWatcher<'env, M> {
+    type Item = Result<(BlockNumber, H256)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(block) = self.pending.pop_front() {
+                return Some(Ok(block));
+            }
+            if self.polled {
+                std::thread::sleep(self.poll_interval);
+            }
+            self.polled = true;
+            if let Err(e) = self.poll() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
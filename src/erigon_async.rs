@@ -0,0 +1,147 @@
+//! An async facade over the read-only [`Erigon`] API, for use from tokio.
+//!
+//! mdbx reads are blocking, and a transaction cannot be moved between
+//! threads, so [`ErigonAsync`] runs each read on tokio's blocking thread pool
+//! in its own read-only transaction. Walks are streamed back over a channel,
+//! and stop early once the stream is dropped.
+use ethereum_types::{Address, H256, U256};
+use mdbx::RO;
+use std::{path::Path, sync::Arc};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+
+use crate::{
+    erigon::{env_open, models::*, Erigon},
+    kv::MdbxEnv,
+    Result,
+};
+
+// The number of items a walk may read ahead of its stream.
+const STREAM_BUFFER: usize = 64;
+
+/// A handle to a read-only Erigon db that can be shared across tasks.
+#[derive(Clone, Debug)]
+pub struct ErigonAsync {
+    env: Arc<MdbxEnv<RO>>,
+}
+
+impl ErigonAsync {
+    pub fn new(env: Arc<MdbxEnv<RO>>) -> Self {
+        Self { env }
+    }
+
+    /// Opens the Erigon db at `path` read-only.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        env_open(path.as_ref()).map(|env| Self::new(Arc::new(env)))
+    }
+
+    pub fn env(&self) -> &Arc<MdbxEnv<RO>> {
+        &self.env
+    }
+
+    /// Runs `f` on a blocking thread, in its own read-only transaction.
+    pub async fn read<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Erigon<'_, RO>) -> Result<R> + Send + 'static,
+    {
+        let env = self.env.clone();
+        tokio::task::spawn_blocking(move || f(&Erigon::begin(&env)?)).await?
+    }
+
+    /// See [`Erigon::read_account`].
+    pub async fn read_account(&self, adr: Address) -> Result<Option<Account>> {
+        self.read(move |db| db.read_account(adr)).await
+    }
+
+    /// See [`Erigon::read_account_hist`].
+    pub async fn read_account_hist(
+        &self,
+        adr: Address,
+        block: impl Into<BlockNumber>,
+    ) -> Result<Option<Account>> {
+        let block = block.into();
+        self.read(move |db| db.read_account_hist(adr, block)).await
+    }
+
+    /// See [`Erigon::read_storage`].
+    pub async fn read_storage(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        slot: H256,
+    ) -> Result<Option<U256>> {
+        let inc = inc.into();
+        self.read(move |db| db.read_storage(adr, inc, slot)).await
+    }
+
+    /// See [`Erigon::read_block`].
+    pub async fn read_block(&self, id: impl Into<BlockId>) -> Result<Option<Block>> {
+        let id = id.into();
+        self.read(move |db| db.read_block(id)).await
+    }
+
+    /// See [`Erigon::read_head_block_number`].
+    pub async fn read_head_block_number(&self) -> Result<Option<BlockNumber>> {
+        self.read(|db| db.read_head_block_number()).await
+    }
+
+    /// Streams the storage (key, value) pairs of the given address and
+    /// account incarnation. See [`Erigon::walk_storage`].
+    pub fn walk_storage(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        start_slot: Option<H256>,
+    ) -> impl Stream<Item = Result<(H256, U256)>> {
+        let inc = inc.into();
+        self.stream(move |db, tx| forward(db.walk_storage(adr, inc, start_slot)?, tx))
+    }
+
+    /// Streams each change to account `adr`. See
+    /// [`Erigon::account_history_iter`].
+    pub fn account_history(
+        &self,
+        adr: Address,
+    ) -> impl Stream<Item = Result<(BlockNumber, Option<Account>)>> {
+        self.stream(move |db, tx| forward(db.account_history_iter(adr)?, tx))
+    }
+
+    /// Streams each change to the storage `slot` of account `adr`. See
+    /// [`Erigon::storage_history_iter`].
+    pub fn storage_history(
+        &self,
+        adr: Address,
+        slot: H256,
+    ) -> impl Stream<Item = Result<(BlockNumber, U256)>> {
+        self.stream(move |db, tx| forward(db.storage_history_iter(adr, slot)?, tx))
+    }
+
+    // Runs `walk` on a blocking thread, in its own read-only transaction,
+    // streaming the items it sends. An error ending the walk is sent as the
+    // last item.
+    fn stream<R, F>(&self, walk: F) -> ReceiverStream<Result<R>>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Erigon<'_, RO>, &mpsc::Sender<Result<R>>) -> Result<()> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let env = self.env.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = Erigon::begin(&env).and_then(|db| walk(&db, &tx)) {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+}
+
+// Sends each item to the stream, until the stream is dropped.
+fn forward<R>(items: impl Iterator<Item = Result<R>>, tx: &mpsc::Sender<Result<R>>) -> Result<()> {
+    for item in items {
+        if tx.blocking_send(item).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
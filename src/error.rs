@@ -0,0 +1,102 @@
+use std::fmt::Display;
+
+pub use crate::rlp::DecodeError as RlpDecodeError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The error type returned by all `kv` and `erigon` operations.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// An error returned by mdbx itself.
+    #[error("mdbx error: {0}")]
+    Mdbx(#[from] mdbx::Error),
+    /// A key or value could not be decoded from its raw bytes.
+    #[error("decode error: {0}")]
+    Decode(#[from] DecodeError),
+    /// An error reading a file outside of mdbx, e.g. a snapshot segment.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// A value needed to complete a lookup was missing from the named table.
+    #[error("no value found in table {0}")]
+    NotFound(&'static str),
+    /// The db was written with a schema version this crate cannot read.
+    #[error("unsupported db schema version {0}")]
+    UnsupportedSchema(crate::erigon::models::SchemaVersion),
+    /// The db cannot be read by this crate, as described by the report.
+    #[error("incompatible db: {0}")]
+    Incompatible(Box<crate::erigon::schema::CompatReport>),
+    /// The db already holds a genesis block, with the given hash.
+    #[error("db already initialized with genesis {0:?}")]
+    AlreadyInitialized(ethereum_types::H256),
+    /// The state at `block` cannot be read from the hashed state tables, which
+    /// hold the state at `hashed_to`.
+    #[error("no hashed state for block {block}, the hashed state is at block {hashed_to}")]
+    StateUnavailable {
+        block: crate::erigon::models::BlockNumber,
+        hashed_to: crate::erigon::models::BlockNumber,
+    },
+    /// An error status returned by a remote KV server.
+    #[cfg(feature = "remote")]
+    #[error("grpc status: {0}")]
+    Grpc(#[from] tonic::Status),
+    /// An error connecting to a remote KV server.
+    #[cfg(feature = "remote")]
+    #[error("grpc transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    /// A blocking read spawned by `ErigonAsync` or `ErigonMiddleware`
+    /// panicked or was cancelled.
+    #[cfg(any(feature = "async", feature = "ethers-types"))]
+    #[error("blocking task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+    /// An error starting the JSON-RPC server.
+    #[cfg(feature = "rpc")]
+    #[error("rpc error: {0}")]
+    Rpc(#[from] jsonrpsee::core::Error),
+    /// An error building the arrow columns of an export.
+    #[cfg(feature = "arrow")]
+    #[error("arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    /// An error writing the Parquet file of an export.
+    #[cfg(feature = "arrow")]
+    #[error("parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+}
+
+/// Errors encountered while decoding the raw bytes stored in the database.
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("invalid length: {expected} != {got}")]
+    InvalidLength { expected: usize, got: usize },
+    #[error("too short: {got} < {minimum}")]
+    TooShort { minimum: usize, got: usize },
+    #[error("too long: {got} > {maximum}")]
+    TooLong { maximum: usize, got: usize },
+    #[error("rlp: {0}")]
+    Rlp(#[from] RlpDecodeError),
+    #[error("cbor: {0}")]
+    Cbor(#[from] serde_cbor::Error),
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("bitmap: {0}")]
+    Bitmap(#[from] std::io::Error),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl DecodeError {
+    pub fn custom(msg: impl Display) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+impl From<RlpDecodeError> for Error {
+    fn from(e: RlpDecodeError) -> Self {
+        Self::Decode(e.into())
+    }
+}
+
+impl From<serde_cbor::Error> for Error {
+    fn from(e: serde_cbor::Error) -> Self {
+        Self::Decode(e.into())
+    }
+}
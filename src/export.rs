@@ -0,0 +1,256 @@
+//! Exports of the typed tables to Parquet files, for analytics with tools
+//! that read Arrow or Parquet, such as DuckDB, Polars or Spark.
+//!
+//! Each column has the arrow type of its field: integers that fit in a u64
+//! are `UInt64`, hashes, addresses and blooms are `FixedSizeBinary` of their
+//! length, and byte strings are `Binary`. U256 values, which overflow the
+//! largest arrow decimal, are written as 32 byte big-endian `FixedSizeBinary`
+//! columns, which sort in numeric order. Fields that only some blocks have,
+//! such as the base fee, are nullable.
+//!
+//! The files are written with snappy compression, in row groups of
+//! [`BATCH_ROWS`] rows.
+use arrow_array::{
+    ArrayRef, BinaryArray, FixedSizeBinaryArray, RecordBatch, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use ethereum_types::U256;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use std::{fs::File, ops::RangeBounds, path::Path, sync::Arc};
+
+use crate::{erigon::Erigon, kv::traits::Mode, Result};
+
+/// The number of rows buffered before they are written to the file.
+pub const BATCH_ROWS: usize = 64 * 1024;
+
+/// Writes every account in the PlainState table to a Parquet file at `path`,
+/// returning the number of rows written.
+///
+/// Columns: `address`, `nonce`, `incarnation`, `balance`, `codehash`.
+pub fn export_accounts<M: Mode>(db: &Erigon<'_, M>, path: impl AsRef<Path>) -> Result<usize> {
+    let schema = Schema::new(vec![
+        Field::new("address", DataType::FixedSizeBinary(20), false),
+        Field::new("nonce", DataType::UInt64, false),
+        Field::new("incarnation", DataType::UInt64, false),
+        Field::new("balance", DataType::FixedSizeBinary(32), false),
+        Field::new("codehash", DataType::FixedSizeBinary(32), false),
+    ]);
+    write_parquet(path.as_ref(), schema, db.walk_accounts(None)?, |rows| {
+        Ok(vec![
+            fixed(20, rows.iter().map(|(adr, _)| Some(adr)))?,
+            u64s(rows.iter().map(|(_, acct)| Some(acct.nonce))),
+            u64s(rows.iter().map(|(_, acct)| Some(*acct.incarnation))),
+            fixed(
+                32,
+                rows.iter().map(|(_, acct)| Some(be_bytes(acct.balance))),
+            )?,
+            fixed(32, rows.iter().map(|(_, acct)| Some(acct.codehash)))?,
+        ])
+    })
+}
+
+/// Writes the headers of the canonical blocks in the range to a Parquet file
+/// at `path`, returning the number of rows written.
+///
+/// Columns: `number`, `hash`, then the fields of
+/// [`BlockHeader`](crate::erigon::models::BlockHeader) except the
+/// seal, with `receipts_hash` as `receipts_root` and `root` as `state_root`.
+pub fn export_headers<M: Mode>(
+    db: &Erigon<'_, M>,
+    range: impl RangeBounds<u64>,
+    path: impl AsRef<Path>,
+) -> Result<usize> {
+    let hash = || DataType::FixedSizeBinary(32);
+    let schema = Schema::new(vec![
+        Field::new("number", DataType::UInt64, false),
+        Field::new("hash", hash(), false),
+        Field::new("parent_hash", hash(), false),
+        Field::new("uncle_hash", hash(), false),
+        Field::new("coinbase", DataType::FixedSizeBinary(20), false),
+        Field::new("state_root", hash(), false),
+        Field::new("tx_root", hash(), false),
+        Field::new("receipts_root", hash(), false),
+        Field::new("bloom", DataType::FixedSizeBinary(256), false),
+        Field::new("difficulty", DataType::FixedSizeBinary(32), false),
+        Field::new("gas_limit", DataType::UInt64, false),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new("time", DataType::UInt64, false),
+        Field::new("extra", DataType::Binary, false),
+        Field::new("mix_digest", hash(), false),
+        Field::new("nonce", DataType::FixedSizeBinary(8), false),
+        Field::new("base_fee", DataType::FixedSizeBinary(32), true),
+        Field::new("withdrawals_root", hash(), true),
+        Field::new("blob_gas_used", DataType::UInt64, true),
+        Field::new("excess_blob_gas", DataType::UInt64, true),
+        Field::new("parent_beacon_block_root", hash(), true),
+        Field::new("requests_hash", hash(), true),
+    ]);
+    let headers = db
+        .walk_canonical_headers(range)?
+        .map(|res| res.map(|(num, header)| (*num, header.hash(), header)));
+    write_parquet(path.as_ref(), schema, headers, |rows| {
+        let headers = || rows.iter().map(|(_, _, h)| h);
+        Ok(vec![
+            u64s(rows.iter().map(|(num, _, _)| Some(*num))),
+            fixed(32, rows.iter().map(|(_, hash, _)| Some(hash)))?,
+            fixed(32, headers().map(|h| Some(h.parent_hash)))?,
+            fixed(32, headers().map(|h| Some(h.uncle_hash)))?,
+            fixed(20, headers().map(|h| Some(h.coinbase)))?,
+            fixed(32, headers().map(|h| Some(h.root)))?,
+            fixed(32, headers().map(|h| Some(h.tx_hash)))?,
+            fixed(32, headers().map(|h| Some(h.receipts_hash)))?,
+            fixed(256, headers().map(|h| Some(h.bloom)))?,
+            fixed(32, headers().map(|h| Some(be_bytes(h.difficulty))))?,
+            u64s(headers().map(|h| Some(h.gas_limit))),
+            u64s(headers().map(|h| Some(h.gas_used))),
+            u64s(headers().map(|h| Some(h.time))),
+            binary(headers().map(|h| &h.extra)),
+            fixed(32, headers().map(|h| Some(h.mix_digest)))?,
+            fixed(8, headers().map(|h| Some(h.nonce)))?,
+            fixed(32, headers().map(|h| h.base_fee.map(be_bytes)))?,
+            fixed(32, headers().map(|h| h.withdrawals_root))?,
+            u64s(headers().map(|h| h.blob_gas_used)),
+            u64s(headers().map(|h| h.excess_blob_gas)),
+            fixed(32, headers().map(|h| h.parent_beacon_block_root))?,
+            fixed(32, headers().map(|h| h.requests_hash))?,
+        ])
+    })
+}
+
+/// Writes the logs emitted in the range of canonical blocks to a Parquet
+/// file at `path`, returning the number of rows written.
+///
+/// Columns: `block_number`, `tx_index`, `log_index` (the index of the log
+/// within its block), `address`, `topic0` to `topic3`, which are null for the
+/// topics a log does not have, and `data`.
+pub fn export_logs<M: Mode>(
+    db: &Erigon<'_, M>,
+    range: impl RangeBounds<u64>,
+    path: impl AsRef<Path>,
+) -> Result<usize> {
+    let mut fields = vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("tx_index", DataType::UInt32, false),
+        Field::new("log_index", DataType::UInt32, false),
+        Field::new("address", DataType::FixedSizeBinary(20), false),
+    ];
+    for i in 0..4 {
+        fields.push(Field::new(
+            format!("topic{i}"),
+            DataType::FixedSizeBinary(32),
+            true,
+        ));
+    }
+    fields.push(Field::new("data", DataType::Binary, false));
+    write_parquet(
+        path.as_ref(),
+        Schema::new(fields),
+        db.walk_logs(range)?,
+        |rows| {
+            let mut cols = vec![
+                u64s(rows.iter().map(|(num, ..)| Some(**num))),
+                u32s(rows.iter().map(|(_, tx_index, ..)| *tx_index)),
+                u32s(rows.iter().map(|(_, _, log_index, _)| *log_index)),
+                fixed(20, rows.iter().map(|(.., log)| Some(log.address)))?,
+            ];
+            for i in 0..4 {
+                cols.push(fixed(32, rows.iter().map(|(.., log)| log.topics.get(i)))?);
+            }
+            cols.push(binary(rows.iter().map(|(.., log)| &log.data)));
+            Ok(cols)
+        },
+    )
+}
+
+/// Writes the stats of the canonical blocks in the range to a Parquet file at
+/// `path`, returning the number of rows written.
+///
+/// Columns: the fields of [`BlockStats`](crate::erigon::stats::BlockStats).
+pub fn export_chain_stats<M: Mode>(
+    db: &Erigon<'_, M>,
+    range: impl RangeBounds<u64>,
+    path: impl AsRef<Path>,
+) -> Result<usize> {
+    let schema = Schema::new(vec![
+        Field::new("number", DataType::UInt64, false),
+        Field::new("timestamp", DataType::UInt64, false),
+        Field::new("tx_count", DataType::UInt64, false),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new("gas_limit", DataType::UInt64, false),
+        Field::new("base_fee", DataType::FixedSizeBinary(32), true),
+        Field::new("size", DataType::UInt64, false),
+        Field::new("uncle_count", DataType::UInt64, false),
+        Field::new("withdrawal_count", DataType::UInt64, true),
+        Field::new("withdrawal_amount", DataType::UInt64, true),
+    ]);
+    write_parquet(path.as_ref(), schema, db.chain_timeseries(range)?, |rows| {
+        Ok(vec![
+            u64s(rows.iter().map(|s| Some(*s.number))),
+            u64s(rows.iter().map(|s| Some(s.timestamp))),
+            u64s(rows.iter().map(|s| Some(s.tx_count))),
+            u64s(rows.iter().map(|s| Some(s.gas_used))),
+            u64s(rows.iter().map(|s| Some(s.gas_limit))),
+            fixed(32, rows.iter().map(|s| s.base_fee.map(be_bytes)))?,
+            u64s(rows.iter().map(|s| Some(s.size))),
+            u64s(rows.iter().map(|s| Some(s.uncle_count))),
+            u64s(rows.iter().map(|s| s.withdrawal_count)),
+            u64s(rows.iter().map(|s| s.withdrawal_amount)),
+        ])
+    })
+}
+
+// Writes the rows to a new Parquet file at `path`, converting each batch of
+// up to BATCH_ROWS rows to the columns of the schema with `columns`.
+fn write_parquet<T>(
+    path: &Path,
+    schema: Schema,
+    rows: impl Iterator<Item = Result<T>>,
+    columns: impl Fn(&[T]) -> Result<Vec<ArrayRef>>,
+) -> Result<usize> {
+    let schema = Arc::new(schema);
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(BATCH_ROWS)
+        .build();
+    let mut w = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props))?;
+    let mut rows = rows.fuse();
+    let mut buf = Vec::with_capacity(BATCH_ROWS);
+    let mut written = 0;
+    loop {
+        buf.clear();
+        for row in rows.by_ref().take(BATCH_ROWS) {
+            buf.push(row?);
+        }
+        if buf.is_empty() {
+            break;
+        }
+        w.write(&RecordBatch::try_new(schema.clone(), columns(&buf)?)?)?;
+        written += buf.len();
+    }
+    w.close()?;
+    Ok(written)
+}
+
+fn u64s(vals: impl Iterator<Item = Option<u64>>) -> ArrayRef {
+    Arc::new(vals.collect::<UInt64Array>())
+}
+
+fn u32s(vals: impl Iterator<Item = u32>) -> ArrayRef {
+    Arc::new(UInt32Array::from_iter_values(vals))
+}
+
+fn binary<B: AsRef<[u8]>>(vals: impl Iterator<Item = B>) -> ArrayRef {
+    Arc::new(BinaryArray::from_iter_values(vals))
+}
+
+fn fixed<B: AsRef<[u8]>>(size: i32, vals: impl Iterator<Item = Option<B>>) -> Result<ArrayRef> {
+    Ok(Arc::new(
+        FixedSizeBinaryArray::try_from_sparse_iter_with_size(vals, size)?,
+    ))
+}
+
+fn be_bytes(val: U256) -> [u8; 32] {
+    let mut buf = [0; 32];
+    val.to_big_endian(&mut buf);
+    buf
+}
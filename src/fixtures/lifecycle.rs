@@ -0,0 +1,115 @@
+//! A short chain that takes a contract through its life: deployed by a
+//! factory with CREATE2, written to, destroyed and then called again. It is
+//! scripted after the scenario the `txgen` binary runs against a node, but
+//! is written straight to a db with a [`ChainWriter`], so tests can read it
+//! without a node or a solc install.
+//!
+//! One transaction is sent per block, as on a dev chain:
+//!
+//! 1. [`SRC`] sends 100 wei to [`DST`].
+//! 2. [`SRC`] deploys the Factory contract, at [`FACTORY`].
+//! 3. `Factory.deploy(0)` creates a Store contract with CREATE2, whose
+//!    constructor sets slots 0, 1 and 2 to 2, 3 and `U256::MAX`, and the
+//!    Factory stores its address in slot 0.
+//! 4. `Store.kill()` self-destructs the Store, sending its balance to
+//!    [`SRC`].
+//! 5. `Store.set(1, 234)` calls the destroyed Store, which has no code left,
+//!    so changes nothing but the sender's nonce.
+//!
+//! No contract is executed. The Factory and Store hold stand-in code, and
+//! the effects of each call are written by hand, so the chain does not
+//! reproduce a `txgen` run: the Store's address is derived from the stand-in
+//! code rather than from the real init code, and only the Factory's address
+//! matches a real run.
+use bytes::Bytes;
+use ethereum_types::{Address, H160, H256, U256};
+use hex_literal::hex;
+use mdbx::RW;
+
+use crate::{
+    erigon::{models::transaction::TxAction, utils::keccak256, Erigon},
+    fixtures::{create2_address, create_address, Chain, ChainWriter, Fixture},
+    Result,
+};
+
+/// The account that sends every transaction.
+pub const SRC: Address = H160(hex!("67b1d87101671b127f5f8714789c7192f7ad340e"));
+/// The recipient of the transfer in block 1.
+pub const DST: Address = H160(hex!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b"));
+/// The Factory contract, the first contract deployed by [`SRC`].
+pub const FACTORY: Address = H160(hex!("0d4c6c6605a729a379216c93e919711a081beba2"));
+
+/// Stand-in code for the Factory, which stops at once.
+pub const FACTORY_CODE: &[u8] = &[0x00];
+/// Stand-in code for the Store, which does what `Store.set` does with its
+/// arguments: `sstore(calldataload(4), calldataload(36))`.
+pub const STORE_CODE: &[u8] = &[0x60, 0x24, 0x35, 0x60, 0x04, 0x35, 0x55, 0x00];
+
+/// Writes the chain to a new env in a temporary directory. The chain's
+/// contracts are the Factory and the Store, in that order.
+pub fn build() -> Result<Fixture> {
+    Fixture::new(build_into)
+}
+
+/// Writes the chain to an empty db in the given transaction, leaving it to
+/// the caller to commit.
+pub fn build_into(db: &Erigon<'_, RW>) -> Result<Chain> {
+    let mut w = ChainWriter::genesis(db, [(SRC, U256::exp10(22))])?;
+
+    w.push_tx(SRC, TxAction::Call(DST), 100.into(), Bytes::new())?;
+    w.seal()?;
+
+    let factory = create_address(SRC, 1);
+    debug_assert_eq!(factory, FACTORY);
+    let code = Bytes::from_static(FACTORY_CODE);
+    w.push_tx(SRC, TxAction::Create, U256::zero(), code.clone())?;
+    w.create_contract(factory, code, [])?;
+    w.seal()?;
+
+    let salt = H256::zero();
+    let data = call_data("deploy(bytes32)", &[salt]);
+    w.push_tx(SRC, TxAction::Call(factory), U256::zero(), data)?;
+    let store = create2_address(factory, salt, STORE_CODE);
+    let slots = [2.into(), 3.into(), U256::MAX]
+        .into_iter()
+        .enumerate()
+        .map(|(slot, val)| (H256::from_low_u64_be(slot as u64), val));
+    w.create_contract(store, Bytes::from_static(STORE_CODE), slots)?;
+    let acct = db.read_account(factory)?.unwrap_or_default();
+    w.set_account(factory, Some(acct.nonce(acct.nonce + 1)))?;
+    w.set_storage(
+        factory,
+        H256::zero(),
+        U256::from_big_endian(store.as_bytes()),
+    )?;
+    w.seal()?;
+
+    w.push_tx(
+        SRC,
+        TxAction::Call(store),
+        U256::zero(),
+        call_data("kill()", &[]),
+    )?;
+    w.selfdestruct(store, SRC)?;
+    w.seal()?;
+
+    let args = [H256::from_low_u64_be(1), H256::from_low_u64_be(234)];
+    let data = call_data("set(uint256,uint256)", &args);
+    w.push_tx(SRC, TxAction::Call(store), U256::zero(), data)?;
+    w.seal()?;
+
+    let mut chain = w.finish()?;
+    chain.accounts = vec![SRC];
+    chain.contracts = vec![factory, store];
+    Ok(chain)
+}
+
+// Encodes a call to the function with the given signature, whose arguments
+// are all static words.
+fn call_data(sig: &str, args: &[H256]) -> Bytes {
+    let mut data = keccak256(sig)[..4].to_vec();
+    for arg in args {
+        data.extend_from_slice(arg.as_bytes());
+    }
+    data.into()
+}
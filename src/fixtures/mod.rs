@@ -0,0 +1,222 @@
+//! Synthetic chains written to a temporary db, for tests that need a
+//! populated chaindata without a synced node.
+//!
+//! A [`ChainWriter`] writes blocks of transfers, contract deployments and
+//! storage writes, keeping the tables that Erigon's stages derive from
+//! execution in step: PlainState, the changesets, the history indices, the
+//! hashed state and each header's state root. [`ChainBuilder`] uses it to
+//! write a random chain of any length, and [`lifecycle`] to write a short,
+//! scripted chain through the life of a contract. The blocks are not
+//! executable: their
+//! transactions carry placeholder signatures and pay no gas, and their
+//! transaction and receipt roots are left empty.
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use mdbx::RW;
+use tempfile::TempDir;
+
+use crate::{
+    erigon::{
+        env_open,
+        models::{transaction::TxAction, BlockNumber},
+        Erigon,
+    },
+    kv::MdbxEnv,
+    Result,
+};
+
+pub mod lifecycle;
+mod writer;
+pub use crate::erigon::utils::{create2_address, create_address};
+pub use writer::ChainWriter;
+
+/// The runtime code given to every contract deployed by a [`ChainBuilder`],
+/// which stores the second word of its calldata in the slot given by the
+/// first: `sstore(calldataload(0), calldataload(32))`.
+pub const CONTRACT_CODE: &[u8] = &[0x60, 0x20, 0x35, 0x60, 0x00, 0x35, 0x55, 0x00];
+
+/// The chain id in the config of every fixture chain.
+pub const CHAIN_ID: u64 = 1337;
+
+/// Builds a synthetic chain of `blocks` blocks on top of a genesis block.
+///
+/// The genesis block funds `accounts` externally owned accounts. Each block
+/// then holds `txs_per_block` transactions sent from random accounts, each
+/// either a transfer to another account or, once a contract exists, a write
+/// to one of its first [`ChainBuilder::slots`] storage slots. The first
+/// transaction of every `deploy_every`th block deploys a contract instead.
+/// The chain is a function of the builder's settings and seed alone.
+#[derive(Clone, Debug)]
+pub struct ChainBuilder {
+    blocks: u64,
+    accounts: u64,
+    txs_per_block: usize,
+    deploy_every: u64,
+    slots: u64,
+    seed: u64,
+}
+
+/// The blocks and accounts of a fixture chain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Chain {
+    /// The hash of each block, indexed by block number.
+    pub hashes: Vec<H256>,
+    /// The externally owned accounts funded at genesis.
+    pub accounts: Vec<Address>,
+    /// The contracts deployed, in the order they were deployed.
+    pub contracts: Vec<Address>,
+}
+
+impl Chain {
+    /// Returns the number of the last block.
+    pub fn head(&self) -> BlockNumber {
+        BlockNumber(self.hashes.len().saturating_sub(1) as u64)
+    }
+}
+
+/// A chain written to an env in a temporary directory, which is deleted when
+/// the fixture is dropped.
+#[derive(Debug)]
+pub struct Fixture {
+    pub env: MdbxEnv<RW>,
+    pub chain: Chain,
+    dir: TempDir,
+}
+
+impl Fixture {
+    /// Opens an env in a new temporary directory and writes a chain to it
+    /// with `f`, committing the writes.
+    pub fn new(f: impl FnOnce(&Erigon<'_, RW>) -> Result<Chain>) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let env = env_open::<RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let chain = f(&db)?;
+        db.0.commit()?;
+        Ok(Self { env, chain, dir })
+    }
+
+    /// Returns the path of the temporary chaindata directory.
+    pub fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+}
+
+impl Default for ChainBuilder {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl ChainBuilder {
+    /// Creates a builder for a chain of `blocks` blocks after genesis.
+    pub fn new(blocks: u64) -> Self {
+        Self {
+            blocks,
+            accounts: 8,
+            txs_per_block: 4,
+            deploy_every: 4,
+            slots: 8,
+            seed: 0,
+        }
+    }
+
+    /// Sets the number of externally owned accounts funded at genesis.
+    pub fn accounts(mut self, accounts: u64) -> Self {
+        self.accounts = accounts.max(1);
+        self
+    }
+
+    /// Sets the number of transactions in each block.
+    pub fn txs_per_block(mut self, txs: usize) -> Self {
+        self.txs_per_block = txs;
+        self
+    }
+
+    /// Sets the interval between the blocks that deploy a contract, or
+    /// disables deployments if 0.
+    pub fn deploy_every(mut self, blocks: u64) -> Self {
+        self.deploy_every = blocks;
+        self
+    }
+
+    /// Sets the number of storage slots of each contract that are written.
+    pub fn slots(mut self, slots: u64) -> Self {
+        self.slots = slots.max(1);
+        self
+    }
+
+    /// Sets the seed of the random choices of senders, recipients and values.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Writes the chain to a new env in a temporary directory.
+    pub fn build(self) -> Result<Fixture> {
+        Fixture::new(|db| self.build_into(db))
+    }
+
+    /// Writes the chain to an empty db in the given transaction, leaving it
+    /// to the caller to commit.
+    pub fn build_into(self, db: &Erigon<'_, RW>) -> Result<Chain> {
+        let mut rng = SplitMix64(self.seed);
+        let accounts = (1..=self.accounts)
+            .map(|i| Address::from_low_u64_be(0xacc0_0000 + i))
+            .collect::<Vec<_>>();
+        let mut w = ChainWriter::genesis(db, accounts.iter().map(|&adr| (adr, U256::exp10(24))))?;
+        let mut contracts = vec![];
+        for num in 1..=self.blocks {
+            for i in 0..self.txs_per_block {
+                let sender = accounts[rng.below(self.accounts) as usize];
+                if i == 0 && self.deploy_every != 0 && num % self.deploy_every == 0 {
+                    let nonce = db.read_account(sender)?.unwrap_or_default().nonce;
+                    let code = Bytes::from_static(CONTRACT_CODE);
+                    w.push_tx(sender, TxAction::Create, U256::zero(), code.clone())?;
+                    let contract = create_address(sender, nonce);
+                    w.create_contract(contract, code, [])?;
+                    contracts.push(contract);
+                } else if !contracts.is_empty() && rng.below(2) == 0 {
+                    let contract = contracts[rng.below(contracts.len() as u64) as usize];
+                    let slot = H256::from_low_u64_be(rng.below(self.slots));
+                    let val = U256::from(rng.next());
+                    let mut data = [0; 64];
+                    data[..32].copy_from_slice(slot.as_bytes());
+                    val.to_big_endian(&mut data[32..]);
+                    w.push_tx(
+                        sender,
+                        TxAction::Call(contract),
+                        U256::zero(),
+                        data.to_vec().into(),
+                    )?;
+                    w.set_storage(contract, slot, val)?;
+                } else {
+                    let to = accounts[rng.below(self.accounts) as usize];
+                    let value = U256::from(rng.below(1_000_000) + 1);
+                    w.push_tx(sender, TxAction::Call(to), value, Bytes::new())?;
+                }
+            }
+            w.seal()?;
+        }
+        let mut chain = w.finish()?;
+        chain.accounts = accounts;
+        chain.contracts = contracts;
+        Ok(chain)
+    }
+}
+
+// A small, seedable generator, so fixtures need no rng dependency.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
@@ -0,0 +1,285 @@
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use mdbx::RW;
+use roaring::RoaringTreemap;
+use std::collections::BTreeMap;
+
+use crate::{
+    erigon::{
+        models::{
+            transaction::{LegacyTx, TxAction, VPackChainId},
+            Account, Block, BlockHeader, BlockNumber, Bytecode, ChainConfig, Genesis,
+            GenesisAccount, Incarnation, PlainCodeKey, StageId, Transaction, TransactionWithSigner,
+        },
+        tables::{Code, PlainCodeHash, PlainState, SyncStage},
+        utils::{consts::EMPTY_LIST_HASH, keccak256},
+        Erigon,
+    },
+    fixtures::{Chain, CHAIN_ID},
+    kv::traits::DbName,
+    Error, Result,
+};
+
+// The interval between block timestamps.
+const BLOCK_TIME: u64 = 12;
+const GAS_LIMIT: u64 = 30_000_000;
+const TX_GAS: u64 = 21_000;
+
+/// Writes a chain block by block through the write methods of [`Erigon`],
+/// as execution and the stages after it would: every state change is
+/// recorded in the changesets and history indices of the block being built,
+/// and each block is sealed with the state root of the hashed state.
+///
+/// Transactions are recorded in the block body and indexed by hash in
+/// BlockTransactionLookup, as the TxLookup stage would. Apart from the nonce
+/// and value transfer made by [`push_tx`](Self::push_tx), their effects are
+/// made with the state methods, such as [`set_storage`](Self::set_storage).
+#[derive(Debug)]
+pub struct ChainWriter<'db, 'env> {
+    db: &'db Erigon<'env, RW>,
+    chain: Chain,
+    txs: Vec<TransactionWithSigner>,
+    account_history: BTreeMap<Address, RoaringTreemap>,
+    storage_history: BTreeMap<(Address, H256), RoaringTreemap>,
+}
+
+impl<'db, 'env> ChainWriter<'db, 'env> {
+    /// Writes a genesis block giving each account its balance into an empty
+    /// db, and begins block 1.
+    pub fn genesis(
+        db: &'db Erigon<'env, RW>,
+        alloc: impl IntoIterator<Item = (Address, U256)>,
+    ) -> Result<Self> {
+        let genesis = Genesis {
+            config: ChainConfig {
+                chain_id: CHAIN_ID,
+                ..Default::default()
+            },
+            gas_limit: GAS_LIMIT.into(),
+            difficulty: 1.into(),
+            alloc: alloc
+                .into_iter()
+                .map(|(adr, balance)| {
+                    let alloc = GenesisAccount {
+                        balance,
+                        ..Default::default()
+                    };
+                    (adr, alloc)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let hash = db.init_genesis(&genesis)?;
+        db.promote_hashed_state()?;
+        Ok(Self {
+            db,
+            chain: Chain {
+                hashes: vec![hash],
+                ..Default::default()
+            },
+            txs: vec![],
+            account_history: Default::default(),
+            storage_history: Default::default(),
+        })
+    }
+
+    pub fn db(&self) -> &'db Erigon<'env, RW> {
+        self.db
+    }
+
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    /// Returns the number of the block being built.
+    pub fn block(&self) -> BlockNumber {
+        BlockNumber(self.chain.hashes.len() as u64)
+    }
+
+    /// Sets or deletes the account at `adr`, recording its previous state.
+    pub fn set_account(&mut self, adr: Address, acct: Option<Account>) -> Result<()> {
+        let num = self.block();
+        self.db
+            .write_account_change(num, adr, self.db.read_account(adr)?)?;
+        match acct {
+            Some(acct) => self.db.write_account(adr, acct)?,
+            None => {
+                self.db.delete_account(adr)?;
+            }
+        }
+        self.account_history.entry(adr).or_default().insert(*num);
+        Ok(())
+    }
+
+    /// Sets the storage `slot` of the contract at `adr`, recording its
+    /// previous value.
+    pub fn set_storage(&mut self, adr: Address, slot: H256, val: U256) -> Result<()> {
+        let num = self.block();
+        let inc = self
+            .db
+            .read_account(adr)?
+            .ok_or(Error::NotFound(PlainState::NAME))?
+            .incarnation;
+        let old = self.db.read_storage(adr, inc, slot)?.unwrap_or_default();
+        self.db.write_storage_change(num, adr, inc, slot, old)?;
+        self.db.write_storage(adr, inc, slot, val)?;
+        self.storage_history
+            .entry((adr, slot))
+            .or_default()
+            .insert(*num);
+        Ok(())
+    }
+
+    /// Moves `value` from the balance of `from` to that of `to`.
+    ///
+    /// # Panics
+    ///
+    /// If the balance of `from` is below `value`.
+    pub fn transfer(&mut self, from: Address, to: Address, value: U256) -> Result<()> {
+        if from == to || value.is_zero() {
+            return Ok(());
+        }
+        let src = self.db.read_account(from)?.unwrap_or_default();
+        let balance = src
+            .balance
+            .checked_sub(value)
+            .expect("transfer exceeds the sender's balance");
+        self.set_account(from, Some(src.balance(balance)))?;
+        let dst = self.db.read_account(to)?.unwrap_or_default();
+        self.set_account(to, Some(dst.balance(dst.balance + value)))
+    }
+
+    /// Creates a contract at `adr` with the given code and storage. As in
+    /// Erigon, its incarnation is one past that of the last contract
+    /// destroyed at the address. Returns the incarnation.
+    pub fn create_contract(
+        &mut self,
+        adr: Address,
+        code: Bytes,
+        storage: impl IntoIterator<Item = (H256, U256)>,
+    ) -> Result<Incarnation> {
+        let inc = Incarnation(self.db.read_incarnation(adr)?.map_or(0, |inc| *inc) + 1);
+        let codehash = H256(keccak256(&code));
+        self.db.write::<Code>(codehash, Bytecode(code))?;
+        self.db
+            .write::<PlainCodeHash>(PlainCodeKey(adr, inc), codehash)?;
+        let balance = self.db.read_account(adr)?.unwrap_or_default().balance;
+        let acct = Account::new()
+            .nonce(1)
+            .balance(balance)
+            .incarnation(inc)
+            .codehash(codehash);
+        self.set_account(adr, Some(acct))?;
+        for (slot, val) in storage {
+            self.set_storage(adr, slot, val)?;
+        }
+        Ok(inc)
+    }
+
+    /// Destroys the contract at `adr`, clearing its storage and sending its
+    /// balance to `beneficiary`. Its incarnation is kept in the
+    /// IncarnationMap table, so that a contract created at the address later
+    /// gets the next one.
+    pub fn selfdestruct(&mut self, adr: Address, beneficiary: Address) -> Result<()> {
+        let acct = self
+            .db
+            .read_account(adr)?
+            .ok_or(Error::NotFound(PlainState::NAME))?;
+        let slots = self
+            .db
+            .walk_storage(adr, acct.incarnation, None)?
+            .collect::<Result<Vec<_>>>()?;
+        for (slot, _) in slots {
+            self.set_storage(adr, slot, U256::zero())?;
+        }
+        self.transfer(adr, beneficiary, acct.balance)?;
+        self.db.write_incarnation(adr, acct.incarnation)?;
+        self.set_account(adr, None)
+    }
+
+    /// Adds a legacy transaction from `sender` to the block being built,
+    /// bumping the sender's nonce and, for a call, transferring `value` to
+    /// the recipient. The transaction pays no gas and has a placeholder
+    /// signature, its sender being recorded in the TxSender table.
+    pub fn push_tx(
+        &mut self,
+        sender: Address,
+        to: TxAction,
+        value: U256,
+        data: Bytes,
+    ) -> Result<()> {
+        let acct = self.db.read_account(sender)?.unwrap_or_default();
+        self.set_account(sender, Some(acct.nonce(acct.nonce + 1)))?;
+        if let TxAction::Call(to) = to {
+            self.transfer(sender, to, value)?;
+        }
+        self.txs.push(TransactionWithSigner {
+            msg: Transaction::Legacy(LegacyTx {
+                nonce: acct.nonce,
+                gas_price: U256::zero(),
+                gas: TX_GAS,
+                to,
+                value,
+                data,
+                v: VPackChainId(27.into()),
+                r: 1.into(),
+                s: 1.into(),
+            }),
+            signer: sender,
+        });
+        Ok(())
+    }
+
+    /// Writes the block being built, with the transactions added since the
+    /// last block and the state root after its changes, and begins the next.
+    /// Returns the hash of the block written.
+    pub fn seal(&mut self) -> Result<H256> {
+        let num = self.block();
+        self.db.write::<SyncStage>(StageId::Execution, num)?;
+        self.db.promote_hashed_state_since(*num - 1)?;
+        let txs = std::mem::take(&mut self.txs);
+        for tx in &txs {
+            self.db
+                .write_transaction_block_number(tx.msg.tx_hash(), (*num).into())?;
+        }
+        let header = BlockHeader {
+            parent_hash: self.chain.hashes[*num as usize - 1],
+            uncle_hash: EMPTY_LIST_HASH,
+            root: self.db.state_root()?,
+            difficulty: 1.into(),
+            number: (*num).into(),
+            gas_limit: GAS_LIMIT,
+            gas_used: TX_GAS * txs.len() as u64,
+            time: *num * BLOCK_TIME,
+            ..Default::default()
+        };
+        let hash = self.db.write_block(Block {
+            header,
+            transactions: txs,
+            uncles: vec![],
+            withdrawals: None,
+        })?;
+        self.chain.hashes.push(hash);
+        Ok(hash)
+    }
+
+    /// Writes the history indices of the sealed blocks and points the head
+    /// and every stage at the last of them. Changes made since the last
+    /// [`seal`](Self::seal) are left in the db, but belong to no block.
+    pub fn finish(self) -> Result<Chain> {
+        for (adr, blocks) in self.account_history {
+            self.db.write_account_history(adr, blocks)?;
+        }
+        for ((adr, slot), blocks) in self.storage_history {
+            self.db.write_storage_history(adr, slot, blocks)?;
+        }
+        let head = self.chain.head();
+        let hash = self.chain.hashes[*head as usize];
+        self.db.write_head_header_hash(hash)?;
+        self.db.write_head_block_hash(hash)?;
+        for &stage in StageId::ALL {
+            self.db.write::<SyncStage>(stage, head)?;
+        }
+        Ok(self.chain)
+    }
+}
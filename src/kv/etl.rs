@@ -0,0 +1,288 @@
+//! Sorted bulk loading of tables, after Erigon's etl package.
+//!
+//! Putting many entries to mdbx in random order is slow, as each put lands on
+//! a different page. A [`Collector`] instead buffers the entries for a table in
+//! memory, spilling them to temporary files in sorted runs whenever the buffer
+//! is full. Loading merges the runs and writes the table in key order, through
+//! a cursor in append mode when the table allows it.
+use mdbx::{DatabaseFlags, WriteFlags, RW};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::PathBuf,
+};
+
+use crate::{
+    kv::{
+        traits::{DbFlags, DefaultFlags, Table, TableEncode},
+        MdbxTx,
+    },
+    Result,
+};
+
+/// The memory budget of a [`Collector`] created with [`Collector::new`].
+pub const DEFAULT_BUFFER_SIZE: usize = 256 << 20;
+
+// Each buffered entry also holds two vec headers.
+const ENTRY_OVERHEAD: usize = 2 * std::mem::size_of::<Vec<u8>>();
+
+type Entry = (Vec<u8>, Vec<u8>);
+
+/// Collects the entries for table `T` in any order, to be written in key order
+/// by [`Collector::load`].
+///
+/// If a key is collected more than once, the last value collected at the key
+/// is written. For a dupsorted table, every distinct value at the key is
+/// written instead.
+#[derive(Debug)]
+pub struct Collector<T> {
+    buf: Vec<Entry>,
+    buf_bytes: usize,
+    buffer_size: usize,
+    tmp_dir: PathBuf,
+    runs: Vec<File>,
+    _table: PhantomData<T>,
+}
+
+impl<T: DefaultFlags> Default for Collector<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DefaultFlags> Collector<T> {
+    /// Creates a collector that buffers up to [`DEFAULT_BUFFER_SIZE`] bytes
+    /// of entries before spilling them to disk.
+    pub fn new() -> Self {
+        Self::with_buffer_size(DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Creates a collector that buffers up to `buffer_size` bytes of entries
+    /// before spilling them to disk.
+    pub fn with_buffer_size(buffer_size: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            buf_bytes: 0,
+            buffer_size,
+            tmp_dir: std::env::temp_dir(),
+            runs: Vec::new(),
+            _table: PhantomData,
+        }
+    }
+
+    /// Sets the directory that sorted runs are spilled to, by default the
+    /// system's temporary directory. The files are deleted once closed.
+    pub fn tmp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.tmp_dir = dir.into();
+        self
+    }
+
+    /// Returns the number of sorted runs spilled to disk so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Adds an entry to the collector.
+    pub fn collect<'tx>(&mut self, key: T::Key, val: T::Value) -> Result<()>
+    where
+        T: Table<'tx>,
+    {
+        self.collect_raw(
+            key.encode().as_ref().to_vec(),
+            val.encode().as_ref().to_vec(),
+        )
+    }
+
+    /// Adds an already encoded entry to the collector.
+    pub fn collect_raw(&mut self, key: Vec<u8>, val: Vec<u8>) -> Result<()> {
+        self.buf_bytes += key.len() + val.len() + ENTRY_OVERHEAD;
+        self.buf.push((key, val));
+        if self.buf_bytes >= self.buffer_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn is_dupsort() -> bool {
+        T::Flags::FLAGS.contains(DatabaseFlags::DUP_SORT)
+    }
+
+    // Sorts the buffer, leaving one entry per key, holding the last value
+    // collected at the key, unless the table is dupsorted.
+    fn sort_buf(&mut self) {
+        if Self::is_dupsort() {
+            self.buf.sort_unstable();
+            self.buf.dedup();
+        } else {
+            // the sort is stable, so the values at a key stay in the order
+            // they were collected
+            self.buf.sort_by(|a, b| a.0.cmp(&b.0));
+            self.buf.dedup_by(|later, kept| {
+                let dup = later.0 == kept.0;
+                if dup {
+                    std::mem::swap(&mut later.1, &mut kept.1);
+                }
+                dup
+            });
+        }
+    }
+
+    // Writes the buffer to a temporary file as a sorted run of
+    // `len(key) || key || len(val) || val` entries.
+    fn spill(&mut self) -> Result<()> {
+        self.sort_buf();
+        let mut w = BufWriter::new(tempfile::tempfile_in(&self.tmp_dir)?);
+        for (key, val) in self.buf.drain(..) {
+            for b in [key, val] {
+                w.write_all(&(b.len() as u32).to_be_bytes())?;
+                w.write_all(&b)?;
+            }
+        }
+        let mut file = w.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        self.buf_bytes = 0;
+        Ok(())
+    }
+
+    /// Writes the collected entries to the table in key order, returning the
+    /// number of entries written.
+    ///
+    /// If the table is empty, or every collected key sorts after the last key
+    /// in the table, the entries are appended, which is much faster than a
+    /// put. Otherwise, they are upserted.
+    pub fn load<'tx>(mut self, tx: &'tx MdbxTx<'_, RW>) -> Result<usize>
+    where
+        T: Table<'tx>,
+    {
+        let dupsort = Self::is_dupsort();
+        self.sort_buf();
+        // the buffer holds the latest entries, so it is merged as the last run
+        let mut runs = std::mem::take(&mut self.runs)
+            .into_iter()
+            .map(|file| Run::File(BufReader::new(file)))
+            .collect::<Vec<_>>();
+        runs.push(Run::Mem(std::mem::take(&mut self.buf).into_iter()));
+
+        let mut heap = BinaryHeap::new();
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some((key, val)) = run.next()? {
+                heap.push(Head::new(key, val, i, dupsort));
+            }
+        }
+
+        let mut cur = tx.cursor::<T, T::Flags>(tx.open_db()?)?;
+        let last = cur.inner.last::<Cow<'_, [u8]>, Cow<'_, [u8]>>()?;
+        let append = match (last, heap.peek()) {
+            (Some((last, _)), Some(first)) => first.key.as_slice() > last.as_ref(),
+            _ => true,
+        };
+        let flags = match (append, dupsort) {
+            (true, true) => WriteFlags::APPEND_DUP,
+            (true, false) => WriteFlags::APPEND,
+            (false, _) => WriteFlags::UPSERT,
+        };
+
+        let mut written = 0;
+        let mut pending: Option<Entry> = None;
+        while let Some(head) = heap.pop() {
+            if let Some((key, val)) = runs[head.run].next()? {
+                heap.push(Head::new(key, val, head.run, dupsort));
+            }
+            let entry = (head.key, head.val);
+            if let Some(prev) = pending.take() {
+                // runs are merged oldest first, so a later value at the same
+                // key replaces the earlier one
+                let replaced = prev.0 == entry.0 && (!dupsort || prev.1 == entry.1);
+                if !replaced {
+                    cur.inner.put(&prev.0[..], &prev.1[..], flags)?;
+                    written += 1;
+                }
+            }
+            pending = Some(entry);
+        }
+        if let Some((key, val)) = pending {
+            cur.inner.put(&key[..], &val[..], flags)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+// A sorted run of entries, either spilled to a file or still in memory.
+enum Run {
+    File(BufReader<File>),
+    Mem(std::vec::IntoIter<Entry>),
+}
+
+impl Run {
+    fn next(&mut self) -> Result<Option<Entry>> {
+        match self {
+            Self::Mem(entries) => Ok(entries.next()),
+            Self::File(r) => {
+                let key = match read_field(r) {
+                    Ok(key) => key,
+                    Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+                Ok(Some((key, read_field(r)?)))
+            }
+        }
+    }
+}
+
+fn read_field(r: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    r.read_exact(&mut len)?;
+    let mut b = vec![0; u32::from_be_bytes(len) as usize];
+    r.read_exact(&mut b)?;
+    Ok(b)
+}
+
+// The next entry of a run, ordered so that the BinaryHeap, a max-heap, pops
+// the smallest key first, and the oldest run first among equal keys.
+struct Head {
+    key: Vec<u8>,
+    val: Vec<u8>,
+    run: usize,
+    dupsort: bool,
+}
+
+impl Head {
+    fn new(key: Vec<u8>, val: Vec<u8>, run: usize, dupsort: bool) -> Self {
+        Self {
+            key,
+            val,
+            run,
+            dupsort,
+        }
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut ord = self.key.cmp(&other.key);
+        if self.dupsort {
+            ord = ord.then_with(|| self.val.cmp(&other.val));
+        }
+        ord.then(self.run.cmp(&other.run)).reverse()
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
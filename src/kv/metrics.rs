@@ -0,0 +1,46 @@
+//! Counters of table accesses, reported through the facade of the `metrics`
+//! crate when the `metrics` feature is enabled, and no-ops otherwise. Each
+//! metric is labeled with the `table` it was recorded for, by the name it is
+//! stored under in the db, or `raw` for a table opened by name with
+//! [`MdbxTx::open_raw`](super::MdbxTx::open_raw):
+//!
+//! - `erigon_db_reads_total`: point reads, with [`MdbxTx::get`](super::MdbxTx::get)
+//!   or [`MdbxTx::get_raw`](super::MdbxTx::get_raw).
+//! - `erigon_db_seeks_total`: cursor seeks, including the first lookup of a
+//!   walk. A batched lookup such as [`MdbxCursor::seek_many`](super::MdbxCursor::seek_many)
+//!   counts one seek per key.
+//! - `erigon_db_decodes_total`: entries decoded, including those that failed.
+//! - `erigon_db_decode_errors_total`: entries that failed to decode.
+//! - `erigon_db_walk_length`: a histogram of the number of entries yielded by
+//!   each walk, recorded when the walker is dropped.
+//!
+//! The metrics are only collected once the application installs a recorder,
+//! such as the one of `metrics-exporter-prometheus`.
+use crate::{kv::traits::Table, Result};
+
+pub(crate) fn read<'tx, T: Table<'tx>>() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!("erigon_db_reads_total", "table" => T::LABEL).increment(1);
+}
+
+pub(crate) fn seek<'tx, T: Table<'tx>>() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!("erigon_db_seeks_total", "table" => T::LABEL).increment(1);
+}
+
+/// Counts the decode of an entry of `T`, passing its result through.
+pub(crate) fn decode<'tx, T: Table<'tx>, V>(res: Result<V>) -> Result<V> {
+    #[cfg(feature = "metrics")]
+    {
+        ::metrics::counter!("erigon_db_decodes_total", "table" => T::LABEL).increment(1);
+        if res.is_err() {
+            ::metrics::counter!("erigon_db_decode_errors_total", "table" => T::LABEL).increment(1);
+        }
+    }
+    res
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn walk_length<'tx, T: Table<'tx>>(len: u64) {
+    ::metrics::histogram!("erigon_db_walk_length", "table" => T::LABEL).record(len as f64);
+}
@@ -1,25 +1,16 @@
-use eyre::Result;
-use mdbx::{DatabaseFlags, EnvironmentKind, NoWriteMap, TransactionKind, WriteFlags, RO, RW};
-use std::{borrow::Cow, path::Path};
+use crate::{error::DecodeError, Result};
+use mdbx::{DatabaseFlags, NoWriteMap, TransactionKind, WriteFlags, RO, RW};
+use std::{borrow::Cow, collections::HashMap, ops::Range, path::Path, sync::Mutex};
 
+pub mod etl;
+pub mod metrics;
+mod spans;
 pub mod tables;
 pub mod traits;
 
-use tables::TableHandle;
+use tables::{NoFlags, RawTable, TableHandle};
 use traits::{DbFlags, DbName, DupSort, Mode, Table, TableDecode, TableEncode};
 
-fn open_env<E: EnvironmentKind>(
-    path: &Path,
-    num_tables: usize,
-    flags: mdbx::EnvironmentFlags,
-) -> Result<mdbx::Environment<E>> {
-    mdbx::Environment::new()
-        .set_max_dbs(num_tables)
-        .set_flags(flags)
-        .open(path)
-        .map_err(From::from)
-}
-
 /// A wrapper around [`mdbx::Environment`].
 ///
 /// We use this wrapper to make a few alterations on the default behavior:
@@ -47,19 +38,9 @@ impl<M> MdbxEnv<M> {
 impl<M: Mode> MdbxEnv<M> {
     /// Open an mdbx environment. Note that even when opening an environment in
     /// read-only mode, mdbx will still modify the LCK-file, unless the filesystem
-    /// is read-only.
+    /// is read-only. See [`MdbxEnv::open_readonly_no_lock`] to avoid this.
     pub fn open(path: &Path, num_tables: usize, flags: EnvFlags) -> Result<Self> {
-        let mode = if M::is_writeable() {
-            mdbx::Mode::ReadWrite {
-                sync_mode: mdbx::SyncMode::Durable,
-            }
-        } else {
-            mdbx::Mode::ReadOnly
-        };
-        Ok(Self {
-            inner: open_env(path, num_tables, flags.with_mode(mode))?,
-            _mode: std::marker::PhantomData,
-        })
+        MdbxEnvBuilder::new(num_tables, flags).open(path)
     }
 
     /// Create a read-only mdbx transaction.
@@ -69,6 +50,15 @@ impl<M: Mode> MdbxEnv<M> {
 }
 
 impl MdbxEnv<RO> {
+    /// Open an mdbx environment read-only, in exclusive mode, so that mdbx
+    /// runs without the LCK-file when it cannot be created or written. This
+    /// works on a read-only filesystem or directory, e.g. a mounted snapshot
+    /// or backup of the chaindata, but fails with MDBX_BUSY if another process
+    /// has the environment open. The `exclusive` field of `flags` is ignored.
+    pub fn open_readonly_no_lock(path: &Path, num_tables: usize, flags: EnvFlags) -> Result<Self> {
+        MdbxEnvBuilder::new(num_tables, flags).open_readonly_no_lock(path)
+    }
+
     /// Create a read-only mdbx transaction.
     pub fn begin(&self) -> Result<MdbxTx<'_, RO>> {
         Ok(MdbxTx::new(self.inner.begin_ro_txn()?))
@@ -82,6 +72,112 @@ impl MdbxEnv<RW> {
     }
 }
 
+/// Configures and opens an [`MdbxEnv`]. Options left unset keep the mdbx
+/// defaults, except for the sync mode, which defaults to durable.
+#[derive(Clone, Debug)]
+pub struct MdbxEnvBuilder {
+    num_tables: usize,
+    flags: EnvFlags,
+    sync_mode: mdbx::SyncMode,
+    max_readers: Option<u32>,
+    size: Option<Range<usize>>,
+    growth_step: Option<isize>,
+    shrink_threshold: Option<isize>,
+    page_size: Option<usize>,
+}
+
+impl MdbxEnvBuilder {
+    pub fn new(num_tables: usize, flags: EnvFlags) -> Self {
+        Self {
+            num_tables,
+            flags,
+            sync_mode: mdbx::SyncMode::Durable,
+            max_readers: None,
+            size: None,
+            growth_step: None,
+            shrink_threshold: None,
+            page_size: None,
+        }
+    }
+
+    /// Sets how writes are flushed to disk when a transaction commits. Only
+    /// used when the environment is opened read-write. The non-durable modes
+    /// trade the last commits, or the whole db with `UtterlyNoSync`, on a
+    /// system crash for much faster writes.
+    pub fn sync_mode(mut self, sync_mode: mdbx::SyncMode) -> Self {
+        self.sync_mode = sync_mode;
+        self
+    }
+
+    /// Sets the maximum number of concurrent read transactions.
+    pub fn max_readers(mut self, max_readers: u32) -> Self {
+        self.max_readers = Some(max_readers);
+        self
+    }
+
+    /// Sets the lower and upper bounds, in bytes, of the size of the db file.
+    pub fn map_size(mut self, size: Range<usize>) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Sets the number of bytes by which the db file grows when it is full.
+    pub fn growth_step(mut self, growth_step: isize) -> Self {
+        self.growth_step = Some(growth_step);
+        self
+    }
+
+    /// Sets the number of free bytes at the end of the db file above which
+    /// the file is shrunk.
+    pub fn shrink_threshold(mut self, shrink_threshold: isize) -> Self {
+        self.shrink_threshold = Some(shrink_threshold);
+        self
+    }
+
+    /// Sets the page size, in bytes, of a new db. An existing db keeps the
+    /// page size it was created with.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Opens the environment read-only without touching the LCK-file. See
+    /// [`MdbxEnv::open_readonly_no_lock`].
+    pub fn open_readonly_no_lock(&self, path: &Path) -> Result<MdbxEnv<RO>> {
+        let mut builder = self.clone();
+        builder.flags.exclusive = true;
+        builder.open(path)
+    }
+
+    /// Opens the environment at `path`.
+    pub fn open<M: Mode>(&self, path: &Path) -> Result<MdbxEnv<M>> {
+        let mode = if M::is_writeable() {
+            mdbx::Mode::ReadWrite {
+                sync_mode: self.sync_mode,
+            }
+        } else {
+            mdbx::Mode::ReadOnly
+        };
+        let mut builder = mdbx::Environment::<NoWriteMap>::new();
+        builder
+            .set_max_dbs(self.num_tables)
+            .set_flags(self.flags.with_mode(mode))
+            .set_geometry(mdbx::Geometry {
+                size: self.size.clone(),
+                growth_step: self.growth_step,
+                shrink_threshold: self.shrink_threshold,
+                page_size: self.page_size.map(mdbx::PageSize::Set),
+            });
+        if let Some(max_readers) = self.max_readers {
+            builder.set_max_readers(max_readers);
+        }
+        Ok(MdbxEnv {
+            inner: builder.open(path)?,
+            _mode: std::marker::PhantomData,
+        })
+    }
+}
+
 /// Holds all [`mdbx::EnvironmentFlags`] except the `mode` field.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct EnvFlags {
@@ -124,50 +220,209 @@ impl EnvFlags {
 }
 
 /// A wrapper around [`mdbx::Transaction`].
+///
+/// Table handles are opened once per transaction, the first time each table
+/// is opened with a given set of flags, so repeated reads from the same table
+/// don't pay for `mdbx_dbi_open` again.
 #[derive(Debug)]
 pub struct MdbxTx<'env, K: TransactionKind> {
     pub inner: mdbx::Transaction<'env, K, NoWriteMap>,
+    dbis: Mutex<DbiCache>,
 }
+
+// The handles opened by a transaction, by table name, along with the flags
+// each was opened with. Each handle is boxed so that it keeps its address as
+// the cache grows, and is only dropped along with the transaction.
+type DbiCache = HashMap<String, Vec<(DatabaseFlags, Box<mdbx::Database<'static>>)>>;
+
 impl<'env, M> MdbxTx<'env, M>
 where
     M: TransactionKind + Mode,
 {
+    /// Opens the table, or returns the handle opened with the same flags by
+    /// an earlier call. Fails with `MDBX_INCOMPATIBLE` if the table holds
+    /// entries and was created with other flags.
     pub fn open_db<Db: DbName, Flags: DbFlags>(&self) -> Result<TableHandle<'_, Db, Flags>> {
+        let _span = spans::open_db(Db::NAME);
         let mut flags = Flags::FLAGS;
         // If the transaction is read-write, create the database if it does not exist already.
         if M::is_writeable() {
             flags |= DatabaseFlags::CREATE;
         }
-        Ok(TableHandle::new(
-            self.inner.open_db_with_flags(Some(Db::NAME), flags)?,
-        ))
+        let db = self.cached_db(Db::NAME, flags, || {
+            self.inner.open_db_with_flags(Some(Db::NAME), flags)
+        })?;
+        Ok(TableHandle::new(db))
+    }
+
+    // Returns the handle of table `name` cached under `flags`, or opens it with
+    // `open` and caches it.
+    fn cached_db<'tx>(
+        &'tx self,
+        name: &str,
+        flags: DatabaseFlags,
+        open: impl FnOnce() -> std::result::Result<mdbx::Database<'tx>, mdbx::Error>,
+    ) -> Result<&'tx mdbx::Database<'tx>> {
+        let mut dbis = self.dbis.lock().unwrap();
+        if !dbis.contains_key(name) {
+            dbis.insert(name.to_owned(), Vec::new());
+        }
+        let handles = dbis.get_mut(name).unwrap();
+        let db: *const mdbx::Database<'static> =
+            match handles.iter().position(|(cached, _)| *cached == flags) {
+                Some(i) => &*handles[i].1,
+                None => {
+                    // SAFETY: the handle only borrows the transaction in its
+                    // lifetime marker. It is stored with a 'static marker, but
+                    // is only handed out below, as a borrow of `self`.
+                    let db = unsafe {
+                        std::mem::transmute::<mdbx::Database<'tx>, mdbx::Database<'static>>(open()?)
+                    };
+                    handles.push((flags, Box::new(db)));
+                    &*handles.last().unwrap().1
+                }
+            };
+        // SAFETY: the box holding the handle is neither moved nor dropped
+        // until the transaction is, so it outlives the borrow of `self`.
+        Ok(unsafe { &*(db as *const mdbx::Database<'tx>) })
+    }
+
+    /// Returns the entry count and page usage of the table.
+    pub fn table_stat<Db: DbName, Flags: DbFlags>(&self) -> Result<TableStat> {
+        let db = self.open_db::<Db, Flags>()?;
+        Ok(self.inner.db_stat(db.as_ref())?.into())
+    }
+
+    /// Returns the entry count and page usage of the table with the given name,
+    /// or `None` if no such table exists in the environment.
+    pub fn table_stat_by_name(&self, name: &str) -> Result<Option<TableStat>> {
+        let db = match self.inner.open_db(Some(name)) {
+            Ok(db) => db,
+            Err(mdbx::Error::NotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(self.inner.db_stat(&db)?.into()))
+    }
+
+    /// Returns the flags the table with the given name was created with, or
+    /// `None` if no such table exists in the environment.
+    pub fn table_flags_by_name(&self, name: &str) -> Result<Option<DatabaseFlags>> {
+        let db = match self.inner.open_db(Some(name)) {
+            Ok(db) => db,
+            Err(mdbx::Error::NotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(self.inner.db_flags(&db)?))
+    }
+
+    /// Opens an existing table by name, without a typed table definition. Keys
+    /// and values are read and written as raw bytes. Unlike [`Self::open_db`],
+    /// this never creates the table.
+    pub fn open_raw(&self, name: &str) -> Result<TableHandle<'_, RawTable, NoFlags>> {
+        let db = self.cached_db(name, DatabaseFlags::empty(), || {
+            self.inner.open_db(Some(name))
+        })?;
+        Ok(TableHandle::new(db))
+    }
+
+    /// Returns the names of all tables in the environment, in sorted order.
+    /// Named tables are stored as keys in mdbx's unnamed main table, so this
+    /// includes tables that are not defined by the crate.
+    pub fn list_tables(&self) -> Result<Vec<String>> {
+        let main = self.inner.open_db(None)?;
+        let mut cur = self.inner.cursor(&main)?;
+        let mut names = Vec::new();
+        let mut next: Option<(Cow<'_, [u8]>, Cow<'_, [u8]>)> = cur.first()?;
+        while let Some((k, _)) = next {
+            names.push(String::from_utf8_lossy(&k).into_owned());
+            next = cur.next()?;
+        }
+        Ok(names)
+    }
+}
+
+/// Statistics for a single table, as reported by `mdbx_dbi_stat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableStat {
+    /// Number of key/value pairs in the table.
+    pub entries: usize,
+    /// Depth of the table's B-tree.
+    pub depth: u32,
+    pub branch_pages: usize,
+    pub leaf_pages: usize,
+    pub overflow_pages: usize,
+    /// Page size in bytes. This is the same for every table in the environment.
+    pub page_size: u32,
+}
+impl TableStat {
+    /// Total number of pages used by the table.
+    pub fn pages(&self) -> usize {
+        self.branch_pages + self.leaf_pages + self.overflow_pages
+    }
+    /// Approximate on-disk size of the table in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.pages() as u64 * self.page_size as u64
+    }
+}
+impl From<mdbx::Stat> for TableStat {
+    fn from(s: mdbx::Stat) -> Self {
+        Self {
+            entries: s.entries(),
+            depth: s.depth(),
+            branch_pages: s.branch_pages(),
+            leaf_pages: s.leaf_pages(),
+            overflow_pages: s.overflow_pages(),
+            page_size: s.page_size(),
+        }
     }
 }
 
 impl<'env, K: TransactionKind> MdbxTx<'env, K> {
     pub fn new(inner: mdbx::Transaction<'env, K, NoWriteMap>) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            dbis: Default::default(),
+        }
     }
 
     pub fn get<'tx, T, F>(
         &'tx self,
-        db: TableHandle<'tx, T::Name, F>,
+        db: TableHandle<'tx, T, F>,
         key: T::Key,
     ) -> Result<Option<T::Value>>
     where
         T: Table<'tx>,
         F: DbFlags,
     {
+        metrics::read::<T>();
+        let key = key.encode();
+        let _span = spans::get::<T>(key.as_ref().len());
         self.inner
-            .get(db.as_ref(), key.encode().as_ref())?
+            .get(db.as_ref(), key.as_ref())?
             .map(decode_one::<T>)
             .transpose()
     }
 
-    pub fn cursor<'tx, T, F>(
+    /// Returns the value at `key` without decoding it. The value borrows from
+    /// the memory map, unless it was written in this transaction.
+    pub fn get_raw<'tx, T, F>(
         &'tx self,
-        db: TableHandle<'tx, T::Name, F>,
-    ) -> Result<MdbxCursor<'tx, K, T>>
+        db: TableHandle<'tx, T, F>,
+        key: T::Key,
+    ) -> Result<Option<Cow<'tx, [u8]>>>
+    where
+        T: Table<'tx>,
+        F: DbFlags,
+    {
+        metrics::read::<T>();
+        let key = key.encode();
+        let _span = spans::get::<T>(key.as_ref().len());
+        self.inner
+            .get(db.as_ref(), key.as_ref())
+            .map_err(From::from)
+    }
+
+    pub fn cursor<'tx, T, F>(&'tx self, db: TableHandle<'tx, T, F>) -> Result<MdbxCursor<'tx, K, T>>
     where
         T: Table<'tx>,
         F: DbFlags,
@@ -179,7 +434,7 @@ impl<'env, K: TransactionKind> MdbxTx<'env, K> {
 impl<'env> MdbxTx<'env, RW> {
     pub fn put<'tx, T, F>(
         &'tx self,
-        db: TableHandle<'tx, T::Name, F>,
+        db: TableHandle<'tx, T, F>,
         key: T::Key,
         val: T::Value,
     ) -> Result<()>
@@ -192,6 +447,34 @@ impl<'env> MdbxTx<'env, RW> {
             .map_err(From::from)
     }
 
+    /// Deletes the entry at `key`. In a dupsorted table, passing a value
+    /// deletes only that duplicate, while `None` deletes all duplicates at the
+    /// key. Returns false if there was nothing to delete.
+    pub fn del<'tx, T, F>(
+        &'tx self,
+        db: TableHandle<'tx, T, F>,
+        key: T::Key,
+        val: Option<T::Value>,
+    ) -> Result<bool>
+    where
+        T: Table<'tx>,
+        F: DbFlags,
+    {
+        let val = val.map(TableEncode::encode);
+        self.inner
+            .del(db.as_ref(), key.encode(), val.as_ref().map(AsRef::as_ref))
+            .map_err(From::from)
+    }
+
+    /// Deletes every entry in the table, leaving it empty.
+    pub fn clear<'tx, Db, F>(&'tx self, db: TableHandle<'tx, Db, F>) -> Result<()>
+    where
+        Db: DbName,
+        F: DbFlags,
+    {
+        self.inner.clear_db(db.as_ref()).map_err(From::from)
+    }
+
     /// Commit the transaction. The Drop impl for mdbx::Transaction will take care
     /// of this, but use this method explicitly if you wish to handle any errors.
     pub fn commit(self) -> Result<bool> {
@@ -230,12 +513,72 @@ where
     where
         T::Key: TableDecode,
     {
+        metrics::seek::<T>();
+        let key = key.encode();
+        let _span = spans::seek::<T>(key.as_ref().len());
         self.inner
-            .set_range(key.encode().as_ref())?
+            .set_range(key.as_ref())?
             .map(decode::<T>)
             .transpose()
     }
 
+    /// Returns the value at exactly `key`, if present.
+    pub fn seek_exact(&mut self, key: T::Key) -> Result<Option<T::Value>> {
+        metrics::seek::<T>();
+        let key = key.encode();
+        let _span = spans::seek::<T>(key.as_ref().len());
+        self.inner
+            .set(key.as_ref())?
+            .map(decode_one::<T>)
+            .transpose()
+    }
+
+    /// Looks up each of the given keys, returning the values in input order.
+    /// Keys are visited in sorted order so the cursor only ever moves forward
+    /// through the table.
+    pub fn seek_many(
+        &mut self,
+        keys: impl IntoIterator<Item = T::Key>,
+    ) -> Result<Vec<Option<T::Value>>> {
+        let mut keys = keys
+            .into_iter()
+            .map(TableEncode::encode)
+            .enumerate()
+            .collect::<Vec<_>>();
+        keys.sort_unstable_by(|a, b| a.1.as_ref().cmp(b.1.as_ref()));
+
+        let mut out = std::iter::repeat_with(|| None)
+            .take(keys.len())
+            .collect::<Vec<_>>();
+        for (i, key) in keys {
+            metrics::seek::<T>();
+            out[i] = self
+                .inner
+                .set(key.as_ref())?
+                .map(decode_one::<T>)
+                .transpose()?;
+        }
+        Ok(out)
+    }
+
+    /// Returns the raw (key, value) pair at the first key >= `key`, without
+    /// decoding or copying it.
+    pub fn seek_raw(
+        &mut self,
+        key: T::SeekKey,
+    ) -> Result<Option<(Cow<'tx, [u8]>, Cow<'tx, [u8]>)>> {
+        metrics::seek::<T>();
+        self.inner
+            .set_range(key.encode().as_ref())
+            .map_err(From::from)
+    }
+
+    /// Returns the next raw (key, value) pair in the table, without decoding
+    /// or copying it.
+    pub fn next_raw(&mut self) -> Result<Option<(Cow<'tx, [u8]>, Cow<'tx, [u8]>)>> {
+        self.inner.next().map_err(From::from)
+    }
+
     /// Returns the first key/value pair in the table
     pub fn first(&mut self) -> Result<Option<(T::Key, T::Value)>>
     where
@@ -244,6 +587,14 @@ where
         self.inner.first()?.map(decode::<T>).transpose()
     }
 
+    /// Returns the last key/value pair in the table
+    pub fn last(&mut self) -> Result<Option<(T::Key, T::Value)>>
+    where
+        T::Key: TableDecode,
+    {
+        self.inner.last()?.map(decode::<T>).transpose()
+    }
+
     /// Returns the first value in the table without attempting to decode the returned key.
     pub fn first_val(&mut self) -> Result<Option<T::Value>> {
         self.inner.first()?.map(decode_val::<T>).transpose()
@@ -257,20 +608,48 @@ where
         self.inner.next()?.map(decode::<T>).transpose()
     }
 
+    /// Returns the next value in the table without attempting to decode the
+    /// returned key.
+    pub fn next_val(&mut self) -> Result<Option<T::Value>> {
+        self.inner.next()?.map(decode_val::<T>).transpose()
+    }
+
     /// Returns an owned iterator over (key, value) pairs beginning at start_key.
-    pub fn walk(
-        mut self,
-        start_key: T::Key,
-    ) -> Result<impl Iterator<Item = Result<(<T as Table<'tx>>::Key, <T as Table<'tx>>::Value)>>>
+    pub fn walk(mut self, start_key: T::Key) -> Result<Walker<'tx, K, T>>
     where
         T::Key: TableDecode,
     {
+        metrics::seek::<T>();
         let first = self
             .inner
             .set_range(start_key.encode().as_ref())?
             .map(decode::<T>);
 
-        Ok(Walker { cur: self, first })
+        Ok(Walker {
+            cur: self,
+            done: first.is_none(),
+            first,
+            #[cfg(feature = "metrics")]
+            len: 0,
+        })
+    }
+
+    /// Returns an owned iterator over values beginning at start_key, without
+    /// attempting to decode the returned keys.
+    pub fn walk_val(mut self, start_key: T::Key) -> Result<ValWalker<'tx, K, T>> {
+        metrics::seek::<T>();
+        let first = self
+            .inner
+            .set_range(start_key.encode().as_ref())?
+            .map(decode_val::<T>);
+
+        Ok(ValWalker {
+            cur: self,
+            done: first.is_none(),
+            first,
+            #[cfg(feature = "metrics")]
+            len: 0,
+        })
     }
 
     /// Returns an iterator over (key, value) pairs beginning at start_key. If the table
@@ -283,6 +662,7 @@ where
     where
         T::Key: TableDecode,
     {
+        metrics::seek::<T>();
         self.inner
             .iter_from(start_key.encode().as_ref())
             .map(|res| decode::<T>(res?))
@@ -296,6 +676,7 @@ where
         &mut self,
         start_key: T::Key,
     ) -> impl Iterator<Item = Result<<T as Table<'tx>>::Value>> + '_ {
+        metrics::seek::<T>();
         self.inner
             .iter_from(start_key.encode().as_ref())
             .map(|res| decode_val::<T>(res?))
@@ -312,13 +693,16 @@ where
     /// returned includes the subkey prefix, meaning you likely want to decode
     /// it into `(subkey, value_at_subkey)`.
     ///
-    /// If you want to find an exact subkey in the dupsort "sub table", you need
-    /// to check that the returned value begins with your subkey. If it does not,
-    /// then the cursor seeked past the requested subkey without a match, meaning
-    /// the table does not contain a value that begins with the provided subkey.
+    /// If the returned value does not begin with your subkey, then the cursor
+    /// seeked past the requested subkey without a match, meaning the table does
+    /// not contain a value that begins with the provided subkey. Use
+    /// [`seek_exact_dup`](Self::seek_exact_dup) to find an exact subkey.
     pub fn seek_dup(&mut self, key: T::Key, subkey: T::Subkey) -> Result<Option<T::Value>> {
+        metrics::seek::<T>();
+        let key = key.encode();
+        let _span = spans::seek::<T>(key.as_ref().len());
         self.inner
-            .get_both_range(key.encode().as_ref(), subkey.encode().as_ref())?
+            .get_both_range(key.as_ref(), subkey.encode().as_ref())?
             .map(decode_one::<T>)
             .transpose()
     }
@@ -344,18 +728,122 @@ where
     /// Returns an owned iterator over duplicate values for the given key. Note
     /// that the values returned include the subkey prefix, meaning you likely
     /// want to decode them into `(subkey, value_at_subkey)`.
-    pub fn walk_dup(
-        mut self,
-        key: T::Key,
-        subkey: T::Subkey,
-    ) -> Result<impl Iterator<Item = Result<<T as Table<'tx>>::Value>>> {
+    pub fn walk_dup(mut self, key: T::Key, subkey: T::Subkey) -> Result<DupWalker<'tx, K, T>> {
+        metrics::seek::<T>();
         let first = self
             .inner
             .get_both_range(key.encode().as_ref(), subkey.encode().as_ref())?
             .map(decode_one::<T>);
 
-        Ok(DupWalker { cur: self, first })
+        Ok(DupWalker {
+            cur: self,
+            done: first.is_none(),
+            first,
+            #[cfg(feature = "metrics")]
+            len: 0,
+        })
+    }
+
+    /// Finds the duplicate entry at the given key whose subkey is exactly
+    /// `subkey`, and returns the rest of its value with the subkey stripped.
+    pub fn seek_exact_dup(
+        &mut self,
+        key: T::Key,
+        subkey: T::Subkey,
+    ) -> Result<Option<T::Subvalue>> {
+        metrics::seek::<T>();
+        let key = key.encode();
+        let _span = spans::seek::<T>(key.as_ref().len());
+        let subkey = subkey.encode();
+        let subkey = subkey.as_ref();
+        let val = self
+            .inner
+            .get_both_range::<Cow<'tx, [u8]>>(key.as_ref(), subkey)?;
+        match val {
+            Some(val) if val.starts_with(subkey) => {
+                metrics::decode::<T, _>(TableDecode::decode(&val[subkey.len()..])).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Looks up each of the given subkeys at `key` as with
+    /// [`seek_exact_dup`](Self::seek_exact_dup), returning the values in
+    /// input order. Subkeys are visited in sorted order so the cursor only
+    /// ever moves forward through the duplicates.
+    pub fn seek_exact_dup_many(
+        &mut self,
+        key: T::Key,
+        subkeys: impl IntoIterator<Item = T::Subkey>,
+    ) -> Result<Vec<Option<T::Subvalue>>> {
+        let key = key.encode();
+        let mut subkeys = subkeys
+            .into_iter()
+            .map(TableEncode::encode)
+            .enumerate()
+            .collect::<Vec<_>>();
+        subkeys.sort_unstable_by(|a, b| a.1.as_ref().cmp(b.1.as_ref()));
+
+        let mut out = std::iter::repeat_with(|| None)
+            .take(subkeys.len())
+            .collect::<Vec<_>>();
+        for (i, subkey) in subkeys {
+            metrics::seek::<T>();
+            let subkey = subkey.as_ref();
+            let val = self
+                .inner
+                .get_both_range::<Cow<'tx, [u8]>>(key.as_ref(), subkey)?;
+            out[i] = match val {
+                Some(val) if val.starts_with(subkey) => Some(metrics::decode::<T, _>(
+                    TableDecode::decode(&val[subkey.len()..]),
+                )?),
+                _ => None,
+            };
+        }
+        Ok(out)
+    }
+
+    /// Returns an owned iterator over the duplicate entries at the given key,
+    /// beginning at the first one whose subkey is >= `subkey`. Each value is
+    /// decoded into its subkey and the rest of the value. All subkeys in the
+    /// table are assumed to have the encoded length of `subkey`.
+    pub fn walk_dup_from(
+        mut self,
+        key: T::Key,
+        subkey: T::Subkey,
+    ) -> Result<DupPairWalker<'tx, K, T>> {
+        metrics::seek::<T>();
+        let subkey = subkey.encode();
+        let next = self
+            .inner
+            .get_both_range(key.encode().as_ref(), subkey.as_ref())?;
+
+        Ok(DupPairWalker {
+            cur: self,
+            next,
+            subkey_len: subkey.as_ref().len(),
+            #[cfg(feature = "metrics")]
+            len: 0,
+        })
+    }
+}
+
+// Splits a dupsorted value into its subkey, of `subkey_len` bytes, and the
+// rest of the value.
+fn split_dup<'tx, T>(val: &[u8], subkey_len: usize) -> Result<(T::Subkey, T::Subvalue)>
+where
+    T: DupSort<'tx>,
+{
+    if val.len() < subkey_len {
+        return Err(DecodeError::TooShort {
+            minimum: subkey_len,
+            got: val.len(),
+        }
+        .into());
     }
+    let (subkey, rest) = val.split_at(subkey_len);
+    let res = TableDecode::decode(subkey).and_then(|k| Ok((k, TableDecode::decode(rest)?)));
+    metrics::decode::<T, _>(res)
 }
 
 // Helper functions, primarily for type inference. These save us from needing
@@ -365,78 +853,245 @@ where
     T: Table<'tx>,
     T::Key: TableDecode,
 {
-    Ok((TableDecode::decode(&kv.0)?, TableDecode::decode(&kv.1)?))
+    let res = TableDecode::decode(&kv.0).and_then(|k| Ok((k, TableDecode::decode(&kv.1)?)));
+    metrics::decode::<T, _>(res)
 }
 // Decodes only the value, ignoring the returned key.
 pub fn decode_val<'tx, T>(kv: (Cow<'tx, [u8]>, Cow<'tx, [u8]>)) -> Result<T::Value>
 where
     T: Table<'tx>,
 {
-    TableDecode::decode(&kv.1)
+    metrics::decode::<T, _>(TableDecode::decode(&kv.1))
 }
 // Decodes a single value.
 pub fn decode_one<'tx, T>(val: Cow<'tx, [u8]>) -> Result<T::Value>
 where
     T: Table<'tx>,
 {
-    TableDecode::decode(&val)
+    metrics::decode::<T, _>(TableDecode::decode(&val))
 }
 
-/// An internal struct for turning a cursor to a dupsorted table into an iterator
-/// over values in that table.
+/// An owned iterator over the (key, value) pairs of a table, returned by
+/// [`MdbxCursor::walk`]. It holds the cursor, so it can be returned from a
+/// function or stored alongside the transaction that it reads from.
 ///
-/// See [Akula](https://github.com/akula-bft/akula/blob/1800ac77b979d410bea5ff3bcd2617cb302d66fe/src/kv/mdbx.rs#L432)
+/// See [Akula](https://github.com/akula-bft/akula/blob/1800ac77b979d410bea5ff3bcd2617cb302d66fe/src/kv/mdbx.rs#L319)
 /// for a much more interesting approach using generators.
-struct DupWalker<'tx, K, T>
+pub struct Walker<'tx, K, T>
 where
     K: TransactionKind,
     T: Table<'tx>,
 {
-    pub cur: MdbxCursor<'tx, K, T>,
-    pub first: Option<Result<T::Value>>,
+    cur: MdbxCursor<'tx, K, T>,
+    first: Option<Result<(T::Key, T::Value)>>,
+    // Set once there are no more pairs. If the first lookup finds nothing,
+    // the cursor is left unpositioned, and mdbx would answer a NEXT with the
+    // first entry in the table.
+    done: bool,
+    // the number of pairs returned, for the walk length metric
+    #[cfg(feature = "metrics")]
+    len: u64,
 }
 
-impl<'tx, K, T> std::iter::Iterator for DupWalker<'tx, K, T>
+#[cfg(feature = "metrics")]
+impl<'tx, K, T> Drop for Walker<'tx, K, T>
 where
     K: TransactionKind,
-    T: DupSort<'tx>,
+    T: Table<'tx>,
+{
+    fn drop(&mut self) {
+        metrics::walk_length::<T>(self.len);
+    }
+}
+
+impl<'tx, K, T> std::iter::Iterator for Walker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: Table<'tx>,
+    T::Key: TableDecode,
+{
+    type Item = Result<(T::Key, T::Value)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let first = self.first.take();
+        if first.is_some() {
+            #[cfg(feature = "metrics")]
+            {
+                self.len += 1;
+            }
+            return first;
+        }
+        let next = self.cur.next().transpose();
+        self.done = next.is_none();
+        #[cfg(feature = "metrics")]
+        {
+            self.len += u64::from(!self.done);
+        }
+        next
+    }
+}
+
+/// An owned iterator over the values of a table, without decoding the keys,
+/// returned by [`MdbxCursor::walk_val`].
+pub struct ValWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: Table<'tx>,
+{
+    cur: MdbxCursor<'tx, K, T>,
+    first: Option<Result<T::Value>>,
+    // see Walker
+    done: bool,
+    #[cfg(feature = "metrics")]
+    len: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl<'tx, K, T> Drop for ValWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: Table<'tx>,
+{
+    fn drop(&mut self) {
+        metrics::walk_length::<T>(self.len);
+    }
+}
+
+impl<'tx, K, T> std::iter::Iterator for ValWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: Table<'tx>,
 {
     type Item = Result<T::Value>;
     fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
         let first = self.first.take();
         if first.is_some() {
+            #[cfg(feature = "metrics")]
+            {
+                self.len += 1;
+            }
             return first;
         }
-        self.cur.next_dup_val().transpose()
+        let next = self.cur.next_val().transpose();
+        self.done = next.is_none();
+        #[cfg(feature = "metrics")]
+        {
+            self.len += u64::from(!self.done);
+        }
+        next
     }
 }
 
-/// An internal struct for turning a cursor to a table into an iterator
-/// over key/value pairs in that table.
+/// An owned iterator over the duplicate values at a single key of a dupsorted
+/// table, returned by [`MdbxCursor::walk_dup`].
 ///
-/// See [Akula](https://github.com/akula-bft/akula/blob/1800ac77b979d410bea5ff3bcd2617cb302d66fe/src/kv/mdbx.rs#L319)
+/// See [Akula](https://github.com/akula-bft/akula/blob/1800ac77b979d410bea5ff3bcd2617cb302d66fe/src/kv/mdbx.rs#L432)
 /// for a much more interesting approach using generators.
-struct Walker<'tx, K, T>
+pub struct DupWalker<'tx, K, T>
 where
     K: TransactionKind,
     T: Table<'tx>,
 {
-    pub cur: MdbxCursor<'tx, K, T>,
-    pub first: Option<Result<(T::Key, T::Value)>>,
+    cur: MdbxCursor<'tx, K, T>,
+    first: Option<Result<T::Value>>,
+    // Set once there are no more values. If the first lookup finds nothing,
+    // the cursor is left unpositioned, and mdbx would answer a NEXT_DUP with
+    // the first entry in the table.
+    done: bool,
+    // see Walker
+    #[cfg(feature = "metrics")]
+    len: u64,
 }
 
-impl<'tx, K, T> std::iter::Iterator for Walker<'tx, K, T>
+#[cfg(feature = "metrics")]
+impl<'tx, K, T> Drop for DupWalker<'tx, K, T>
 where
     K: TransactionKind,
     T: Table<'tx>,
-    T::Key: TableDecode,
 {
-    type Item = Result<(T::Key, T::Value)>;
+    fn drop(&mut self) {
+        metrics::walk_length::<T>(self.len);
+    }
+}
+
+impl<'tx, K, T> std::iter::Iterator for DupWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: DupSort<'tx>,
+{
+    type Item = Result<T::Value>;
     fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
         let first = self.first.take();
         if first.is_some() {
+            #[cfg(feature = "metrics")]
+            {
+                self.len += 1;
+            }
             return first;
         }
-        self.cur.next().transpose()
+        let next = self.cur.next_dup_val().transpose();
+        self.done = next.is_none();
+        #[cfg(feature = "metrics")]
+        {
+            self.len += u64::from(!self.done);
+        }
+        next
+    }
+}
+
+/// An owned iterator over the duplicate values at a single key of a dupsorted
+/// table, each split into its subkey and the rest of the value, returned by
+/// [`MdbxCursor::walk_dup_from`].
+pub struct DupPairWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: Table<'tx>,
+{
+    cur: MdbxCursor<'tx, K, T>,
+    // the raw value to be returned next, looked up one step ahead so that the
+    // cursor is never moved once unpositioned
+    next: Option<Cow<'tx, [u8]>>,
+    subkey_len: usize,
+    // see Walker
+    #[cfg(feature = "metrics")]
+    len: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl<'tx, K, T> Drop for DupPairWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: Table<'tx>,
+{
+    fn drop(&mut self) {
+        metrics::walk_length::<T>(self.len);
+    }
+}
+
+impl<'tx, K, T> std::iter::Iterator for DupPairWalker<'tx, K, T>
+where
+    K: TransactionKind,
+    T: DupSort<'tx>,
+{
+    type Item = Result<(T::Subkey, T::Subvalue)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let val = self.next.take()?;
+        self.next = match self.cur.inner.next_dup::<Cow<'tx, [u8]>, Cow<'tx, [u8]>>() {
+            Ok(kv) => kv.map(|(_, v)| v),
+            Err(e) => return Some(Err(e.into())),
+        };
+        #[cfg(feature = "metrics")]
+        {
+            self.len += 1;
+        }
+        Some(split_dup::<T>(&val, self.subkey_len))
     }
 }
@@ -0,0 +1,48 @@
+//! `tracing` spans around table accesses, entered when the `tracing` feature
+//! is enabled. Each span records the `table` it accesses, by the name it is
+//! stored under in the db (or `raw`, for a table opened with `open_raw`), and
+//! the `key_len` of the encoded key. The spans of point reads and seeks are at
+//! the TRACE level, so a subscriber can filter them out while keeping the
+//! DEBUG spans of the composite readers, such as `Erigon::read_block`.
+//!
+//! Without the feature, these return a guard that does nothing.
+pub(crate) use imp::*;
+
+#[cfg(feature = "tracing")]
+mod imp {
+    use crate::kv::traits::Table;
+
+    /// Exits the span when dropped.
+    pub(crate) type Guard = tracing::span::EnteredSpan;
+
+    pub(crate) fn open_db(table: &'static str) -> Guard {
+        tracing::trace_span!("open_db", table).entered()
+    }
+
+    pub(crate) fn get<'tx, T: Table<'tx>>(key_len: usize) -> Guard {
+        tracing::trace_span!("get", table = T::LABEL, key_len).entered()
+    }
+
+    pub(crate) fn seek<'tx, T: Table<'tx>>(key_len: usize) -> Guard {
+        tracing::trace_span!("seek", table = T::LABEL, key_len).entered()
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod imp {
+    use crate::kv::traits::Table;
+
+    pub(crate) struct Guard;
+
+    pub(crate) fn open_db(_table: &'static str) -> Guard {
+        Guard
+    }
+
+    pub(crate) fn get<'tx, T: Table<'tx>>(_key_len: usize) -> Guard {
+        Guard
+    }
+
+    pub(crate) fn seek<'tx, T: Table<'tx>>(_key_len: usize) -> Guard {
+        Guard
+    }
+}
@@ -1,33 +1,28 @@
 use arrayvec::ArrayVec;
 use derive_more::{Deref, DerefMut};
 use ethereum_types::{Address, H256, U256};
-use eyre::{eyre, Result};
 use mdbx::DatabaseFlags;
-use roaring::RoaringTreemap;
-use std::{
-    convert::AsRef,
-    fmt::{Debug, Display},
-    ops::Deref,
-};
+use roaring::{RoaringBitmap, RoaringTreemap};
+use std::{convert::AsRef, fmt::Debug, ops::Deref};
 
-use crate::kv::traits::*;
+use crate::{error::DecodeError, kv::traits::*, Result};
 
 const KECCAK_LENGTH: usize = 32;
 const ADDRESS_LENGTH: usize = 20;
 
 pub struct TableHandle<'tx, Dbi, Flags> {
-    inner: mdbx::Database<'tx>,
+    inner: &'tx mdbx::Database<'tx>,
     _dbi: std::marker::PhantomData<(Dbi, Flags)>,
 }
 impl<'tx, Dbi, Flags: DbFlags> TableHandle<'tx, Dbi, Flags> {
-    pub fn new(inner: mdbx::Database<'tx>) -> Self {
+    pub fn new(inner: &'tx mdbx::Database<'tx>) -> Self {
         Self {
             inner,
             _dbi: std::marker::PhantomData,
         }
     }
     pub fn inner(&self) -> &mdbx::Database<'tx> {
-        &self.inner
+        self.inner
     }
 }
 impl<'tx, Dbi, Flags: DbFlags> Deref for TableHandle<'tx, Dbi, Flags> {
@@ -50,6 +45,24 @@ pub struct DupSortFlags;
 impl DbFlags for DupSortFlags {
     const FLAGS: DatabaseFlags = DatabaseFlags::DUP_SORT;
 }
+/// An untyped table whose keys and values are raw bytes, for tables that the
+/// crate does not know about. Handles to it are opened by name at runtime with
+/// [`MdbxTx::open_raw`](crate::kv::MdbxTx::open_raw).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawTable;
+
+impl<'tx> Table<'tx> for RawTable {
+    const LABEL: &'static str = "raw";
+    type Key = Vec<u8>;
+    type SeekKey = Vec<u8>;
+    type Value = Vec<u8>;
+}
+
+impl DupSort<'_> for RawTable {
+    type Subkey = Vec<u8>;
+    type Subvalue = Vec<u8>;
+}
+
 #[macro_export]
 macro_rules! table_without_flags {
     ($name:ident => $key:ty => $value:ty, seek_key = $seek_key:ty, rename = $rename:ident) => {
@@ -57,7 +70,7 @@ macro_rules! table_without_flags {
         pub struct $name;
 
         impl<'tx> $crate::kv::traits::Table<'tx> for $name {
-            type Name = Self;
+            const LABEL: &'static str = stringify!($rename);
             type Key = $key;
             type SeekKey = $seek_key;
             type Value = $value;
@@ -99,18 +112,134 @@ macro_rules! table {
 }
 #[macro_export]
 macro_rules! dupsort_table {
-    ($name:ident => $key:ty => $value:ty, subkey = $subkey:ty, rename = $rename:ident) => {
+    (
+        $name:ident => $key:ty => $value:ty,
+        subkey = $subkey:ty,
+        subvalue = $subvalue:ty,
+        rename = $rename:ident
+    ) => {
         $crate::table_without_flags!($name => $key => $value, rename = $rename);
         impl $crate::kv::traits::DefaultFlags for $name {
             type Flags = $crate::kv::tables::DupSortFlags;
         }
-        impl crate::kv::traits::DupSort<'_> for $name {
+        impl $crate::kv::traits::DupSort<'_> for $name {
             type Subkey = $subkey;
+            type Subvalue = $subvalue;
         }
     };
-    ($name:ident => $key:ty => $value:ty, subkey = $subkey:ty) => {
-        $crate::dupsort_table!($name => $key => $value, subkey = $subkey, rename = $name);
+    ($name:ident => $key:ty => $value:ty, subkey = $subkey:ty, subvalue = $subvalue:ty) => {
+        $crate::dupsort_table!(
+            $name => $key => $value,
+            subkey = $subkey,
+            subvalue = $subvalue,
+            rename = $name
+        );
+    }
+}
+
+/// table_set! declares each of the given tables with `table!` or
+/// `dupsort_table!`, along with a const listing their names as they are stored
+/// in the db, in order, and a second const listing those of them that are
+/// dupsort. Tables that share a db (e.g. Storage and PlainState) are listed
+/// once, and the db is dupsort if any of them is. A table declared in the set
+/// cannot be left off the lists.
+#[macro_export]
+macro_rules! table_set {
+    (
+        $(#[$meta:meta])*
+        $vis:vis const $set:ident;
+        $(#[$dup_meta:meta])*
+        $dup_vis:vis const $dup_set:ident;
+        $($mac:ident!($name:ident => $($args:tt)*);)*
+    ) => {
+        $($crate::$mac!($name => $($args)*);)*
+
+        $(#[$meta])*
+        $vis const $set: &[&str] = {
+            const NAMES: &[&str] = &[$(<$name as $crate::kv::traits::DbName>::NAME),*];
+            const UNIQUE: [&str; $crate::kv::tables::count_unique(NAMES, &[true; NAMES.len()])] =
+                $crate::kv::tables::unique(NAMES, &[true; NAMES.len()]);
+            &UNIQUE
+        };
+
+        $(#[$dup_meta])*
+        $dup_vis const $dup_set: &[&str] = {
+            const NAMES: &[&str] = &[$(<$name as $crate::kv::traits::DbName>::NAME),*];
+            const DUPSORT: &[bool] = &[$($crate::kv::tables::is_dupsort::<
+                <$name as $crate::kv::traits::DefaultFlags>::Flags,
+            >()),*];
+            const UNIQUE: [&str; $crate::kv::tables::count_unique(NAMES, DUPSORT)] =
+                $crate::kv::tables::unique(NAMES, DUPSORT);
+            &UNIQUE
+        };
+    };
+}
+
+/// Whether tables with the given flags are dupsort.
+#[doc(hidden)]
+pub const fn is_dupsort<F: DbFlags>() -> bool {
+    F::FLAGS.bits() & DatabaseFlags::DUP_SORT.bits() != 0
+}
+
+/// Returns the number of distinct names in `names` that are marked in `keep`.
+/// A name is counted if any of its appearances is marked.
+#[doc(hidden)]
+pub const fn count_unique(names: &[&str], keep: &[bool]) -> usize {
+    let (mut i, mut count) = (0, 0);
+    while i < names.len() {
+        if is_first_kept(names, keep, i) {
+            count += 1;
+        }
+        i += 1;
     }
+    count
+}
+
+/// Returns the distinct names in `names` that are marked in `keep`, of which
+/// there must be `N`, in order of their first marked appearance.
+#[doc(hidden)]
+pub const fn unique<const N: usize>(names: &[&'static str], keep: &[bool]) -> [&'static str; N] {
+    let mut out = [""; N];
+    let (mut i, mut n) = (0, 0);
+    while i < names.len() {
+        if is_first_kept(names, keep, i) {
+            out[n] = names[i];
+            n += 1;
+        }
+        i += 1;
+    }
+    assert!(n == N, "wrong number of distinct table names");
+    out
+}
+
+// Whether names[i] is marked, and no earlier appearance of its name is.
+const fn is_first_kept(names: &[&str], keep: &[bool], i: usize) -> bool {
+    if !keep[i] {
+        return false;
+    }
+    let mut j = 0;
+    while j < i {
+        if keep[j] && str_eq(names[j], names[i]) {
+            return false;
+        }
+        j += 1;
+    }
+    true
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
 }
 
 // -- Key/Value Encoding/Decoding --
@@ -125,12 +254,27 @@ impl TableEncode for () {
 impl TableDecode for () {
     fn decode(b: &[u8]) -> Result<Self> {
         if !b.is_empty() {
-            return Err(TooLong::<0> { got: b.len() }.into());
+            return Err(DecodeError::TooLong {
+                maximum: 0,
+                got: b.len(),
+            }
+            .into());
         }
         Ok(())
     }
 }
 
+// An empty value decodes to None, as Erigon stores a deleted or missing value
+// as an empty one.
+impl<T: TableDecode> TableDecode for Option<T> {
+    fn decode(b: &[u8]) -> Result<Self> {
+        if b.is_empty() {
+            return Ok(None);
+        }
+        T::decode(b).map(Some)
+    }
+}
+
 impl TableEncode for Vec<u8> {
     type Encoded = Self;
 
@@ -175,7 +319,11 @@ impl<const LEN: usize> TableEncode for VariableVec<LEN> {
 impl<const LEN: usize> TableDecode for VariableVec<LEN> {
     fn decode(b: &[u8]) -> Result<Self> {
         let mut out = Self::default();
-        out.try_extend_from_slice(b)?;
+        out.try_extend_from_slice(b)
+            .map_err(|_| DecodeError::TooLong {
+                maximum: LEN,
+                got: b.len(),
+            })?;
         Ok(out)
     }
 }
@@ -186,44 +334,6 @@ impl<const LEN: usize> From<VariableVec<LEN>> for Vec<u8> {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct InvalidLength<const EXPECTED: usize> {
-    pub got: usize,
-}
-
-impl<const EXPECTED: usize> Display for InvalidLength<EXPECTED> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Invalid length: {} != {}", EXPECTED, self.got)
-    }
-}
-
-impl<const EXPECTED: usize> std::error::Error for InvalidLength<EXPECTED> {}
-
-#[derive(Clone, Debug)]
-pub struct TooShort<const MINIMUM: usize> {
-    pub got: usize,
-}
-
-impl<const MINIMUM: usize> Display for TooShort<MINIMUM> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Too short: {} < {}", self.got, MINIMUM)
-    }
-}
-
-impl<const MINIMUM: usize> std::error::Error for TooShort<MINIMUM> {}
-
-#[derive(Clone, Debug)]
-pub struct TooLong<const MAXIMUM: usize> {
-    pub got: usize,
-}
-impl<const MAXIMUM: usize> Display for TooLong<MAXIMUM> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Too long: {} > {}", self.got, MAXIMUM)
-    }
-}
-
-impl<const MAXIMUM: usize> std::error::Error for TooLong<MAXIMUM> {}
-
 impl TableEncode for H256 {
     type Encoded = [u8; KECCAK_LENGTH];
     fn encode(self) -> Self::Encoded {
@@ -235,7 +345,11 @@ impl TableDecode for H256 {
     fn decode(b: &[u8]) -> Result<Self> {
         match b.len() {
             KECCAK_LENGTH => Ok(H256::from_slice(&*b)),
-            _ => Err(eyre!("bad")),
+            other => Err(DecodeError::InvalidLength {
+                expected: KECCAK_LENGTH,
+                got: other,
+            }
+            .into()),
         }
     }
 }
@@ -252,7 +366,11 @@ impl TableEncode for U256 {
 impl TableDecode for U256 {
     fn decode(b: &[u8]) -> Result<Self> {
         if b.len() > KECCAK_LENGTH {
-            return Err(TooLong::<KECCAK_LENGTH> { got: b.len() }.into());
+            return Err(DecodeError::TooLong {
+                maximum: KECCAK_LENGTH,
+                got: b.len(),
+            }
+            .into());
         }
         let mut v = [0; 32];
         v[KECCAK_LENGTH - b.len()..].copy_from_slice(b);
@@ -272,7 +390,11 @@ impl TableDecode for Address {
     fn decode(b: &[u8]) -> Result<Self> {
         match b.len() {
             ADDRESS_LENGTH => Ok(Address::from_slice(&*b)),
-            other => Err(InvalidLength::<ADDRESS_LENGTH> { got: other }.into()),
+            other => Err(DecodeError::InvalidLength {
+                expected: ADDRESS_LENGTH,
+                got: other,
+            }
+            .into()),
         }
     }
 }
@@ -291,11 +413,19 @@ impl TableEncode for (H256, U256) {
 impl TableDecode for (H256, U256) {
     fn decode(b: &[u8]) -> Result<Self> {
         if b.len() > KECCAK_LENGTH + KECCAK_LENGTH {
-            return Err(TooLong::<{ KECCAK_LENGTH + KECCAK_LENGTH }> { got: b.len() }.into());
+            return Err(DecodeError::TooLong {
+                maximum: KECCAK_LENGTH + KECCAK_LENGTH,
+                got: b.len(),
+            }
+            .into());
         }
 
         if b.len() < KECCAK_LENGTH {
-            return Err(TooShort::<{ KECCAK_LENGTH }> { got: b.len() }.into());
+            return Err(DecodeError::TooShort {
+                minimum: KECCAK_LENGTH,
+                got: b.len(),
+            }
+            .into());
         }
 
         let (location, value) = b.split_at(KECCAK_LENGTH);
@@ -326,12 +456,13 @@ where
 {
     fn decode(v: &[u8]) -> Result<Self> {
         if v.len() != A_LEN + B_LEN {
-            eyre::bail!("Invalid len: {} != {} + {}", v.len(), A_LEN, B_LEN);
+            return Err(DecodeError::InvalidLength {
+                expected: A_LEN + B_LEN,
+                got: v.len(),
+            }
+            .into());
         }
-        Ok((
-            A::decode(&v[..A_LEN]).unwrap(),
-            B::decode(&v[A_LEN..]).unwrap(),
-        ))
+        Ok((A::decode(&v[..A_LEN])?, B::decode(&v[A_LEN..])?))
     }
 }
 
@@ -345,7 +476,21 @@ impl TableEncode for RoaringTreemap {
 }
 impl TableDecode for RoaringTreemap {
     fn decode(b: &[u8]) -> Result<Self> {
-        Ok(RoaringTreemap::deserialize_from(b)?)
+        RoaringTreemap::deserialize_from(b).map_err(|e| DecodeError::from(e).into())
+    }
+}
+
+impl TableEncode for RoaringBitmap {
+    type Encoded = Vec<u8>;
+    fn encode(self) -> Self::Encoded {
+        let mut buf = Vec::with_capacity(self.serialized_size());
+        self.serialize_into(&mut buf).unwrap();
+        buf
+    }
+}
+impl TableDecode for RoaringBitmap {
+    fn decode(b: &[u8]) -> Result<Self> {
+        RoaringBitmap::deserialize_from(b).map_err(|e| DecodeError::from(e).into())
     }
 }
 
@@ -363,6 +508,27 @@ impl TableDecode for bytes::Bytes {
     }
 }
 
+impl TableEncode for u8 {
+    type Encoded = [u8; 1];
+
+    fn encode(self) -> Self::Encoded {
+        [self]
+    }
+}
+
+impl TableDecode for u8 {
+    fn decode(b: &[u8]) -> Result<Self> {
+        match b {
+            [v] => Ok(*v),
+            _ => Err(DecodeError::InvalidLength {
+                expected: 1,
+                got: b.len(),
+            }
+            .into()),
+        }
+    }
+}
+
 impl TableEncode for u32 {
     type Encoded = [u8; 4];
 
@@ -375,7 +541,11 @@ impl TableDecode for u32 {
     fn decode(b: &[u8]) -> Result<Self> {
         match b.len() {
             4 => Ok(u32::from_be_bytes(*arrayref::array_ref!(&*b, 0, 4))),
-            other => Err(InvalidLength::<4> { got: other }.into()),
+            other => Err(DecodeError::InvalidLength {
+                expected: 4,
+                got: other,
+            }
+            .into()),
         }
     }
 }
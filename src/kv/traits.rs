@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{borrow::Cow, fmt::Debug};
 
 pub trait TableEncode: Send + Sync + Sized {
     type Encoded: AsRef<[u8]> + Send + Sync;
@@ -6,7 +6,21 @@ pub trait TableEncode: Send + Sync + Sized {
 }
 
 pub trait TableDecode: Send + Sync + Sized {
-    fn decode(b: &[u8]) -> eyre::Result<Self>;
+    fn decode(b: &[u8]) -> crate::Result<Self>;
+}
+
+/// Decodes a value that borrows its bytes from the db rather than copying
+/// them. The bytes are borrowed from the memory map for the life of the
+/// transaction, unless the page holding them was written in the same
+/// transaction, in which case they are copied.
+pub trait TableDecodeBorrowed<'tx>: Sized {
+    fn decode_borrowed(b: Cow<'tx, [u8]>) -> crate::Result<Self>;
+}
+
+impl<'tx> TableDecodeBorrowed<'tx> for Cow<'tx, [u8]> {
+    fn decode_borrowed(b: Cow<'tx, [u8]>) -> crate::Result<Self> {
+        Ok(b)
+    }
 }
 
 pub trait TableObject: TableEncode + TableDecode {}
@@ -14,14 +28,25 @@ pub trait TableObject: TableEncode + TableDecode {}
 impl<T> TableObject for T where T: TableEncode + TableDecode {}
 
 pub trait Table<'tx>: Send + Sync + Debug + 'static {
-    type Name: DbName;
+    /// The name the table is reported under in metrics and tracing spans. A
+    /// table defined with `table!` is opened under the same name, as its
+    /// [`DbName`], while [`RawTable`](crate::kv::tables::RawTable), which is
+    /// opened by name at runtime, has no `DbName`.
+    const LABEL: &'static str;
     type Key: TableEncode;
     type Value: TableObject;
+    /// The key taken by [`MdbxCursor::seek`](crate::kv::MdbxCursor::seek),
+    /// which may encode to a prefix of `Key`, such as the block number of a
+    /// block number and hash key. Set by the `seek_key` argument of `table!`,
+    /// and `Key` otherwise.
     type SeekKey: TableEncode;
 }
 
+/// A dupsorted table, whose values at each key are sorted and each begin with
+/// an encoded `Subkey`, followed by an encoded `Subvalue`.
 pub trait DupSort<'tx>: Table<'tx> {
     type Subkey: TableObject;
+    type Subvalue: TableDecode;
 }
 
 pub trait DbName {
@@ -31,7 +56,7 @@ pub trait DbName {
 pub trait DbFlags {
     const FLAGS: mdbx::DatabaseFlags;
 }
-pub trait DefaultFlags {
+pub trait DefaultFlags: DbName {
     type Flags: DbFlags;
 }
 
@@ -1,8 +1,26 @@
 #![doc = include_str!("../README.md")]
 #![doc = include_str!("../doc/mdbx.md")]
+#[cfg(feature = "alloy")]
+pub mod alloy;
 pub mod erigon;
+#[cfg(feature = "async")]
+pub mod erigon_async;
+pub mod error;
+#[cfg(feature = "arrow")]
+pub mod export;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 pub mod kv;
+#[cfg(feature = "ethers-types")]
+pub mod middleware;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub(crate) mod rlp;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod snapshots;
 pub use erigon::*;
+pub use error::{Error, Result};
 
 #[cfg(test)]
 mod tests {
@@ -10,7 +28,7 @@ mod tests {
     use crate::{erigon::Erigon, kv::MdbxEnv};
     use ethereum_types::*;
     use once_cell::sync::Lazy;
-    use std::{path::Path, sync::Arc};
+    use std::sync::Arc;
 
     struct TempMdbxEnv<M> {
         pub inner: MdbxEnv<M>,
@@ -31,37 +49,3471 @@ mod tests {
         db.write_head_header_hash(hash)?;
         let res = db.read_head_header_hash()?.unwrap();
         assert_eq!(res, hash);
+        // a fresh db has no version, and is read as an Erigon 2 db
+        assert_eq!(db.schema()?, erigon::schema::Schema::V2);
+        // the same entry, read by name through an untyped handle
+        assert!(db.0.list_tables()?.iter().any(|name| name == "LastHeader"));
+        let raw = db.0.open_raw("LastHeader")?;
+        let val =
+            db.0.get::<kv::tables::RawTable, _>(raw, b"LastHeader".to_vec())?;
+        assert_eq!(val, Some(hash.as_bytes().to_vec()));
         Ok(())
     }
 
     #[test]
+    fn test_read_many() -> eyre::Result<()> {
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let hashes = (1..=3).map(H256::from_low_u64_be).collect::<Vec<_>>();
+        for (i, hash) in hashes.iter().enumerate() {
+            db.write_header_number(*hash, models::BlockNumber(i as u64))?;
+        }
+        let keys = vec![hashes[2], H256::zero(), hashes[0], hashes[1]];
+        let res = db.read_many::<tables::HeaderNumber>(keys)?;
+        let expected = vec![
+            Some(models::BlockNumber(2)),
+            None,
+            Some(models::BlockNumber(0)),
+            Some(models::BlockNumber(1)),
+        ];
+        assert_eq!(res, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_storage() -> eyre::Result<()> {
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let adr = Address::from_low_u64_be(0x5107);
+        let slot = H256::from_low_u64_be(1);
+        db.write_storage(adr, 1, slot, 1.into())?;
+        db.write_storage(adr, 1, slot, 2.into())?;
+        db.write_storage(adr, 1, H256::from_low_u64_be(2), 3.into())?;
+        // overwriting a slot replaces its entry rather than adding a duplicate
+        let slots = db.walk_storage(adr, 1, None)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            slots,
+            vec![(slot, 2.into()), (H256::from_low_u64_be(2), 3.into())]
+        );
+        // a walk can start mid-bucket
+        let start = Some(H256::from_low_u64_be(2));
+        let slots = db
+            .walk_storage(adr, 1, start)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(slots, vec![(H256::from_low_u64_be(2), 3.into())]);
+        let start = Some(H256::from_low_u64_be(3));
+        assert!(db.walk_storage(adr, 1, start)?.next().is_none());
+        // writing zero clears the slot
+        db.write_storage(adr, 1, slot, U256::zero())?;
+        assert_eq!(db.read_storage(adr, 1, slot)?, None);
+        assert!(!db.delete_storage(adr, 1, slot)?);
+        assert!(db.delete_storage(adr, 1, H256::from_low_u64_be(2))?);
+        assert_eq!(db.read_storage(adr, 1, H256::from_low_u64_be(2))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_changesets() -> eyre::Result<()> {
+        use crate::kv::traits::TableEncode;
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let adr = Address::from_low_u64_be(0xc5);
+        let acct = models::Account::new()
+            .nonce(1)
+            .incarnation(1.into())
+            .codehash(H256::repeat_byte(1));
+        db.write_account_change(7, adr, Some(acct))?;
+        // only the first change in a block is kept
+        db.write_account_change(7, adr, None)?;
+        db.write_account_change(8, adr, None)?;
+
+        let mut cur = db.cursor::<tables::AccountChangeSet>()?;
+        let val = cur.seek_dup(7.into(), adr)?.unwrap();
+        // the codehash is dropped, as in Erigon
+        assert_eq!(
+            val,
+            models::AccountCSVal(adr, Some(acct.codehash(H256::zero())))
+        );
+        let val = cur.seek_dup(8.into(), adr)?.unwrap();
+        assert_eq!(val.encode(), adr.0.to_vec());
+
+        let slot = H256::from_low_u64_be(1);
+        db.write_storage_change(7, adr, 1, slot, 5.into())?;
+        db.write_storage_change(7, adr, 1, slot, 6.into())?;
+        let mut cur = db.cursor::<tables::StorageChangeSet>()?;
+        let val = cur.seek_dup((7, adr, models::Incarnation(1)).into(), slot)?;
+        assert_eq!(val, Some(models::StorageCSVal(slot, 5.into())));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dup_cursor() -> eyre::Result<()> {
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let adr = Address::from_low_u64_be(0xd0);
+        let key = models::StorageKey(adr, 1.into());
+        for i in [1, 3, 5] {
+            let slot = H256::from_low_u64_be(i);
+            db.write::<tables::Storage>(key, (slot, (i * 10).into()))?;
+        }
+
+        let mut cur = db.cursor::<tables::Storage>()?;
+        let val = cur.seek_exact_dup(key, H256::from_low_u64_be(3))?;
+        assert_eq!(val, Some(30.into()));
+        assert_eq!(cur.seek_exact_dup(key, H256::from_low_u64_be(4))?, None);
+
+        let vals = db
+            .cursor::<tables::Storage>()?
+            .walk_dup_from(key, H256::from_low_u64_be(2))?
+            .collect::<Result<Vec<_>>>()?;
+        let want = [3, 5].map(|i| (H256::from_low_u64_be(i), U256::from(i * 10)));
+        assert_eq!(vals, want);
+
+        let empty = models::StorageKey(adr, 2.into());
+        let mut vals = db
+            .cursor::<tables::Storage>()?
+            .walk_dup_from(empty, H256::zero())?;
+        assert!(vals.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_walkers() -> eyre::Result<()> {
+        use kv::{ValWalker, Walker};
+        use mdbx::RW;
+        // owned walkers can be returned from the function holding the cursor
+        fn walk<'tx>(db: &'tx Erigon<RW>, from: u64) -> Result<Walker<'tx, RW, tables::Issuance>> {
+            db.cursor::<tables::Issuance>()?.walk(from.into())
+        }
+        fn walk_val<'tx>(
+            db: &'tx Erigon<RW>,
+            from: u64,
+        ) -> Result<ValWalker<'tx, RW, tables::Issuance>> {
+            db.cursor::<tables::Issuance>()?.walk_val(from.into())
+        }
+
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        for i in 1..=3u64 {
+            db.write::<tables::Issuance>(i.into(), (i * 100).into())?;
+        }
+        let pairs = walk(&db, 2)?.collect::<Result<Vec<_>>>()?;
+        let want = [2u64, 3].map(|i| (models::BlockNumber(i), U256::from(i * 100)));
+        assert_eq!(pairs, want);
+        let vals = walk_val(&db, 0)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(vals, [100u64, 200, 300].map(U256::from));
+        // a walk starting past the last key yields nothing
+        assert!(walk(&db, 4)?.next().is_none());
+        assert!(walk_val(&db, 4)?.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_history() -> eyre::Result<()> {
+        use roaring::RoaringTreemap;
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let adr = Address::from_low_u64_be(0x4157);
+        let acct = models::Account::new().nonce(3);
+        db.write_account_change(5, adr, Some(acct))?;
+        db.write_account_history(adr, [5u64].into_iter().collect())?;
+        assert_eq!(db.read_account_hist(adr, 3)?, Some(acct));
+        assert_eq!(db.read_account_hist(adr, 6)?, None);
+
+        // enough blocks to need several shards, written in two batches
+        let blocks = (1u64..5000).map(|i| i * 7).collect::<RoaringTreemap>();
+        db.write_account_history(adr, blocks.iter().take(1000).collect())?;
+        db.write_account_history(adr, blocks.iter().skip(1000).collect())?;
+        let mut cur = db.cursor::<tables::AccountHistory>()?;
+        let mut all = RoaringTreemap::new();
+        let mut keys = vec![];
+        for read in cur.iter((adr, models::BlockNumber(0)).into()) {
+            let (models::AccountHistKey(k, shard), bitmap) = read?;
+            if k != adr {
+                break;
+            }
+            assert!(bitmap.serialized_size() <= 1950);
+            keys.push(*shard);
+            all |= bitmap;
+        }
+        assert!(keys.len() > 2);
+        assert_eq!(keys.last(), Some(&u64::MAX));
+        assert_eq!(all, blocks | [5u64].into_iter().collect::<RoaringTreemap>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> eyre::Result<()> {
+        use erigon::diff::Change;
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let adr = Address::from_low_u64_be(0xd1ff);
+        let codehash = H256::repeat_byte(0xd1);
+        db.write::<tables::PlainCodeHash>(models::PlainCodeKey(adr, 1.into()), codehash)?;
+        let acct = |bal: u64| {
+            models::Account::new()
+                .balance(bal.into())
+                .incarnation(1.into())
+                .codehash(codehash)
+        };
+        // the account is created in block 1, then its balance changes in 2 and 3
+        db.write_account_change(1, adr, None)?;
+        db.write_account_change(2, adr, Some(acct(1)))?;
+        db.write_account_change(3, adr, Some(acct(2)))?;
+        db.write_account(adr, acct(3))?;
+        db.write_account_history(adr, [1u64, 2, 3].into_iter().collect())?;
+
+        let diff = db.account_diff(adr, 0, 2)?;
+        assert_eq!(
+            diff,
+            vec![
+                Change {
+                    block: 1.into(),
+                    before: None,
+                    after: Some(acct(1))
+                },
+                Change {
+                    block: 2.into(),
+                    before: Some(acct(1)),
+                    after: Some(acct(2))
+                },
+            ]
+        );
+        assert_eq!(db.account_diff(adr, 2, 3)?[0].after, Some(acct(3)));
+
+        let slot = H256::from_low_u64_be(1);
+        db.write_storage_change(2, adr, 1, slot, U256::zero())?;
+        db.write_storage_change(3, adr, 1, slot, 5.into())?;
+        db.write_storage(adr, 1, slot, 6.into())?;
+        db.write_storage_history(adr, slot, [2u64, 3].into_iter().collect())?;
+
+        let diff = db.storage_diff(adr, .., 0, 3)?;
+        let changes = vec![
+            Change {
+                block: 2.into(),
+                before: U256::zero(),
+                after: 5.into(),
+            },
+            Change {
+                block: 3.into(),
+                before: 5.into(),
+                after: 6.into(),
+            },
+        ];
+        assert_eq!(diff.get(&slot), Some(&changes));
+        assert!(db
+            .storage_diff(adr, H256::from_low_u64_be(2).., 0, 3)?
+            .is_empty());
+
+        // a block in the history index must have a changeset entry
+        let other = Address::from_low_u64_be(0xd2ff);
+        db.write_account_history(other, [2u64].into_iter().collect())?;
+        assert!(matches!(
+            db.account_diff(other, 0, 3),
+            Err(Error::NotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_in_block() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let (a, b) = (Address::from_low_u64_be(0xa), Address::from_low_u64_be(0xb));
+        let acct = models::Account::new().nonce(1);
+        db.write_account_change(20, b, Some(acct))?;
+        db.write_account_change(20, a, None)?;
+        assert_eq!(db.changed_accounts(20)?, vec![(a, None), (b, Some(acct))]);
+        // blocks before and after the only changeset are empty
+        assert!(db.changed_accounts(19)?.is_empty());
+        assert!(db.changed_accounts(21)?.is_empty());
+
+        let (slot1, slot2) = (H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+        db.write_storage_change(20, b, 1, slot2, 5.into())?;
+        db.write_storage_change(20, b, 1, slot1, 4.into())?;
+        db.write_storage_change(21, a, 1, slot1, 3.into())?;
+        assert_eq!(
+            db.changed_storage(20)?,
+            vec![(b, slot1, 4.into()), (b, slot2, 5.into())]
+        );
+        assert!(db.changed_storage(22)?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_erigon_async() -> eyre::Result<()> {
+        use tokio_stream::StreamExt;
+        let dir = tempfile::tempdir()?;
+        let adr = Address::from_low_u64_be(0xa5);
+        let acct = models::Account::new().nonce(3).incarnation(1.into());
+        {
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            let db = Erigon::begin_rw(&env)?;
+            db.write_account(adr, acct)?;
+            for i in 1..=3u64 {
+                db.write_storage(adr, 1, H256::from_low_u64_be(i), i.into())?;
+            }
+            db.0.commit()?;
+        }
+
+        let db = erigon_async::ErigonAsync::open(dir.path())?;
+        assert_eq!(db.read_account(adr).await?, Some(acct));
+        let slots = db
+            .walk_storage(adr, 1, Some(H256::from_low_u64_be(2)))
+            .collect::<Result<Vec<_>>>()
+            .await?;
+        let want = [2u64, 3].map(|i| (H256::from_low_u64_be(i), U256::from(i)));
+        assert_eq!(slots, want);
+        // a dropped stream ends its walk
+        let mut slots = Box::pin(db.walk_storage(adr, 1, None));
+        assert!(slots.next().await.is_some());
+        drop(slots);
+        // a failed read is returned as an error
+        assert!(db.read_head_block_number().await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_erigon_owned() -> eyre::Result<()> {
+        fn assert_shareable<T: Send + Sync + 'static>(_: &T) {}
+        let dir = tempfile::tempdir()?;
+        let adr = Address::from_low_u64_be(0x0e);
+        let acct = models::Account::new().nonce(1);
+        {
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            let db = Erigon::begin_rw(&env)?;
+            db.write_account(adr, acct)?;
+            db.0.commit()?;
+        }
+
+        let env = Arc::new(erigon::env_open::<mdbx::RO>(dir.path())?);
+        let db = erigon::owned::ErigonOwned::begin(env)?;
+        assert_shareable(&db);
+        assert_eq!(db.with(|db| db.read_account(adr))?, Some(acct));
+        assert_eq!(db.renew()?.with(|db| db.read_account(adr))?, Some(acct));
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_builder() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_builder()
+            .sync_mode(mdbx::SyncMode::UtterlyNoSync)
+            .map_size(0..64 << 20)
+            .growth_step(1 << 20)
+            .page_size(4096)
+            .max_readers(16)
+            .open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let hash = H256::repeat_byte(0xb1);
+        db.write_head_header_hash(hash)?;
+        db.0.commit()?;
+        let db = Erigon::begin_rw(&env)?;
+        assert_eq!(db.read_head_header_hash()?, Some(hash));
+        Ok(())
+    }
+
+    #[test]
+    fn test_live_reader() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let hash = H256::repeat_byte(0x11);
+        {
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            let db = Erigon::begin_rw(&env)?;
+            db.write_head_header_hash(hash)?;
+            db.0.commit()?;
+        }
+        let mut reader = erigon::live::LiveReader::open(dir.path())?.max_retries(1);
+        assert_eq!(reader.read(|db| db.read_head_header_hash())?, Some(hash));
+        // errors other than a resize are returned without a retry
+        let mut calls = 0;
+        let res = reader.read(|_| -> Result<()> {
+            calls += 1;
+            Err(Error::NotFound("test"))
+        });
+        assert!(matches!(res, Err(Error::NotFound("test"))));
+        assert_eq!(calls, 1);
+
+        // a read that fails on a resized db is run again in a reopened env
+        let mut calls = 0;
+        let res = reader.read(|db| {
+            calls += 1;
+            if calls == 1 {
+                return Err(Error::Mdbx(mdbx::Error::UnableExtendMapsize));
+            }
+            db.read_head_header_hash()
+        });
+        assert_eq!(res?, Some(hash));
+        assert_eq!(calls, 2);
+        // up to the max number of retries
+        let mut calls = 0;
+        let res = reader.read(|_| -> Result<()> {
+            calls += 1;
+            Err(Error::Mdbx(mdbx::Error::BadRslot))
+        });
+        assert!(matches!(res, Err(Error::Mdbx(mdbx::Error::BadRslot))));
+        assert_eq!(calls, 2);
+
+        // the env is opened again from its path, so moving the db away during
+        // a stale read fails the reopen
+        let moved = dir.path().with_extension("moved");
+        let mut calls = 0;
+        let res = reader.read(|_| -> Result<()> {
+            calls += 1;
+            std::fs::rename(dir.path(), &moved)?;
+            Err(Error::Mdbx(mdbx::Error::UnableExtendMapsize))
+        });
+        std::fs::rename(&moved, dir.path())?;
+        assert!(res.is_err());
+        assert!(!matches!(
+            res,
+            Err(Error::Mdbx(mdbx::Error::UnableExtendMapsize))
+        ));
+        assert_eq!(calls, 1);
+        assert_eq!(reader.read(|db| db.read_head_header_hash())?, Some(hash));
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_head() -> eyre::Result<()> {
+        use models::BlockNumber;
+        use std::time::Duration;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let extend = |blocks: &[(u64, H256)]| -> Result<()> {
+            let db = Erigon::begin_rw(&env)?;
+            for &(num, hash) in blocks {
+                db.write::<tables::CanonicalHeader>(num.into(), hash)?;
+                db.write_header_number(hash, num.into())?;
+                db.write_head_header_hash(hash)?;
+            }
+            db.0.commit()?;
+            Ok(())
+        };
+        let (a, b) = (H256::repeat_byte(0xa), H256::repeat_byte(0xb));
+        extend(&[(0, H256::zero()), (1, a), (2, a)])?;
+
+        // the watch begins at the current head
+        let mut watch = erigon::watch::watch_head(&env, Duration::from_millis(1));
+        assert_eq!(watch.next().unwrap()?, (BlockNumber(2), a));
+        extend(&[(3, a)])?;
+        assert_eq!(watch.next().unwrap()?, (BlockNumber(3), a));
+        // a reorg yields the new chain from the fork point
+        extend(&[(2, b), (3, b), (4, b)])?;
+        let blocks = (0..3)
+            .map(|_| watch.next().unwrap())
+            .collect::<Result<Vec<_>>>()?;
+        let want = [2, 3, 4].map(|num| (BlockNumber(num), b));
+        assert_eq!(blocks, want);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fork_headers() -> eyre::Result<()> {
+        use models::{BlockHeader, BlockNumber, HeaderKey};
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let write = |parent: H256, num: u64, extra: u8| -> Result<H256> {
+            let header = BlockHeader {
+                parent_hash: parent,
+                number: num.into(),
+                extra: vec![extra].into(),
+                ..Default::default()
+            };
+            let hash = header.hash();
+            db.write_header(HeaderKey(num.into(), hash), header)?;
+            db.write_header_number(hash, num.into())?;
+            Ok(hash)
+        };
+        let genesis = write(H256::zero(), 0, 0)?;
+        let a1 = write(genesis, 1, 1)?;
+        let a2 = write(a1, 2, 1)?;
+        let b1 = write(genesis, 1, 2)?;
+        db.write::<tables::CanonicalHeader>(1.into(), a1)?;
+
+        let mut hashes = vec![a1, b1];
+        hashes.sort();
+        assert_eq!(db.read_header_hashes(1)?, hashes);
+        let forks = db.read_non_canonical_headers(1)?;
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].0, b1);
+
+        let ancestor = db.find_common_ancestor(a2, b1)?;
+        assert_eq!(ancestor, Some(HeaderKey(BlockNumber(0), genesis)));
+        let ancestor = db.find_common_ancestor(a1, a2)?;
+        assert_eq!(ancestor, Some(HeaderKey(BlockNumber(1), a1)));
+        assert_eq!(db.find_common_ancestor(a2, H256::zero())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_reads() -> eyre::Result<()> {
+        use models::{BlockHeader, BlockNumber, HeaderKey};
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let base = 100;
+        let headers = (base..base + 3)
+            .map(|num| BlockHeader {
+                number: num.into(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for header in &headers {
+            let key = HeaderKey(header.number.as_u64().into(), header.hash());
+            db.write_header(key, header.clone())?;
+            db.write::<tables::CanonicalHeader>(key.0, key.1)?;
+        }
+        let signer = Address::from_low_u64_be(0x5e);
+        let key = HeaderKey(BlockNumber(base), headers[0].hash());
+        db.write::<tables::TxSender>(key, vec![signer])?;
+
+        assert_eq!(
+            db.read_canonical_header(base + 1)?,
+            Some(headers[1].clone())
+        );
+        assert_eq!(db.read_canonical_header(base + 3)?, None);
+        assert_eq!(db.read_canonical_senders(base)?, Some(vec![signer]));
+        assert_eq!(db.read_canonical_body(base)?, None);
+        let walked = db
+            .walk_canonical_headers(base + 1..)?
+            .collect::<Result<Vec<_>>>()?;
+        let want = vec![
+            (BlockNumber(base + 1), headers[1].clone()),
+            (BlockNumber(base + 2), headers[2].clone()),
+        ];
+        assert_eq!(walked, want);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_by_timestamp() -> eyre::Result<()> {
+        use models::{BlockHeader, BlockNumber, HeaderKey, Hint};
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        assert_eq!(db.block_by_timestamp(0, Hint::After)?, None);
+        for (num, time) in (0u64..).zip([10, 20, 20, 30]) {
+            let header = BlockHeader {
+                number: num.into(),
+                time,
+                ..Default::default()
+            };
+            let key = HeaderKey(BlockNumber(num), header.hash());
+            db.write_header(key, header)?;
+            db.write::<tables::CanonicalHeader>(key.0, key.1)?;
+        }
+        let find = |time, hint| db.block_by_timestamp(time, hint);
+        assert_eq!(find(20, Hint::Before)?, Some(BlockNumber(2)));
+        assert_eq!(find(20, Hint::After)?, Some(BlockNumber(1)));
+        assert_eq!(find(25, Hint::Before)?, Some(BlockNumber(2)));
+        assert_eq!(find(25, Hint::After)?, Some(BlockNumber(3)));
+        assert_eq!(find(5, Hint::Before)?, None);
+        assert_eq!(find(5, Hint::After)?, Some(BlockNumber(0)));
+        assert_eq!(find(31, Hint::After)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_borrowed() -> eyre::Result<()> {
+        use std::borrow::Cow;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let code = models::Bytecode(vec![0x60; 5000].into());
+        let hash = H256::repeat_byte(0xc0);
+        let db = Erigon::begin_rw(&env)?;
+        db.write::<tables::Code>(hash, code.clone())?;
+        db.0.commit()?;
+
+        let db = Erigon::new(env.begin_ro()?);
+        let borrowed = db.read_code_ref(hash)?.unwrap();
+        assert!(matches!(borrowed.0, Cow::Borrowed(_)));
+        assert_eq!(borrowed.into_owned(), code);
+        let raw = db.read_raw::<tables::Code>(hash)?.unwrap();
+        assert_eq!(&raw[..], &code[..]);
+        assert_eq!(db.read_code_ref(H256::zero())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_etl_collector() -> eyre::Result<()> {
+        use kv::etl::Collector;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+
+        // a tiny buffer, so that most entries are spilled to disk
+        let mut collector = Collector::<tables::HeaderNumber>::with_buffer_size(256);
+        for i in (0..100u64).rev() {
+            collector.collect(H256::from_low_u64_be(i), i.into())?;
+        }
+        // the last value collected at a key wins
+        collector.collect(H256::from_low_u64_be(7), 700.into())?;
+        assert!(collector.spilled_runs() > 1);
+        assert_eq!(collector.load(&db.0)?, 100);
+        let pairs = db
+            .cursor::<tables::HeaderNumber>()?
+            .walk(H256::zero())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(pairs.len(), 100);
+        assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(pairs[7], (H256::from_low_u64_be(7), 700.into()));
+
+        // every distinct value is kept in a dupsorted table
+        let key = models::StorageKey(Address::repeat_byte(1), 1.into());
+        let mut collector = Collector::<tables::Storage>::with_buffer_size(128);
+        for i in [3u64, 1, 2, 1] {
+            collector.collect(key, (H256::from_low_u64_be(i), i.into()))?;
+        }
+        assert_eq!(collector.load(&db.0)?, 3);
+        let slots = db.walk_storage(Address::repeat_byte(1), 1, None)?;
+        assert_eq!(slots.count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_writer() -> eyre::Result<()> {
+        use erigon::batch::BatchWriter;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let mut commits = vec![];
+        let mut batch = BatchWriter::new(&env)?
+            .max_ops(4)
+            .on_commit(|progress| commits.push(progress.ops));
+        for i in 0..10u64 {
+            batch.write::<tables::HeaderNumber>(H256::from_low_u64_be(i), i.into())?;
+        }
+        assert!(batch.delete::<tables::HeaderNumber>(H256::from_low_u64_be(0), None)?);
+        batch.with(1, 0, |db| db.write_head_header_hash(H256::repeat_byte(1)))?;
+        let progress = batch.finish()?;
+        assert_eq!(progress.ops, 12);
+        // the last batch was committed as it filled up
+        assert_eq!(progress.commits, 3);
+        assert_eq!(commits, vec![4, 8, 12]);
+
+        let mut commits = vec![];
+        let mut batch = BatchWriter::new(&env)?
+            .max_ops(4)
+            .on_commit(|progress| commits.push(progress.ops));
+        for i in 10..15u64 {
+            batch.write::<tables::HeaderNumber>(H256::from_low_u64_be(i), i.into())?;
+        }
+        assert_eq!(
+            batch.db()?.read_header_number(H256::from_low_u64_be(14))?,
+            Some(14.into())
+        );
+        assert_eq!(batch.finish()?.commits, 2);
+        assert_eq!(commits, vec![4, 5]);
+
+        let db = Erigon::begin_rw(&env)?;
+        assert_eq!(db.read_header_number(H256::from_low_u64_be(0))?, None);
+        assert_eq!(
+            db.read_header_number(H256::from_low_u64_be(9))?,
+            Some(9.into())
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_blocks() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        for i in 0..100u64 {
+            db.write::<tables::CanonicalHeader>(i.into(), H256::from_low_u64_be(i + 1))?;
+        }
+        db.write_head_header_hash(H256::from_low_u64_be(100))?;
+        db.write_header_number(H256::from_low_u64_be(100), 99.into())?;
+        db.0.commit()?;
+
+        fn count(db: &Erigon<'_, mdbx::RO>, chunk: std::ops::Range<u64>) -> Result<u64> {
+            let mut n = 0;
+            for i in chunk {
+                n += db.read_canonical_hash(i)?.is_some() as u64;
+            }
+            Ok(n)
+        }
+        let counts = Erigon::par_blocks_chunked(&env, 0..100, 30, count)?;
+        assert_eq!(counts, vec![30, 30, 30, 10]);
+        // an unbounded range ends at the head block
+        let counts = Erigon::par_blocks(&env, 10.., count)?;
+        assert_eq!(counts.iter().sum::<u64>(), 90);
+        Ok(())
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_export_parquet() -> eyre::Result<()> {
+        use arrow_array::{Array, FixedSizeBinaryArray, UInt32Array, UInt64Array};
+        use models::{Account, BlockHeader, CborLog, CborLogs, HeaderKey, LogsKey};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        for i in 1..=3u64 {
+            let acct = Account {
+                nonce: i,
+                balance: U256::from(i) << 128,
+                ..Default::default()
+            };
+            db.write_account(Address::from_low_u64_be(i), acct)?;
+            let header = BlockHeader {
+                number: i.into(),
+                base_fee: (i > 1).then_some(i.into()),
+                ..Default::default()
+            };
+            let key = HeaderKey(i.into(), header.hash());
+            db.write_header(key, header)?;
+            db.write::<tables::CanonicalHeader>(key.0, key.1)?;
+            let log = CborLog {
+                address: Address::from_low_u64_be(i),
+                topics: vec![H256::from_low_u64_be(i); i as usize],
+                data: vec![i as u8].into(),
+            };
+            db.write::<tables::TransactionLog>(LogsKey(i.into(), 0), CborLogs(Some(vec![log])))?;
+        }
+        db.0.commit()?;
+
+        let db = Erigon::new(env.begin_ro()?);
+        let read = |name: &str| -> eyre::Result<arrow_array::RecordBatch> {
+            let file = std::fs::File::open(dir.path().join(name))?;
+            let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+            Ok(reader.next().unwrap()?)
+        };
+
+        let path = dir.path().join("accounts.parquet");
+        assert_eq!(export::export_accounts(&db, &path)?, 3);
+        let batch = read("accounts.parquet")?;
+        let balance = batch
+            .column_by_name("balance")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<FixedSizeBinaryArray>()
+            .unwrap();
+        let mut want = [0; 32];
+        want[15] = 3;
+        assert_eq!(balance.value(2), want);
+
+        let path = dir.path().join("headers.parquet");
+        assert_eq!(export::export_headers(&db, 2.., &path)?, 2);
+        let batch = read("headers.parquet")?;
+        let number = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(number.values().to_vec(), vec![2, 3]);
+        assert_eq!(batch.column_by_name("base_fee").unwrap().null_count(), 0);
+
+        let path = dir.path().join("logs.parquet");
+        assert_eq!(export::export_logs(&db, 1..3, &path)?, 2);
+        let batch = read("logs.parquet")?;
+        let log_index = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<UInt32Array>()
+            .unwrap();
+        assert_eq!(log_index.values().to_vec(), vec![0, 0]);
+        // the first log has one topic, the second two
+        assert_eq!(batch.column_by_name("topic1").unwrap().null_count(), 1);
+        assert_eq!(batch.column_by_name("topic2").unwrap().null_count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_table() -> eyre::Result<()> {
+        use erigon::dump::DumpFormat;
+        use models::HeaderKey;
+        let open = || -> eyre::Result<_> {
+            let dir = tempfile::tempdir()?;
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            Ok((dir, env))
+        };
+        let (_dir, env) = open()?;
+        let db = Erigon::begin_rw(&env)?;
+        for i in 1..=2u64 {
+            let key = HeaderKey(i.into(), H256::from_low_u64_be(i));
+            db.write::<tables::TxSender>(key, vec![Address::from_low_u64_be(i); i as usize])?;
+        }
+
+        let mut json = Vec::new();
+        assert_eq!(
+            db.dump_table::<tables::TxSender>(&mut json, DumpFormat::Json)?,
+            2
+        );
+        let first = String::from_utf8(json.clone())?
+            .lines()
+            .next()
+            .unwrap()
+            .to_owned();
+        assert_eq!(
+            first,
+            format!(
+                r#"{{"key":"0x{:016x}{:064x}","value":["0x{:040x}"]}}"#,
+                1, 1, 1
+            )
+        );
+        let mut csv = Vec::new();
+        db.dump_table::<tables::TxSender>(&mut csv, DumpFormat::Csv)?;
+
+        // both formats load back to the same table
+        for dump in [json, csv.clone()] {
+            let (_dir, env) = open()?;
+            let db = Erigon::begin_rw(&env)?;
+            assert_eq!(db.load_table::<tables::TxSender>(&dump[..])?, 2);
+            let mut reloaded = Vec::new();
+            db.dump_table::<tables::TxSender>(&mut reloaded, DumpFormat::Csv)?;
+            assert_eq!(reloaded, csv);
+        }
+
+        let err = db.load_table::<tables::TxSender>(&b"key,value\n0x01,[]"[..]);
+        assert!(err.unwrap_err().to_string().contains("line 2"));
+        let err = db.load_table::<tables::TxSender>(&b"{\"key\":\"0xzz\",\"value\":[]}"[..]);
+        assert!(err.unwrap_err().to_string().contains("line 1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_aura_seal() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use models::BlockHeader;
+        let mut header = BlockHeader {
+            number: 1.into(),
+            extra: vec![1, 2, 3].into(),
+            ..Default::default()
+        };
+        header.set_aura_seal(1_000_000, vec![7; 65].into());
+
+        let encoded = header.clone().encode();
+        let decoded = BlockHeader::decode(&encoded)?;
+        assert_eq!(decoded, header);
+        assert_eq!(decoded.aura_seal(), Some((1_000_000, vec![7; 65].into())));
+        assert_eq!(decoded.hash(), H256(erigon::utils::keccak256(&encoded)));
+
+        // the seal is absent from ethash headers
+        header.seal = None;
+        let decoded = BlockHeader::decode(&header.clone().encode())?;
+        assert_eq!(decoded.aura_seal(), None);
+        assert_eq!(decoded.seal, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clique_signer() -> eyre::Result<()> {
+        use k256::ecdsa::SigningKey;
+        use models::BlockHeader;
+        // the address of the secp256k1 key 1
+        let signer: Address = "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf".parse()?;
+        let mut extra = vec![0; 32];
+        extra.extend_from_slice(signer.as_bytes());
+        extra.extend_from_slice(&[0; 65]);
+        let mut header = BlockHeader {
+            number: 30000.into(),
+            extra: extra.into(),
+            ..Default::default()
+        };
+
+        let mut key = [0; 32];
+        key[31] = 1;
+        let key = SigningKey::from_slice(&key)?;
+        let (sig, recid) = key.sign_prehash_recoverable(header.clique_seal_hash()?.as_bytes())?;
+        let mut extra = header.extra[..32 + 20].to_vec();
+        extra.extend_from_slice(&sig.to_bytes());
+        extra.push(recid.to_byte());
+        header.extra = extra.into();
+
+        let parsed = header.clique_extra()?;
+        assert_eq!(parsed.vanity, H256::zero());
+        assert_eq!(parsed.signers, vec![signer]);
+        assert_eq!(header.clique_signer()?, signer);
+
+        // the extra data must hold at least a vanity and a signature
+        header.extra = vec![0; 96].into();
+        assert!(header.clique_extra().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_tables() -> eyre::Result<()> {
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let key = (models::BlockNumber(7), H256::repeat_byte(7));
+        db.write::<tables::DevEpoch>(key.into(), vec![1, 2].into())?;
+        db.write::<tables::DevPendingEpoch>(key.into(), vec![3].into())?;
+        assert_eq!(db.read_epoch_transition(key)?, Some(vec![1, 2].into()));
+        assert_eq!(db.read_pending_epoch(key)?, Some(vec![3].into()));
+        Ok(())
+    }
+
+    #[cfg(feature = "op")]
+    #[test]
+    fn test_deposit_tx() -> eyre::Result<()> {
+        use crate::kv::traits::TableDecode;
+        use crate::rlp::Encodable;
+        use models::{transaction::DepositTx, Transaction};
+        let deposit = DepositTx {
+            source_hash: H256::repeat_byte(1),
+            from: Address::repeat_byte(2),
+            to: Some(Address::repeat_byte(3)).into(),
+            mint: 100.into(),
+            value: 100.into(),
+            gas: 21000,
+            is_system_tx: false,
+            data: Default::default(),
+        };
+        // 0x7e || rlp([source_hash, from, to, mint, value, gas, is_system_tx, data])
+        let mut envelope = bytes::BytesMut::new();
+        envelope.extend_from_slice(&[DepositTx::TYPE]);
+        deposit.encode(&mut envelope);
+
+        let tx = Transaction::decode(&envelope)?;
+        assert_eq!(tx, Transaction::Deposit(deposit));
+        assert_eq!(tx.tx_type(), Some(0x7e));
+        assert_eq!(tx.deposit_from(), Some(Address::repeat_byte(2)));
+        assert_eq!(tx.tx_hash(), H256(erigon::utils::keccak256(&envelope)));
+        assert_eq!(tx.gas_price(), Some(U256::zero()));
+        Ok(())
+    }
+
+    #[cfg(feature = "polygon")]
+    #[test]
+    fn test_bor_tables() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use erigon::bor::{bor_tx_hash, models::*, tables as bor};
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+
+        // events 1 and 2 are committed in block 16, 3 and 4 in block 32, and
+        // 5 and 6 in block 48, the last block with events
+        db.write::<bor::BorEventNums>(16.into(), EventId(1))?;
+        db.write::<bor::BorEventNums>(32.into(), EventId(3))?;
+        db.write::<bor::BorEventNums>(48.into(), EventId(5))?;
+        for id in 1..=6u8 {
+            db.write::<bor::BorEvents>(EventId(id.into()), vec![id].into())?;
+        }
+        let events = |num: u64| -> eyre::Result<Vec<Vec<u8>>> {
+            let events = db.read_state_sync_events(num)?;
+            Ok(events.into_iter().map(|e| e.to_vec()).collect())
+        };
+        assert_eq!(events(16)?, vec![vec![1], vec![2]]);
+        assert_eq!(events(32)?, vec![vec![3], vec![4]]);
+        assert_eq!(events(48)?, vec![vec![5], vec![6]]);
+        assert!(events(0)?.is_empty());
+        assert!(events(17)?.is_empty());
+        assert!(events(64)?.is_empty());
+
+        let receipt = models::CborReceipt {
+            status: 1,
+            ..Default::default()
+        };
+        db.write::<bor::BorReceipt>(16.into(), CborBorReceipt(Some(receipt.clone())))?;
+        assert_eq!(db.read_bor_receipt(16)?, Some(receipt));
+        assert_eq!(db.read_bor_receipt(17)?, None);
+
+        // keccak256("matic-bor-receipt-" || be_u64(16) || 0x01 * 32), computed
+        // with a separate keccak implementation
+        let tx_hash = bor_tx_hash(16, H256::repeat_byte(1));
+        assert_eq!(
+            tx_hash,
+            H256(hex_literal::hex!(
+                "9e714bcc9c7db1b95d863ee937399ef9a5fe58f3c0bdae55f602e2510228a7c5"
+            ))
+        );
+        assert_ne!(tx_hash, bor_tx_hash(17, H256::repeat_byte(1)));
+        db.write::<bor::BlockBorTransactionLookup>(tx_hash, 16.into())?;
+        assert_eq!(db.read_bor_tx_block_number(tx_hash)?, Some(16.into()));
+
+        let json = br#"{"span_id":1,"start_block":256,"end_block":6655,
+            "validator_set":{"validators":[]},"selected_producers":[],"bor_chain_id":"137"}"#;
+        let span = Span::decode(json)?;
+        assert_eq!((*span.id, span.start_block, span.end_block), (1, 256, 6655));
+        assert_eq!(span.chain_id, "137");
+        assert_eq!(Span::decode(&span.clone().encode())?, span);
+        db.write::<bor::BorSpans>(span.id, span.clone())?;
+        assert_eq!(db.read_span(1)?, Some(span));
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_supply() -> eyre::Result<()> {
+        use erigon::supply::SupplyChange;
+        use models::{BlockNumber, BurntKey};
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        for (block, issued, burnt) in [(0u64, 0u64, 0u64), (1, 5, 2), (2, 10, 9)] {
+            db.write::<tables::Issuance>(block.into(), issued.into())?;
+            db.write::<tables::Burnt>(BurntKey(block.into()), burnt.into())?;
+        }
+        assert_eq!(db.read_issuance(2)?, Some(10.into()));
+        assert_eq!(db.read_burnt(2)?, Some(9.into()));
+
+        let changes = db.walk_supply(1..)?.collect::<Result<Vec<_>>>()?;
+        let change = |block, issued: u64, burnt: u64| SupplyChange {
+            block: BlockNumber(block),
+            issued: issued.into(),
+            burnt: burnt.into(),
+        };
+        assert_eq!(changes, vec![change(1, 5, 2), change(2, 5, 7)]);
+        assert_eq!(changes[0].delta(), Some(3));
+        assert_eq!(changes[1].delta(), Some(-2));
+        // amounts past i128 have no delta rather than a truncated one
+        let big = SupplyChange {
+            issued: U256::one() << 127,
+            ..change(3, 0, 0)
+        };
+        assert_eq!(big.delta(), None);
+        let big = SupplyChange {
+            burnt: U256::one() << 128,
+            ..change(3, 1, 0)
+        };
+        assert_eq!(big.delta(), None);
+        // a range starting past the last block processed is empty
+        assert!(db.walk_supply(3..)?.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_iter() -> eyre::Result<()> {
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let adr = Address::from_low_u64_be(0x4157e2);
+        let acct = |bal: u64| models::Account::new().balance(bal.into());
+        // created in block 4, changed in 9, and deleted in 12
+        db.write_account_change(4, adr, None)?;
+        db.write_account_change(9, adr, Some(acct(1)))?;
+        db.write_account_change(12, adr, Some(acct(2)))?;
+        db.write_account_history(adr, [4u64, 9, 12].into_iter().collect())?;
+        let hist = db.account_history_iter(adr)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            hist,
+            vec![
+                (4.into(), Some(acct(1))),
+                (9.into(), Some(acct(2))),
+                (12.into(), None)
+            ]
+        );
+
+        // the slot changes under two incarnations of the account
+        let slot = H256::from_low_u64_be(3);
+        db.write_storage_change(5, adr, 1, slot, U256::zero())?;
+        db.write_storage_change(6, adr, 2, slot, 7.into())?;
+        db.write_storage_history(adr, slot, [5u64, 6].into_iter().collect())?;
+        let hist = db
+            .storage_history_iter(adr, slot)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(hist, vec![(5.into(), 7.into()), (6.into(), U256::zero())]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unwind() -> eyre::Result<()> {
+        let env = ENV.clone();
+        let db = Erigon::begin_rw(&env.inner)?;
+        let (adr, created) = (
+            Address::from_low_u64_be(0x0dd),
+            Address::from_low_u64_be(0x0de),
+        );
+        let slot = H256::from_low_u64_be(1);
+        let before = models::Account::new().nonce(1);
+        db.write_account(adr, before)?;
+
+        // block 1001 changes an account and one of its slots
+        db.write_account_change(1001, adr, Some(before))?;
+        db.write_account(adr, before.nonce(2))?;
+        db.write_storage_change(1001, adr, 0, slot, U256::zero())?;
+        db.write_storage(adr, 0, slot, 7.into())?;
+        db.write_account_history(adr, [1001u64].into_iter().collect())?;
+        db.write_storage_history(adr, slot, [1001u64].into_iter().collect())?;
+        // block 1002 creates an account
+        db.write_account_change(1002, created, None)?;
+        db.write_account(created, models::Account::new().nonce(1))?;
+        db.write_account_history(created, [1002u64].into_iter().collect())?;
+        let hash = H256::from_low_u64_be(1002);
+        db.write::<tables::CanonicalHeader>(1002.into(), hash)?;
+        db.write_header_number(hash, 1002.into())?;
+
+        db.unwind_to(1000)?;
+        assert_eq!(db.read_account(adr)?, Some(before));
+        assert_eq!(db.read_storage(adr, 0, slot)?, None);
+        assert_eq!(db.read_account(created)?, None);
+        assert_eq!(db.read_account_hist(adr, 1000)?, None);
+        assert_eq!(db.read_storage_hist(adr, 0, slot, 1000)?, None);
+        assert_eq!(db.read_canonical_hash(1002)?, None);
+        assert_eq!(db.read_header_number(hash)?, None);
+        let mut cur = db.cursor::<tables::AccountChangeSet>()?;
+        assert_eq!(cur.seek_dup(1001.into(), adr)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_genesis() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let genesis: models::Genesis = serde_json::from_str(
+            r#"{
+                "config": { "chainId": 1337, "londonBlock": 0, "shanghaiTime": 0 },
+                "difficulty": "0x1",
+                "gasLimit": "0x1c9c380",
+                "extraData": "0x00",
+                "alloc": {
+                    "0x0000000000000000000000000000000000000001": { "balance": "0x3e8" },
+                    "0x0000000000000000000000000000000000000002": {
+                        "code": "0x6000",
+                        "storage": {
+                            "0x0000000000000000000000000000000000000000000000000000000000000001":
+                            "0x000000000000000000000000000000000000000000000000000000000000002a"
+                        }
+                    }
+                }
+            }"#,
+        )?;
+        let hash = db.init_genesis(&genesis)?;
+        assert_eq!(db.read_genesis_hash()?, Some(hash));
+        assert_eq!(db.read_head_block_number()?, Some(models::BlockNumber(0)));
+        assert_eq!(db.read_chain_config()?.map(|c| c.chain_id), Some(1337));
+        let header = db.read_header((models::BlockNumber(0), hash))?.unwrap();
+        assert_eq!(header.base_fee, Some(1_000_000_000u64.into()));
+        assert!(header.withdrawals_root.is_some());
+        assert_eq!(
+            header.root,
+            H256(hex_literal::hex!(
+                "b91d5347320311885711e34d7dc892a14b0aa5ddbcd966f6c532066147707bb3"
+            ))
+        );
+
+        let eoa = db.read_account(Address::from_low_u64_be(1))?.unwrap();
+        assert_eq!(eoa.balance, 1000.into());
+        let contract = Address::from_low_u64_be(2);
+        let acct = db.read_account(contract)?.unwrap();
+        assert_eq!(*acct.incarnation, 1);
+        assert_eq!(db.read_code(acct.codehash)?.unwrap().0, &[0x60, 0x00][..]);
+        let slot = H256::from_low_u64_be(1);
+        assert_eq!(db.read_storage(contract, 1, slot)?, Some(42.into()));
+        assert_eq!(
+            genesis.alloc[&contract].storage_root(),
+            H256(hex_literal::hex!(
+                "fcbdb9e7191a6bc6efbe2e1903a50bd3c79312366db1e46acf7e94788c2b4c3e"
+            ))
+        );
+        assert_eq!(
+            erigon::trie::HashBuilder::new().root(),
+            erigon::utils::consts::EMPTY_ROOT
+        );
+
+        assert!(matches!(
+            db.init_genesis(&genesis),
+            Err(Error::AlreadyInitialized(h)) if h == hash
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_promote_hashed_state() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let mut genesis: models::Genesis = serde_json::from_str(
+            r#"{
+                "config": { "chainId": 1 },
+                "difficulty": "0x1",
+                "gasLimit": "0x1388",
+                "alloc": {
+                    "0x0000000000000000000000000000000000000001": { "balance": "0x3e8" },
+                    "0x0000000000000000000000000000000000000002": {
+                        "code": "0x6000",
+                        "storage": {
+                            "0x0000000000000000000000000000000000000000000000000000000000000001":
+                            "0x000000000000000000000000000000000000000000000000000000000000002a"
+                        }
+                    }
+                }
+            }"#,
+        )?;
+        let hash = db.init_genesis(&genesis)?;
+        assert_eq!(db.promote_hashed_state()?, models::BlockNumber(0));
+        let header = db.read_header((models::BlockNumber(0), hash))?.unwrap();
+        assert_eq!(db.state_root()?, header.root);
+
+        // in block 1, the eoa sends half its balance to the contract, which
+        // clears slot 1 and sets slot 2
+        let (eoa, contract) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let (slot1, slot2) = (H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+        for (adr, balance) in [(eoa, 500u64), (contract, 500)] {
+            let acct = db.read_account(adr)?.unwrap();
+            db.write_account_change(1, adr, Some(acct))?;
+            db.write_account(adr, acct.balance(balance.into()))?;
+        }
+        db.write_storage_change(1, contract, 1, slot1, 42.into())?;
+        db.write_storage(contract, 1, slot1, U256::zero())?;
+        db.write_storage_change(1, contract, 1, slot2, U256::zero())?;
+        db.write_storage(contract, 1, slot2, 7.into())?;
+        db.write::<tables::SyncStage>(models::StageId::Execution, models::BlockNumber(1))?;
+        assert_eq!(db.promote_hashed_state_since(0)?, models::BlockNumber(1));
+
+        genesis.alloc.get_mut(&eoa).unwrap().balance = 500.into();
+        let alloc = genesis.alloc.get_mut(&contract).unwrap();
+        alloc.balance = 500.into();
+        alloc.storage.remove(&slot1);
+        alloc.storage.insert(slot2, H256::from_low_u64_be(7));
+        assert_eq!(db.state_root()?, genesis.state_root());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_proof() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let genesis: models::Genesis = serde_json::from_str(
+            r#"{
+                "config": { "chainId": 1 },
+                "difficulty": "0x1",
+                "gasLimit": "0x1388",
+                "alloc": {
+                    "0x0000000000000000000000000000000000000001": { "balance": "0x3e8" },
+                    "0x0000000000000000000000000000000000000002": { "nonce": "0x1" },
+                    "0x0000000000000000000000000000000000000003": { "balance": "0x1" }
+                }
+            }"#,
+        )?;
+        for (adr, alloc) in &genesis.alloc {
+            let acct = models::Account::new()
+                .nonce(alloc.nonce.as_u64())
+                .balance(alloc.balance);
+            db.write::<tables::HashedAccount>(H256(erigon::utils::keccak256(adr)), acct)?;
+        }
+        db.write::<tables::SyncStage>(models::StageId::HashState, models::BlockNumber(0))?;
+        let root = db.state_root()?;
+        assert_eq!(root, genesis.state_root());
+
+        let adr = Address::from_low_u64_be(1);
+        let proof = db.get_proof(adr, &[H256::zero()], 0)?;
+        assert_eq!(proof.balance, 1000.into());
+        assert_eq!(proof.code_hash, erigon::utils::consts::EMPTY_HASH);
+        assert_eq!(proof.storage_hash, erigon::utils::consts::EMPTY_ROOT);
+        assert_eq!(
+            H256(erigon::utils::keccak256(&proof.account_proof[0])),
+            root
+        );
+        assert_eq!(proof.storage_proof[0].value, U256::zero());
+        assert!(proof.storage_proof[0].proof.is_empty());
+
+        assert!(matches!(
+            db.get_proof(adr, &[], 1),
+            Err(Error::StateUnavailable { .. })
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "alloy")]
+    #[test]
+    fn test_alloy_conversions() -> eyre::Result<()> {
+        use models::transaction::{DynamicFeeTx, TxAction};
+        let header = models::BlockHeader {
+            number: 5.into(),
+            gas_limit: 30_000_000,
+            base_fee: Some(7.into()),
+            withdrawals_root: Some(H256::repeat_byte(1)),
+            ..Default::default()
+        };
+        let converted = alloy_consensus::Header::try_from(header.clone()).unwrap();
+        assert_eq!(converted.hash_slow(), alloy::b256(header.hash()));
+        // an AuRa header only converts sealed with its block hash
+        let mut aura = header.clone();
+        aura.set_aura_seal(9, vec![0x5e; 65].into());
+        assert!(alloy_consensus::Header::try_from(aura.clone()).is_err());
+        let sealed = alloy_primitives::Sealed::<alloy_consensus::Header>::try_from(aura.clone());
+        assert_eq!(sealed.unwrap().hash(), alloy::b256(aura.hash()));
+        assert_ne!(aura.hash(), header.hash());
+        // numbers past their alloy fields are not truncated
+        let far = models::BlockHeader {
+            number: U256::from(u64::MAX) + 1,
+            ..header
+        };
+        assert_eq!(alloy_consensus::Header::try_from(far.clone()), Err(far));
+
+        let tx = models::Transaction::DynamicFee(DynamicFeeTx {
+            chain_id: 1.into(),
+            nonce: 3,
+            tip: 1.into(),
+            fee_cap: 10.into(),
+            gas: 21000,
+            to: TxAction::Call(Address::repeat_byte(2)),
+            value: 1.into(),
+            data: Default::default(),
+            access_list: vec![],
+            v: 1.into(),
+            r: 1.into(),
+            s: 1.into(),
+        });
+        let hash = tx.tx_hash();
+        let envelope = alloy_consensus::TxEnvelope::try_from(tx.clone()).unwrap();
+        assert_eq!(*envelope.tx_hash(), alloy::b256(hash));
+        let mut costly = tx;
+        if let models::Transaction::DynamicFee(tx) = &mut costly {
+            tx.fee_cap = U256::from(u128::MAX) + 1;
+        }
+        assert_eq!(
+            alloy_consensus::TxEnvelope::try_from(costly.clone()),
+            Err(costly)
+        );
+        assert_eq!(alloy::u256(U256::MAX), alloy_primitives::U256::MAX);
+        Ok(())
+    }
+
+    #[cfg(feature = "ethers-types")]
+    #[test]
+    fn test_ethers_block() {
+        use models::transaction::{LegacyTx, TxAction, VPackChainId};
+        let tx = models::TransactionWithSigner {
+            msg: models::Transaction::Legacy(LegacyTx {
+                nonce: 0,
+                gas_price: 1.into(),
+                gas: 21000,
+                to: TxAction::Call(Address::from_low_u64_be(2)),
+                value: 1.into(),
+                data: Default::default(),
+                v: VPackChainId(37.into()),
+                r: 1.into(),
+                s: 1.into(),
+            }),
+            signer: Address::from_low_u64_be(1),
+        };
+        let tx_hash = tx.msg.tx_hash();
+        // the v of an Eip155 tx keeps its chain id
+        let converted = ethers::types::Transaction::from(tx.clone());
+        assert_eq!(converted.v, 37.into());
+        assert_eq!(converted.chain_id, Some(1.into()));
+        let header = models::BlockHeader {
+            number: 5.into(),
+            gas_used: 21000,
+            ..Default::default()
+        };
+        let block = models::Block {
+            header: header.clone(),
+            transactions: vec![tx],
+            uncles: vec![],
+            withdrawals: None,
+        };
+        let converted = middleware::ethers_block(block, Some(10.into()));
+        assert_eq!(converted.hash, Some(header.hash()));
+        assert_eq!(converted.number, Some(5.into()));
+        assert_eq!(converted.gas_used, 21000.into());
+        assert_eq!(converted.transactions, vec![tx_hash]);
+        assert_eq!(converted.total_difficulty, Some(10.into()));
+    }
+
+    #[test]
+    fn test_write_block() -> eyre::Result<()> {
+        use models::transaction::{LegacyTx, TxAction, VPackChainId};
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let genesis: models::Genesis = serde_json::from_str(
+            r#"{ "config": { "chainId": 1 }, "difficulty": "0x2", "gasLimit": "0x1388" }"#,
+        )?;
+        let genesis_hash = db.init_genesis(&genesis)?;
+
+        let tx = |nonce| models::TransactionWithSigner {
+            msg: models::Transaction::Legacy(LegacyTx {
+                nonce,
+                gas_price: 1.into(),
+                gas: 21000,
+                to: TxAction::Call(Address::from_low_u64_be(2)),
+                value: 1.into(),
+                data: Default::default(),
+                v: VPackChainId(27.into()),
+                r: 1.into(),
+                s: 1.into(),
+            }),
+            signer: Address::from_low_u64_be(1),
+        };
+        let block = models::Block {
+            header: models::BlockHeader {
+                parent_hash: genesis_hash,
+                number: 1.into(),
+                difficulty: 3.into(),
+                ..Default::default()
+            },
+            transactions: vec![tx(0), tx(1)],
+            uncles: vec![],
+            withdrawals: None,
+        };
+        let hash = db.write_block(block.clone())?;
+        assert_eq!(db.read_canonical_hash(1)?, Some(hash));
+        assert_eq!(db.read_block(1)?, Some(block));
+        let td = db
+            .read_total_difficulty((models::BlockNumber(1), hash))?
+            .unwrap();
+        assert_eq!(*td, 5.into());
+        // genesis took ids 0 and 1 for its system txs, and this block starts at 2
+        let body = db
+            .read_body_for_storage((models::BlockNumber(1), hash))?
+            .unwrap();
+        assert_eq!((body.base_tx_id, body.tx_amount), (3, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rlp() -> eyre::Result<()> {
+        use crate::{
+            kv::traits::{TableDecode, TableEncode},
+            rlp::DecodeError,
+        };
+        use models::transaction::{LegacyTx, TxAction, VPackChainId};
+        let decode_u64 = |mut b: &[u8]| <u64 as crate::rlp::Decodable>::decode(&mut b);
+        // wrappers encode as their inner value, not as a list of it
+        let td = models::TotalDifficulty(0x0400.into());
+        assert_eq!(&td.encode()[..], &[0x82, 0x04, 0x00]);
+        assert_eq!(models::TotalDifficulty::decode(&[0x82, 0x04, 0x00])?, td);
+
+        let tx = models::Transaction::Legacy(LegacyTx {
+            nonce: 9,
+            gas_price: 20_000_000_000u64.into(),
+            gas: 21000,
+            to: TxAction::Call(Address::repeat_byte(0x35)),
+            value: 1_000_000_000_000_000_000u64.into(),
+            data: Default::default(),
+            v: VPackChainId(37.into()),
+            r: 1.into(),
+            s: 2.into(),
+        });
+        let encoded = tx.clone().encode();
+        assert_eq!(models::Transaction::decode(&encoded)?, tx);
+        // the Eip155 example tx, whose signing hash is given in the Eip
+        assert_eq!(
+            tx.signing_hash(),
+            "0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53".parse()?
+        );
+
+        // integers must be canonical
+        assert_eq!(
+            decode_u64(&[0x82, 0x00, 0x01]),
+            Err(DecodeError::LeadingZero)
+        );
+        assert_eq!(
+            decode_u64(&[0x81, 0x01]),
+            Err(DecodeError::NonCanonicalSingleByte)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_receipts() -> eyre::Result<()> {
+        use crate::kv::traits::TableDecode;
+        // [[0, null, 1, 21000], [2, h'', 1, 42000]]
+        let raw = hex::decode("828400f6011952088402400119a410")?;
+        let receipts = models::CborReceipts::decode(&raw)?.0.unwrap();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[1].tx_type, 2);
+        assert_eq!(receipts[1].post_state, None);
+        assert_eq!(receipts[1].status, 1);
+        assert_eq!(models::gas_used_per_tx(&receipts), vec![21000, 21000]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_account() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        // fieldset 0b1111, then length-prefixed nonce, balance, incarnation and codehash
+        let raw = hex::decode(
+            "0f0101080de0b6b3a7640000010120abababababababababababababababababababababababababababababababab",
+        )?;
+        let acct = models::Account::new()
+            .nonce(1)
+            .balance(U256::exp10(18))
+            .incarnation(1.into())
+            .codehash(H256::repeat_byte(0xab));
+        assert_eq!(acct.encode(), raw);
+        assert_eq!(models::Account::decode(&raw)?, acct);
+        // truncated fields are an error rather than a panic or a default
+        assert!(models::Account::decode(&raw[..raw.len() - 1]).is_err());
+        assert!(models::Account::decode(&raw[..4]).is_err());
+
+        // fields holding their default are omitted, as is the empty codehash
+        let eoa = models::Account::new().balance(U256::from(0x0100));
+        let raw = eoa.encode();
+        assert_eq!(raw, hex::decode("02020100")?);
+        assert_eq!(models::Account::decode(&raw)?, eoa);
+        let empty = models::Account::new().codehash(erigon::utils::consts::EMPTY_HASH);
+        assert_eq!(empty.encode(), vec![0]);
+        assert_eq!(models::Account::decode(&[0])?, models::Account::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_trie_node() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        // children 1 and 3 are hashed, and the node holds the root hash first
+        let mut raw = hex::decode("000e0002000a")?;
+        raw.extend([0xff; 32].iter().chain(&[0x01; 32]).chain(&[0x03; 32]));
+        let node = models::TrieNode::decode(&raw)?;
+        assert_eq!(
+            (node.state_mask, node.tree_mask, node.hash_mask),
+            (0xe, 2, 0xa)
+        );
+        assert_eq!(node.root_hash, Some(H256::repeat_byte(0xff)));
+        assert_eq!(node.child_hash(1), Some(H256::repeat_byte(0x01)));
+        assert_eq!(node.child_hash(2), None);
+        assert_eq!(node.child_hash(3), Some(H256::repeat_byte(0x03)));
+        assert_eq!(node.encode(), raw);
+        // a hash missing from the list, or a partial hash, is an error
+        assert!(models::TrieNode::decode(&raw[..6 + 32]).is_err());
+        assert!(models::TrieNode::decode(&raw[..raw.len() - 1]).is_err());
+
+        let key = models::TrieStorageKey(
+            H256::repeat_byte(0xaa),
+            models::Incarnation(1),
+            models::Nibbles::unpack([0x1f]),
+        );
+        let raw = key.clone().encode();
+        assert_eq!(raw.len(), 32 + 8 + 2);
+        assert_eq!(&raw[40..], &[0x01, 0x0f]);
+        assert_eq!(models::TrieStorageKey::decode(&raw)?, key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_corrupt() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use models::transaction::{LegacyTx, TxAction, VPackChainId};
+        // a field promised by the fieldset must be present and fit its type
+        assert!(models::Account::decode(&[0x01]).is_err());
+        assert!(models::Account::decode(&hex::decode("010900000000000000000001")?).is_err());
+        assert!(models::Account::decode(&[0x08, 0x20, 0xab]).is_err());
+        assert!(models::Account::decode(&[0x08, 0x01, 0xab]).is_err());
+
+        // every truncation of a nested tuple key is an error
+        let key = models::StorageCSKey(
+            7.into(),
+            models::StorageKey(Address::repeat_byte(0x01), 1.into()),
+        );
+        let raw = key.encode().to_vec();
+        assert_eq!(models::StorageCSKey::decode(&raw)?, key);
+        for len in 0..raw.len() {
+            assert!(models::StorageCSKey::decode(&raw[..len]).is_err());
+        }
+
+        // as is every truncation of an rlp header or transaction, including
+        // an empty input and an empty typed transaction
+        let mut header = Vec::new();
+        rlp::Encodable::encode(&models::BlockHeader::default(), &mut header);
+        let mut tx = Vec::new();
+        let legacy = models::Transaction::Legacy(LegacyTx {
+            nonce: 1,
+            gas_price: U256::zero(),
+            gas: 21000,
+            to: TxAction::Call(Address::repeat_byte(0x02)),
+            value: 100.into(),
+            data: Default::default(),
+            v: VPackChainId(27.into()),
+            r: 1.into(),
+            s: 1.into(),
+        });
+        rlp::Encodable::encode(&legacy, &mut tx);
+        for len in 0..header.len() {
+            assert!(<models::BlockHeader as rlp::Decodable>::decode(&mut &header[..len]).is_err());
+        }
+        for len in 0..tx.len() {
+            assert!(<models::Transaction as rlp::Decodable>::decode(&mut &tx[..len]).is_err());
+        }
+        assert!(<models::Transaction as rlp::Decodable>::decode(&mut &[0x80][..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "reads a synced db at $ERIGON_CHAINDATA"]
     fn test_live() -> eyre::Result<()> {
-        let path = Path::new(env!("ERIGON_CHAINDATA"));
-        let env = env_open(path)?;
+        let path = std::env::var("ERIGON_CHAINDATA")?;
+        let env = env_open(std::path::Path::new(&path))?;
         let db = Erigon::begin(&env)?;
 
-        let _dst: Address = "0xa94f5374Fce5edBC8E2a8697C15331677e6EbF0B".parse()?;
-        let contract: Address = "0x0d4c6c6605a729a379216c93e919711a081beba2".parse()?;
-        let _res = db.read_account_hist(contract, 3)?;
+        let _dst: Address = "0xa94f5374Fce5edBC8E2a8697C15331677e6EbF0B".parse()?;
+        let contract: Address = "0x0d4c6c6605a729a379216c93e919711a081beba2".parse()?;
+        let _res = db.read_account_hist(contract, 3)?;
+
+        // accounts re-encode to exactly the bytes stored in PlainState
+        if let Some(acct) = db.read_account(contract)? {
+            use crate::kv::traits::TableEncode;
+            let raw = db.0.open_raw("PlainState")?;
+            let stored =
+                db.0.get::<kv::tables::RawTable, _>(raw, contract.0.to_vec())?;
+            assert_eq!(Some(acct.encode()), stored);
+        }
+
+        let slot = H256::from_low_u64_be(1);
+        let res = db.read_storage_hist(contract, 1, slot, 0)?;
+        let current = db.read_storage(contract, 2, slot)?;
+        dbg!(res);
+        dbg!(current);
+        let state = db.state_at(3);
+        dbg!(state.storage(contract, slot)?);
+        for read in db.walk_storage(contract, 1, None)? {
+            let (key, val) = read?;
+            dbg!(key, val);
+        }
+
+        let hash = db.read_head_header_hash()?.unwrap();
+        let num = db.read_header_number(hash)?.unwrap();
+        assert!(db.verify_canonical(num)?);
+
+        let td = db.read_total_difficulty((num, hash))?.unwrap();
+        dbg!(td);
+
+        dbg!(db.read_issuance(1)?, db.read_burnt(1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_fixture() -> eyre::Result<()> {
+        use crate::kv::traits::TableEncode;
+        let fixture = fixtures::ChainBuilder::new(12).build()?;
+        let chain = &fixture.chain;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        // a contract is deployed in blocks 4, 8 and 12
+        assert_eq!(chain.contracts.len(), 3);
+
+        let head = db.read_head_header_hash()?.unwrap();
+        assert_eq!(head, chain.hashes[12]);
+        for num in 0..=*chain.head() {
+            assert!(db.verify_canonical(num)?);
+        }
+        let header = db.read_header((chain.head(), head))?.unwrap();
+        assert_eq!(db.state_root()?, header.root);
+        let td = db.read_total_difficulty((chain.head(), head))?.unwrap();
+        assert_eq!(*td, 13.into());
+
+        // accounts re-encode to exactly the bytes stored in PlainState
+        let contract = chain.contracts[0];
+        let acct = db.read_account(contract)?.unwrap();
+        let raw = db.0.open_raw("PlainState")?;
+        let stored =
+            db.0.get::<kv::tables::RawTable, _>(raw, contract.0.to_vec())?;
+        assert_eq!(Some(acct.encode()), stored);
+        assert_eq!(db.state_at(3).account(contract)?, None);
+        assert_eq!(db.state_at(4).account(contract)?, Some(acct));
+
+        // the history of each slot agrees with its current value
+        for read in db.walk_storage(contract, 1, None)? {
+            let (slot, val) = read?;
+            let blocks = db.read_storage_history_index(contract, slot)?;
+            let (first, last) = (blocks.min().unwrap(), blocks.max().unwrap());
+            assert_eq!(
+                db.state_at(first - 1).storage(contract, slot)?,
+                U256::zero()
+            );
+            assert_eq!(db.state_at(last).storage(contract, slot)?, val);
+        }
+        let eoa = db.state_at(0).account(chain.accounts[0])?.unwrap();
+        assert_eq!(eoa.balance, U256::exp10(24));
+
+        // the chain depends only on the builder's settings
+        assert_eq!(fixtures::ChainBuilder::new(12).build()?.chain, *chain);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lifecycle_fixture() -> eyre::Result<()> {
+        use fixtures::lifecycle::{DST, FACTORY, SRC, STORE_CODE};
+        use models::{BlockNumber, Incarnation};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+
+        // the contracts are at the addresses their deployers derive
+        assert_eq!(fixtures::create_address(SRC, 1), FACTORY);
+        assert_eq!(
+            fixtures::create2_address(FACTORY, H256::zero(), STORE_CODE),
+            store
+        );
+        // the sender's nonce counts the txs sealed so far, read through history
+        for num in 0..=5u64 {
+            let src = db.state_at(num).account(SRC)?.unwrap();
+            assert_eq!(src.nonce, num);
+        }
+        assert_eq!(db.state_at(0).account(DST)?, None);
+        assert_eq!(db.state_at(1).account(DST)?.unwrap().balance, 100.into());
+        // the Store's account changed only when it was created and destroyed
+        let hist = db
+            .account_history_iter(store)?
+            .map(|res| res.map(|(num, acct)| (num, acct.is_some())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(hist, [(BlockNumber(3), true), (BlockNumber(4), false)]);
+        // the Factory keeps the address of the Store it created in block 3
+        let last = db.read_storage(FACTORY, 1, H256::zero())?.unwrap();
+        assert_eq!(last, U256::from_big_endian(store.as_bytes()));
+
+        // the Store was destroyed in block 4, leaving its storage in history
+        let slot = H256::from_low_u64_be(1);
+        assert_eq!(db.read_account(store)?, None);
+        assert_eq!(db.read_incarnation(store)?, Some(Incarnation(1)));
+        assert_eq!(db.read_storage(store, 1, slot)?, None);
+        let state = db.state_at(3);
+        assert_eq!(state.storage(store, slot)?, 3.into());
+        assert_eq!(state.storage(store, H256::from_low_u64_be(2))?, U256::MAX);
+        assert_eq!(state.code(store)?.0, STORE_CODE);
+        assert_eq!(db.state_at(4).storage(store, slot)?, U256::zero());
+        assert_eq!(db.state_at(5).account(store)?, None);
+
+        let head = fixture.chain.head();
+        let header = db.read_header((head, fixture.chain.hashes[5]))?.unwrap();
+        assert_eq!(db.state_root()?, header.root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_code_by_address() -> eyre::Result<()> {
+        use fixtures::lifecycle::{FACTORY, FACTORY_CODE, SRC};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+
+        let code = db.read_code_by_address(FACTORY)?.unwrap();
+        assert_eq!(code.0, FACTORY_CODE);
+        assert_eq!(db.read_code_size(FACTORY)?, Some(FACTORY_CODE.len()));
+        // neither an EOA nor a destroyed contract has code
+        assert_eq!(db.read_code_by_address(SRC)?, None);
+        assert_eq!(db.read_code_size(SRC)?, None);
+        assert_eq!(db.read_code_by_address(store)?, None);
+        assert_eq!(db.read_code_size(Address::repeat_byte(0xee))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_code_hist() -> eyre::Result<()> {
+        use fixtures::lifecycle::{FACTORY, FACTORY_CODE, STORE_CODE};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+
+        // the Factory was deployed in block 2 and is still live
+        assert_eq!(db.read_code_hist(FACTORY, 2)?, None);
+        assert_eq!(db.read_code_hist(FACTORY, 3)?.unwrap().0, FACTORY_CODE);
+        assert_eq!(db.read_code_hist(FACTORY, 6)?.unwrap().0, FACTORY_CODE);
+        // the Store was created in block 3 and destroyed in block 4
+        assert_eq!(db.read_code_hist(store, 3)?, None);
+        assert_eq!(db.read_code_hist(store, 4)?.unwrap().0, STORE_CODE);
+        assert_eq!(db.read_code_hist(store, 5)?, None);
+        // the code before block 4 is the code after block 3
+        let after = db.state_at(3).code(store)?;
+        assert_eq!(db.read_code_hist(store, 4)?, Some(after));
+        Ok(())
+    }
+
+    #[test]
+    fn test_storage_layout() -> eyre::Result<()> {
+        use erigon::layout::{array_slot, mapping_slot, ERC20_BALANCES_SLOT};
+        use fixtures::{ChainWriter, Fixture};
+        use hex_literal::hex;
+        let holder = Address::repeat_byte(0x11);
+        let slot = mapping_slot(ERC20_BALANCES_SLOT, holder.into());
+        assert_eq!(
+            slot,
+            H256(hex!(
+                "f043c50fe795c69f30b8ff78b84032dc53a9d87ca283ae10a1dacfbb648e83ef"
+            ))
+        );
+        // keccak256(uint256(2)) + 1
+        assert_eq!(
+            array_slot(H256::from_low_u64_be(2), 1.into()),
+            H256(hex!(
+                "405787fa12a823e0f2b7631cc41b3ba8828b3321ca811111fa75cd3aa3bb5acf"
+            ))
+        );
+
+        let token = Address::repeat_byte(0x70);
+        let fixture = Fixture::new(|db| {
+            let mut w = ChainWriter::genesis(db, [])?;
+            let code = bytes::Bytes::from_static(&[0x00]);
+            w.create_contract(token, code, [(slot, 1000.into())])?;
+            w.seal()?;
+            w.set_storage(token, slot, 400.into())?;
+            w.seal()?;
+            w.finish()
+        })?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        assert_eq!(db.read_erc20_balance(token, holder, 0)?, U256::zero());
+        assert_eq!(db.read_erc20_balance(token, holder, 1)?, 1000.into());
+        assert_eq!(db.read_erc20_balance(token, holder, 2)?, 400.into());
+        let other = Address::repeat_byte(0x22);
+        assert_eq!(db.read_erc20_balance(token, other, 2)?, U256::zero());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_storage_many() -> eyre::Result<()> {
+        use fixtures::{ChainWriter, Fixture};
+        let pool = Address::repeat_byte(0x50);
+        let slot = H256::from_low_u64_be;
+        let fixture = Fixture::new(|db| {
+            let mut w = ChainWriter::genesis(db, [])?;
+            let storage = (1..=100).map(|i| (slot(i * 2), U256::from(i)));
+            w.create_contract(pool, bytes::Bytes::from_static(&[0x00]), storage)?;
+            w.seal()?;
+            w.finish()
+        })?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+
+        // unsorted, with a repeat and slots that hold no value
+        let slots = [slot(200), slot(3), slot(2), slot(0), slot(100), slot(2)];
+        let vals = db.read_storage_many(pool, 1, &slots)?;
+        let expected = [(slot(2), 1), (slot(100), 50), (slot(200), 100)]
+            .into_iter()
+            .map(|(slot, val)| (slot, U256::from(val)))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        assert_eq!(vals, expected);
+        for slot in slots {
+            assert_eq!(db.read_storage(pool, 1, slot)?, vals.get(&slot).copied());
+        }
+        assert!(db.read_storage_many(pool, 2, &slots)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_storage_auto() -> eyre::Result<()> {
+        use fixtures::lifecycle::{FACTORY, SRC};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+
+        let last = U256::from_big_endian(store.as_bytes());
+        assert_eq!(db.read_storage_auto(FACTORY, H256::zero())?, Some(last));
+        let storage = db.walk_storage_auto(FACTORY)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(storage, vec![(H256::zero(), last)]);
+        // the destroyed Store is found by its last incarnation, with its
+        // storage cleared
+        assert_eq!(db.read_storage_auto(store, H256::from_low_u64_be(1))?, None);
+        assert_eq!(db.walk_storage_auto(store)?.count(), 0);
+        assert_eq!(db.read_storage_auto(SRC, H256::zero())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_hashed_storage() -> eyre::Result<()> {
+        use erigon::utils::keccak256;
+        use fixtures::lifecycle::FACTORY;
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+
+        let hashed_adr = H256(keccak256(FACTORY));
+        let storage = db
+            .walk_hashed_storage(hashed_adr, 1)?
+            .collect::<Result<Vec<_>>>()?;
+        let last = U256::from_big_endian(store.as_bytes());
+        assert_eq!(storage, vec![(H256(keccak256(H256::zero())), last)]);
+        assert_eq!(db.walk_hashed_storage(hashed_adr, 2)?.count(), 0);
+        // the Store's hashed storage was cleared when it was destroyed
+        let hashed_store = H256(keccak256(store));
+        assert_eq!(db.walk_hashed_storage(hashed_store, 1)?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_by_block_number() -> eyre::Result<()> {
+        use models::{BlockNumber, HeaderKey};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let hashes = &fixture.chain.hashes;
+
+        let mut headers = db.cursor::<tables::Header>()?;
+        let (key, header) = headers.seek(BlockNumber(3))?.unwrap();
+        assert_eq!(key, HeaderKey(BlockNumber(3), hashes[3]));
+        assert_eq!(header.hash(), hashes[3]);
+        assert_eq!(header.parent_hash, hashes[2]);
+        assert!(headers.seek(BlockNumber(6))?.is_none());
+
+        let mut bodies = db.cursor::<tables::BlockBody>()?;
+        let (key, body) = bodies.seek(BlockNumber(1))?.unwrap();
+        assert_eq!(key, HeaderKey(BlockNumber(1), hashes[1]));
+        // the stored count includes a system tx at either end of the block
+        assert_eq!(body.tx_amount, 3);
+        let (key, _) = bodies.seek(BlockNumber(0))?.unwrap();
+        assert_eq!(key, HeaderKey(BlockNumber(0), hashes[0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_registry() -> eyre::Result<()> {
+        let names = tables::ALL_TABLES;
+        assert_eq!(names.len(), 40);
+        assert!(erigon::NUM_TABLES > names.len());
+        for name in ["PlainState", "Issuance", "DbInfo", "Sequence"] {
+            assert_eq!(names.iter().filter(|&&n| n == name).count(), 1);
+        }
+        assert!(!names.contains(&"Storage") && !names.contains(&"Burnt"));
+        assert_eq!(erigon::erigon3::tables::ALL_TABLES.len(), 10);
+
+        // an Erigon 3 table is unknown to a schema 6 db
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        db.write_head_header_hash(H256::repeat_byte(0x01))?;
+        db.write::<erigon::erigon3::tables::MaxTxNum>(1.into(), 2.into())?;
+        assert_eq!(db.unknown_tables()?, vec!["MaxTxNum".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_rename() -> eyre::Result<()> {
+        use crate::kv::traits::DbName;
+        use models::{BurntKey, StorageKey};
+        assert_eq!(tables::Burnt::NAME, "Issuance");
+        assert_eq!(tables::Storage::NAME, "PlainState");
+        assert_eq!(tables::TxSequence::NAME, "Sequence");
+        assert_eq!(tables::Burnt.to_string(), "Burnt");
+
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let adr = Address::repeat_byte(0x01);
+        db.write::<tables::Burnt>(BurntKey(5.into()), 7.into())?;
+        db.write::<tables::Storage>(StorageKey(adr, 1.into()), (H256::zero(), 9.into()))?;
+        let names = db.0.list_tables()?;
+        assert!(names.iter().any(|name| name == "Issuance"));
+        assert!(names.iter().any(|name| name == "PlainState"));
+        assert!(!names
+            .iter()
+            .any(|name| name == "Burnt" || name == "Storage"));
+
+        // the entries are in the renamed dbs, under their own keys
+        let raw = db.0.open_raw("Issuance")?;
+        let key = [&b"burnt"[..], &5u64.to_be_bytes()].concat();
+        let val = db.0.get::<kv::tables::RawTable, _>(raw, key)?;
+        assert_eq!(val, Some(vec![7]));
+        assert_eq!(db.read::<tables::Issuance>(5.into())?, None);
+        assert_eq!(db.read_storage(adr, 1, H256::zero())?, Some(9.into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_compat() -> eyre::Result<()> {
+        use erigon::schema::{FlagMismatch, Schema};
+        use models::{DbSchemaVersionKey, SchemaVersion};
+        let dir = tempfile::tempdir()?;
+        {
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            let db = Erigon::begin_rw(&env)?;
+            db.write_storage(Address::repeat_byte(0x01), 1, H256::zero(), 9.into())?;
+            db.0.commit()?;
+            let report = Erigon::new(env.begin_ro()?).check_compat()?;
+            assert_eq!(report.version, None);
+            assert_eq!(report.schema, Some(Schema::V2));
+            assert!(report.missing_tables.contains(&"Header"));
+            assert!(!report.missing_tables.contains(&"PlainState"));
+            assert!(report.is_compatible());
+
+            // a dupsort table created without the flag
+            let db = Erigon::begin_rw(&env)?;
+            db.0.open_db::<tables::AccountChangeSet, kv::tables::NoFlags>()?;
+            db.0.commit()?;
+            let report = Erigon::new(env.begin_ro()?).check_compat()?;
+            let mismatch = FlagMismatch {
+                table: "AccountChangeSet",
+                expected_dupsort: true,
+            };
+            assert_eq!(report.flag_mismatches, vec![mismatch]);
+            assert!(!report.is_compatible());
+            assert!(!report.history_v3);
+
+            // Erigon 3 history next to an Erigon 2 PlainState
+            let db = Erigon::begin_rw(&env)?;
+            let key = erigon::schema::HISTORY_V3_KEY;
+            db.write::<tables::DbInfo>(key.into(), vec![1].into())?;
+            db.0.commit()?;
+            let report = Erigon::new(env.begin_ro()?).check_compat()?;
+            assert!(report.history_v3);
+            assert!(report.to_string().ends_with(", history.v3 enabled"));
+            let report = erigon::schema::CompatReport {
+                flag_mismatches: vec![],
+                ..report
+            };
+            assert!(!report.is_compatible());
+
+            // an unknown version leaves the tables unchecked
+            let db = Erigon::begin_rw(&env)?;
+            let version = SchemaVersion::new(9, 0, 0);
+            db.write::<tables::DbSchemaVersion>(DbSchemaVersionKey, version)?;
+            db.0.commit()?;
+            let report = Erigon::new(env.begin_ro()?).check_compat()?;
+            assert_eq!(report.version, Some(version));
+            assert_eq!(report.schema, None);
+            assert!(report.flag_mismatches.is_empty());
+            assert_eq!(report.to_string(), "unsupported schema version 9.0.0");
+        }
+        let res = erigon::env_open_checked::<mdbx::RO>(dir.path());
+        assert!(matches!(res, Err(Error::Incompatible(report)) if report.schema.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_open_readonly_no_lock() -> eyre::Result<()> {
+        use std::{fs, os::unix::fs::PermissionsExt};
+        let dir = tempfile::tempdir()?;
+        let adr = Address::from_low_u64_be(0x10c);
+        let acct = models::Account::new().nonce(2);
+        {
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            let db = Erigon::begin_rw(&env)?;
+            db.write_account(adr, acct)?;
+            db.0.commit()?;
+        }
+        // without its LCK-file, in a directory it cannot be created in
+        let lck = dir.path().join("mdbx.lck");
+        fs::remove_file(&lck)?;
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o555))?;
+        // root ignores the permissions, and mdbx then creates the LCK-file
+        let probe = dir.path().join("probe");
+        let writable = fs::File::create(&probe).is_ok();
+        let read = erigon::env_open_readonly_no_lock(dir.path()).and_then(|env| {
+            let db = Erigon::begin(&env)?;
+            Ok((db.read_account(adr)?, db.schema()?))
+        });
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755))?;
+        assert_eq!(read?, (Some(acct), erigon::schema::Schema::V2));
+        if !writable {
+            assert!(!lck.exists());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_check() -> eyre::Result<()> {
+        use models::BlockNumber;
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let report = db.check()?;
+        assert!(report.passed());
+        let names = report.checks.iter().map(|c| c.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "canonical_blocks",
+                "senders",
+                "tx_lookup",
+                "account_history",
+                "storage_history",
+                "incarnations"
+            ]
+        );
+        assert!(report.checks.iter().all(|c| c.checked > 0));
+        assert_eq!(report.checks[2].checked, 5);
+        let (key1, key2) = (
+            db.read_header_key(1)?.unwrap(),
+            db.read_header_key(2)?.unwrap(),
+        );
+        let tx_hash = db.read_block_transactions(key1)?.unwrap()[0].tx_hash();
+        let tx_hash2 = db.read_block_transactions(key2)?.unwrap()[0].tx_hash();
+        drop(db);
+
+        let db = Erigon::begin_rw(&fixture.env)?;
+        db.write::<tables::TxSender>(key1, vec![])?;
+        db.delete::<tables::BlockBody>(key2, None)?;
+        db.write_transaction_block_number(tx_hash, 1.into())?;
+        db.write_transaction_block_number(H256::repeat_byte(0xbb), 1.into())?;
+        let adr = Address::repeat_byte(0xad);
+        db.write_account_history(adr, [3u64].into_iter().collect())?;
+        let report = db.check()?;
+        // the lookup of the block 2 tx now points at a missing body
+        let mut bad_lookups = [tx_hash2, H256::repeat_byte(0xbb)];
+        bad_lookups.sort();
+        let failures = report
+            .failures()
+            .map(|c| (c.name, c.failed, c.samples.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failures,
+            [
+                ("canonical_blocks", 1, vec![format!("{:?}", key2)]),
+                (
+                    "senders",
+                    2,
+                    vec![format!("{:?}", key1), format!("{:?}", key2)]
+                ),
+                (
+                    "tx_lookup",
+                    2,
+                    bad_lookups.iter().map(|h| format!("{:?}", h)).collect()
+                ),
+                (
+                    "account_history",
+                    1,
+                    vec![format!("{:?}", (BlockNumber(3), adr))]
+                ),
+            ]
+        );
+        assert_eq!(report.checks[2].checked, 6);
+        Ok(())
+    }
+
+    #[cfg(feature = "lru")]
+    #[test]
+    fn test_cached_erigon() -> eyre::Result<()> {
+        use erigon::cached::{CacheSizes, CacheStats, CachedErigon};
+        use fixtures::lifecycle::{FACTORY, FACTORY_CODE, SRC};
+        use std::num::NonZeroUsize;
+        let fixture = fixtures::lifecycle::build()?;
+        let mut cached = CachedErigon::new(&fixture.env, CacheSizes::default())?;
+        let acct = cached.read_account(SRC)?.unwrap();
+        assert_eq!(
+            cached.read_code_by_address(FACTORY)?.unwrap().0,
+            FACTORY_CODE
+        );
+        let key = cached.read_header_key(2)?.unwrap();
+        assert_eq!(
+            cached.read_header(key)?,
+            cached.read::<tables::Header>(key)?
+        );
+
+        // a committed change is seen only once the transaction is refreshed,
+        // and the stale read is answered from the cache
+        let db = Erigon::begin_rw(&fixture.env)?;
+        db.write_account(SRC, acct.nonce(acct.nonce + 1))?;
+        db.0.commit()?;
+        let hits = cached.stats().hits;
+        assert_eq!(cached.read_account(SRC)?, Some(acct));
+        assert_eq!(cached.stats().hits, hits + 1);
+        cached.refresh()?;
+        assert_eq!(cached.stats(), CacheStats::default());
+        assert_eq!(cached.read_account(SRC)?.unwrap().nonce, acct.nonce + 1);
+        assert_eq!(cached.stats().misses, 1);
+
+        // a cache of one entry keeps only the last account read
+        let one = NonZeroUsize::new(1).unwrap();
+        let sizes = CacheSizes {
+            code: one,
+            headers: one,
+            accounts: one,
+        };
+        let cached = CachedErigon::new(&fixture.env, sizes)?;
+        for adr in [SRC, SRC, FACTORY, SRC] {
+            cached.read_account(adr)?;
+        }
+        let want = CacheStats { hits: 1, misses: 3 };
+        assert_eq!(cached.stats(), want);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_accounts_hist() -> eyre::Result<()> {
+        use fixtures::lifecycle::{DST, FACTORY, SRC};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+        // unsorted, with a duplicate and an account that never existed
+        let addrs = [store, SRC, Address::repeat_byte(0xee), DST, FACTORY, SRC];
+        for block in 0..=6 {
+            let want = addrs
+                .iter()
+                .map(|&adr| db.read_account_hist(adr, block))
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(db.read_accounts_hist(&addrs, block)?, want);
+        }
+        assert!(db.read_accounts_hist(&[], 1)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_reader() -> eyre::Result<()> {
+        use fixtures::lifecycle::{DST, FACTORY, SRC};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+        let addrs = [SRC, DST, FACTORY, store, Address::repeat_byte(0xee)];
+        let slots = (0..3).map(H256::from_low_u64_be).collect::<Vec<_>>();
+        for block in 1..=6u64 {
+            // the state before the block is the state after its parent
+            let state = db.state_at(block - 1);
+            let mut reader = db.history_reader(block)?;
+            for _ in 0..2 {
+                for &adr in &addrs {
+                    assert_eq!(reader.account(adr)?, state.account(adr)?);
+                    for &slot in &slots {
+                        assert_eq!(reader.storage(adr, slot)?, state.storage(adr, slot)?);
+                    }
+                }
+            }
+        }
+        // the Store's slots are only set between its creation and destruction
+        let mut reader = db.history_reader(4)?;
+        assert_eq!(reader.storage(store, slots[1])?, 3.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_shards() -> eyre::Result<()> {
+        use models::{Account, AccountHistKey, BlockNumber};
+        use roaring::RoaringTreemap;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        // a change every third block, from block 0, with the nonce one past
+        // the block so that no changeset holds an empty account
+        let adr = Address::repeat_byte(0x5d);
+        let blocks = (0..3000u64).map(|i| i * 3).collect::<RoaringTreemap>();
+        for block in blocks.iter() {
+            db.write_account_change(block, adr, Some(Account::new().nonce(block + 1)))?;
+        }
+        db.write_account_history(adr, blocks)?;
+        let shards = db
+            .cursor::<tables::AccountHistory>()?
+            .walk(AccountHistKey(adr, BlockNumber(0)))?
+            .collect::<Result<Vec<_>>>()?;
+        assert!(shards.len() > 1);
+        assert_eq!(shards.last().unwrap().0 .1, BlockNumber(u64::MAX));
+        for block in 0..=9000u64 {
+            let want = (block <= 8997).then(|| (block + 2) / 3 * 3 + 1);
+            let acct = db.read_account_hist(adr, block)?;
+            assert_eq!(acct.map(|acct| acct.nonce), want, "block {}", block);
+        }
+
+        // a shard keyed past its last block is searched past
+        let adr = Address::repeat_byte(0x5e);
+        for block in [50, 200] {
+            db.write_account_change(block, adr, Some(Account::new().nonce(block)))?;
+        }
+        let shard = |n: u64| [n].into_iter().collect::<RoaringTreemap>();
+        db.write::<tables::AccountHistory>(AccountHistKey(adr, BlockNumber(100)), shard(50))?;
+        db.write::<tables::AccountHistory>(AccountHistKey(adr, BlockNumber(u64::MAX)), shard(200))?;
+        assert_eq!(db.read_account_hist(adr, 0)?.unwrap().nonce, 50);
+        assert_eq!(db.read_account_hist(adr, 60)?.unwrap().nonce, 200);
+        assert_eq!(db.read_accounts_hist(&[adr], 60)?[0].unwrap().nonce, 200);
+        assert_eq!(db.read_account_hist(adr, 201)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_storage_at() -> eyre::Result<()> {
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let store = fixture.chain.contracts[1];
+        let slots = (0..4).map(H256::from_low_u64_be).collect::<Vec<_>>();
+        for block in 1..=6u64 {
+            let state = db.state_at(block - 1);
+            for &slot in &slots {
+                let val = db.read_storage_at(store, slot, block)?;
+                assert_eq!(val.unwrap_or_default(), state.storage(store, slot)?);
+            }
+        }
+        // the Store only exists between its creation in block 3 and its
+        // destruction in block 4
+        assert_eq!(db.read_storage_at(store, slots[0], 3)?, None);
+        assert_eq!(db.read_storage_at(store, slots[0], 4)?, Some(2.into()));
+        assert_eq!(db.read_storage_at(store, slots[2], 4)?, Some(U256::MAX));
+        assert_eq!(db.read_storage_at(store, slots[3], 4)?, None);
+        assert_eq!(db.read_storage_at(store, slots[0], 5)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_transaction_receipt() -> eyre::Result<()> {
+        use fixtures::{
+            create_address,
+            lifecycle::{DST, SRC},
+            ChainWriter,
+        };
+        use models::{transaction::TxAction, *};
+        let fixture = fixtures::Fixture::new(|db| {
+            let mut w = ChainWriter::genesis(db, [(SRC, U256::exp10(18))])?;
+            w.push_tx(SRC, TxAction::Call(DST), 100.into(), Default::default())?;
+            w.push_tx(SRC, TxAction::Create, U256::zero(), Default::default())?;
+            w.seal()?;
+            w.finish()
+        })?;
+        let db = Erigon::begin_rw(&fixture.env)?;
+        let key = db.read_header_key(1)?.unwrap();
+        let hashes = db
+            .read_block_transactions(key)?
+            .unwrap()
+            .iter()
+            .map(Transaction::tx_hash)
+            .collect::<Vec<_>>();
+        for hash in &hashes {
+            db.write_transaction_block_number(*hash, 1.into())?;
+        }
+        let receipt = |status, cumulative_gas_used| CborReceipt {
+            tx_type: 0,
+            post_state: None,
+            status,
+            cumulative_gas_used,
+        };
+        let receipts = vec![receipt(1, 21_000), receipt(0, 74_000)];
+        db.write::<tables::Receipt>(BlockNumber(1), CborReceipts(Some(receipts)))?;
+        let log = |byte| CborLog {
+            address: Address::repeat_byte(byte),
+            topics: vec![H256::repeat_byte(byte)],
+            data: Default::default(),
+        };
+        let logs = [vec![log(1)], vec![log(2), log(3)]];
+        for (i, logs) in logs.into_iter().enumerate() {
+            db.write::<tables::TransactionLog>(LogsKey(1.into(), i as u32), CborLogs(Some(logs)))?;
+        }
+
+        let transfer = db.read_transaction_receipt(hashes[0])?.unwrap();
+        assert_eq!(transfer.block_hash, fixture.chain.hashes[1]);
+        assert_eq!((transfer.from, transfer.to), (SRC, Some(DST)));
+        assert_eq!(transfer.gas_used, 21_000);
+        assert_eq!(transfer.status, Some(1));
+        assert_eq!(transfer.contract_address, None);
+        assert_eq!(transfer.logs, vec![(0, log(1))]);
+        assert_eq!(transfer.logs_bloom, logs_bloom(&[log(1)]));
+
+        let create = db.read_transaction_receipt(hashes[1])?.unwrap();
+        assert_eq!(create.transaction_index, 1);
+        assert_eq!(create.to, None);
+        assert_eq!(create.contract_address, Some(create_address(SRC, 1)));
+        assert_eq!(create.cumulative_gas_used, 74_000);
+        assert_eq!(create.gas_used, 53_000);
+        assert_eq!(create.status, Some(0));
+        // log indices run across the block
+        assert_eq!(create.logs, vec![(1, log(2)), (2, log(3))]);
+        let bloom = create.logs_bloom;
+        assert!(bloom.contains_input(BloomInput::Raw(&[3; 20])));
+        assert!(!bloom.contains_input(BloomInput::Raw(&[1; 20])));
+
+        assert_eq!(db.read_transaction_receipt(H256::repeat_byte(0xee))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocks_maybe_matching() -> eyre::Result<()> {
+        use filter::{BloomExt, LogFilter};
+        use models::{logs_bloom, CborLog};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::begin_rw(&fixture.env)?;
+        let (adr, topic) = (Address::repeat_byte(0xaa), H256::repeat_byte(0xbb));
+        let log = CborLog {
+            address: adr,
+            topics: vec![topic],
+            data: Default::default(),
+        };
+        for num in [2, 4] {
+            let key = db.read_header_key(num)?.unwrap();
+            let mut header = db.read_header(key)?.unwrap();
+            header.bloom = logs_bloom([&log]);
+            assert!(header.bloom.contains_address(&adr));
+            assert!(header.bloom.contains_topic(&topic));
+            db.write_header(key, header)?;
+        }
+
+        let all = (0..=5).map(models::BlockNumber).collect::<Vec<_>>();
+        assert_eq!(db.blocks_maybe_matching(&LogFilter::new(), ..)?, all);
+        let filter = LogFilter::new().address(adr).topic(0, topic);
+        let want = [2, 4].map(models::BlockNumber);
+        assert_eq!(db.blocks_maybe_matching(&filter, ..)?, want);
+        assert_eq!(db.blocks_maybe_matching(&filter, 3..)?, want[1..]);
+        // any of the addresses or topics at a position may match
+        let filter = filter
+            .address(Address::zero())
+            .topic(1, H256::zero())
+            .topic(1, topic);
+        assert_eq!(db.blocks_maybe_matching(&filter, ..)?, want);
+        let filter = LogFilter::new().address(adr).topic(0, H256::zero());
+        assert!(db.blocks_maybe_matching(&filter, ..)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_timeseries() -> eyre::Result<()> {
+        use crate::rlp::{length_of_length, Encodable};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let rows = db.chain_timeseries(..)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(rows.len(), fixture.chain.hashes.len());
+        for (num, row) in rows.iter().enumerate() {
+            let block = db.read_block(num as u64)?.unwrap();
+            assert_eq!(*row.number, num as u64);
+            assert_eq!(row.tx_count, block.transactions.len() as u64);
+            assert_eq!(row.gas_used, block.header.gas_used);
+            assert_eq!(row.timestamp, block.header.time);
+            assert_eq!((row.uncle_count, row.withdrawal_count), (0, None));
+            // [header, txs, []], every tx being legacy
+            let txs = block
+                .transactions
+                .iter()
+                .map(|tx| tx.msg.length())
+                .sum::<usize>();
+            let payload = block.header.length() + length_of_length(txs) + txs + 1;
+            assert_eq!(row.size as usize, length_of_length(payload) + payload);
+        }
+        assert_eq!(rows[1].tx_count, 1);
+        let tail = db.chain_timeseries(4..)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(tail, rows[4..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tx_hash() -> eyre::Result<()> {
+        use crate::kv::traits::TableEncode;
+        use erigon::utils::keccak256;
+        use models::transaction::{DynamicFeeTx, TxAction};
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::begin_rw(&fixture.env)?;
+        let key = db.read_header_key(1)?.unwrap();
+        let legacy = db.read_block_transactions(key)?.unwrap().remove(0);
+        // a legacy tx is stored as its rlp list, which is what is hashed
+        let hash = legacy.tx_hash();
+        assert_eq!(hash, H256(keccak256(legacy.clone().encode())));
+        assert_ne!(hash, legacy.signing_hash());
+
+        // the lookup table is keyed by the tx hash
+        db.write_transaction_block_number(hash, 1.into())?;
+        assert_eq!(db.read_transaction_by_hash(hash)?.unwrap().0, legacy);
+        assert_eq!(db.read_transaction_by_hash(legacy.signing_hash())?, None);
+
+        // a typed tx is hashed as its Eip2718 envelope, type || rlp(payload)
+        let inner = DynamicFeeTx {
+            chain_id: 1.into(),
+            nonce: 1,
+            tip: 1.into(),
+            fee_cap: 2.into(),
+            gas: 21000,
+            to: TxAction::Call(Address::repeat_byte(1)),
+            value: 1.into(),
+            data: Default::default(),
+            access_list: Default::default(),
+            v: 1.into(),
+            r: 1.into(),
+            s: 1.into(),
+        };
+        let mut envelope = vec![DynamicFeeTx::TYPE];
+        crate::rlp::Encodable::encode(&inner, &mut envelope);
+        let typed = models::Transaction::DynamicFee(inner);
+        assert_eq!(typed.tx_hash(), H256(keccak256(envelope)));
+        assert_ne!(typed.tx_hash(), typed.signing_hash());
+        // the old name is kept for the signing hash
+        #[allow(deprecated)]
+        let old = typed.hash();
+        assert_eq!(old, typed.signing_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tx_encoding() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use models::{
+            transaction::{AccessListTx, AccessTuple, BlobTx, DynamicFeeTx, TxAction},
+            Transaction,
+        };
+        let enveloped = |tx: &Transaction| {
+            let mut out = Vec::new();
+            tx.encode_enveloped(&mut out);
+            out
+        };
+        let body = |tx: &Transaction| {
+            let mut out = Vec::new();
+            tx.encode_for_block_body(&mut out);
+            out
+        };
+
+        // the signed Eip155 example tx, which is the same in every form
+        let raw = hex::decode(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f\
+             761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )?;
+        let legacy = Transaction::decode(&raw)?;
+        assert_eq!(legacy.nonce(), 9);
+        assert_eq!(legacy.clone().encode().to_vec(), raw);
+        assert_eq!(enveloped(&legacy), raw);
+        assert_eq!(body(&legacy), raw);
+        assert_eq!(crate::rlp::Encodable::length(&legacy), raw.len());
+
+        // a typed tx is stored as an rlp string holding its envelope
+        let raw = hex::decode(
+            "b8af02f8ac012a843b9aca00850ba43b7400825208943535353535353535353535353535353535353535\
+             880de0b6b3a764000080f838f7940101010101010101010101010101010101010101e1a0000000000000\
+             000000000000000000000000000000000000000000000000000101a028ef61340bd939bc2195fe537567\
+             866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc6421\
+             4b297fb1966a3b6d83",
+        )?;
+        let typed = Transaction::DynamicFee(DynamicFeeTx {
+            chain_id: 1.into(),
+            nonce: 42,
+            tip: 1_000_000_000u64.into(),
+            fee_cap: 50_000_000_000u64.into(),
+            gas: 21000,
+            to: TxAction::Call(Address::repeat_byte(0x35)),
+            value: U256::exp10(18),
+            data: Default::default(),
+            access_list: vec![AccessTuple {
+                address: Address::repeat_byte(1),
+                slots: vec![H256::from_low_u64_be(1)],
+            }],
+            v: 1.into(),
+            r: U256::from_big_endian(&raw[raw.len() - 65..raw.len() - 33]),
+            s: U256::from_big_endian(&raw[raw.len() - 32..]),
+        });
+        assert_eq!(Transaction::decode(&raw)?, typed);
+        assert_eq!(typed.clone().encode().to_vec(), raw);
+        assert_eq!(body(&typed), raw);
+        assert_eq!(crate::rlp::Encodable::length(&typed), raw.len());
+        assert_eq!(enveloped(&typed), raw[2..]);
+        assert_eq!(typed.enveloped_length(), raw.len() - 2);
+        // the envelope decodes too, as it is sent over RPC
+        assert_eq!(Transaction::decode(&raw[2..])?, typed);
+        // but not with bytes past its end inside the string that holds it
+        let mut padded = vec![0xb8, raw[1] + 1];
+        padded.extend_from_slice(&raw[2..]);
+        padded.push(0);
+        assert!(Transaction::decode(&padded).is_err());
+
+        // an Eip2930 contract creation and an Eip4844 blob tx. No db with
+        // these was at hand, so the bytes were built field by field from the
+        // layouts in the Eips with a separate rlp encoder, not by this crate.
+        let (r, s) = match &typed {
+            Transaction::DynamicFee(tx) => (tx.r, tx.s),
+            _ => unreachable!(),
+        };
+        let access_list = Transaction::AccessList(AccessListTx {
+            chain_id: 5.into(),
+            nonce: 7,
+            gas_price: 20_000_000_000u64.into(),
+            gas: 100_000,
+            to: TxAction::Create,
+            value: U256::zero(),
+            data: vec![0x60, 0x80, 0x60, 0x40, 0x52].into(),
+            access_list: vec![AccessTuple {
+                address: Address::repeat_byte(2),
+                slots: vec![H256::from_low_u64_be(3), H256::from_low_u64_be(4)],
+            }],
+            v: 0.into(),
+            r,
+            s,
+        });
+        // a kzg commitment hash, with the version byte in front
+        let versioned = |b: u8| {
+            let mut hash = H256::repeat_byte(b);
+            hash.0[0] = 1;
+            hash
+        };
+        let blob = Transaction::Blob(BlobTx {
+            chain_id: 1.into(),
+            nonce: 3,
+            tip: 2_000_000_000u64.into(),
+            fee_cap: 30_000_000_000u64.into(),
+            gas: 50_000,
+            to: Address::repeat_byte(0x35),
+            value: U256::zero(),
+            data: Default::default(),
+            access_list: Default::default(),
+            max_fee_per_blob_gas: 1_000_000_000u64.into(),
+            blob_versioned_hashes: vec![versioned(0xaa), versioned(0xbb)],
+            v: 1.into(),
+            r,
+            s,
+        });
+        let cases = [
+            (
+                access_list,
+                "b8b701f8b405078504a817c800830186a08080856080604052f85bf859940202020202020202020202\
+                 020202020202020202f842a00000000000000000000000000000000000000000000000000000000000\
+                 000003a0000000000000000000000000000000000000000000000000000000000000000480a028ef61\
+                 340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703\
+                 304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+            ),
+            (
+                blob,
+                "b8b703f8b4010384773594008506fc23ac0082c3509435353535353535353535353535353535353535\
+                 358080c0843b9aca00f842a001aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\
+                 aaaaaaa001bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb01a028ef61\
+                 340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703\
+                 304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+            ),
+        ];
+        for (tx, raw) in cases {
+            let raw = hex::decode(raw)?;
+            assert_eq!(Transaction::decode(&raw)?, tx);
+            assert_eq!(tx.clone().encode().to_vec(), raw);
+            assert_eq!(crate::rlp::Encodable::length(&tx), raw.len());
+            assert_eq!(enveloped(&tx), raw[2..]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_v() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use models::{
+            transaction::{LegacyTx, VPackChainId},
+            Transaction,
+        };
+        let v = |v: u64| VPackChainId(v.into());
+        // unsigned by chain id, as in every mainnet tx before Spurious Dragon
+        assert_eq!(v(27).derive_chain_id()?, None);
+        assert!(!v(27).y_parity()?);
+        assert!(v(28).y_parity()?);
+        assert_eq!(v(28).derive_v()?, 28.into());
+        // Eip155, for mainnet and a chain id above a u32
+        assert_eq!(v(37).derive_chain_id()?, Some(1.into()));
+        assert!(!v(37).y_parity()?);
+        assert!(v(38).y_parity()?);
+        assert_eq!(v(38).derive_v()?, 1.into());
+        let big = (1u64 << 40) * 2 + 36;
+        assert_eq!(v(big).derive_chain_id()?, Some((1u64 << 40).into()));
+        assert!(v(big).y_parity()?);
+        // anything else is an error rather than a panic
+        for bad in [0, 1, 26, 29, 34] {
+            assert!(v(bad).derive_chain_id().is_err());
+            assert!(v(bad).y_parity().is_err());
+            assert!(v(bad).derive_v().is_err());
+        }
+
+        // the signed Eip155 example tx
+        let raw = hex::decode(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f\
+             761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )?;
+        let tx = Transaction::decode(&raw)?;
+        assert_eq!(tx.chain_id(), Some(1.into()));
+        assert_eq!(tx.v(), 0.into());
+        assert_eq!(tx.raw_v(), 37.into());
+
+        // mainnet txs without a chain id, given with their tx hash and the
+        // hash their signature is over:
+        // - the first tx on mainnet, in block 46147, with v = 28
+        // - the deployment of the deterministic deployment proxy, with v = 27,
+        //   which was sent after Spurious Dragon
+        let unprotected = [
+            (
+                concat!(
+                    "f86780862d79883d2000825208945df9b87991262f6ba471f09758cde1c0fc1de734827a69801c",
+                    "a088ff6cf0fefd94db46111149ae4bfc179e9b94721fffd821d38d16464b3f71d0a045e0aff800",
+                    "961cfce805daef7016b9b675c137a6a41a548f7b60a3484c06a33a",
+                ),
+                28u64,
+                "5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060",
+                "19b1e28c14f33e74b96b88eba97d4a4fc8a97638d72e972310025b7e1189b049",
+            ),
+            (
+                concat!(
+                    "f8a58085174876e800830186a08080b853604580600e600039806000f350fe7fffffffffffffff",
+                    "ffffffffffffffffffffffffffffffffffffffffffffffffe03601600081602082378035828234",
+                    "f58015156039578182fd5b8082525050506014600cf31ba0222222222222222222222222222222",
+                    "2222222222222222222222222222222222a0222222222222222222222222222222222222222222",
+                    "2222222222222222222222",
+                ),
+                27u64,
+                "eddf9e61fb9d8f5111840daef55e5fde0041f5702856532cdbb5a02998033d26",
+                "3de642d76cf5cf9ffcf9b51e11b3b21e09f63278ed94a89281ca8054b2225434",
+            ),
+        ];
+        for (raw, sig_v, hash, signing_hash) in unprotected {
+            let raw = hex::decode(raw)?;
+            let tx = Transaction::decode(&raw)?;
+            assert_eq!(tx.chain_id(), None);
+            assert_eq!(tx.v(), sig_v.into());
+            assert_eq!(tx.raw_v(), sig_v.into());
+            assert_eq!(tx.tx_hash(), hash.parse()?);
+            assert_eq!(tx.signing_hash(), signing_hash.parse()?);
+            assert_eq!(&tx.encode()[..], &raw[..]);
+        }
+
+        // a tx with an invalid v still decodes and can be read
+        let mut legacy = match tx {
+            Transaction::Legacy(tx) => tx,
+            _ => unreachable!(),
+        };
+        legacy.v = v(29);
+        let tx = Transaction::Legacy(legacy.clone());
+        assert_eq!(tx.chain_id(), None);
+        assert_eq!(tx.v(), 29.into());
+        let unprotected = LegacyTx {
+            v: v(27),
+            ..legacy.clone()
+        };
+        assert_eq!(legacy.signing_hash(), unprotected.signing_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stage_names() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use models::StageId;
+        // the keys of Erigon's SyncStage table, in the order the stages run
+        let names = [
+            "Snapshots",
+            "Headers",
+            "BlockHashes",
+            "Bodies",
+            "Senders",
+            "Execution",
+            "Translation",
+            "HashState",
+            "IntermediateHashes",
+            "AccountHistoryIndex",
+            "StorageHistoryIndex",
+            "LogIndex",
+            "CallTraces",
+            "TxLookup",
+            "WatchTheBurn",
+            "Finish",
+        ];
+        assert_eq!(StageId::ALL.len(), names.len());
+        for (&stage, name) in StageId::ALL.iter().zip(names) {
+            assert_eq!(stage.encode(), name.as_bytes());
+            assert_eq!(StageId::decode(name.as_bytes())?, stage);
+        }
+        assert_eq!(StageId::decode(b"WatchTheBurn")?, StageId::Issuance);
+        assert!(StageId::decode(b"Issuance").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_db_flags() -> eyre::Result<()> {
+        use kv::tables::{DupSortFlags, NoFlags};
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        db.write_account_change(1, Address::repeat_byte(0x01), None)?;
+        let first = db.0.open_db::<tables::AccountChangeSet, DupSortFlags>()?;
+        // an open with other flags checks them, rather than reusing the handle
+        assert!(db.0.open_db::<tables::AccountChangeSet, NoFlags>().is_err());
+        // while an open with the same flags returns the cached handle
+        let again = db.0.open_db::<tables::AccountChangeSet, DupSortFlags>()?;
+        assert!(std::ptr::eq(first.inner(), again.inner()));
+        db.write_head_header_hash(H256::zero())?;
+        let raw = db.0.open_raw("LastHeader")?;
+        let raw_again = db.0.open_raw("LastHeader")?;
+        assert!(std::ptr::eq(raw.inner(), raw_again.inner()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_index() -> eyre::Result<()> {
+        use snapshots::{eliasfano::EliasFano, recsplit::Index};
+        // written with a port of the encoders of erigon-lib's recsplit and
+        // eliasfano32 packages
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test/snapshots");
+
+        // offsets i * 5 + i % 4, spanning a few jumps
+        let raw = std::fs::read(dir.join("eliasfano.bin"))?;
+        let (ef, size) = EliasFano::read(&raw)?;
+        assert_eq!(size, raw.len());
+        assert_eq!(ef.len(), 600);
+        assert_eq!(ef.max(), 599 * 5 + 3);
+        for i in 0..600 {
+            assert_eq!(ef.get(i), Some(i * 5 + i % 4), "element {}", i);
+        }
+        assert_eq!(ef.get(600), None);
+        assert!(EliasFano::read(&raw[..raw.len() - 1]).is_err());
+        // sizes that overflow are errors rather than panics
+        let mut header = [0xff; 16];
+        assert!(EliasFano::read(&header).is_err());
+        header[..8].copy_from_slice(&(u64::MAX - 1).to_be_bytes());
+        assert!(EliasFano::read(&header).is_err());
+
+        let idx = Index::open(&dir.join("headers.idx"))?;
+        assert_eq!(idx.base_data_id(), 500_000);
+        assert_eq!(idx.key_count(), 6);
+        assert_eq!(idx.bytes_per_rec(), 2);
+        assert_eq!(
+            (idx.bucket_count(), idx.bucket_size(), idx.leaf_size()),
+            (1, 100, 8)
+        );
+        assert_eq!(idx.salt(), 0x12345678);
+        assert_eq!(idx.start_seed(), [0x106393c187cae21a, 0x6453cec3f7376937]);
+        let offsets = (0..7).map(|i| idx.ordinal_lookup(i)).collect::<Vec<_>>();
+        let want = [0, 3, 10, 50, 51, 200].map(Some);
+        assert_eq!(offsets[..6], want);
+        assert_eq!(offsets[6], None);
+
+        let tmp = tempfile::tempdir()?;
+        let mut raw = std::fs::read(dir.join("headers.idx"))?;
+        raw[8..16].copy_from_slice(&u64::MAX.to_be_bytes());
+        std::fs::write(tmp.path().join("bad.idx"), &raw)?;
+        assert!(Index::open(&tmp.path().join("bad.idx")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_cs_val() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use models::{Account, AccountCSVal};
+        let adr = Address::repeat_byte(0xe1);
+        // an account that did not exist is the bare address, and an empty
+        // account that did is encoded with an empty fieldset, as in Erigon
+        let cases = [
+            (None, vec![]),
+            (Some(Account::default()), vec![0]),
+            (Some(Account::new().nonce(2)), vec![1, 1, 2]),
+        ];
+        for (acct, encoded) in cases {
+            let raw = [adr.as_bytes(), &encoded[..]].concat();
+            assert_eq!(AccountCSVal(adr, acct).encode(), raw);
+            assert_eq!(AccountCSVal::decode(&raw)?, AccountCSVal(adr, acct));
+        }
+
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let empty = Address::repeat_byte(0xe2);
+        db.write_account_change(3, adr, None)?;
+        db.write_account_change(3, empty, Some(Account::default()))?;
+        for adr in [adr, empty] {
+            db.write_account_history(adr, [3].into_iter().collect())?;
+        }
+        assert_eq!(db.read_account_hist(adr, 2)?, None);
+        assert_eq!(db.read_account_hist(empty, 2)?, Some(Account::default()));
+        assert_eq!(db.changed_accounts(3)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_hist_missing_changeset() -> eyre::Result<()> {
+        use crate::kv::traits::DbName;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        // the history index says the account changed in block 4, but there
+        // is no changeset entry to read its previous value from
+        let adr = Address::repeat_byte(0x3c);
+        db.write_account_history(adr, [4u64].into_iter().collect())?;
+        fn missing<T>(res: Result<T>) -> bool {
+            let name = tables::AccountChangeSet::NAME;
+            matches!(res, Err(Error::NotFound(n)) if n == name)
+        }
+        assert!(missing(db.read_account_hist(adr, 2)));
+        assert!(missing(db.read_accounts_hist(&[adr], 2)));
+        assert!(missing(db.state_at(3).account(adr)));
+        assert!(missing(db.history_reader(4)?.account(adr)));
+        // past the last change the account is read from PlainState
+        assert_eq!(db.read_account_hist(adr, 5)?, None);
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() -> eyre::Result<()> {
+        use crate::kv::traits::DbName;
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+        use models::BlockNumber;
+        use std::collections::HashMap;
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        ::metrics::with_local_recorder(&recorder, || -> eyre::Result<()> {
+            let db = Erigon::begin_rw(&env)?;
+            for i in 1..=3 {
+                db.write::<tables::CanonicalHeader>(BlockNumber(i), H256::from_low_u64_be(i))?;
+            }
+            assert!(db
+                .read::<tables::CanonicalHeader>(BlockNumber(2))?
+                .is_some());
+            let walk = db
+                .cursor::<tables::CanonicalHeader>()?
+                .walk(BlockNumber(2))?;
+            assert_eq!(walk.count(), 2);
+            Ok(())
+        })?;
+
+        let metrics = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| {
+                key.key()
+                    .labels()
+                    .any(|l| l.key() == "table" && l.value() == tables::CanonicalHeader::NAME)
+            })
+            .map(|(key, _, _, val)| (key.key().name().to_string(), val))
+            .collect::<HashMap<_, _>>();
+        let counter = |name: &str| match metrics.get(name) {
+            Some(DebugValue::Counter(n)) => *n,
+            _ => 0,
+        };
+        assert_eq!(counter("erigon_db_reads_total"), 1);
+        assert_eq!(counter("erigon_db_seeks_total"), 1);
+        // the read, then both entries of the walk
+        assert_eq!(counter("erigon_db_decodes_total"), 3);
+        assert_eq!(counter("erigon_db_decode_errors_total"), 0);
+        match metrics.get("erigon_db_walk_length") {
+            Some(DebugValue::Histogram(lens)) => {
+                assert_eq!(lens.iter().map(|len| len.0).collect::<Vec<_>>(), [2.0])
+            }
+            val => panic!("unexpected walk length {:?}", val),
+        }
+        Ok(())
+    }
 
+    #[test]
+    fn test_history_reader_cache() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+        let db = Erigon::begin_rw(&env)?;
+        let adr = Address::repeat_byte(0x4c);
         let slot = H256::from_low_u64_be(1);
-        let res = db.read_storage_hist(contract, 1, slot, 0)?;
-        let current = db.read_storage(contract, 2, slot)?;
-        dbg!(res);
-        dbg!(current);
-        for read in db.walk_storage(contract, 1, None)? {
-            let (key, val) = read?;
-            dbg!(key, val);
+        let acct = models::Account::new().nonce(1).incarnation(1.into());
+        db.write_account(adr, acct)?;
+        db.write_storage(adr, 1, slot, 5.into())?;
+        let mut reader = db.history_reader(10)?;
+        assert_eq!(reader.account(adr)?, Some(acct));
+        assert_eq!(reader.storage(adr, slot)?, 5.into());
+
+        // later writes are not seen by lookups the reader already resolved
+        db.write_account(adr, acct.nonce(2))?;
+        db.write_storage(adr, 1, slot, 6.into())?;
+        assert_eq!(reader.account(adr)?, Some(acct));
+        assert_eq!(reader.storage(adr, slot)?, 5.into());
+        let mut fresh = db.history_reader(10)?;
+        assert_eq!(fresh.account(adr)?, Some(acct.nonce(2)));
+        assert_eq!(fresh.storage(adr, slot)?, 6.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_stats_withdrawals() -> eyre::Result<()> {
+        use erigon::stats::BlockStats;
+        use models::{Block, Withdrawal};
+        let block = |amounts: &[u64]| Block {
+            header: Default::default(),
+            transactions: vec![],
+            uncles: vec![],
+            withdrawals: Some(
+                amounts
+                    .iter()
+                    .map(|&amount| Withdrawal {
+                        amount,
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+        };
+        let stats = BlockStats::try_from(&block(&[1, u64::MAX - 1]))?;
+        assert_eq!(stats.withdrawal_count, Some(2));
+        assert_eq!(stats.withdrawal_amount, Some(u64::MAX));
+        assert!(BlockStats::try_from(&block(&[2, u64::MAX - 1])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mainnet_hashes() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use erigon::utils::consts::{EMPTY_LIST_HASH, EMPTY_ROOT};
+        use hex_literal::hex;
+        // the mainnet genesis header
+        let genesis = models::BlockHeader {
+            uncle_hash: EMPTY_LIST_HASH,
+            root: H256(hex!(
+                "d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544"
+            )),
+            tx_hash: EMPTY_ROOT,
+            receipts_hash: EMPTY_ROOT,
+            difficulty: 0x400000000u64.into(),
+            gas_limit: 5000,
+            extra: hex!("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa")
+                .to_vec()
+                .into(),
+            nonce: H64::from_low_u64_be(0x42),
+            ..Default::default()
+        };
+        assert_eq!(
+            genesis.hash(),
+            H256(hex!(
+                "d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3"
+            ))
+        );
+        assert_eq!(
+            models::BlockHeader::decode(&genesis.clone().encode())?,
+            genesis
+        );
+
+        // the same header with the Shanghai and Cancun fields appended, whose
+        // hash was computed over the Eip layouts with a separate rlp encoder
+        // and keccak implementation rather than taken from a mainnet block
+        let cancun = models::BlockHeader {
+            number: 19426587.into(),
+            time: 1710338135,
+            base_fee: Some(7.into()),
+            withdrawals_root: Some(EMPTY_ROOT),
+            blob_gas_used: Some(0x20000),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(H256::repeat_byte(0x11)),
+            ..genesis
+        };
+        assert_eq!(
+            cancun.hash(),
+            H256(hex!(
+                "c540eb92be52f64620f38060a1bf4762852f73e634aeff03a79880835ec30eee"
+            ))
+        );
+        assert_eq!(
+            models::BlockHeader::decode(&cancun.clone().encode())?,
+            cancun
+        );
+
+        // with the Prague requests hash appended, computed the same way
+        let prague = models::BlockHeader {
+            requests_hash: Some(erigon::utils::consts::EMPTY_REQUESTS_HASH),
+            ..cancun
+        };
+        assert_eq!(
+            prague.hash(),
+            H256(hex!(
+                "39b7a73822ab89bd4ce51bc86af128fa235f67e7f207846eb7344bf2ef7051b8"
+            ))
+        );
+        assert_eq!(
+            models::BlockHeader::decode(&prague.clone().encode())?,
+            prague
+        );
+        // a field past the known ones fails to decode, rather than being
+        // dropped from the re-encoded header and its hash
+        let append_field = |encoded: &[u8]| -> eyre::Result<Vec<u8>> {
+            let mut payload = encoded;
+            let head = rlp::Header::decode(&mut payload)?;
+            let mut out = Vec::new();
+            rlp::Header {
+                list: true,
+                payload_length: head.payload_length + 33,
+            }
+            .encode(&mut out);
+            out.extend_from_slice(payload);
+            rlp::Encodable::encode(&H256::zero(), &mut out);
+            Ok(out)
+        };
+        let extended = append_field(&prague.encode())?;
+        assert!(models::BlockHeader::decode(&extended).is_err());
+        let body = models::BodyForStorage {
+            base_tx_id: 1,
+            tx_amount: 2,
+            uncles: vec![],
+            withdrawals: Some(vec![]),
+        };
+        let extended = append_field(&body.clone().encode())?;
+        assert!(models::BodyForStorage::decode(&extended).is_err());
+        assert_eq!(
+            models::BodyForStorage::decode(&body.clone().encode())?,
+            body
+        );
+
+        // the signed Eip155 example tx on mainnet
+        let signed = hex::decode(concat!(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6",
+            "b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa",
+            "636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        ))?;
+        let tx = models::Transaction::decode(&signed)?;
+        assert_eq!(
+            tx.tx_hash(),
+            H256(hex!(
+                "33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"
+            ))
+        );
+        assert_eq!(
+            tx.signing_hash(),
+            H256(hex!(
+                "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+            ))
+        );
+        assert_eq!(&tx.encode()[..], &signed[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_v3_encoding() -> eyre::Result<()> {
+        use crate::kv::traits::{TableDecode, TableEncode};
+        use erigon::{erigon3::models::AccountV3, utils::consts::EMPTY_HASH};
+        // laid out by hand after erigon-lib's accounts.SerialiseV3: each field
+        // is a one-byte length followed by the trimmed big-endian value
+        let acct = AccountV3(
+            models::Account::new()
+                .nonce(2)
+                .balance(1000.into())
+                .codehash(H256::repeat_byte(0xab))
+                .incarnation(1.into()),
+        );
+        let encoded = [
+            &[0x01, 0x02, 0x02, 0x03, 0xe8, 0x20][..],
+            &[0xab; 32],
+            &[0x01, 0x01],
+        ]
+        .concat();
+        assert_eq!(acct.encode(), encoded);
+        assert_eq!(AccountV3::decode(&encoded)?, acct);
+
+        // a nonce past one byte, a zero balance and no code
+        let acct = AccountV3(models::Account::new().nonce(0x0100));
+        let encoded = [0x02u8, 0x01, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(acct.encode(), encoded);
+        assert_eq!(AccountV3::decode(&encoded)?, acct);
+        // the empty code hash is left out, like a zero one
+        let acct = AccountV3(models::Account::new().codehash(EMPTY_HASH));
+        assert_eq!(acct.encode(), [0u8; 4]);
+        assert_eq!(*AccountV3::decode(&[0; 4])?, models::Account::new());
+        assert_eq!(*AccountV3::decode(&[])?, models::Account::new());
+        // a code hash must be a full hash
+        assert!(AccountV3::decode(&[0, 0, 0x01, 0xab, 0]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_filter_matches() {
+        use erigon::filter::LogFilter;
+        let (a, b) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+        let (t1, t2, t3) = (
+            H256::repeat_byte(1),
+            H256::repeat_byte(2),
+            H256::repeat_byte(3),
+        );
+        let log = models::CborLog {
+            address: a,
+            topics: vec![t1, t2],
+            data: Default::default(),
+        };
+
+        // an empty filter matches every log
+        assert!(LogFilter::new().matches(&log));
+        // any of the addresses
+        assert!(LogFilter::new().address(b).address(a).matches(&log));
+        assert!(!LogFilter::new().address(b).matches(&log));
+        // any of the topics at each position, which must be the log's
+        assert!(LogFilter::new().topic(0, t3).topic(0, t1).matches(&log));
+        assert!(!LogFilter::new().topic(0, t2).matches(&log));
+        // an empty position is a wildcard
+        assert!(LogFilter::new().topic(1, t2).matches(&log));
+        assert!(!LogFilter::new().topic(1, t1).matches(&log));
+        // a position past the log's topics never matches
+        assert!(!LogFilter::new().topic(2, t3).matches(&log));
+        // the address and every topic position must all match
+        let filter = LogFilter::new().address(a).topic(0, t1).topic(1, t3);
+        assert!(!filter.matches(&log));
+        assert!(filter.topic(1, t2).matches(&log));
+    }
+
+    #[test]
+    fn test_read_tx_range_gap() -> eyre::Result<()> {
+        use crate::kv::traits::DbName;
+        use models::TxIndex;
+        let fixture = fixtures::lifecycle::build()?;
+        let db = Erigon::begin_rw(&fixture.env)?;
+        let key = db.read_header_key(2)?.unwrap();
+        let body = db.read_body_for_storage(key)?.unwrap();
+        assert_eq!(body.tx_amount, 1);
+        assert_eq!(
+            db.read_block_transactions(key)?.map(|txs| txs.len()),
+            Some(1)
+        );
+
+        // without its tx, the block's range would reach the next block's tx
+        db.delete::<tables::BlockTransaction>(TxIndex(body.base_tx_id), None)?;
+        let res = db.read_block_transactions(key);
+        assert!(
+            matches!(res, Err(Error::NotFound(n)) if n == tables::BlockTransaction::NAME),
+            "{:?}",
+            res
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_config_paris() -> eyre::Result<()> {
+        let config: models::ChainConfig = serde_json::from_str(
+            r#"{
+                "chainId": 1,
+                "londonBlock": 12965000,
+                "grayGlacierBlock": 15050000,
+                "terminalTotalDifficulty": 58750000000000000000000
+            }"#,
+        )?;
+        let ttd = U256::from(58_750_000_000_000_000_000_000u128);
+        assert_eq!(config.terminal_total_difficulty, Some(ttd.as_u128()));
+        assert!(!config.is_paris(ttd - 1));
+        assert!(config.is_paris(ttd));
+        // a netsplit block alone doesn't activate the merge
+        let netsplit = models::ChainConfig {
+            merge_netsplit_block: Some(0),
+            ..Default::default()
+        };
+        assert!(!netsplit.is_paris(ttd));
+
+        #[cfg(feature = "revm")]
+        {
+            use erigon::evm::spec_id;
+            use revm::SpecId;
+            assert_eq!(spec_id(&config, 15_537_393, ttd - 1), SpecId::GRAY_GLACIER);
+            assert_eq!(spec_id(&config, 15_537_394, ttd), SpecId::MERGE);
+            assert_eq!(spec_id(&netsplit, 1, U256::zero()), SpecId::FRONTIER);
         }
+        Ok(())
+    }
 
-        let hash = db.read_head_header_hash()?.unwrap();
-        let num = db.read_header_number(hash)?.unwrap();
+    #[test]
+    fn test_rlp_list_length() {
+        use crate::rlp::Encodable;
+        use models::transaction::AccessTuple;
+        let tuple = |n: u64| AccessTuple {
+            address: Address::from_low_u64_be(n),
+            slots: (0..n).map(H256::from_low_u64_be).collect(),
+        };
+        for list in [vec![], vec![tuple(1)], (0..40).map(tuple).collect()] {
+            let mut out = Vec::new();
+            list.encode(&mut out);
+            assert_eq!(list.length(), out.len());
+        }
+        let uints = [0u64, 1, 0x7f, 0x80, 0xff, 0x100, u64::MAX];
+        for n in uints.map(U256::from).into_iter().chain([U256::MAX]) {
+            let mut out = Vec::new();
+            n.encode(&mut out);
+            assert_eq!(n.length(), out.len(), "{}", n);
+        }
+    }
 
-        let td = db.read_total_difficulty((num, hash))?.unwrap();
-        dbg!(td);
+    #[cfg(feature = "ethers-types")]
+    #[tokio::test]
+    async fn test_middleware() -> eyre::Result<()> {
+        use ethers::providers::{Middleware, Provider};
+        use fixtures::lifecycle::DST;
+        let dir = tempfile::tempdir()?;
+        let fork = H256::repeat_byte(0xf0);
+        let chain = {
+            let env = erigon::env_open::<mdbx::RW>(dir.path())?;
+            let db = Erigon::begin_rw(&env)?;
+            let chain = fixtures::lifecycle::build_into(&db)?;
+            // a header that lost its slot to the canonical block 1
+            db.write_header_number(fork, 1.into())?;
+            db.0.commit()?;
+            chain
+        };
+
+        let env = std::sync::Arc::new(erigon::env_open(dir.path())?);
+        let (inner, _mock) = Provider::mocked();
+        let client = middleware::ErigonMiddleware::new(env, inner);
+        assert_eq!(client.get_balance(DST, None).await?, 100.into());
+        let block = client.get_block(chain.hashes[1]).await?.unwrap();
+        assert_eq!(block.number, Some(1.into()));
+        // a non-canonical hash is left to the inner provider, which has no
+        // response queued
+        assert!(client.get_block(fork).await.is_err());
+        Ok(())
+    }
+
+    // Writes the lifecycle chain to `dir` and reopens it read-only, as the
+    // servers take a shared read-only env.
+    #[cfg(any(feature = "remote", feature = "rpc"))]
+    fn lifecycle_ro(
+        dir: &std::path::Path,
+    ) -> eyre::Result<(std::sync::Arc<MdbxEnv<mdbx::RO>>, fixtures::Chain)> {
+        let chain = {
+            let env = erigon::env_open::<mdbx::RW>(dir)?;
+            let db = Erigon::begin_rw(&env)?;
+            let chain = fixtures::lifecycle::build_into(&db)?;
+            db.0.commit()?;
+            chain
+        };
+        Ok((std::sync::Arc::new(erigon::env_open(dir)?), chain))
+    }
+
+    #[cfg(feature = "remote")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve_kv() -> eyre::Result<()> {
+        use fixtures::lifecycle::{DST, FACTORY};
+        let dir = tempfile::tempdir()?;
+        let (env, chain) = lifecycle_ro(dir.path())?;
+        let store = chain.contracts[1];
+        let (inc, slots) = {
+            let db = Erigon::begin(&env)?;
+            let inc = db.read_account(FACTORY)?.unwrap().incarnation;
+            let slots = db
+                .walk_storage(FACTORY, inc, None)?
+                .collect::<Result<Vec<_>>>()?;
+            (inc, slots)
+        };
+        assert!(!slots.is_empty());
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let server = tokio::spawn(remote::serve_kv(env, addr));
+        // the server only listens once its task has run
+        let kv = loop {
+            match remote::RemoteKv::connect(format!("http://{}", addr)).await {
+                Ok(kv) => break kv,
+                Err(e) if server.is_finished() => return Err(e.into()),
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        let db = kv.begin().await?;
+        assert_eq!(db.read_head_block_number().await?, Some(chain.head()));
+        assert_eq!(db.read_account(DST).await?.unwrap().balance, 100.into());
+        let last = U256::from_big_endian(store.as_bytes());
+        let slot = H256::zero();
+        assert_eq!(db.read_storage(FACTORY, inc, slot).await?, Some(last));
+        let slot = H256::repeat_byte(0xff);
+        assert_eq!(db.read_storage(FACTORY, inc, slot).await?, None);
+        assert_eq!(db.walk_storage(FACTORY, inc, None).await?, slots);
+        server.abort();
+        Ok(())
+    }
+
+    #[cfg(feature = "rpc")]
+    #[tokio::test]
+    async fn test_rpc_module() -> eyre::Result<()> {
+        use fixtures::lifecycle::{DST, FACTORY};
+        let dir = tempfile::tempdir()?;
+        let (env, chain) = lifecycle_ro(dir.path())?;
+        let store = chain.contracts[1];
+        let module = rpc::rpc_module(env)?;
+
+        let balance: U256 = module.call("eth_getBalance", (DST, "latest")).await?;
+        assert_eq!(balance, 100.into());
+        // the transfer to DST is in block 1
+        let balance: U256 = module.call("eth_getBalance", (DST, "0x0")).await?;
+        assert_eq!(balance, U256::zero());
+        let balance: U256 = module.call("eth_getBalance", (DST, "0x1")).await?;
+        assert_eq!(balance, 100.into());
+
+        let slot = U256::zero();
+        let val: H256 = module
+            .call("eth_getStorageAt", (FACTORY, slot, "latest"))
+            .await?;
+        assert_eq!(val, H256::from(store));
+        let val: H256 = module
+            .call("eth_getStorageAt", (FACTORY, slot, "0x2"))
+            .await?;
+        assert_eq!(val, H256::zero());
+
+        let block: serde_json::Value = module.call("eth_getBlockByNumber", ("0x1", false)).await?;
+        assert_eq!(block["number"], "0x1");
+        assert_eq!(block["hash"], serde_json::to_value(chain.hashes[1])?);
+        assert_eq!(block["transactions"].as_array().map(Vec::len), Some(1));
+        let block: serde_json::Value = module.call("eth_getBlockByNumber", ("0x63", false)).await?;
+        assert!(block.is_null());
+        Ok(())
+    }
+
+    // Writes a contract that returns its slot 0, set to 42 in block 1 and to
+    // 43 in block 2, and an account whose code is missing from the db. Block 2
+    // also holds two transfers, the second spending what the first sent, and
+    // a call to the account without code.
+    #[cfg(feature = "revm")]
+    fn evm_chain(db: &Erigon<'_, mdbx::RW>) -> Result<fixtures::Chain> {
+        use fixtures::{lifecycle::SRC, ChainWriter};
+        use models::{transaction::TxAction, Account};
+        // mstore(0, sload(0)) return(0, 32)
+        const CODE: &[u8] = &[
+            0x60, 0x00, 0x54, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+        ];
+        let contract = Address::repeat_byte(0xcc);
+        let broken = Address::repeat_byte(0xdd);
+        let (a, b) = (Address::repeat_byte(0xaa), Address::repeat_byte(0xbb));
+
+        let mut w = ChainWriter::genesis(db, [(SRC, U256::exp10(22))])?;
+        let code = bytes::Bytes::from_static(CODE);
+        w.create_contract(contract, code, [(H256::zero(), 42.into())])?;
+        let acct = Account::new()
+            .incarnation(1.into())
+            .codehash(H256::repeat_byte(0xdd));
+        w.set_account(broken, Some(acct))?;
+        w.seal()?;
+
+        w.set_storage(contract, H256::zero(), 43.into())?;
+        w.push_tx(SRC, TxAction::Call(a), 100.into(), Default::default())?;
+        w.push_tx(a, TxAction::Call(b), 60.into(), Default::default())?;
+        w.push_tx(SRC, TxAction::Call(broken), 0.into(), Default::default())?;
+        w.seal()?;
+
+        let mut chain = w.finish()?;
+        chain.accounts = vec![SRC, a, b];
+        chain.contracts = vec![contract, broken];
+        Ok(chain)
+    }
+
+    #[cfg(feature = "revm")]
+    #[test]
+    fn test_evm_call() -> eyre::Result<()> {
+        use erigon::evm::CallRequest;
+        use kv::traits::DbName;
+        let fixture = fixtures::Fixture::new(evm_chain)?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let (contract, broken) = (fixture.chain.contracts[0], fixture.chain.contracts[1]);
+        let call = |block: u64, to| {
+            let req = CallRequest {
+                to: Some(to),
+                ..Default::default()
+            };
+            db.call(block, req)
+        };
+
+        // the slot is read from the changesets at block 1 and from
+        // PlainState at the head
+        for (block, want) in [(1, 42), (2, 43)] {
+            let res = call(block, contract)?.unwrap();
+            assert!(res.is_success());
+            assert_eq!(U256::from_big_endian(&res.output), want.into());
+        }
+        assert_eq!(call(3, contract)?, None);
+        // a failed read ends the call with the db's error
+        let res = call(2, broken);
+        assert!(matches!(res, Err(Error::NotFound(name)) if name == erigon::tables::Code::NAME));
+        Ok(())
+    }
+
+    #[cfg(feature = "revm")]
+    #[test]
+    fn test_trace_transaction() -> eyre::Result<()> {
+        use kv::traits::DbName;
+        use revm::NoOpInspector;
+        let fixture = fixtures::Fixture::new(evm_chain)?;
+        let db = Erigon::new(fixture.env.begin_ro()?);
+        let block = db.read_block(2)?.unwrap();
+
+        let first = db.trace_block_transaction(2, 0, NoOpInspector)?.unwrap();
+        assert_eq!(first.gas_used, 21_000);
+        // the second transfer is only funded once the first is replayed
+        let hash = block.transactions[1].msg.tx_hash();
+        let second = db.trace_transaction(hash, NoOpInspector)?.unwrap();
+        assert_eq!(second.exit_reason, first.exit_reason);
+        assert_eq!(second.gas_used, 21_000);
+        // a failed read ends the replay with the db's error
+        let res = db.trace_block_transaction(2, 2, NoOpInspector);
+        assert!(matches!(res, Err(Error::NotFound(name)) if name == erigon::tables::Code::NAME));
+        assert!(db.trace_block_transaction(2, 3, NoOpInspector)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompress() -> eyre::Result<()> {
+        use snapshots::decompress::Decompressor;
+        // written with a port of the word encoding of erigon-lib's compress
+        // package, with the patterns "abcdefgh" and "0123"
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test/snapshots");
+        let seg = Decompressor::open(&dir.join("words.seg"))?;
+        let words: [&[u8]; 6] = [
+            b"",
+            b"abcdefgh",
+            b"xx0123yabcdefgh0123zzz",
+            b"no patterns",
+            b"",
+            b"01230123",
+        ];
+        assert_eq!(seg.words_count(), 6);
+        assert_eq!(seg.empty_words_count(), 2);
+        let mut g = seg.getter();
+        let mut offsets = Vec::new();
+        for word in words {
+            assert!(g.has_next());
+            offsets.push(g.offset());
+            assert_eq!(g.next_word()?, word);
+        }
+        assert!(!g.has_next());
+        assert_eq!(offsets, [0, 1, 2, 11, 23, 24]);
+        // a getter can be moved to the offset of any word
+        g.reset(offsets[2]);
+        assert_eq!(g.next_word()?, words[2]);
+        assert_eq!(g.next_word()?, words[3]);
 
-        // let burnt = db.read::<crate::tables::Burnt>(1.into())?.unwrap();
-        // dbg!(burnt);
+        let tmp = tempfile::tempdir()?;
+        let raw = std::fs::read(dir.join("words.seg"))?;
+        std::fs::write(tmp.path().join("short.seg"), &raw[..30])?;
+        assert!(Decompressor::open(&tmp.path().join("short.seg")).is_err());
         Ok(())
     }
 }
@@ -0,0 +1,332 @@
+//! An ethers [`Middleware`] that answers reads of blocks, transactions and
+//! state straight from an Erigon db, passing every other request on to an
+//! inner provider.
+//!
+//! mdbx reads are blocking, so each read runs on tokio's blocking thread pool,
+//! in its own read-only transaction.
+use async_trait::async_trait;
+use ethereum_types::{Address, H256, U256, U64};
+use ethers::{
+    providers::{Middleware, MiddlewareError},
+    types::{
+        Block as EthersBlock, BlockId as EthersBlockId, BlockNumber as Tag, Bytes as EthersBytes,
+        NameOrAddress, Transaction as EthersTransaction, TxHash,
+    },
+};
+use mdbx::RO;
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    erigon::{models::*, tables::*, Erigon},
+    kv::{traits::DbName, MdbxEnv},
+    Error, Result,
+};
+
+/// Wraps a provider, answering `get_block`, `get_transaction`, `get_balance`,
+/// `get_storage_at` and `get_code` from the db.
+///
+/// Requests the db cannot answer, e.g. for a `pending` block, an ENS name, or
+/// a block or transaction the db has not synced yet, go to the inner
+/// provider, as does everything else.
+#[derive(Clone, Debug)]
+pub struct ErigonMiddleware<M> {
+    env: Arc<MdbxEnv<RO>>,
+    inner: M,
+}
+
+/// An error from the db or from the inner provider.
+#[derive(Debug, thiserror::Error)]
+pub enum ErigonMiddlewareError<M: Middleware> {
+    #[error(transparent)]
+    Db(#[from] Error),
+    #[error("{0}")]
+    Middleware(M::Error),
+}
+
+impl<M: Middleware> MiddlewareError for ErigonMiddlewareError<M> {
+    type Inner = M::Error;
+
+    fn from_err(src: M::Error) -> Self {
+        Self::Middleware(src)
+    }
+
+    fn as_inner(&self) -> Option<&Self::Inner> {
+        match self {
+            Self::Middleware(e) => Some(e),
+            Self::Db(_) => None,
+        }
+    }
+}
+
+impl<M: Middleware> ErigonMiddleware<M> {
+    pub fn new(env: Arc<MdbxEnv<RO>>, inner: M) -> Self {
+        Self { env, inner }
+    }
+
+    pub fn env(&self) -> &Arc<MdbxEnv<RO>> {
+        &self.env
+    }
+
+    // Runs `f` on a blocking thread, in a new read-only transaction.
+    async fn read<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Erigon<'_, RO>) -> Result<R> + Send + 'static,
+    {
+        let env = self.env.clone();
+        tokio::task::spawn_blocking(move || f(&Erigon::begin(&env)?)).await?
+    }
+}
+
+// Where to read state from. Latest state is read from PlainState rather than
+// through the history.
+enum At {
+    Latest,
+    Block(BlockNumber),
+}
+
+// Resolves a block id to the canonical block it names, or None if only the
+// inner provider can answer, e.g. for the pending block or a non-canonical
+// hash.
+fn resolve(db: &Erigon<'_, RO>, id: EthersBlockId) -> Result<Option<BlockNumber>> {
+    Ok(match id {
+        EthersBlockId::Hash(hash) => match db.read_header_number(hash)? {
+            Some(num) if db.read_canonical_hash(num)? == Some(hash) => Some(num),
+            _ => None,
+        },
+        EthersBlockId::Number(Tag::Number(num)) => Some(BlockNumber(num.as_u64())),
+        EthersBlockId::Number(Tag::Earliest) => Some(BlockNumber(0)),
+        EthersBlockId::Number(Tag::Latest) => Some(head(db)?),
+        EthersBlockId::Number(_) => None,
+    })
+}
+
+// Resolves the block at which to read state, or None if only the inner
+// provider can answer, e.g. for a block past the head.
+fn resolve_state(db: &Erigon<'_, RO>, id: Option<EthersBlockId>) -> Result<Option<At>> {
+    let num = match id {
+        None => return Ok(Some(At::Latest)),
+        Some(id) => match resolve(db, id)? {
+            Some(num) => num,
+            None => return Ok(None),
+        },
+    };
+    let head = head(db)?;
+    Ok(match num.cmp(&head) {
+        Ordering::Less => Some(At::Block(num)),
+        Ordering::Equal => Some(At::Latest),
+        Ordering::Greater => None,
+    })
+}
+
+fn head(db: &Erigon<'_, RO>) -> Result<BlockNumber> {
+    db.read_head_block_number()?
+        .ok_or(Error::NotFound(HeaderNumber::NAME))
+}
+
+fn read_account(db: &Erigon<'_, RO>, adr: Address, at: At) -> Result<Option<Account>> {
+    match at {
+        At::Latest => db.read_account(adr),
+        At::Block(num) => db.state_at(num).account(adr),
+    }
+}
+
+/// Converts a block to its ethers form, listing its transactions by hash.
+pub fn ethers_block(block: Block, total_difficulty: Option<U256>) -> EthersBlock<TxHash> {
+    let Block {
+        header,
+        transactions,
+        uncles,
+        ..
+    } = block;
+    EthersBlock {
+        hash: Some(header.hash()),
+        parent_hash: header.parent_hash,
+        uncles_hash: header.uncle_hash,
+        author: Some(header.coinbase),
+        state_root: header.root,
+        transactions_root: header.tx_hash,
+        receipts_root: header.receipts_hash,
+        number: Some(U64::from(header.number.low_u64())),
+        gas_used: header.gas_used.into(),
+        gas_limit: header.gas_limit.into(),
+        extra_data: header.extra.clone().into(),
+        logs_bloom: Some(header.bloom),
+        timestamp: header.time.into(),
+        difficulty: header.difficulty,
+        total_difficulty,
+        uncles: uncles.iter().map(BlockHeader::hash).collect(),
+        transactions: transactions.iter().map(|tx| tx.msg.tx_hash()).collect(),
+        mix_hash: Some(header.mix_digest),
+        nonce: Some(header.nonce),
+        base_fee_per_gas: header.base_fee,
+        ..Default::default()
+    }
+}
+
+#[async_trait]
+impl<M: Middleware> Middleware for ErigonMiddleware<M> {
+    type Error = ErigonMiddlewareError<M>;
+    type Provider = M::Provider;
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    async fn get_block<T: Into<EthersBlockId> + Send + Sync>(
+        &self,
+        id: T,
+    ) -> std::result::Result<Option<EthersBlock<TxHash>>, Self::Error> {
+        let id = id.into();
+        let block = self
+            .read(move |db| {
+                let num = match resolve(db, id)? {
+                    Some(num) => num,
+                    None => return Ok(None),
+                };
+                let block = match db.read_block(num)? {
+                    Some(block) => block,
+                    None => return Ok(None),
+                };
+                let td = db
+                    .read_total_difficulty((num, block.header.hash()))?
+                    .map(|td| *td);
+                Ok(Some(ethers_block(block, td)))
+            })
+            .await?;
+        match block {
+            Some(block) => Ok(Some(block)),
+            None => self
+                .inner
+                .get_block(id)
+                .await
+                .map_err(MiddlewareError::from_err),
+        }
+    }
+
+    async fn get_transaction<T: Send + Sync + Into<TxHash>>(
+        &self,
+        hash: T,
+    ) -> std::result::Result<Option<EthersTransaction>, Self::Error> {
+        let hash = hash.into();
+        let tx = self
+            .read(move |db| {
+                let (msg, num, index) = match db.read_transaction_by_hash(hash)? {
+                    Some(found) => found,
+                    None => return Ok(None),
+                };
+                let key = db
+                    .read_header_key(num)?
+                    .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
+                let signer = db
+                    .read_senders(key)?
+                    .and_then(|senders| senders.get(index).copied())
+                    .ok_or(Error::NotFound(TxSender::NAME))?;
+                let tx_hash = msg.tx_hash();
+                Ok(Some(EthersTransaction {
+                    hash: tx_hash,
+                    block_hash: Some(key.1),
+                    block_number: Some(U64::from(*num)),
+                    transaction_index: Some(U64::from(index)),
+                    ..EthersTransaction::from(TransactionWithSigner { msg, signer })
+                }))
+            })
+            .await?;
+        match tx {
+            Some(tx) => Ok(Some(tx)),
+            None => self
+                .inner
+                .get_transaction(hash)
+                .await
+                .map_err(MiddlewareError::from_err),
+        }
+    }
+
+    async fn get_balance<T: Into<NameOrAddress> + Send + Sync>(
+        &self,
+        from: T,
+        block: Option<EthersBlockId>,
+    ) -> std::result::Result<U256, Self::Error> {
+        let from = from.into();
+        if let NameOrAddress::Address(adr) = from {
+            let balance = self
+                .read(move |db| match resolve_state(db, block)? {
+                    Some(at) => Ok(Some(
+                        read_account(db, adr, at)?.map_or(U256::zero(), |acct| acct.balance),
+                    )),
+                    None => Ok(None),
+                })
+                .await?;
+            if let Some(balance) = balance {
+                return Ok(balance);
+            }
+        }
+        self.inner
+            .get_balance(from, block)
+            .await
+            .map_err(MiddlewareError::from_err)
+    }
+
+    async fn get_storage_at<T: Into<NameOrAddress> + Send + Sync>(
+        &self,
+        from: T,
+        location: H256,
+        block: Option<EthersBlockId>,
+    ) -> std::result::Result<H256, Self::Error> {
+        let from = from.into();
+        if let NameOrAddress::Address(adr) = from {
+            let val = self
+                .read(move |db| {
+                    let val = match resolve_state(db, block)? {
+                        Some(At::Block(num)) => db.state_at(num).storage(adr, location)?,
+                        Some(At::Latest) => match db.read_account(adr)? {
+                            Some(acct) => db
+                                .read_storage(adr, acct.incarnation, location)?
+                                .unwrap_or_default(),
+                            None => U256::zero(),
+                        },
+                        None => return Ok(None),
+                    };
+                    let mut out = H256::zero();
+                    val.to_big_endian(out.as_bytes_mut());
+                    Ok(Some(out))
+                })
+                .await?;
+            if let Some(val) = val {
+                return Ok(val);
+            }
+        }
+        self.inner
+            .get_storage_at(from, location, block)
+            .await
+            .map_err(MiddlewareError::from_err)
+    }
+
+    async fn get_code<T: Into<NameOrAddress> + Send + Sync>(
+        &self,
+        at: T,
+        block: Option<EthersBlockId>,
+    ) -> std::result::Result<EthersBytes, Self::Error> {
+        let at = at.into();
+        if let NameOrAddress::Address(adr) = at {
+            let code = self
+                .read(move |db| {
+                    let code = match resolve_state(db, block)? {
+                        Some(At::Block(num)) => db.state_at(num).code(adr)?,
+                        Some(At::Latest) => db.read_code_by_address(adr)?.unwrap_or_default(),
+                        None => return Ok(None),
+                    };
+                    Ok(Some(EthersBytes::from(code.0)))
+                })
+                .await?;
+            if let Some(code) = code {
+                return Ok(code);
+            }
+        }
+        self.inner
+            .get_code(at, block)
+            .await
+            .map_err(MiddlewareError::from_err)
+    }
+}
@@ -0,0 +1,408 @@
+//! A client for Erigon's remote KV gRPC service.
+//!
+//! Erigon serves its db over the `remote.KV` service (by default on the
+//! private api address, `localhost:9090`). A transaction is a bidirectional
+//! stream: the client sends cursor operations and the server replies with one
+//! key/value pair per operation. [`RemoteErigon`] decodes those pairs with the
+//! same typed tables as [`Erigon`](crate::Erigon), but only mirrors a handful
+//! of its readers. Anything else can be read through
+//! [`RemoteErigon::cursor`] with the table types.
+//!
+//! [`serve_kv`] does the reverse, serving a local env over the same protocol
+//! so that other clients, including Erigon's rpcdaemon, can read through it.
+use ethereum_types::{Address, H256, U256};
+use std::{borrow::Cow, marker::PhantomData};
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    transport::{Channel, Endpoint},
+    Streaming,
+};
+
+use crate::{
+    erigon::{models::*, tables::*},
+    error::DecodeError,
+    kv::{
+        self,
+        traits::{DbFlags, DbName, DefaultFlags, DupSort, Table, TableDecode, TableEncode},
+    },
+    Error, Result,
+};
+
+pub mod proto;
+pub mod server;
+
+use proto::{Cursor, Op, Pair, VersionReply};
+pub use server::{serve_kv, KvServer};
+
+/// A connection to an Erigon node's remote KV service.
+#[derive(Clone, Debug)]
+pub struct RemoteKv {
+    grpc: tonic::client::Grpc<Channel>,
+}
+
+impl RemoteKv {
+    /// Connects to the KV service at `addr`, e.g. `http://localhost:9090`.
+    pub async fn connect(addr: impl Into<String>) -> Result<Self> {
+        let channel = Endpoint::from_shared(addr.into())?.connect().await?;
+        Ok(Self::new(channel))
+    }
+
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            grpc: tonic::client::Grpc::new(channel),
+        }
+    }
+
+    /// Returns the version of the KV interface implemented by the server.
+    pub async fn version(&self) -> Result<VersionReply> {
+        let mut grpc = self.grpc.clone();
+        grpc.ready()
+            .await
+            .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+        let reply = grpc
+            .unary(
+                tonic::Request::new(()),
+                PathAndQuery::from_static(proto::VERSION_PATH),
+                ProstCodec::<(), VersionReply>::default(),
+            )
+            .await?;
+        Ok(reply.into_inner())
+    }
+
+    /// Begins a read-only transaction on the server.
+    pub async fn begin(&self) -> Result<RemoteErigon> {
+        let mut grpc = self.grpc.clone();
+        grpc.ready()
+            .await
+            .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+        let (send, rx) = mpsc::channel(16);
+        let mut recv = grpc
+            .streaming(
+                tonic::Request::new(ReceiverStream::new(rx)),
+                PathAndQuery::from_static(proto::TX_PATH),
+                ProstCodec::<Cursor, Pair>::default(),
+            )
+            .await?
+            .into_inner();
+        // the server announces the tx before any cursor is opened
+        let first = recv
+            .message()
+            .await?
+            .ok_or_else(|| tonic::Status::aborted("tx stream closed before opening"))?;
+        Ok(RemoteErigon {
+            stream: Mutex::new(TxStream { send, recv }),
+            tx_id: first.tx_id,
+            view_id: first.view_id,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TxStream {
+    send: mpsc::Sender<Cursor>,
+    recv: Streaming<Pair>,
+}
+
+/// A read-only transaction on a remote Erigon db. The transaction is rolled
+/// back when this is dropped.
+///
+/// Only the point reads below are mirrored from [`Erigon`](crate::Erigon):
+/// its other readers run over a local cursor and are not available here.
+/// Every cursor op is a round trip to the server, so multi-table reads are
+/// best written against [`cursor`](Self::cursor) directly.
+#[derive(Debug)]
+pub struct RemoteErigon {
+    stream: Mutex<TxStream>,
+    tx_id: u64,
+    view_id: u64,
+}
+
+impl RemoteErigon {
+    /// The server's id for the transaction.
+    pub fn tx_id(&self) -> u64 {
+        self.tx_id
+    }
+
+    /// The id of the last write transaction visible to this one.
+    pub fn view_id(&self) -> u64 {
+        self.view_id
+    }
+
+    // Sends one cursor operation and waits for its reply.
+    async fn round_trip(&self, req: Cursor) -> Result<Pair> {
+        let mut stream = self.stream.lock().await;
+        stream
+            .send
+            .send(req)
+            .await
+            .map_err(|_| tonic::Status::aborted("tx stream closed"))?;
+        Ok(stream
+            .recv
+            .message()
+            .await?
+            .ok_or_else(|| tonic::Status::aborted("tx stream closed"))?)
+    }
+
+    /// Opens a cursor into the table on the server.
+    pub async fn cursor<'tx, T>(&'tx self) -> Result<RemoteCursor<'tx, T>>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        let op = if T::Flags::FLAGS.contains(mdbx::DatabaseFlags::DUP_SORT) {
+            Op::OpenDupSort
+        } else {
+            Op::Open
+        };
+        let reply = self
+            .round_trip(Cursor {
+                op: op as i32,
+                bucket_name: T::NAME.to_string(),
+                ..Default::default()
+            })
+            .await?;
+        Ok(RemoteCursor {
+            tx: self,
+            id: reply.cursor_id,
+            _dbi: PhantomData,
+        })
+    }
+
+    /// Reads from the table, opening and closing a cursor on the server.
+    pub async fn read<'tx, T>(&'tx self, key: T::Key) -> Result<Option<T::Value>>
+    where
+        T: Table<'tx> + DefaultFlags,
+    {
+        let mut cur = self.cursor::<T>().await?;
+        let val = cur.seek_exact(key).await?;
+        cur.close().await?;
+        Ok(val)
+    }
+
+    /// Returns the hash of the current canonical head header.
+    pub async fn read_head_header_hash(&self) -> Result<Option<H256>> {
+        self.read::<LastHeader>(LastHeaderKey).await
+    }
+
+    /// Returns the header number assigned to a hash.
+    pub async fn read_header_number(&self, hash: H256) -> Result<Option<BlockNumber>> {
+        self.read::<HeaderNumber>(hash).await
+    }
+
+    /// Returns the number of the current canonical block header.
+    pub async fn read_head_block_number(&self) -> Result<Option<BlockNumber>> {
+        let hash = self
+            .read_head_header_hash()
+            .await?
+            .ok_or(Error::NotFound(LastHeader::NAME))?;
+        self.read_header_number(hash).await
+    }
+
+    /// Returns the hash assigned to a canonical block number.
+    pub async fn read_canonical_hash(&self, num: impl Into<BlockNumber>) -> Result<Option<H256>> {
+        self.read::<CanonicalHeader>(num.into()).await
+    }
+
+    /// Returns the block header identified by the (block number, block hash) key
+    pub async fn read_header(&self, key: impl Into<HeaderKey>) -> Result<Option<BlockHeader>> {
+        self.read::<Header>(key.into()).await
+    }
+
+    /// Returns the decoded account data as stored in the PlainState table.
+    pub async fn read_account(&self, adr: Address) -> Result<Option<Account>> {
+        self.read::<PlainState>(adr).await
+    }
+
+    /// Returns the code associated with the given codehash.
+    pub async fn read_code(&self, codehash: H256) -> Result<Option<Bytecode>> {
+        if codehash == crate::erigon::utils::consts::EMPTY_HASH {
+            return Ok(Default::default());
+        }
+        self.read::<Code>(codehash).await
+    }
+
+    /// Returns the value of the storage for account `adr` indexed by `slot`.
+    pub async fn read_storage(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        slot: H256,
+    ) -> Result<Option<U256>> {
+        let mut cur = self.cursor::<Storage>().await?;
+        let val = cur
+            .seek_exact_dup(StorageKey(adr, inc.into()), slot)
+            .await?;
+        cur.close().await?;
+        Ok(val)
+    }
+
+    /// Returns all of the storage (key, value) pairs for the given address and
+    /// account incarnation. If a start_slot is provided, the returned pairs
+    /// begin at the smallest slot >= start_slot.
+    ///
+    /// Unlike [`Erigon::walk_storage`](crate::Erigon::walk_storage), the pairs
+    /// are collected up front, as each one is a round trip to the server.
+    pub async fn walk_storage(
+        &self,
+        adr: Address,
+        inc: impl Into<Incarnation>,
+        start_slot: Option<H256>,
+    ) -> Result<Vec<(H256, U256)>> {
+        let mut cur = self.cursor::<Storage>().await?;
+        let mut out = Vec::new();
+        let mut next = cur
+            .seek_dup(StorageKey(adr, inc.into()), start_slot.unwrap_or_default())
+            .await?;
+        while let Some(pair) = next {
+            out.push(pair);
+            next = cur.next_dup_val().await?;
+        }
+        cur.close().await?;
+        Ok(out)
+    }
+}
+
+/// A typed cursor into a table on the server, mirroring
+/// [`MdbxCursor`](crate::kv::MdbxCursor).
+#[derive(Debug)]
+pub struct RemoteCursor<'tx, T> {
+    tx: &'tx RemoteErigon,
+    id: u32,
+    _dbi: PhantomData<T>,
+}
+
+impl<'tx, T: Table<'tx>> RemoteCursor<'tx, T> {
+    async fn op(&mut self, op: Op, k: Vec<u8>, v: Vec<u8>) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let reply = self
+            .tx
+            .round_trip(Cursor {
+                op: op as i32,
+                cursor: self.id,
+                k,
+                v,
+                ..Default::default()
+            })
+            .await?;
+        if reply.k.is_empty() && reply.v.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((reply.k, reply.v)))
+    }
+
+    /// Returns the (key, value) pair at the first key >= `key`
+    pub async fn seek(&mut self, key: T::SeekKey) -> Result<Option<(T::Key, T::Value)>>
+    where
+        T::Key: TableDecode,
+    {
+        let kv = self
+            .op(Op::Seek, key.encode().as_ref().to_vec(), vec![])
+            .await?;
+        kv.map(|(k, v)| kv::decode::<T>((Cow::Owned(k), Cow::Owned(v))))
+            .transpose()
+    }
+
+    /// Returns the value at exactly `key`, if present.
+    pub async fn seek_exact(&mut self, key: T::Key) -> Result<Option<T::Value>> {
+        let kv = self
+            .op(Op::SeekExact, key.encode().as_ref().to_vec(), vec![])
+            .await?;
+        kv.map(|(_, v)| kv::decode_one::<T>(Cow::Owned(v)))
+            .transpose()
+    }
+
+    /// Returns the first key/value pair in the table
+    pub async fn first(&mut self) -> Result<Option<(T::Key, T::Value)>>
+    where
+        T::Key: TableDecode,
+    {
+        let kv = self.op(Op::First, vec![], vec![]).await?;
+        kv.map(|(k, v)| kv::decode::<T>((Cow::Owned(k), Cow::Owned(v))))
+            .transpose()
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub async fn next(&mut self) -> Result<Option<(T::Key, T::Value)>>
+    where
+        T::Key: TableDecode,
+    {
+        let kv = self.op(Op::Next, vec![], vec![]).await?;
+        kv.map(|(k, v)| kv::decode::<T>((Cow::Owned(k), Cow::Owned(v))))
+            .transpose()
+    }
+
+    /// Returns the number of entries in the table.
+    pub async fn count(&mut self) -> Result<u64> {
+        let reply = self
+            .tx
+            .round_trip(Cursor {
+                op: Op::Count as i32,
+                cursor: self.id,
+                ..Default::default()
+            })
+            .await?;
+        let got = reply.v.len();
+        let count = <[u8; 8]>::try_from(reply.v.as_slice())
+            .map_err(|_| DecodeError::InvalidLength { expected: 8, got })?;
+        Ok(u64::from_be_bytes(count))
+    }
+
+    /// Closes the cursor on the server. Cursors that are not closed are freed
+    /// when the transaction ends.
+    pub async fn close(self) -> Result<()> {
+        let id = self.id;
+        self.tx
+            .round_trip(Cursor {
+                op: Op::Close as i32,
+                cursor: id,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+impl<'tx, T: DupSort<'tx>> RemoteCursor<'tx, T> {
+    /// Returns the first value at `key` whose subkey is >= `subkey`. As with
+    /// [`MdbxCursor::seek_dup`](crate::kv::MdbxCursor::seek_dup), the value
+    /// includes the subkey prefix.
+    pub async fn seek_dup(&mut self, key: T::Key, subkey: T::Subkey) -> Result<Option<T::Value>> {
+        let kv = self
+            .op(
+                Op::SeekBoth,
+                key.encode().as_ref().to_vec(),
+                subkey.encode().as_ref().to_vec(),
+            )
+            .await?;
+        kv.map(|(_, v)| kv::decode_one::<T>(Cow::Owned(v)))
+            .transpose()
+    }
+
+    /// Returns the rest of the value at `key` whose subkey is exactly
+    /// `subkey`, as with
+    /// [`MdbxCursor::seek_exact_dup`](crate::kv::MdbxCursor::seek_exact_dup).
+    pub async fn seek_exact_dup(
+        &mut self,
+        key: T::Key,
+        subkey: T::Subkey,
+    ) -> Result<Option<T::Subvalue>> {
+        let subkey = subkey.encode().as_ref().to_vec();
+        let kv = self
+            .op(Op::SeekBoth, key.encode().as_ref().to_vec(), subkey.clone())
+            .await?;
+        match kv {
+            Some((_, v)) if v.starts_with(&subkey) => {
+                TableDecode::decode(&v[subkey.len()..]).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the next duplicate value at the current key.
+    pub async fn next_dup_val(&mut self) -> Result<Option<T::Value>> {
+        let kv = self.op(Op::NextDup, vec![], vec![]).await?;
+        kv.map(|(_, v)| kv::decode_one::<T>(Cow::Owned(v)))
+            .transpose()
+    }
+}
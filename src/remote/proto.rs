@@ -0,0 +1,81 @@
+//! The messages of Erigon's `remote.KV` gRPC service, written out by hand to
+//! avoid a protoc build step.
+// erigon-interfaces: remote/kv.proto
+#![allow(clippy::derive_partial_eq_without_eq)]
+
+/// The path of the bidirectional streaming Tx method.
+pub const TX_PATH: &str = "/remote.KV/Tx";
+/// The path of the Version method.
+pub const VERSION_PATH: &str = "/remote.KV/Version";
+
+/// A cursor operation, mirroring the mdbx cursor ops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Op {
+    First = 0,
+    FirstDup = 1,
+    Seek = 2,
+    SeekBoth = 3,
+    Current = 4,
+    Last = 6,
+    LastDup = 7,
+    Next = 8,
+    NextDup = 9,
+    NextNoDup = 11,
+    Prev = 12,
+    PrevDup = 13,
+    PrevNoDup = 14,
+    SeekExact = 15,
+    SeekBothExact = 16,
+    Open = 30,
+    Close = 31,
+    OpenDupSort = 32,
+    Count = 33,
+}
+
+/// A request sent by the client on the Tx stream.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Cursor {
+    #[prost(enumeration = "Op", tag = "1")]
+    pub op: i32,
+    /// The table to open, only set for `Open` and `OpenDupSort`.
+    #[prost(string, tag = "2")]
+    pub bucket_name: String,
+    /// The id of the cursor, as returned by the server on open.
+    #[prost(uint32, tag = "3")]
+    pub cursor: u32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub k: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub v: Vec<u8>,
+}
+
+/// A reply sent by the server on the Tx stream. Empty `k` and `v` mean the
+/// cursor found nothing.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Pair {
+    #[prost(bytes = "vec", tag = "1")]
+    pub k: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub v: Vec<u8>,
+    /// Sent once, in reply to opening a cursor.
+    #[prost(uint32, tag = "3")]
+    pub cursor_id: u32,
+    /// Sent once, when the tx is opened.
+    #[prost(uint64, tag = "4")]
+    pub view_id: u64,
+    /// Sent once, when the tx is opened.
+    #[prost(uint64, tag = "5")]
+    pub tx_id: u64,
+}
+
+/// The version of the KV service interface.
+#[derive(Clone, Copy, PartialEq, Eq, ::prost::Message)]
+pub struct VersionReply {
+    #[prost(uint32, tag = "1")]
+    pub major: u32,
+    #[prost(uint32, tag = "2")]
+    pub minor: u32,
+    #[prost(uint32, tag = "3")]
+    pub patch: u32,
+}
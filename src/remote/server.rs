@@ -0,0 +1,276 @@
+use mdbx::{Cursor as MdbxRawCursor, RO};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    body::BoxBody,
+    codec::ProstCodec,
+    codegen::{empty_body, http, Body, BoxFuture, Context, Poll, Service, StdError},
+    server::{StreamingService, UnaryService},
+    Request, Response, Status, Streaming,
+};
+
+use crate::{
+    kv::MdbxEnv,
+    remote::proto::{self, Cursor, Op, Pair, VersionReply},
+    Result,
+};
+
+/// The version of the KV interface implemented by [`KvServer`].
+pub const KV_SERVICE_API_VERSION: VersionReply = VersionReply {
+    major: 5,
+    minor: 1,
+    patch: 0,
+};
+
+type PairStream = ReceiverStream<std::result::Result<Pair, Status>>;
+
+/// Serves Erigon's `remote.KV` gRPC service from a local env until the server
+/// fails. Only read-only transactions are supported, as in Erigon itself.
+pub async fn serve_kv(env: Arc<MdbxEnv<RO>>, addr: SocketAddr) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(KvServer::new(env))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+/// An implementation of Erigon's `remote.KV` gRPC service on top of a local
+/// env, for use with a [`tonic::transport::Server`].
+///
+/// Each Tx stream gets its own read-only transaction, which lives on a
+/// blocking thread for as long as the client keeps the stream open.
+#[derive(Clone, Debug)]
+pub struct KvServer {
+    env: Arc<MdbxEnv<RO>>,
+    next_tx_id: Arc<AtomicU64>,
+}
+
+impl KvServer {
+    pub fn new(env: Arc<MdbxEnv<RO>>) -> Self {
+        Self {
+            env,
+            next_tx_id: Default::default(),
+        }
+    }
+
+    fn tx(&self, req: Request<Streaming<Cursor>>) -> Response<PairStream> {
+        let mut inbound = req.into_inner();
+        let (req_send, req_recv) = mpsc::channel(16);
+        let (resp_send, resp_recv) = mpsc::channel(16);
+        tokio::spawn(async move {
+            while let Ok(Some(cursor)) = inbound.message().await {
+                if req_send.send(cursor).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let env = self.env.clone();
+        let tx_id = self.next_tx_id.fetch_add(1, Ordering::Relaxed);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = run_tx(&env, tx_id, req_recv, &resp_send) {
+                let _ = resp_send.blocking_send(Err(Status::internal(e.to_string())));
+            }
+        });
+        Response::new(ReceiverStream::new(resp_recv))
+    }
+}
+
+// Runs the cursor ops sent on one Tx stream until the client hangs up.
+fn run_tx(
+    env: &MdbxEnv<RO>,
+    tx_id: u64,
+    mut reqs: mpsc::Receiver<Cursor>,
+    resps: &mpsc::Sender<std::result::Result<Pair, Status>>,
+) -> Result<()> {
+    let tx = env.begin()?;
+    let send = |pair| resps.blocking_send(Ok(pair)).is_ok();
+    let opened = Pair {
+        tx_id,
+        view_id: tx.inner.id(),
+        ..Default::default()
+    };
+    if !send(opened) {
+        return Ok(());
+    }
+
+    let mut cursors = HashMap::new();
+    let mut next_cursor_id = 0;
+    while let Some(req) = reqs.blocking_recv() {
+        let pair = match Op::from_i32(req.op) {
+            Some(Op::Open | Op::OpenDupSort) => {
+                let db = match tx.inner.open_db(Some(req.bucket_name.as_str())) {
+                    Ok(db) => db,
+                    Err(e) => {
+                        let msg = format!("cannot open table {}: {}", req.bucket_name, e);
+                        let _ = resps.blocking_send(Err(Status::not_found(msg)));
+                        continue;
+                    }
+                };
+                next_cursor_id += 1;
+                let cur = tx.inner.cursor(&db)?;
+                cursors.insert(next_cursor_id, (db, cur));
+                Pair {
+                    cursor_id: next_cursor_id,
+                    ..Default::default()
+                }
+            }
+            Some(Op::Close) => {
+                cursors.remove(&req.cursor);
+                Pair::default()
+            }
+            Some(op) => {
+                let (db, cur) = match cursors.get_mut(&req.cursor) {
+                    Some((db, cur)) => (db, cur),
+                    None => {
+                        let msg = format!("unknown cursor {}", req.cursor);
+                        let _ = resps.blocking_send(Err(Status::invalid_argument(msg)));
+                        continue;
+                    }
+                };
+                // Erigon replies to Count with the entry count of the whole
+                // table, whatever the cursor position
+                if op == Op::Count {
+                    let count = tx.inner.db_stat(db)?.entries() as u64;
+                    Pair {
+                        v: count.to_be_bytes().to_vec(),
+                        ..Default::default()
+                    }
+                } else {
+                    match cursor_op(cur, op, &req.k, &req.v)? {
+                        Some(pair) => pair,
+                        None => {
+                            let msg = format!("unsupported op {:?}", op);
+                            let _ = resps.blocking_send(Err(Status::unimplemented(msg)));
+                            continue;
+                        }
+                    }
+                }
+            }
+            None => {
+                let msg = format!("unknown op {}", req.op);
+                let _ = resps.blocking_send(Err(Status::invalid_argument(msg)));
+                continue;
+            }
+        };
+        if !send(pair) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Applies a positioning op to the cursor, returning None if the op is not
+// supported. Ops that only return a value reply with the requested key.
+fn cursor_op<'txn>(
+    cur: &mut MdbxRawCursor<'txn, RO>,
+    op: Op,
+    k: &[u8],
+    v: &[u8],
+) -> Result<Option<Pair>> {
+    type Kv<'a> = Option<(Cow<'a, [u8]>, Cow<'a, [u8]>)>;
+    fn with_key<'a>(k: &[u8], val: Option<Cow<'a, [u8]>>) -> Kv<'a> {
+        val.map(|val| (Cow::Owned(k.to_vec()), val))
+    }
+    let kv: Kv<'txn> = match op {
+        Op::First => cur.first()?,
+        Op::FirstDup => with_key(k, cur.first_dup()?),
+        Op::Seek => cur.set_range(k)?,
+        Op::SeekBoth => with_key(k, cur.get_both_range(k, v)?),
+        Op::Current => cur.get_current()?,
+        Op::Last => cur.last()?,
+        Op::LastDup => with_key(k, cur.last_dup()?),
+        Op::Next => cur.next()?,
+        Op::NextDup => cur.next_dup()?,
+        Op::NextNoDup => cur.next_nodup()?,
+        Op::Prev => cur.prev()?,
+        Op::PrevDup => cur.prev_dup()?,
+        Op::PrevNoDup => cur.prev_nodup()?,
+        Op::SeekExact => cur.set_key(k)?,
+        Op::SeekBothExact => with_key(k, cur.get_both(k, v)?),
+        Op::Open | Op::OpenDupSort | Op::Close | Op::Count => return Ok(None),
+    };
+    Ok(Some(match kv {
+        Some((k, v)) => Pair {
+            k: k.into_owned(),
+            v: v.into_owned(),
+            ..Default::default()
+        },
+        None => Pair::default(),
+    }))
+}
+
+impl<B> Service<http::Request<B>> for KvServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let server = self.clone();
+        match req.uri().path() {
+            proto::TX_PATH => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(ProstCodec::<Pair, Cursor>::default());
+                Ok(grpc.streaming(TxSvc(server), req).await)
+            }),
+            proto::VERSION_PATH => Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(ProstCodec::<VersionReply, ()>::default());
+                Ok(grpc.unary(VersionSvc, req).await)
+            }),
+            // grpc status 12 is UNIMPLEMENTED
+            _ => Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", "12")
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap())
+            }),
+        }
+    }
+}
+
+impl tonic::transport::NamedService for KvServer {
+    const NAME: &'static str = "remote.KV";
+}
+
+struct TxSvc(KvServer);
+
+impl StreamingService<Cursor> for TxSvc {
+    type Response = Pair;
+    type ResponseStream = PairStream;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, req: Request<Streaming<Cursor>>) -> Self::Future {
+        let resp = self.0.tx(req);
+        Box::pin(async move { Ok(resp) })
+    }
+}
+
+struct VersionSvc;
+
+impl UnaryService<()> for VersionSvc {
+    type Response = VersionReply;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, _req: Request<()>) -> Self::Future {
+        Box::pin(async move { Ok(Response::new(KV_SERVICE_API_VERSION)) })
+    }
+}
@@ -0,0 +1,454 @@
+//! A minimal rlp encoder and decoder, covering the types stored by Erigon.
+//!
+//! The encoding is canonical, and decoding rejects non-canonical input (e.g.
+//! integers with leading zeroes), as Erigon's own decoder does.
+//! Structs encoded as a list of their fields are declared with [`rlp_struct`],
+//! and newtypes encoded as their only field with [`rlp_wrapper`].
+// https://ethereum.org/en/developers/docs/data-structures-and-encoding/rlp/
+use bytes::{Buf, Bytes};
+use ethereum_types::{Bloom, H160, H256, H520, H64, U256};
+
+pub use bytes::BufMut;
+
+pub const EMPTY_STRING_CODE: u8 = 0x80;
+pub const EMPTY_LIST_CODE: u8 = 0xc0;
+
+/// Errors encountered while decoding rlp.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    #[error("integer overflow")]
+    Overflow,
+    #[error("leading zero in integer")]
+    LeadingZero,
+    #[error("input too short")]
+    InputTooShort,
+    #[error("single byte below 0x80 encoded as a string")]
+    NonCanonicalSingleByte,
+    #[error("length below 56 encoded in long form")]
+    NonCanonicalSize,
+    #[error("unexpected length")]
+    UnexpectedLength,
+    #[error("unexpected string")]
+    UnexpectedString,
+    #[error("unexpected list")]
+    UnexpectedList,
+    #[error("list length mismatch: {got} != {expected}")]
+    ListLengthMismatch { expected: usize, got: usize },
+    #[error("{0}")]
+    Custom(&'static str),
+}
+
+/// The header of an rlp item, giving its kind and the length of its payload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub list: bool,
+    pub payload_length: usize,
+}
+
+impl Header {
+    /// Decodes the header of the next item. A single byte below 0x80 is its
+    /// own header, so is left in the buffer as the payload.
+    pub fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+        let b = *buf.first().ok_or(DecodeError::InputTooShort)?;
+        let head = match b {
+            0..=0x7f => Self {
+                list: false,
+                payload_length: 1,
+            },
+            0x80..=0xb7 => {
+                buf.advance(1);
+                let payload_length = (b - EMPTY_STRING_CODE) as usize;
+                if payload_length == 1 && buf.first().is_some_and(|&b| b < EMPTY_STRING_CODE) {
+                    return Err(DecodeError::NonCanonicalSingleByte);
+                }
+                Self {
+                    list: false,
+                    payload_length,
+                }
+            }
+            0xb8..=0xbf => Self {
+                list: false,
+                payload_length: decode_long_length(buf, b - 0xb7)?,
+            },
+            0xc0..=0xf7 => {
+                buf.advance(1);
+                Self {
+                    list: true,
+                    payload_length: (b - EMPTY_LIST_CODE) as usize,
+                }
+            }
+            0xf8..=0xff => Self {
+                list: true,
+                payload_length: decode_long_length(buf, b - 0xf7)?,
+            },
+        };
+        if buf.len() < head.payload_length {
+            return Err(DecodeError::InputTooShort);
+        }
+        Ok(head)
+    }
+
+    pub fn encode(&self, out: &mut dyn BufMut) {
+        let code = if self.list {
+            EMPTY_LIST_CODE
+        } else {
+            EMPTY_STRING_CODE
+        };
+        if self.payload_length < 56 {
+            out.put_u8(code + self.payload_length as u8);
+        } else {
+            let len = (self.payload_length as u64).to_be_bytes();
+            let len = &len[len.iter().take_while(|&&b| b == 0).count()..];
+            out.put_u8(code + 55 + len.len() as u8);
+            out.put_slice(len);
+        }
+    }
+
+    /// The length of the encoded header.
+    pub fn length(&self) -> usize {
+        length_of_length(self.payload_length)
+    }
+}
+
+// Decodes the big-endian length following the first byte of a long string or
+// list, which gives the length of the length.
+fn decode_long_length(buf: &mut &[u8], len_of_len: u8) -> Result<usize, DecodeError> {
+    buf.advance(1);
+    let len_of_len = len_of_len as usize;
+    let len = buf.get(..len_of_len).ok_or(DecodeError::InputTooShort)?;
+    if len[0] == 0 {
+        return Err(DecodeError::LeadingZero);
+    }
+    if len_of_len > 8 {
+        return Err(DecodeError::Overflow);
+    }
+    let len = len.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+    buf.advance(len_of_len);
+    let len = usize::try_from(len).map_err(|_| DecodeError::Overflow)?;
+    if len < 56 {
+        return Err(DecodeError::NonCanonicalSize);
+    }
+    Ok(len)
+}
+
+/// Returns the length of the header of an item with the given payload length.
+pub fn length_of_length(payload_length: usize) -> usize {
+    if payload_length < 56 {
+        1
+    } else {
+        1 + (usize::BITS - payload_length.leading_zeros()).div_ceil(8) as usize
+    }
+}
+
+pub trait Encodable {
+    fn encode(&self, out: &mut dyn BufMut);
+
+    /// The length of the encoded item, including its header.
+    fn length(&self) -> usize {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out.len()
+    }
+}
+
+pub trait Decodable: Sized {
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl<T: Encodable + ?Sized> Encodable for &T {
+    fn encode(&self, out: &mut dyn BufMut) {
+        (**self).encode(out)
+    }
+    fn length(&self) -> usize {
+        (**self).length()
+    }
+}
+
+// Returns the payload of the next string item, advancing past it.
+fn decode_string<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let head = Header::decode(buf)?;
+    if head.list {
+        return Err(DecodeError::UnexpectedList);
+    }
+    let (payload, rest) = buf.split_at(head.payload_length);
+    *buf = rest;
+    Ok(payload)
+}
+
+impl Encodable for [u8] {
+    fn encode(&self, out: &mut dyn BufMut) {
+        if self.len() != 1 || self[0] >= EMPTY_STRING_CODE {
+            Header {
+                list: false,
+                payload_length: self.len(),
+            }
+            .encode(out);
+        }
+        out.put_slice(self);
+    }
+    fn length(&self) -> usize {
+        if self.len() == 1 && self[0] < EMPTY_STRING_CODE {
+            1
+        } else {
+            length_of_length(self.len()) + self.len()
+        }
+    }
+}
+
+impl Encodable for Bytes {
+    fn encode(&self, out: &mut dyn BufMut) {
+        self[..].encode(out)
+    }
+    fn length(&self) -> usize {
+        self[..].length()
+    }
+}
+
+impl Decodable for Bytes {
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+        decode_string(buf).map(Bytes::copy_from_slice)
+    }
+}
+
+// Integers are encoded as big-endian strings without leading zeroes, so zero
+// is the empty string.
+macro_rules! impl_uint {
+    ($($t:ty),+) => {$(
+        impl Encodable for $t {
+            fn encode(&self, out: &mut dyn BufMut) {
+                let be = self.to_be_bytes();
+                be[be.iter().take_while(|&&b| b == 0).count()..].encode(out)
+            }
+            fn length(&self) -> usize {
+                let be = self.to_be_bytes();
+                be[be.iter().take_while(|&&b| b == 0).count()..].length()
+            }
+        }
+
+        impl Decodable for $t {
+            fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+                let b = decode_string(buf)?;
+                if b.len() > std::mem::size_of::<$t>() {
+                    return Err(DecodeError::Overflow);
+                }
+                if b.first() == Some(&0) {
+                    return Err(DecodeError::LeadingZero);
+                }
+                let mut be = [0; std::mem::size_of::<$t>()];
+                be[std::mem::size_of::<$t>() - b.len()..].copy_from_slice(b);
+                Ok(<$t>::from_be_bytes(be))
+            }
+        }
+    )+};
+}
+impl_uint!(u8, u16, u32, u64, u128, usize);
+
+impl Encodable for bool {
+    fn encode(&self, out: &mut dyn BufMut) {
+        (*self as u8).encode(out)
+    }
+    fn length(&self) -> usize {
+        1
+    }
+}
+
+impl Decodable for bool {
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+        match u8::decode(buf)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Custom("invalid bool, must be 0 or 1")),
+        }
+    }
+}
+
+impl Encodable for U256 {
+    fn encode(&self, out: &mut dyn BufMut) {
+        let mut be = [0; 32];
+        self.to_big_endian(&mut be);
+        be[32 - self.bits().div_ceil(8)..].encode(out)
+    }
+    fn length(&self) -> usize {
+        let len = self.bits().div_ceil(8);
+        if len == 1 && self.low_u32() < u32::from(EMPTY_STRING_CODE) {
+            1
+        } else {
+            length_of_length(len) + len
+        }
+    }
+}
+
+impl Decodable for U256 {
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+        let b = decode_string(buf)?;
+        if b.len() > 32 {
+            return Err(DecodeError::Overflow);
+        }
+        if b.first() == Some(&0) {
+            return Err(DecodeError::LeadingZero);
+        }
+        Ok(U256::from_big_endian(b))
+    }
+}
+
+// Fixed-size hashes are encoded as strings of exactly their length.
+macro_rules! impl_fixed_hash {
+    ($($t:ty),+) => {$(
+        impl Encodable for $t {
+            fn encode(&self, out: &mut dyn BufMut) {
+                self.as_bytes().encode(out)
+            }
+            fn length(&self) -> usize {
+                self.as_bytes().length()
+            }
+        }
+
+        impl Decodable for $t {
+            fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+                let b = decode_string(buf)?;
+                if b.len() != <$t>::len_bytes() {
+                    return Err(DecodeError::UnexpectedLength);
+                }
+                Ok(<$t>::from_slice(b))
+            }
+        }
+    )+};
+}
+impl_fixed_hash!(H64, H160, H256, H520, Bloom);
+
+impl<T: Encodable> Encodable for Vec<T> {
+    fn encode(&self, out: &mut dyn BufMut) {
+        Header {
+            list: true,
+            payload_length: self.iter().map(Encodable::length).sum(),
+        }
+        .encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn length(&self) -> usize {
+        let head = Header {
+            list: true,
+            payload_length: self.iter().map(Encodable::length).sum(),
+        };
+        head.length() + head.payload_length
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn decode(buf: &mut &[u8]) -> Result<Self, DecodeError> {
+        let head = Header::decode(buf)?;
+        if !head.list {
+            return Err(DecodeError::UnexpectedString);
+        }
+        let (mut payload, rest) = buf.split_at(head.payload_length);
+        let mut items = Vec::new();
+        while !payload.is_empty() {
+            items.push(T::decode(&mut payload)?);
+        }
+        *buf = rest;
+        Ok(items)
+    }
+}
+
+/// rlp_struct! declares a struct that is rlp-encoded as the list of its
+/// fields, in order. A struct without a lifetime is also decodable, and the
+/// whole list must be consumed by its fields.
+macro_rules! rlp_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident<$lt:lifetime> {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<$lt> {
+            $($(#[$fmeta])* $fvis $field: $ty),*
+        }
+        $crate::rlp::impl_encodable_struct!($name<$lt> { $($field),* });
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $ty),*
+        }
+        $crate::rlp::impl_encodable_struct!($name { $($field),* });
+
+        impl $crate::rlp::Decodable for $name {
+            fn decode(buf: &mut &[u8]) -> Result<Self, $crate::rlp::DecodeError> {
+                let head = $crate::rlp::Header::decode(buf)?;
+                if !head.list {
+                    return Err($crate::rlp::DecodeError::UnexpectedString);
+                }
+                let start = buf.len();
+                let this = Self {
+                    $($field: $crate::rlp::Decodable::decode(buf)?),*
+                };
+                let consumed = start - buf.len();
+                if consumed != head.payload_length {
+                    return Err($crate::rlp::DecodeError::ListLengthMismatch {
+                        expected: head.payload_length,
+                        got: consumed,
+                    });
+                }
+                Ok(this)
+            }
+        }
+    };
+}
+pub(crate) use rlp_struct;
+
+macro_rules! impl_encodable_struct {
+    ($name:ident$(<$lt:lifetime>)? { $($field:ident),* }) => {
+        impl$(<$lt>)? $name$(<$lt>)? {
+            fn rlp_payload_length(&self) -> usize {
+                0 $(+ $crate::rlp::Encodable::length(&self.$field))*
+            }
+        }
+
+        impl$(<$lt>)? $crate::rlp::Encodable for $name$(<$lt>)? {
+            fn encode(&self, out: &mut dyn $crate::rlp::BufMut) {
+                $crate::rlp::Header {
+                    list: true,
+                    payload_length: self.rlp_payload_length(),
+                }
+                .encode(out);
+                $($crate::rlp::Encodable::encode(&self.$field, out);)*
+            }
+            fn length(&self) -> usize {
+                let payload_length = self.rlp_payload_length();
+                $crate::rlp::length_of_length(payload_length) + payload_length
+            }
+        }
+    };
+}
+pub(crate) use impl_encodable_struct;
+
+/// rlp_wrapper! implements rlp encoding for a newtype, encoding it as its
+/// only field.
+macro_rules! rlp_wrapper {
+    ($name:ident) => {
+        impl $crate::rlp::Encodable for $name {
+            fn encode(&self, out: &mut dyn $crate::rlp::BufMut) {
+                $crate::rlp::Encodable::encode(&self.0, out)
+            }
+            fn length(&self) -> usize {
+                $crate::rlp::Encodable::length(&self.0)
+            }
+        }
+
+        impl $crate::rlp::Decodable for $name {
+            fn decode(buf: &mut &[u8]) -> Result<Self, $crate::rlp::DecodeError> {
+                $crate::rlp::Decodable::decode(buf).map(Self)
+            }
+        }
+    };
+}
+pub(crate) use rlp_wrapper;
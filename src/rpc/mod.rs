@@ -0,0 +1,215 @@
+//! A minimal, read-only JSON-RPC server that answers `eth_` requests straight
+//! from an Erigon db, without running Erigon's rpcdaemon.
+//!
+//! Supported methods: `eth_blockNumber`, `eth_getBalance`, `eth_getCode`,
+//! `eth_getStorageAt`, `eth_getBlockByNumber`, `eth_getBlockByHash`,
+//! `eth_getTransactionByHash`, and `eth_getTransactionReceipt`.
+use ethereum_types::{Address, H256, U256, U64};
+use jsonrpsee::{
+    core::Error as RpcError,
+    server::{ServerBuilder, ServerHandle},
+    types::{error::CallError, Params},
+    RpcModule,
+};
+use mdbx::RO;
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::{
+    erigon::{models::*, tables::*, Erigon},
+    kv::{traits::DbName, MdbxEnv},
+    Error, Result,
+};
+
+pub mod types;
+
+use types::{BlockNumberOrTag, HexBytes, RpcBlock, RpcReceipt, RpcTransaction};
+
+/// Starts the JSON-RPC server on `addr`. The server runs until the returned
+/// handle is stopped or dropped.
+pub async fn serve_rpc(env: Arc<MdbxEnv<RO>>, addr: SocketAddr) -> Result<ServerHandle> {
+    let server = ServerBuilder::default().build(addr).await?;
+    Ok(server.start(rpc_module(env)?)?)
+}
+
+/// Returns the module of `eth_` methods served by [`serve_rpc`], for merging
+/// into another jsonrpsee server.
+pub fn rpc_module(env: Arc<MdbxEnv<RO>>) -> Result<RpcModule<MdbxEnv<RO>>> {
+    let mut module = RpcModule::from_arc(env);
+    register(&mut module, "eth_blockNumber", |_, db| {
+        Ok(U64::from(*head(db)?))
+    })?;
+    register(&mut module, "eth_getBalance", |params, db| {
+        let mut seq = params.sequence();
+        let adr: Address = seq.next()?;
+        let block = seq.optional_next()?.unwrap_or_default();
+        Ok(account_at(db, adr, block)?
+            .map(|acct| acct.balance)
+            .unwrap_or_default())
+    })?;
+    register(&mut module, "eth_getCode", |params, db| {
+        let mut seq = params.sequence();
+        let adr: Address = seq.next()?;
+        let block = seq.optional_next()?.unwrap_or_default();
+        let code = match resolve(db, block)? {
+            Some(num) => db.state_at(num).code(adr)?,
+            None => db.read_code_by_address(adr)?.unwrap_or_default(),
+        };
+        Ok(HexBytes(code.0))
+    })?;
+    register(&mut module, "eth_getStorageAt", |params, db| {
+        let mut seq = params.sequence();
+        let adr: Address = seq.next()?;
+        let slot: U256 = seq.next()?;
+        let block = seq.optional_next()?.unwrap_or_default();
+        let mut key = H256::zero();
+        slot.to_big_endian(key.as_bytes_mut());
+        let val = match resolve(db, block)? {
+            Some(num) => db.state_at(num).storage(adr, key)?,
+            None => match db.read_account(adr)? {
+                Some(acct) => db
+                    .read_storage(adr, acct.incarnation, key)?
+                    .unwrap_or_default(),
+                None => U256::zero(),
+            },
+        };
+        let mut out = H256::zero();
+        val.to_big_endian(out.as_bytes_mut());
+        Ok(out)
+    })?;
+    register(&mut module, "eth_getBlockByNumber", |params, db| {
+        let mut seq = params.sequence();
+        let block: BlockNumberOrTag = seq.next()?;
+        let full: bool = seq.optional_next()?.unwrap_or_default();
+        let num = match block {
+            BlockNumberOrTag::Latest => head(db)?,
+            BlockNumberOrTag::Earliest => BlockNumber(0),
+            BlockNumberOrTag::Number(num) => num,
+        };
+        Ok(read_rpc_block(db, num.into(), full)?)
+    })?;
+    register(&mut module, "eth_getBlockByHash", |params, db| {
+        let mut seq = params.sequence();
+        let hash: H256 = seq.next()?;
+        let full: bool = seq.optional_next()?.unwrap_or_default();
+        Ok(read_rpc_block(db, hash.into(), full)?)
+    })?;
+    register(&mut module, "eth_getTransactionByHash", |params, db| {
+        let hash: H256 = params.one()?;
+        let (msg, num, index) = match db.read_transaction_by_hash(hash)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let key = db
+            .read_header_key(num)?
+            .ok_or(Error::NotFound(CanonicalHeader::NAME))?;
+        let header = db.read_header(key)?.ok_or(Error::NotFound(Header::NAME))?;
+        let signer = db
+            .read_senders(key)?
+            .and_then(|senders| senders.get(index).copied())
+            .ok_or(Error::NotFound(TxSender::NAME))?;
+        let tx = TransactionWithSigner { msg, signer };
+        Ok(Some(RpcTransaction::new(
+            tx,
+            Some((key.1, *num, index, header.base_fee)),
+        )))
+    })?;
+    register(&mut module, "eth_getTransactionReceipt", |params, db| {
+        let hash: H256 = params.one()?;
+        Ok(db.read_transaction_receipt(hash)?.map(RpcReceipt::from))
+    })?;
+    Ok(module)
+}
+
+// Registers a method that runs on a blocking thread, in its own read-only tx.
+fn register<R, F>(module: &mut RpcModule<MdbxEnv<RO>>, name: &'static str, f: F) -> Result<()>
+where
+    R: serde::Serialize + 'static,
+    F: Fn(Params<'_>, &Erigon<'_, RO>) -> std::result::Result<R, MethodError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    module.register_blocking_method(name, move |params, env| {
+        let db = Erigon::begin(&env).map_err(|e| RpcError::Custom(e.to_string()))?;
+        f(params, &db).map_err(|e| match e {
+            MethodError::Params(e) => e,
+            MethodError::Db(e) => RpcError::Custom(e.to_string()),
+            MethodError::UnknownBlock(num) => RpcError::Custom(format!("block {} not found", num)),
+        })
+    })?;
+    Ok(())
+}
+
+// Lets method bodies use `?` on both param parsing and db reads.
+enum MethodError {
+    Params(RpcError),
+    Db(Error),
+    // a state read at a block past the head
+    UnknownBlock(BlockNumber),
+}
+
+impl From<RpcError> for MethodError {
+    fn from(e: RpcError) -> Self {
+        Self::Params(e)
+    }
+}
+
+impl From<CallError> for MethodError {
+    fn from(e: CallError) -> Self {
+        Self::Params(e.into())
+    }
+}
+
+impl From<Error> for MethodError {
+    fn from(e: Error) -> Self {
+        Self::Db(e)
+    }
+}
+
+fn head(db: &Erigon<'_, RO>) -> Result<BlockNumber> {
+    db.read_head_block_number()?
+        .ok_or(Error::NotFound(HeaderNumber::NAME))
+}
+
+// Resolves the block at which to read state. Returns None for the head block,
+// whose state is read from PlainState rather than through the history, and
+// an error for a block past the head.
+fn resolve(
+    db: &Erigon<'_, RO>,
+    block: BlockNumberOrTag,
+) -> std::result::Result<Option<BlockNumber>, MethodError> {
+    let num = match block {
+        BlockNumberOrTag::Latest => return Ok(None),
+        BlockNumberOrTag::Earliest => BlockNumber(0),
+        BlockNumberOrTag::Number(num) => num,
+    };
+    let head = head(db)?;
+    if num > head {
+        return Err(MethodError::UnknownBlock(num));
+    }
+    Ok(if num == head { None } else { Some(num) })
+}
+
+fn account_at(
+    db: &Erigon<'_, RO>,
+    adr: Address,
+    block: BlockNumberOrTag,
+) -> std::result::Result<Option<Account>, MethodError> {
+    Ok(match resolve(db, block)? {
+        Some(num) => db.state_at(num).account(adr)?,
+        None => db.read_account(adr)?,
+    })
+}
+
+fn read_rpc_block(db: &Erigon<'_, RO>, id: BlockId, full: bool) -> Result<Option<RpcBlock>> {
+    let block = match db.read_block(id)? {
+        Some(block) => block,
+        None => return Ok(None),
+    };
+    let num = BlockNumber(block.header.number.low_u64());
+    let td = db
+        .read_total_difficulty((num, block.header.hash()))?
+        .map(|td| *td);
+    Ok(Some(RpcBlock::new(block, td, full)))
+}
@@ -0,0 +1,351 @@
+use ethereum_types::{Address, Bloom, H256, H64, U256, U64};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::erigon::models::{
+    AccessTuple, Block, BlockHeader, BlockNumber, CborLog, TransactionReceipt,
+    TransactionWithSigner, Withdrawal,
+};
+
+/// The block parameter of the `eth_` state and block methods.
+///
+/// There is no pending block in the db, and Erigon's safe and finalized blocks
+/// are tracked outside of it, so `pending`, `safe` and `finalized` are all read
+/// as `latest`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockNumberOrTag {
+    Latest,
+    Earliest,
+    Number(BlockNumber),
+}
+
+impl Default for BlockNumberOrTag {
+    fn default() -> Self {
+        Self::Latest
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockNumberOrTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match s.as_str() {
+            "latest" | "pending" | "safe" | "finalized" => Ok(Self::Latest),
+            "earliest" => Ok(Self::Earliest),
+            hex => {
+                let digits = hex
+                    .strip_prefix("0x")
+                    .ok_or_else(|| serde::de::Error::custom("block number must be 0x-prefixed"))?;
+                u64::from_str_radix(digits, 16)
+                    .map(|num| Self::Number(BlockNumber(num)))
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    }
+}
+
+/// Bytes serialized as 0x-prefixed hex.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HexBytes(pub bytes::Bytes);
+
+impl Serialize for HexBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(&self.0)))
+    }
+}
+
+impl From<bytes::Bytes> for HexBytes {
+    fn from(b: bytes::Bytes) -> Self {
+        Self(b)
+    }
+}
+
+/// Either the hashes of a block's transactions or the full transactions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum BlockTransactions {
+    Hashes(Vec<H256>),
+    Full(Vec<RpcTransaction>),
+}
+
+/// A block, as returned by `eth_getBlockByNumber` and `eth_getBlockByHash`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlock {
+    pub number: U64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub nonce: H64,
+    pub mix_hash: H256,
+    pub sha3_uncles: H256,
+    pub logs_bloom: Bloom,
+    pub transactions_root: H256,
+    pub state_root: H256,
+    pub receipts_root: H256,
+    pub miner: Address,
+    pub difficulty: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_difficulty: Option<U256>,
+    pub extra_data: HexBytes,
+    pub gas_limit: U64,
+    pub gas_used: U64,
+    pub timestamp: U64,
+    pub transactions: BlockTransactions,
+    pub uncles: Vec<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawals_root: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub withdrawals: Option<Vec<RpcWithdrawal>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<U64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_beacon_block_root: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_hash: Option<H256>,
+}
+
+impl RpcBlock {
+    pub fn new(block: Block, total_difficulty: Option<U256>, full: bool) -> Self {
+        let Block {
+            header,
+            transactions,
+            uncles,
+            withdrawals,
+        } = block;
+        let hash = header.hash();
+        let num = header.number.low_u64();
+        let transactions = if full {
+            BlockTransactions::Full(
+                transactions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, tx)| RpcTransaction::new(tx, Some((hash, num, i, header.base_fee))))
+                    .collect(),
+            )
+        } else {
+            BlockTransactions::Hashes(transactions.iter().map(|tx| tx.msg.tx_hash()).collect())
+        };
+        Self {
+            number: num.into(),
+            hash,
+            parent_hash: header.parent_hash,
+            nonce: header.nonce,
+            mix_hash: header.mix_digest,
+            sha3_uncles: header.uncle_hash,
+            logs_bloom: header.bloom,
+            transactions_root: header.tx_hash,
+            state_root: header.root,
+            receipts_root: header.receipts_hash,
+            miner: header.coinbase,
+            difficulty: header.difficulty,
+            total_difficulty,
+            extra_data: header.extra.clone().into(),
+            gas_limit: header.gas_limit.into(),
+            gas_used: header.gas_used.into(),
+            timestamp: header.time.into(),
+            transactions,
+            uncles: uncles.iter().map(BlockHeader::hash).collect(),
+            base_fee_per_gas: header.base_fee,
+            withdrawals_root: header.withdrawals_root,
+            withdrawals: withdrawals.map(|ws| ws.into_iter().map(From::from).collect()),
+            blob_gas_used: header.blob_gas_used.map(From::from),
+            excess_blob_gas: header.excess_blob_gas.map(From::from),
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            requests_hash: header.requests_hash,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcWithdrawal {
+    pub index: U64,
+    pub validator_index: U64,
+    pub address: Address,
+    pub amount: U64,
+}
+
+impl From<Withdrawal> for RpcWithdrawal {
+    fn from(w: Withdrawal) -> Self {
+        Self {
+            index: w.index.into(),
+            validator_index: w.validator.into(),
+            address: w.address,
+            amount: w.amount.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+}
+
+impl From<AccessTuple> for RpcAccessListItem {
+    fn from(t: AccessTuple) -> Self {
+        Self {
+            address: t.address,
+            storage_keys: t.slots,
+        }
+    }
+}
+
+/// A transaction, as returned by `eth_getTransactionByHash` and in full blocks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransaction {
+    pub hash: H256,
+    pub nonce: U64,
+    pub block_hash: Option<H256>,
+    pub block_number: Option<U64>,
+    pub transaction_index: Option<U64>,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: U256,
+    /// The effective gas price for txs in a block, otherwise the fee cap.
+    pub gas_price: U256,
+    pub gas: U64,
+    pub input: HexBytes,
+    #[serde(rename = "type")]
+    pub tx_type: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority_fee_per_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_list: Option<Vec<RpcAccessListItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_per_blob_gas: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_versioned_hashes: Option<Vec<H256>>,
+    pub v: U256,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl RpcTransaction {
+    /// Builds the rpc representation of a tx. `location` is the hash, number
+    /// and base fee of the block containing the tx, along with its index in
+    /// the block.
+    pub fn new(
+        tx: TransactionWithSigner,
+        location: Option<(H256, u64, usize, Option<U256>)>,
+    ) -> Self {
+        let TransactionWithSigner { msg, signer } = tx;
+        let base_fee = location.and_then(|(_, _, _, base_fee)| base_fee);
+        Self {
+            hash: msg.tx_hash(),
+            nonce: msg.nonce().into(),
+            block_hash: location.map(|(hash, ..)| hash),
+            block_number: location.map(|(_, num, ..)| num.into()),
+            transaction_index: location.map(|(_, _, i, _)| (i as u64).into()),
+            from: signer,
+            to: msg.to().into(),
+            value: msg.value(),
+            gas_price: msg.effective_gas_price(base_fee),
+            gas: msg.gas().into(),
+            input: msg.data().clone().into(),
+            tx_type: u64::from(msg.tx_type().unwrap_or(0)).into(),
+            chain_id: msg.chain_id(),
+            max_fee_per_gas: msg.fee_cap(),
+            max_priority_fee_per_gas: msg.tip(),
+            access_list: msg
+                .access_list()
+                .map(|al| al.into_owned().into_iter().map(From::from).collect()),
+            max_fee_per_blob_gas: msg.max_fee_per_blob_gas(),
+            blob_versioned_hashes: msg.blob_hashes().map(<[H256]>::to_vec),
+            v: msg.raw_v(),
+            r: msg.r(),
+            s: msg.s(),
+        }
+    }
+}
+
+/// A log, as returned in receipts.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLog {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: HexBytes,
+    pub block_hash: H256,
+    pub block_number: U64,
+    pub transaction_hash: H256,
+    pub transaction_index: U64,
+    pub log_index: U64,
+    pub removed: bool,
+}
+
+/// A receipt, as returned by `eth_getTransactionReceipt`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcReceipt {
+    pub transaction_hash: H256,
+    pub transaction_index: U64,
+    pub block_hash: H256,
+    pub block_number: U64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub cumulative_gas_used: U64,
+    pub gas_used: U64,
+    pub effective_gas_price: U256,
+    pub contract_address: Option<Address>,
+    pub logs: Vec<RpcLog>,
+    pub logs_bloom: Bloom,
+    #[serde(rename = "type")]
+    pub tx_type: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<H256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<U64>,
+}
+
+impl From<TransactionReceipt> for RpcReceipt {
+    fn from(r: TransactionReceipt) -> Self {
+        let logs = r
+            .logs
+            .into_iter()
+            .map(|(log_index, log)| {
+                let CborLog {
+                    address,
+                    topics,
+                    data,
+                } = log;
+                RpcLog {
+                    address,
+                    topics,
+                    data: data.into(),
+                    block_hash: r.block_hash,
+                    block_number: r.block_number.0.into(),
+                    transaction_hash: r.transaction_hash,
+                    transaction_index: r.transaction_index.into(),
+                    log_index: u64::from(log_index).into(),
+                    removed: false,
+                }
+            })
+            .collect();
+        Self {
+            transaction_hash: r.transaction_hash,
+            transaction_index: r.transaction_index.into(),
+            block_hash: r.block_hash,
+            block_number: r.block_number.0.into(),
+            from: r.from,
+            to: r.to,
+            cumulative_gas_used: r.cumulative_gas_used.into(),
+            gas_used: r.gas_used.into(),
+            effective_gas_price: r.effective_gas_price,
+            contract_address: r.contract_address,
+            logs,
+            logs_bloom: r.logs_bloom,
+            tx_type: u64::from(r.tx_type).into(),
+            root: r.root,
+            status: r.status.map(From::from),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use memmap2::Mmap;
+use std::{fs::File, path::Path};
+
+use crate::{error::DecodeError, Result};
+
+// Erigon refuses to open segments with deeper huffman codes.
+const MAX_ALLOWED_DEPTH: u64 = 50;
+
+/// A reader for Erigon's compressed segment (`.seg`) files.
+///
+/// A segment is a sequence of words, compressed with two huffman codes: one
+/// over a dictionary of patterns (common substrings), and one over the
+/// positions at which those patterns are inserted into each word. The bytes of
+/// a word not covered by any pattern are stored uncompressed after the codes.
+// https://github.com/ledgerwatch/erigon-lib/blob/0c2ea0e5cc4a8ae6d0b8bd3ee93d4bf0e9c2ca81/compress/decompress.go#L103
+pub struct Decompressor {
+    mmap: Mmap,
+    words_count: u64,
+    empty_words_count: u64,
+    words_start: usize,
+    patterns: Option<Huffman<Vec<u8>>>,
+    positions: Option<Huffman<u64>>,
+}
+
+impl Decompressor {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // Safety: snapshot files are immutable once written
+        let mmap = unsafe { Mmap::map(&file)? };
+        let data = &mmap[..];
+
+        let words_count = read_u64(data, 0)?;
+        let empty_words_count = read_u64(data, 8)?;
+
+        let dict_size = read_u64(data, 16)? as usize;
+        let dict = slice(data, 24, dict_size)?;
+        let mut patterns = Vec::new();
+        let mut pos = 0;
+        while pos < dict.len() {
+            let depth = read_uvarint(dict, &mut pos)?;
+            let len = read_uvarint(dict, &mut pos)? as usize;
+            patterns.push((depth, slice(dict, pos, len)?.to_vec()));
+            pos += len;
+        }
+
+        let pos_start = 24 + dict_size;
+        let dict_size = read_u64(data, pos_start)? as usize;
+        let dict = slice(data, pos_start + 8, dict_size)?;
+        let mut positions = Vec::new();
+        let mut pos = 0;
+        while pos < dict.len() {
+            let depth = read_uvarint(dict, &mut pos)?;
+            let p = read_uvarint(dict, &mut pos)?;
+            positions.push((depth, p));
+        }
+
+        Ok(Self {
+            words_count,
+            empty_words_count,
+            words_start: pos_start + 8 + dict_size,
+            patterns: Huffman::build(patterns)?,
+            positions: Huffman::build(positions)?,
+            mmap,
+        })
+    }
+
+    pub fn words_count(&self) -> u64 {
+        self.words_count
+    }
+    pub fn empty_words_count(&self) -> u64 {
+        self.empty_words_count
+    }
+
+    /// Returns a getter positioned at the first word of the segment.
+    pub fn getter(&self) -> Getter<'_> {
+        Getter {
+            data: &self.mmap[self.words_start..],
+            pos: 0,
+            bit: 0,
+            patterns: self.patterns.as_ref(),
+            positions: self.positions.as_ref(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Decompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decompressor")
+            .field("words_count", &self.words_count)
+            .field("empty_words_count", &self.empty_words_count)
+            .finish()
+    }
+}
+
+/// Reads words from a segment, starting at an offset into the compressed data.
+pub struct Getter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // the next unread bit of data[pos], codes are read lsb first
+    bit: u8,
+    patterns: Option<&'a Huffman<Vec<u8>>>,
+    positions: Option<&'a Huffman<u64>>,
+}
+
+impl<'a> Getter<'a> {
+    /// Moves the getter to `offset`, e.g. as returned by a recsplit index.
+    pub fn reset(&mut self, offset: u64) {
+        self.pos = offset as usize;
+        self.bit = 0;
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.pos < self.data.len()
+    }
+
+    /// Returns the offset of the next word.
+    pub fn offset(&self) -> u64 {
+        self.pos as u64
+    }
+
+    /// Decompresses the next word and advances past it.
+    pub fn next_word(&mut self) -> Result<Vec<u8>> {
+        let save = self.pos;
+        self.align();
+        // positions are stored +1, as 0 terminates the list
+        let word_len =
+            self.next_pos()?
+                .checked_sub(1)
+                .ok_or_else(|| DecodeError::custom("invalid word length"))? as usize;
+        if word_len == 0 {
+            self.align();
+            return Ok(Vec::new());
+        }
+        let mut buf = vec![0; word_len];
+
+        // first pass: copy the patterns into place
+        let mut buf_pos = 0;
+        loop {
+            let pos = self.next_pos()? as usize;
+            if pos == 0 {
+                break;
+            }
+            buf_pos += pos - 1;
+            let pattern = self.next_pattern()?;
+            buf.get_mut(buf_pos..buf_pos + pattern.len())
+                .ok_or_else(|| DecodeError::custom("pattern overflows word"))?
+                .copy_from_slice(pattern);
+        }
+        self.align();
+        let mut uncompressed = self.pos;
+
+        // second pass: fill the gaps between patterns from the uncompressed bytes
+        self.pos = save;
+        self.bit = 0;
+        self.next_pos()?;
+        let mut buf_pos = 0;
+        let mut last_uncovered = 0;
+        loop {
+            let pos = self.next_pos()? as usize;
+            if pos == 0 {
+                break;
+            }
+            buf_pos += pos - 1;
+            if buf_pos > last_uncovered {
+                let n = buf_pos - last_uncovered;
+                buf[last_uncovered..buf_pos].copy_from_slice(slice(self.data, uncompressed, n)?);
+                uncompressed += n;
+            }
+            last_uncovered = buf_pos + self.next_pattern()?.len();
+        }
+        if word_len > last_uncovered {
+            let n = word_len - last_uncovered;
+            buf[last_uncovered..].copy_from_slice(slice(self.data, uncompressed, n)?);
+            uncompressed += n;
+        }
+
+        self.pos = uncompressed;
+        self.bit = 0;
+        Ok(buf)
+    }
+
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.pos += 1;
+            self.bit = 0;
+        }
+    }
+
+    fn read_bit(&mut self) -> Result<bool> {
+        let byte = self.data.get(self.pos).ok_or(DecodeError::TooShort {
+            minimum: self.pos + 1,
+            got: self.data.len(),
+        })?;
+        let set = (byte >> self.bit) & 1 == 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.pos += 1;
+            self.bit = 0;
+        }
+        Ok(set)
+    }
+
+    fn next_pos(&mut self) -> Result<u64> {
+        let table = self
+            .positions
+            .ok_or_else(|| DecodeError::custom("segment has no position dictionary"))?;
+        table.decode(self).copied()
+    }
+
+    fn next_pattern(&mut self) -> Result<&'a [u8]> {
+        let table = self
+            .patterns
+            .ok_or_else(|| DecodeError::custom("segment has no pattern dictionary"))?;
+        table.decode(self).map(Vec::as_slice)
+    }
+}
+
+enum Node<T> {
+    Leaf(T),
+    Branch(usize, usize),
+}
+
+// A huffman code, stored in the segment as a list of (depth, value) pairs
+// ordered by code. Reading a 0 bit descends to the left child.
+struct Huffman<T> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T> Huffman<T> {
+    fn build(entries: Vec<(u64, T)>) -> Result<Option<Self>> {
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let mut nodes = Vec::with_capacity(2 * entries.len());
+        let mut entries = entries.into_iter().peekable();
+        Self::build_node(&mut nodes, &mut entries, 0)?;
+        if entries.next().is_some() {
+            return Err(DecodeError::custom("malformed huffman table").into());
+        }
+        Ok(Some(Self { nodes }))
+    }
+
+    fn build_node(
+        nodes: &mut Vec<Node<T>>,
+        entries: &mut std::iter::Peekable<std::vec::IntoIter<(u64, T)>>,
+        depth: u64,
+    ) -> Result<usize> {
+        let next_depth = match entries.peek() {
+            Some((d, _)) => *d,
+            None => return Err(DecodeError::custom("malformed huffman table").into()),
+        };
+        if next_depth == depth {
+            let (_, val) = entries.next().unwrap();
+            nodes.push(Node::Leaf(val));
+            return Ok(nodes.len() - 1);
+        }
+        if next_depth < depth || depth >= MAX_ALLOWED_DEPTH {
+            return Err(DecodeError::custom("malformed huffman table").into());
+        }
+        let idx = nodes.len();
+        nodes.push(Node::Branch(0, 0));
+        let left = Self::build_node(nodes, entries, depth + 1)?;
+        let right = Self::build_node(nodes, entries, depth + 1)?;
+        nodes[idx] = Node::Branch(left, right);
+        Ok(idx)
+    }
+
+    fn decode<'a>(&'a self, g: &mut Getter<'_>) -> Result<&'a T> {
+        let mut i = 0;
+        loop {
+            match &self.nodes[i] {
+                Node::Leaf(val) => return Ok(val),
+                Node::Branch(left, right) => i = if g.read_bit()? { *right } else { *left },
+            }
+        }
+    }
+}
+
+fn slice(data: &[u8], start: usize, len: usize) -> Result<&[u8]> {
+    let end = start.saturating_add(len);
+    data.get(start..end).ok_or_else(|| {
+        DecodeError::TooShort {
+            minimum: end,
+            got: data.len(),
+        }
+        .into()
+    })
+}
+
+fn read_u64(data: &[u8], start: usize) -> Result<u64> {
+    Ok(u64::from_be_bytes(
+        slice(data, start, 8)?.try_into().unwrap(),
+    ))
+}
+
+// Reads an unsigned LEB128 varint, as written by go's binary.PutUvarint.
+fn read_uvarint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut out = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos).ok_or(DecodeError::TooShort {
+            minimum: *pos + 1,
+            got: data.len(),
+        })?;
+        *pos += 1;
+        out |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(out);
+        }
+    }
+    Err(DecodeError::custom("uvarint overflows u64").into())
+}
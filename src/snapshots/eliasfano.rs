@@ -0,0 +1,175 @@
+use crate::{error::DecodeError, Result};
+
+const LOG2Q: u64 = 8;
+const Q: u64 = 1 << LOG2Q;
+const Q_MASK: u64 = Q - 1;
+const SUPER_Q: u64 = 1 << 14;
+const Q_PER_SUPER_Q: u64 = SUPER_Q / Q;
+const SUPER_Q_SIZE: u64 = 1 + Q_PER_SUPER_Q / 2;
+
+/// A monotone sequence of integers in Elias-Fano encoding, as serialized by
+/// Erigon's `eliasfano32` package. Recsplit indices use it to map ordinals to
+/// word offsets in a segment file.
+///
+/// Each element is split into `l` lower bits, stored verbatim, and upper bits,
+/// stored in unary. A jump table of absolute positions in the upper bits every
+/// 2^14 elements, and relative positions every 2^8, keeps lookups O(1).
+// https://github.com/ledgerwatch/erigon-lib/blob/0c2ea0e5cc4a8ae6d0b8bd3ee93d4bf0e9c2ca81/recsplit/eliasfano32/elias_fano.go
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EliasFano {
+    // the number of elements minus one
+    count: u64,
+    // one past the largest element
+    u: u64,
+    l: u64,
+    lower_bits_mask: u64,
+    lower_bits: Vec<u64>,
+    upper_bits: Vec<u64>,
+    jump: Vec<u64>,
+}
+
+impl EliasFano {
+    /// Reads an Elias-Fano sequence from the start of `b`, returning it along
+    /// with the number of bytes it occupies.
+    pub fn read(b: &[u8]) -> Result<(Self, usize)> {
+        if b.len() < 16 {
+            return Err(DecodeError::TooShort {
+                minimum: 16,
+                got: b.len(),
+            }
+            .into());
+        }
+        let count = u64::from_be_bytes(b[..8].try_into().unwrap());
+        let u = u64::from_be_bytes(b[8..16].try_into().unwrap());
+        let overflow =
+            || DecodeError::custom(format!("elias-fano size overflows: {} {}", count, u));
+        let len = count.checked_add(1).ok_or_else(overflow)?;
+        if u == 0 {
+            return Err(DecodeError::custom("elias-fano upper bound is zero").into());
+        }
+
+        let per_elem = u / len;
+        let l = if per_elem == 0 {
+            0
+        } else {
+            63 - u64::from(per_elem.leading_zeros())
+        };
+        let words_lower = len
+            .checked_mul(l)
+            .and_then(|bits| bits.checked_add(63))
+            .map(|bits| bits / 64 + 1)
+            .ok_or_else(overflow)?;
+        let words_upper = len
+            .checked_add(u >> l)
+            .and_then(|bits| bits.checked_add(63))
+            .map(|bits| bits / 64)
+            .ok_or_else(overflow)?;
+        let words_jump = jump_size_words(len);
+        let size = words_lower
+            .checked_add(words_upper)
+            .and_then(|words| words.checked_add(words_jump))
+            .and_then(|words| words.checked_mul(8))
+            .and_then(|bytes| bytes.checked_add(16))
+            .and_then(|bytes| usize::try_from(bytes).ok())
+            .ok_or_else(overflow)?;
+        if b.len() < size {
+            return Err(DecodeError::TooShort {
+                minimum: size,
+                got: b.len(),
+            }
+            .into());
+        }
+        // the words are written straight from memory, i.e. little endian
+        let mut words = b[16..size]
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes(w.try_into().unwrap()));
+        let lower_bits = words.by_ref().take(words_lower as usize).collect();
+        let upper_bits = words.by_ref().take(words_upper as usize).collect();
+        let jump = words.collect();
+
+        Ok((
+            Self {
+                count,
+                u,
+                l,
+                lower_bits_mask: (1 << l) - 1,
+                lower_bits,
+                upper_bits,
+                jump,
+            },
+            size,
+        ))
+    }
+
+    /// The number of elements in the sequence.
+    pub fn len(&self) -> u64 {
+        self.count + 1
+    }
+
+    // a serialized sequence always holds at least one element
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The largest element in the sequence.
+    pub fn max(&self) -> u64 {
+        // the header holds one past it
+        self.u - 1
+    }
+
+    /// Returns the `i`th element of the sequence.
+    pub fn get(&self, i: u64) -> Option<u64> {
+        if i > self.count {
+            return None;
+        }
+
+        let lower = i * self.l;
+        let (idx, shift) = ((lower / 64) as usize, lower % 64);
+        let mut lower = self.lower_bits.get(idx)? >> shift;
+        if shift > 0 {
+            lower |= self.lower_bits.get(idx + 1)? << (64 - shift);
+        }
+
+        let jump_super_q = (i / SUPER_Q) * SUPER_Q_SIZE;
+        let jump_inside_super_q = (i % SUPER_Q) / Q;
+        let idx = (jump_super_q + 1 + (jump_inside_super_q >> 1)) as usize;
+        let shift = 32 * (jump_inside_super_q % 2);
+        let mask = 0xffff_ffff << shift;
+        let jump = self
+            .jump
+            .get(jump_super_q as usize)?
+            .checked_add((self.jump.get(idx)? & mask) >> shift)?;
+
+        let mut curr_word = (jump / 64) as usize;
+        let mut window = self.upper_bits.get(curr_word)? & (u64::MAX << (jump % 64));
+        let mut d = i & Q_MASK;
+        while u64::from(window.count_ones()) <= d {
+            d -= u64::from(window.count_ones());
+            curr_word += 1;
+            window = *self.upper_bits.get(curr_word)?;
+        }
+        let sel = select64(window, d);
+        // the `i`th set bit is at least `i` bits in, unless the file is corrupt
+        let upper = (curr_word as u64 * 64 + sel).checked_sub(i)?;
+        Some((upper << self.l) | (lower & self.lower_bits_mask))
+    }
+}
+
+// The number of words in the jump table of a sequence of `len` elements.
+fn jump_size_words(len: u64) -> u64 {
+    // whole blocks
+    let mut size = (len / SUPER_Q) * SUPER_Q_SIZE;
+    // partial block
+    if len % SUPER_Q != 0 {
+        size += 1 + ((len % SUPER_Q + Q - 1) / Q + 3) / 2;
+    }
+    size
+}
+
+// Returns the index of the `n`th set bit in `x`.
+fn select64(mut x: u64, n: u64) -> u64 {
+    for _ in 0..n {
+        x &= x - 1;
+    }
+    u64::from(x.trailing_zeros())
+}
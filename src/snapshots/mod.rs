@@ -0,0 +1,215 @@
+//! Readers for Erigon's frozen block snapshots.
+//!
+//! Erigon moves old blocks out of the db into immutable segment files, each
+//! covering a range of blocks, e.g. `v1-000000-000500-headers.seg` holds the
+//! headers of blocks `[0, 500_000)`. Each segment has a recsplit index (`.idx`)
+//! mapping block numbers to the offset of their compressed word.
+use ethereum_types::Address;
+use std::path::Path;
+
+use crate::{
+    erigon::models::{BlockHeader, BodyForStorage, Transaction, TransactionWithSigner},
+    error::DecodeError,
+    rlp::Decodable,
+    Result,
+};
+
+pub mod decompress;
+pub mod eliasfano;
+pub mod recsplit;
+
+use decompress::Decompressor;
+use recsplit::Index;
+
+/// A segment file along with its index.
+#[derive(Debug)]
+pub struct Segment {
+    /// The first block in the segment.
+    pub from: u64,
+    /// One past the last block in the segment.
+    pub to: u64,
+    pub seg: Decompressor,
+    pub idx: Index,
+}
+
+impl Segment {
+    /// Returns the word with the given id, which is the block number for
+    /// header and body segments and the tx id for transaction segments. Returns
+    /// `None` if the id is not in the segment.
+    pub fn read_word(&self, id: u64) -> Result<Option<Vec<u8>>> {
+        let offset = match id
+            .checked_sub(self.idx.base_data_id())
+            .and_then(|i| self.idx.ordinal_lookup(i))
+        {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let mut getter = self.seg.getter();
+        getter.reset(offset);
+        getter.next_word().map(Some)
+    }
+}
+
+/// The snapshot segments found in an Erigon snapshots directory.
+#[derive(Debug, Default)]
+pub struct Snapshots {
+    headers: Vec<Segment>,
+    bodies: Vec<Segment>,
+    transactions: Vec<Segment>,
+}
+
+impl Snapshots {
+    /// Opens every indexed segment in `dir`, which is usually `<datadir>/snapshots`.
+    /// Segments without a `.idx` file are skipped, as Erigon is still building them.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let mut snapshots = Self::default();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let (from, to, kind) = match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_file_name)
+            {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            let idx_path = path.with_extension("idx");
+            if !idx_path.exists() {
+                continue;
+            }
+            let segment = Segment {
+                from,
+                to,
+                seg: Decompressor::open(&path)?,
+                idx: Index::open(&idx_path)?,
+            };
+            match kind {
+                "headers" => snapshots.headers.push(segment),
+                "bodies" => snapshots.bodies.push(segment),
+                "transactions" => snapshots.transactions.push(segment),
+                _ => continue,
+            }
+        }
+        snapshots.headers.sort_by_key(|s| s.from);
+        snapshots.bodies.sort_by_key(|s| s.from);
+        snapshots.transactions.sort_by_key(|s| s.from);
+        Ok(snapshots)
+    }
+
+    pub fn header_segments(&self) -> &[Segment] {
+        &self.headers
+    }
+    pub fn body_segments(&self) -> &[Segment] {
+        &self.bodies
+    }
+    pub fn transaction_segments(&self) -> &[Segment] {
+        &self.transactions
+    }
+
+    /// One past the last block with a header in the snapshots.
+    pub fn headers_available(&self) -> u64 {
+        self.headers.last().map_or(0, |s| s.to)
+    }
+
+    /// One past the last block with a body and transactions in the snapshots.
+    pub fn blocks_available(&self) -> u64 {
+        let bodies = self.bodies.last().map_or(0, |s| s.to);
+        let txs = self.transactions.last().map_or(0, |s| s.to);
+        bodies.min(txs)
+    }
+
+    /// Returns the canonical header of block `num`, or `None` if it is not in
+    /// the snapshots.
+    pub fn read_header(&self, num: u64) -> Result<Option<BlockHeader>> {
+        let segment = match find_segment(&self.headers, num) {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        // the word is the first byte of the header hash, followed by the rlp header
+        match segment.read_word(num)? {
+            Some(word) if !word.is_empty() => Ok(Some(
+                Decodable::decode(&mut &word[1..]).map_err(DecodeError::from)?,
+            )),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the body of block `num`, or `None` if it is not in the snapshots.
+    /// As with [`Erigon::read_body_for_storage`](crate::Erigon::read_body_for_storage),
+    /// the system txs at either end of the block are excluded.
+    pub fn read_body_for_storage(&self, num: u64) -> Result<Option<BodyForStorage>> {
+        let segment = match find_segment(&self.bodies, num) {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        let word = match segment.read_word(num)? {
+            Some(word) if !word.is_empty() => word,
+            _ => return Ok(None),
+        };
+        let mut body: BodyForStorage =
+            Decodable::decode(&mut &word[..]).map_err(DecodeError::from)?;
+        body.base_tx_id = body
+            .base_tx_id
+            .checked_add(1)
+            .ok_or_else(|| DecodeError::custom("Block body tx id overflows"))?;
+        body.tx_amount = body.tx_amount.checked_sub(2).ok_or_else(|| {
+            DecodeError::custom(format!(
+                "Block body has too few txs: {}. Block: {}",
+                body.tx_amount, num,
+            ))
+        })?;
+        Ok(Some(body))
+    }
+
+    /// Returns the transactions of block `num`, along with their signers, or
+    /// `None` if they are not in the snapshots.
+    pub fn read_transactions(
+        &self,
+        num: u64,
+        body: &BodyForStorage,
+    ) -> Result<Option<Vec<TransactionWithSigner>>> {
+        let segment = match find_segment(&self.transactions, num) {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        let end = body
+            .base_tx_id
+            .checked_add(u64::from(body.tx_amount))
+            .ok_or_else(|| DecodeError::custom("Block body tx ids overflow"))?;
+        let mut txs = Vec::with_capacity(body.tx_amount as usize);
+        for id in body.base_tx_id..end {
+            let word = match segment.read_word(id)? {
+                Some(word) => word,
+                None => return Ok(None),
+            };
+            // the first byte of the tx hash, then the sender, then the rlp tx
+            if word.len() < 21 {
+                return Err(DecodeError::TooShort {
+                    minimum: 21,
+                    got: word.len(),
+                }
+                .into());
+            }
+            txs.push(TransactionWithSigner {
+                signer: Address::from_slice(&word[1..21]),
+                msg: Transaction::decode(&mut &word[21..]).map_err(DecodeError::from)?,
+            });
+        }
+        Ok(Some(txs))
+    }
+}
+
+fn find_segment(segments: &[Segment], num: u64) -> Option<&Segment> {
+    segments.iter().find(|s| (s.from..s.to).contains(&num))
+}
+
+// Parses names like v1-000000-000500-headers.seg, where block numbers are in thousands.
+fn parse_file_name(name: &str) -> Option<(u64, u64, &str)> {
+    let stem = name.strip_suffix(".seg")?;
+    let mut parts = stem.splitn(4, '-');
+    let _version = parts.next()?;
+    let from = parts.next()?.parse::<u64>().ok()?;
+    let to = parts.next()?.parse::<u64>().ok()?;
+    let kind = parts.next()?;
+    Some((from * 1_000, to * 1_000, kind))
+}
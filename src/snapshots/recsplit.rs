@@ -0,0 +1,162 @@
+use memmap2::Mmap;
+use std::{fs::File, path::Path};
+
+use crate::{error::DecodeError, snapshots::eliasfano::EliasFano, Result};
+
+/// A reader for Erigon's recsplit index (`.idx`) files.
+///
+/// A recsplit index is a minimal perfect hash function over the keys of a
+/// segment file. When built with `enums`, each key maps to its ordinal, and
+/// an Elias-Fano sequence maps each ordinal to the offset of its word in the
+/// segment. For block snapshots the ordinal is the block number minus
+/// `base_data_id`, so lookups by number never need to hash a key.
+// https://github.com/ledgerwatch/erigon-lib/blob/0c2ea0e5cc4a8ae6d0b8bd3ee93d4bf0e9c2ca81/recsplit/index.go#L83
+pub struct Index {
+    _mmap: Mmap,
+    base_data_id: u64,
+    key_count: u64,
+    bytes_per_rec: u8,
+    bucket_count: u64,
+    bucket_size: u16,
+    leaf_size: u16,
+    salt: u32,
+    start_seed: Vec<u64>,
+    offsets: Option<EliasFano>,
+}
+
+impl Index {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // Safety: snapshot files are immutable once written
+        let mmap = unsafe { Mmap::map(&file)? };
+        let mut r = Reader {
+            data: &mmap,
+            pos: 0,
+        };
+
+        let base_data_id = r.u64()?;
+        let key_count = r.u64()?;
+        let bytes_per_rec = r.u8()?;
+        // skip the per-key records
+        let records = usize::try_from(key_count)
+            .ok()
+            .and_then(|n| n.checked_mul(usize::from(bytes_per_rec)))
+            .ok_or_else(|| DecodeError::custom(format!("too many keys: {}", key_count)))?;
+        r.skip(records)?;
+        let bucket_count = r.u64()?;
+        let bucket_size = r.u16()?;
+        let leaf_size = r.u16()?;
+        let salt = r.u32()?;
+        let start_seed_len = r.u8()?;
+        let start_seed = (0..start_seed_len)
+            .map(|_| r.u64())
+            .collect::<Result<_>>()?;
+        let enums = r.u8()? != 0;
+        let offsets = if enums {
+            let (ef, size) = EliasFano::read(r.rest())?;
+            r.skip(size)?;
+            Some(ef)
+        } else {
+            None
+        };
+        // The golomb-rice codes and the double Elias-Fano bucket offsets
+        // follow, but they are only needed to look up keys by hash.
+
+        Ok(Self {
+            base_data_id,
+            key_count,
+            bytes_per_rec,
+            bucket_count,
+            bucket_size,
+            leaf_size,
+            salt,
+            start_seed,
+            offsets,
+            _mmap: mmap,
+        })
+    }
+
+    /// The id (e.g. block number) of the first key in the index.
+    pub fn base_data_id(&self) -> u64 {
+        self.base_data_id
+    }
+    pub fn key_count(&self) -> u64 {
+        self.key_count
+    }
+    pub fn bytes_per_rec(&self) -> u8 {
+        self.bytes_per_rec
+    }
+    pub fn bucket_count(&self) -> u64 {
+        self.bucket_count
+    }
+    pub fn bucket_size(&self) -> u16 {
+        self.bucket_size
+    }
+    pub fn leaf_size(&self) -> u16 {
+        self.leaf_size
+    }
+    pub fn salt(&self) -> u32 {
+        self.salt
+    }
+    pub fn start_seed(&self) -> &[u64] {
+        &self.start_seed
+    }
+
+    /// Returns the offset in the segment file of the word with ordinal `i`, or
+    /// `None` if `i` is out of range or the index was built without ordinals.
+    pub fn ordinal_lookup(&self, i: u64) -> Option<u64> {
+        if i >= self.key_count {
+            return None;
+        }
+        self.offsets.as_ref()?.get(i)
+    }
+}
+
+impl std::fmt::Debug for Index {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Index")
+            .field("base_data_id", &self.base_data_id)
+            .field("key_count", &self.key_count)
+            .field("bytes_per_rec", &self.bytes_per_rec)
+            .field("bucket_count", &self.bucket_count)
+            .field("bucket_size", &self.bucket_size)
+            .field("leaf_size", &self.leaf_size)
+            .finish()
+    }
+}
+
+// A cursor over the big endian header fields of an index file.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.saturating_add(n);
+        let b = self.data.get(self.pos..end).ok_or(DecodeError::TooShort {
+            minimum: end,
+            got: self.data.len(),
+        })?;
+        self.pos = end;
+        Ok(b)
+    }
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.take(n).map(|_| ())
+    }
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
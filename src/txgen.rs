@@ -2,7 +2,8 @@ use ethers::{abi::Abi, prelude::*, signers::LocalWallet, utils::format_ether};
 use eyre::{eyre, Result};
 use std::{fs, path::Path, sync::Arc, time::Duration};
 
-/// Temporary script used for seeding test data
+/// Temporary script used for seeding test data. `fixtures::txgen` writes the
+/// same chain straight to a db, for tests that cannot run a node.
 
 #[cfg(feature = "txgen")]
 mod bindings;